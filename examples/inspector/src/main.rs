@@ -0,0 +1,126 @@
+use std::sync::mpsc::Receiver;
+use theframework::prelude::*;
+
+#[derive(TheInspect, Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    Circle,
+    Square,
+    #[inspect(label = "Rounded Box")]
+    RoundedBox,
+}
+
+#[derive(TheInspect, Debug)]
+pub struct Material {
+    pub color: TheColor,
+    #[inspect(min = 0.0, max = 1.0, slider)]
+    pub roughness: f32,
+    pub metallic: bool,
+}
+
+#[derive(TheInspect, Debug)]
+pub struct Settings {
+    pub name: String,
+    pub shape: Shape,
+    #[inspect(label = "Radius", min = 1.0, max = 100.0, continuous)]
+    pub size: f32,
+    pub position: Vec2<f32>,
+    pub material: Material,
+    #[inspect(skip)]
+    pub version: i32,
+}
+
+pub struct Inspector {
+    settings: Settings,
+    node_ui: TheNodeUI,
+
+    event_receiver: Option<Receiver<TheEvent>>,
+}
+
+impl TheTrait for Inspector {
+    fn new() -> Self
+    where
+        Self: Sized,
+    {
+        let settings = Settings {
+            name: "Object".to_string(),
+            shape: Shape::Circle,
+            size: 20.0,
+            position: Vec2::zero(),
+            material: Material {
+                color: TheColor::white(),
+                roughness: 0.5,
+                metallic: false,
+            },
+            version: 1,
+        };
+
+        Self {
+            node_ui: settings.to_node_ui(),
+            settings,
+
+            event_receiver: None,
+        }
+    }
+
+    fn window_title(&self) -> String {
+        "Inspector".to_string()
+    }
+
+    fn init_ui(&mut self, ui: &mut TheUI, ctx: &mut TheContext) {
+        let mut text_layout = TheTextLayout::new(TheId::named("Inspector Layout"));
+        text_layout.limiter_mut().set_max_width(400);
+        self.node_ui.apply_to_text_layout(&mut text_layout);
+
+        let mut canvas = TheCanvas::new();
+        canvas.set_layout(text_layout);
+        ui.canvas.set_center(canvas);
+
+        // Statusbar
+        let mut status_canvas = TheCanvas::new();
+        let mut statusbar = TheStatusbar::new(TheId::named("Statusbar"));
+        statusbar.set_text("Edit the settings, the changes are printed to the console.".into());
+        status_canvas.set_widget(statusbar);
+
+        ui.set_statusbar_name("Statusbar".to_string());
+        ui.canvas.set_bottom(status_canvas);
+
+        ui.relayout_layout("Inspector Layout", ctx);
+
+        self.event_receiver = Some(ui.add_state_listener("Main".into()));
+    }
+
+    fn update_ui(&mut self, ui: &mut TheUI, ctx: &mut TheContext) -> bool {
+        let mut redraw = false;
+
+        if let Some(receiver) = &mut self.event_receiver {
+            while let Ok(event) = receiver.try_recv() {
+                let had_errors = self.node_ui.has_errors();
+                if self.node_ui.handle_event(&event) {
+                    self.settings.apply_from_node_ui(&self.node_ui);
+                    println!("{:?}", self.settings);
+
+                    // A group was collapsed or expanded or a value was rejected or accepted
+                    // again, rebuild the layout to show the values and the status texts.
+                    if matches!(event, TheEvent::StateChanged(..))
+                        || had_errors
+                        || self.node_ui.has_errors()
+                    {
+                        if let Some(layout) = ui.get_text_layout("Inspector Layout") {
+                            self.node_ui.apply_to_text_layout(layout);
+                        }
+                        ui.relayout_layout("Inspector Layout", ctx);
+                        redraw = true;
+                    }
+                }
+            }
+        }
+
+        redraw
+    }
+}
+
+fn main() {
+    let inspector = Inspector::new();
+    let app = TheApp::new();
+    () = app.run(Box::new(inspector));
+}
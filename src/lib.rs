@@ -1,11 +1,24 @@
 pub mod theapp;
+pub mod theatlas;
+pub mod theblendmode;
 pub mod thecolor;
+pub mod thecolorspace;
 pub mod thecontext;
 pub mod thedim;
 pub mod thedraw2d;
+pub mod thefilter;
+pub mod thegradient;
+pub mod theimage;
+pub mod theimagedocument;
 pub mod thenodecanvas;
+pub mod thenodelayout;
 pub mod thepalette;
+pub mod thepaletteio;
+pub mod thepixelbuffer;
+pub mod thequantize;
 pub mod thergbabuffer;
+pub mod thergbapaint;
+pub mod thergbatransform;
 pub mod thetime;
 pub mod thetrait;
 #[cfg(feature = "winit_app")]
@@ -85,20 +98,40 @@ pub mod prelude {
     pub use crate::thergbabuffer::{
         TheRGBABuffer, TheRGBARegion, TheRGBARegionSequence, TheRGBATile,
     };
+    pub use crate::thergbatransform::{TheResampleFilter, TheTransform2D};
 
     pub use rustc_hash::*;
     pub use uuid::Uuid;
     pub use vek::*;
 
     pub use crate::theapp::TheApp;
+    pub use crate::theatlas::{
+        TheAtlas, TheAtlasBuilder, TheAtlasError, TheAtlasPacking, TheAtlasSprite,
+    };
+    pub use crate::theblendmode::TheBlendMode;
     pub use crate::thecolor::TheColor;
+    pub use crate::thecolorspace::{TheColorHarmony, TheColorSpace};
     pub use crate::thecontext::TheContext;
     pub use crate::thecontext::TheCursorIcon;
     pub use crate::thedraw2d::{
         TheDraw2D, TheFontPreference, TheFontSettings, TheHorizontalAlign, TheVerticalAlign,
     };
-    pub use crate::thenodecanvas::{TheNode, TheNodeCanvas, TheNodeTerminal};
-    pub use crate::thepalette::ThePalette;
+    pub use crate::thefilter::TheConvolutionKernel;
+    pub use crate::thegradient::{TheGradient, TheGradientStop};
+    pub use crate::theimage::{TheImageError, TheImageFormat, TheImageFrame, TheImageSequence};
+    pub use crate::theimagedocument::{TheImageDocument, TheLayer, TheLayerKind};
+    pub use crate::thenodecanvas::{
+        TheNode, TheNodeCanvas, TheNodeCommand, TheNodeConnectionError, TheNodeConnectionId,
+        TheNodeDataType, TheNodeFrame, TheNodeReroute, TheNodeTerminal, TheNodeWireStyle,
+    };
+    pub use crate::thenodelayout::TheNodeAlignment;
+    pub use crate::thepalette::{ThePalette, ThePaletteSort};
+    pub use crate::thepaletteio::{ThePaletteError, ThePaletteFormat};
+    pub use crate::thepixelbuffer::{
+        TheGray8Buffer, ThePixel, ThePixelBuffer, ThePixelFormat, TheRGBA16FBuffer,
+        TheRGBA32FBuffer, TheToneMapping,
+    };
+    pub use crate::thequantize::{TheDitherMode, TheQuantizeMethod};
     pub use crate::thetime::TheTime;
 
     pub use crate::thetrait::TheTrait;
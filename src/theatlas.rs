@@ -0,0 +1,567 @@
+use crate::prelude::*;
+use indexmap::IndexMap;
+
+/// Errors which can occur while building an atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TheAtlasError {
+    /// No images were added to the builder.
+    Empty,
+    /// An image with the same name was already added.
+    DuplicateName(String),
+    /// The images do not fit into the maximum atlas size.
+    DoesNotFit(i32, i32),
+}
+
+impl std::fmt::Display for TheAtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The atlas contains no images"),
+            Self::DuplicateName(name) => write!(f, "Duplicate image name: {}", name),
+            Self::DoesNotFit(width, height) => {
+                write!(f, "The images do not fit into {}x{}", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TheAtlasError {}
+
+/// The rectangle packing algorithm used by TheAtlasBuilder.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TheAtlasPacking {
+    /// MaxRects with best short side fit, produces the tightest atlases.
+    #[default]
+    MaxRects,
+    /// Skyline bottom-left, faster but wastes more space.
+    Skyline,
+}
+
+impl TheAtlasPacking {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::MaxRects => "MaxRects",
+            Self::Skyline => "Skyline",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheAtlasPacking> {
+        [Self::MaxRects, Self::Skyline].iter().copied()
+    }
+}
+
+/// A named image inside an atlas. The region covers the (possibly trimmed) pixels in the atlas,
+/// the offset and source size describe where the trimmed pixels sit inside the original image.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TheAtlasSprite {
+    pub region: TheRGBARegion,
+    pub offset_x: usize,
+    pub offset_y: usize,
+    pub source_width: usize,
+    pub source_height: usize,
+}
+
+impl TheAtlasSprite {
+    /// Returns true if transparent borders were trimmed from the image.
+    pub fn is_trimmed(&self) -> bool {
+        self.region.width != self.source_width || self.region.height != self.source_height
+    }
+}
+
+/// A packed texture atlas: one buffer plus the named sprites and sequences inside it. Only the
+/// description is serialized, the buffer is usually stored separately as a PNG.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TheAtlas {
+    pub width: i32,
+    pub height: i32,
+    pub sprites: IndexMap<String, TheAtlasSprite>,
+    /// Named animation sequences, each a list of sprite names.
+    pub sequences: IndexMap<String, Vec<String>>,
+
+    #[serde(skip)]
+    buffer: TheRGBABuffer,
+}
+
+impl Default for TheAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TheAtlas {
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            sprites: IndexMap::default(),
+            sequences: IndexMap::default(),
+            buffer: TheRGBABuffer::empty(),
+        }
+    }
+
+    /// Returns the atlas buffer.
+    pub fn buffer(&self) -> &TheRGBABuffer {
+        &self.buffer
+    }
+
+    /// Sets the atlas buffer, i.e. after loading the description from JSON.
+    pub fn set_buffer(&mut self, buffer: TheRGBABuffer) {
+        self.buffer = buffer;
+    }
+
+    /// Returns the sprite with the given name.
+    pub fn sprite(&self, name: &str) -> Option<&TheAtlasSprite> {
+        self.sprites.get(name)
+    }
+
+    /// Returns the region of the sprite with the given name.
+    pub fn region(&self, name: &str) -> Option<&TheRGBARegion> {
+        self.sprites.get(name).map(|sprite| &sprite.region)
+    }
+
+    /// Returns the regions of the sequence with the given name.
+    pub fn sequence(&self, name: &str) -> Option<TheRGBARegionSequence> {
+        let names = self.sequences.get(name)?;
+        let mut sequence = TheRGBARegionSequence::new();
+        for name in names {
+            sequence.regions.push(self.region(name)?.clone());
+        }
+        Some(sequence)
+    }
+
+    /// Extracts the sprite with its original size, trimmed borders are restored as transparent pixels.
+    pub fn extract_sprite(&self, name: &str) -> Option<TheRGBABuffer> {
+        let sprite = self.sprites.get(name)?;
+        let pixels = self.buffer.extract_region(&sprite.region);
+        if !sprite.is_trimmed() {
+            return Some(pixels);
+        }
+        let mut buffer = TheRGBABuffer::new(TheDim::sized(
+            sprite.source_width as i32,
+            sprite.source_height as i32,
+        ));
+        buffer.copy_into(sprite.offset_x as i32, sprite.offset_y as i32, &pixels);
+        Some(buffer)
+    }
+
+    /// Creates a tile for the sequence or the single sprite with the given name.
+    pub fn tile(&self, name: &str) -> Option<TheRGBATile> {
+        let mut tile = TheRGBATile::new();
+        tile.name = name.to_string();
+        if let Some(names) = self.sequences.get(name) {
+            for name in names {
+                tile.buffer.push(self.extract_sprite(name)?);
+            }
+        } else {
+            tile.buffer.push(self.extract_sprite(name)?);
+        }
+        Some(tile)
+    }
+
+    /// Creates a tile for every sequence and for every sprite which is not part of a sequence.
+    pub fn tiles(&self) -> Vec<TheRGBATile> {
+        let mut tiles: Vec<TheRGBATile> = self
+            .sequences
+            .keys()
+            .filter_map(|name| self.tile(name))
+            .collect();
+        for name in self.sprites.keys() {
+            if !self.sequences.values().any(|names| names.contains(name)) {
+                if let Some(tile) = self.tile(name) {
+                    tiles.push(tile);
+                }
+            }
+        }
+        tiles
+    }
+
+    /// Load the atlas description from json. The buffer has to be set separately.
+    pub fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_default()
+    }
+
+    /// Convert the atlas description to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+}
+
+/// Packs many buffers into a single TheAtlas.
+#[derive(Clone, Debug)]
+pub struct TheAtlasBuilder {
+    pub packing: TheAtlasPacking,
+    /// The maximum size of the atlas.
+    pub max_width: i32,
+    pub max_height: i32,
+    /// Removes fully transparent borders before packing.
+    pub trim: bool,
+    /// Empty pixels between the sprites.
+    pub padding: i32,
+    /// Repeats the border pixels of each sprite outwards, avoids bleeding when filtering.
+    pub extrude: i32,
+    /// Rounds the atlas size up to powers of two.
+    pub power_of_two: bool,
+
+    images: Vec<(String, TheRGBABuffer)>,
+    sequences: IndexMap<String, Vec<String>>,
+}
+
+impl Default for TheAtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TheAtlasBuilder {
+    pub fn new() -> Self {
+        Self {
+            packing: TheAtlasPacking::default(),
+            max_width: 4096,
+            max_height: 4096,
+            trim: false,
+            padding: 0,
+            extrude: 0,
+            power_of_two: false,
+            images: vec![],
+            sequences: IndexMap::default(),
+        }
+    }
+
+    /// Adds a named image.
+    pub fn add(&mut self, name: &str, buffer: TheRGBABuffer) {
+        self.images.push((name.to_string(), buffer));
+    }
+
+    /// Adds the frames of a named sequence. The frames are named "name_index".
+    pub fn add_sequence(&mut self, name: &str, frames: Vec<TheRGBABuffer>) {
+        let mut names = vec![];
+        for (index, frame) in frames.into_iter().enumerate() {
+            let frame_name = format!("{}_{}", name, index);
+            self.add(&frame_name, frame);
+            names.push(frame_name);
+        }
+        self.sequences.insert(name.to_string(), names);
+    }
+
+    /// Adds the frames of a tile as a sequence named after the tile.
+    pub fn add_tile(&mut self, tile: &TheRGBATile) {
+        self.add_sequence(&tile.name, tile.buffer.clone());
+    }
+
+    /// Packs all images and builds the atlas.
+    pub fn build(&self) -> Result<TheAtlas, TheAtlasError> {
+        if self.images.is_empty() {
+            return Err(TheAtlasError::Empty);
+        }
+        let mut seen = FxHashSet::default();
+        for (name, _) in &self.images {
+            if !seen.insert(name.as_str()) {
+                return Err(TheAtlasError::DuplicateName(name.clone()));
+            }
+        }
+
+        let padding = self.padding.max(0);
+        let extrude = self.extrude.max(0);
+
+        // Trim and compute the packed cell size of each image
+        let mut trimmed = Vec::with_capacity(self.images.len());
+        for (_, buffer) in &self.images {
+            let bounds = if self.trim {
+                opaque_bounds(buffer)
+            } else {
+                TheDim::sized(buffer.dim().width, buffer.dim().height)
+            };
+            let mut pixels = TheRGBABuffer::new(TheDim::sized(bounds.width, bounds.height));
+            pixels.copy_into(-bounds.x, -bounds.y, buffer);
+            trimmed.push((bounds, pixels));
+        }
+        let sizes: Vec<(i32, i32)> = trimmed
+            .iter()
+            .map(|(bounds, _)| {
+                (
+                    bounds.width + extrude * 2 + padding,
+                    bounds.height + extrude * 2 + padding,
+                )
+            })
+            .collect();
+
+        // The padding is only needed between cells, so the bin gets one padding of slack
+        let (positions, size) = self
+            .pack(&sizes, padding)
+            .ok_or(TheAtlasError::DoesNotFit(self.max_width, self.max_height))?;
+
+        let mut atlas = TheAtlas::new();
+        atlas.width = size.width;
+        atlas.height = size.height;
+        let mut buffer = TheRGBABuffer::new(size);
+
+        for (index, (name, source)) in self.images.iter().enumerate() {
+            let (bounds, pixels) = &trimmed[index];
+            let (x, y) = positions[index];
+            copy_extruded(&mut buffer, x + extrude, y + extrude, pixels, extrude);
+            atlas.sprites.insert(
+                name.clone(),
+                TheAtlasSprite {
+                    region: TheRGBARegion::new(
+                        (x + extrude) as usize,
+                        (y + extrude) as usize,
+                        bounds.width as usize,
+                        bounds.height as usize,
+                    ),
+                    offset_x: bounds.x as usize,
+                    offset_y: bounds.y as usize,
+                    source_width: source.dim().width as usize,
+                    source_height: source.dim().height as usize,
+                },
+            );
+        }
+
+        atlas.sequences = self.sequences.clone();
+        atlas.buffer = buffer;
+        Ok(atlas)
+    }
+
+    /// Finds the smallest bin the cells fit into. Returns the cell positions and the atlas size.
+    fn pack(&self, sizes: &[(i32, i32)], padding: i32) -> Option<(Vec<(i32, i32)>, TheDim)> {
+        let max_width = self.max_width + padding;
+        let max_height = self.max_height + padding;
+
+        let area: i64 = sizes.iter().map(|(w, h)| *w as i64 * *h as i64).sum();
+        let widest = sizes.iter().map(|(w, _)| *w).max()?;
+        let tallest = sizes.iter().map(|(_, h)| *h).max()?;
+        if widest > max_width || tallest > max_height {
+            return None;
+        }
+
+        let round = |v: i32| {
+            if self.power_of_two {
+                (v.max(1) as u32).next_power_of_two() as i32
+            } else {
+                v
+            }
+        };
+
+        // Start with a square of the total area and grow the shorter side until everything fits
+        let side = (area as f64).sqrt().ceil() as i32;
+        let mut bin_width = round(side.max(widest)).min(max_width);
+        let mut bin_height = round(side.max(tallest)).min(max_height);
+
+        loop {
+            let positions = match self.packing {
+                TheAtlasPacking::MaxRects => pack_maxrects(sizes, bin_width, bin_height),
+                TheAtlasPacking::Skyline => pack_skyline(sizes, bin_width, bin_height),
+            };
+            if let Some(positions) = positions {
+                let used_width = positions
+                    .iter()
+                    .zip(sizes)
+                    .map(|((x, _), (w, _))| x + w)
+                    .max()?;
+                let used_height = positions
+                    .iter()
+                    .zip(sizes)
+                    .map(|((_, y), (_, h))| y + h)
+                    .max()?;
+                let width = round((used_width - padding).max(1));
+                let height = round((used_height - padding).max(1));
+                return Some((positions, TheDim::sized(width, height)));
+            }
+
+            if bin_width >= max_width && bin_height >= max_height {
+                return None;
+            }
+            let grow = |v: i32| {
+                if self.power_of_two {
+                    v * 2
+                } else {
+                    v + (v / 8).max(1)
+                }
+            };
+            if (bin_width <= bin_height || bin_height >= max_height) && bin_width < max_width {
+                bin_width = grow(bin_width).min(max_width);
+            } else {
+                bin_height = grow(bin_height).min(max_height);
+            }
+        }
+    }
+}
+
+/// Returns the bounding rectangle of the non transparent pixels, or a single pixel if the buffer
+/// is fully transparent.
+fn opaque_bounds(buffer: &TheRGBABuffer) -> TheDim {
+    let (width, height) = (buffer.dim().width, buffer.dim().height);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, -1, -1);
+    for y in 0..height {
+        for x in 0..width {
+            if buffer.get_pixel(x, y).is_some_and(|p| p[3] > 0) {
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
+                min_y = min_y.min(y);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+    if max_x < 0 {
+        TheDim::sized(width.min(1), height.min(1))
+    } else {
+        TheDim::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+    }
+}
+
+/// Copies the source into the buffer and repeats its border pixels outwards by extrude pixels.
+fn copy_extruded(buffer: &mut TheRGBABuffer, x: i32, y: i32, source: &TheRGBABuffer, extrude: i32) {
+    let (width, height) = (source.dim().width, source.dim().height);
+    if width <= 0 || height <= 0 {
+        // Empty sources have no border pixels to extrude
+        return;
+    }
+    if extrude == 0 {
+        buffer.copy_into(x, y, source);
+        return;
+    }
+    for sy in -extrude..height + extrude {
+        for sx in -extrude..width + extrude {
+            if let Some(pixel) = source.get_pixel(sx.clamp(0, width - 1), sy.clamp(0, height - 1)) {
+                buffer.set_pixel(x + sx, y + sy, &pixel);
+            }
+        }
+    }
+}
+
+/// Returns the packing order of the cells, largest first.
+fn packing_order(sizes: &[(i32, i32)], key: impl Fn(&(i32, i32)) -> (i32, i32)) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(key(&sizes[i])));
+    order
+}
+
+/// MaxRects packing with the best short side fit heuristic.
+fn pack_maxrects(sizes: &[(i32, i32)], width: i32, height: i32) -> Option<Vec<(i32, i32)>> {
+    let mut free = vec![TheDim::new(0, 0, width, height)];
+    let mut positions = vec![(0, 0); sizes.len()];
+
+    for index in packing_order(sizes, |(w, h)| (*w.max(h), w * h)) {
+        let (w, h) = sizes[index];
+
+        let mut best: Option<(TheDim, i32, i32)> = None;
+        for rect in &free {
+            if rect.width >= w && rect.height >= h {
+                let short = (rect.width - w).min(rect.height - h);
+                let long = (rect.width - w).max(rect.height - h);
+                if best.is_none_or(|(_, s, l)| (short, long) < (s, l)) {
+                    best = Some((TheDim::new(rect.x, rect.y, w, h), short, long));
+                }
+            }
+        }
+        let (placed, _, _) = best?;
+        positions[index] = (placed.x, placed.y);
+
+        // Split every free rectangle which intersects the placed one
+        let mut split = Vec::with_capacity(free.len() + 4);
+        for rect in free {
+            if placed.x >= rect.x + rect.width
+                || placed.x + placed.width <= rect.x
+                || placed.y >= rect.y + rect.height
+                || placed.y + placed.height <= rect.y
+            {
+                split.push(rect);
+                continue;
+            }
+            if placed.x > rect.x {
+                split.push(TheDim::new(rect.x, rect.y, placed.x - rect.x, rect.height));
+            }
+            if placed.x + placed.width < rect.x + rect.width {
+                let x = placed.x + placed.width;
+                split.push(TheDim::new(x, rect.y, rect.x + rect.width - x, rect.height));
+            }
+            if placed.y > rect.y {
+                split.push(TheDim::new(rect.x, rect.y, rect.width, placed.y - rect.y));
+            }
+            if placed.y + placed.height < rect.y + rect.height {
+                let y = placed.y + placed.height;
+                split.push(TheDim::new(rect.x, y, rect.width, rect.y + rect.height - y));
+            }
+        }
+
+        // Remove free rectangles contained in others
+        let contains = |a: &TheDim, b: &TheDim| {
+            b.x >= a.x
+                && b.y >= a.y
+                && b.x + b.width <= a.x + a.width
+                && b.y + b.height <= a.y + a.height
+        };
+        free = vec![];
+        for (i, rect) in split.iter().enumerate() {
+            let redundant = split.iter().enumerate().any(|(j, other)| {
+                i != j && contains(other, rect) && (!contains(rect, other) || j < i)
+            });
+            if !redundant {
+                free.push(*rect);
+            }
+        }
+    }
+
+    Some(positions)
+}
+
+/// Skyline packing with the bottom-left heuristic.
+fn pack_skyline(sizes: &[(i32, i32)], width: i32, height: i32) -> Option<Vec<(i32, i32)>> {
+    // Each segment is (x, y, width)
+    let mut skyline = vec![(0, 0, width)];
+    let mut positions = vec![(0, 0); sizes.len()];
+
+    for index in packing_order(sizes, |(w, h)| (*h, *w)) {
+        let (w, h) = sizes[index];
+
+        // Find the segment where the cell rests lowest, ties go to the left
+        let mut best: Option<(usize, i32, i32)> = None;
+        for start in 0..skyline.len() {
+            let x = skyline[start].0;
+            if x + w > width {
+                break;
+            }
+            let mut y = 0;
+            let mut remaining = w;
+            let mut i = start;
+            while remaining > 0 {
+                y = y.max(skyline[i].1);
+                remaining -= skyline[i].2;
+                i += 1;
+            }
+            if y + h <= height && best.is_none_or(|(_, by, bx)| (y, x) < (by, bx)) {
+                best = Some((start, y, x));
+            }
+        }
+        let (start, y, x) = best?;
+        positions[index] = (x, y);
+
+        // Insert the new segment and cut away the segments it covers
+        skyline.insert(start, (x, y + h, w));
+        let next = start + 1;
+        while next < skyline.len() {
+            let (sx, sy, sw) = skyline[next];
+            let covered = x + w - sx;
+            if covered <= 0 {
+                break;
+            }
+            if covered >= sw {
+                skyline.remove(next);
+            } else {
+                skyline[next] = (sx + covered, sy, sw - covered);
+                break;
+            }
+        }
+
+        // Merge neighbouring segments of the same height
+        let mut i = 0;
+        while i + 1 < skyline.len() {
+            if skyline[i].1 == skyline[i + 1].1 {
+                skyline[i].2 += skyline[i + 1].2;
+                skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    Some(positions)
+}
@@ -0,0 +1,511 @@
+use crate::prelude::*;
+
+/// The color spaces TheColor can convert to and mix in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TheColorSpace {
+    /// Gamma encoded sRGB, the space the color values are stored in.
+    #[default]
+    Srgb,
+    LinearRgb,
+    Hsl,
+    Hsv,
+    /// CIE L*a*b* (D65).
+    Lab,
+    /// Cylindrical CIE L*a*b*.
+    Lch,
+    OkLab,
+    /// Cylindrical OKLab.
+    OkLch,
+}
+
+impl TheColorSpace {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Srgb => "sRGB",
+            Self::LinearRgb => "Linear RGB",
+            Self::Hsl => "HSL",
+            Self::Hsv => "HSV",
+            Self::Lab => "Lab",
+            Self::Lch => "LCh",
+            Self::OkLab => "OKLab",
+            Self::OkLch => "OKLCH",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheColorSpace> {
+        [
+            Self::Srgb,
+            Self::LinearRgb,
+            Self::Hsl,
+            Self::Hsv,
+            Self::Lab,
+            Self::Lch,
+            Self::OkLab,
+            Self::OkLch,
+        ]
+        .iter()
+        .copied()
+    }
+}
+
+/// Color harmonies, generated by rotating the hue in OKLCH.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TheColorHarmony {
+    Complementary,
+    SplitComplementary,
+    Analogous,
+    Triadic,
+    Tetradic,
+    Square,
+}
+
+impl TheColorHarmony {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Complementary => "Complementary",
+            Self::SplitComplementary => "Split Complementary",
+            Self::Analogous => "Analogous",
+            Self::Triadic => "Triadic",
+            Self::Tetradic => "Tetradic",
+            Self::Square => "Square",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheColorHarmony> {
+        [
+            Self::Complementary,
+            Self::SplitComplementary,
+            Self::Analogous,
+            Self::Triadic,
+            Self::Tetradic,
+            Self::Square,
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// The hue offsets in degrees, the base color is always at 0.
+    pub fn hue_offsets(self) -> &'static [f32] {
+        match self {
+            Self::Complementary => &[0.0, 180.0],
+            Self::SplitComplementary => &[0.0, 150.0, 210.0],
+            Self::Analogous => &[-30.0, 0.0, 30.0],
+            Self::Triadic => &[0.0, 120.0, 240.0],
+            Self::Tetradic => &[0.0, 60.0, 180.0, 240.0],
+            Self::Square => &[0.0, 90.0, 180.0, 270.0],
+        }
+    }
+}
+
+// D65 reference white
+const WHITE_X: f32 = 0.95047;
+const WHITE_Z: f32 = 1.08883;
+
+// D50 reference white, used by ICC profiles and Adobe Swatch Exchange files
+const D50_WHITE_X: f32 = 0.9642;
+const D50_WHITE_Z: f32 = 0.8249;
+
+/// Converts CIE L*a*b* to XYZ relative to the given reference white.
+#[inline]
+fn lab_to_xyz(l: f32, a: f32, b: f32, white_x: f32, white_z: f32) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let inv = |t: f32| {
+        if t * t * t > 216.0 / 24389.0 {
+            t * t * t
+        } else {
+            (116.0 * t - 16.0) * 27.0 / 24389.0
+        }
+    };
+    [inv(fx) * white_x, inv(fy), inv(fz) * white_z]
+}
+
+/// Converts a gamma encoded sRGB channel to linear.
+#[inline]
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a linear channel to gamma encoded sRGB.
+#[inline]
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts linear sRGB to OKLab.
+#[inline]
+pub fn linear_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+/// Converts OKLab to linear sRGB.
+#[inline]
+pub fn oklab_to_linear(lab: [f32; 3]) -> [f32; 3] {
+    let l = (lab[0] + 0.39633778 * lab[1] + 0.21580376 * lab[2]).powi(3);
+    let m = (lab[0] - 0.105561346 * lab[1] - 0.06385417 * lab[2]).powi(3);
+    let s = (lab[0] - 0.08948418 * lab[1] - 1.2914855 * lab[2]).powi(3);
+    [
+        4.0767417 * l - 3.3077116 * m + 0.23096994 * s,
+        -1.268438 * l + 2.6097574 * m - 0.34131938 * s,
+        -0.0041960863 * l - 0.7034186 * m + 1.7076147 * s,
+    ]
+}
+
+/// Converts polar (lightness, chroma, hue in degrees) to rectangular coordinates.
+fn polar_to_rect(l: f32, c: f32, h: f32) -> Vec3<f32> {
+    let h = h.to_radians();
+    Vec3::new(l, c * h.cos(), c * h.sin())
+}
+
+/// Converts rectangular to polar (lightness, chroma, hue in degrees) coordinates.
+fn rect_to_polar(lab: Vec3<f32>) -> Vec3<f32> {
+    let c = (lab.y * lab.y + lab.z * lab.z).sqrt();
+    let h = lab.z.atan2(lab.y).to_degrees().rem_euclid(360.0);
+    Vec3::new(lab.x, c, h)
+}
+
+/// Color space conversions and color math for TheColor. The color values are treated as gamma
+/// encoded sRGB, results converted back to sRGB are clamped into the gamut.
+impl TheColor {
+    /// Creates a color from hsv, the hue is in degrees.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let h = (h.rem_euclid(360.0)) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as i32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Self::new(r + m, g + m, b + m, 1.0)
+    }
+
+    /// Convert the color to HSV, the hue is in 0..1 like in as_hsl.
+    pub fn as_hsv(&self) -> Vec3<f32> {
+        let max = self.r.max(self.g.max(self.b));
+        let min = self.r.min(self.g.min(self.b));
+        let d = max - min;
+
+        let h = if d == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / d).rem_euclid(6.0) / 6.0
+        } else if max == self.g {
+            ((self.b - self.r) / d + 2.0) / 6.0
+        } else {
+            ((self.r - self.g) / d + 4.0) / 6.0
+        };
+        let s = if max == 0.0 { 0.0 } else { d / max };
+
+        Vec3::new(h, s, max)
+    }
+
+    /// Converts the color to linear RGB.
+    pub fn to_linear(&self) -> TheColor {
+        TheColor::new(
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        )
+    }
+
+    /// Creates a color from linear RGB values.
+    pub fn from_linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::new(
+            linear_to_srgb(r.clamp(0.0, 1.0)),
+            linear_to_srgb(g.clamp(0.0, 1.0)),
+            linear_to_srgb(b.clamp(0.0, 1.0)),
+            a,
+        )
+    }
+
+    /// Convert the color to CIE L*a*b*, L is in 0..100.
+    pub fn as_lab(&self) -> Vec3<f32> {
+        let lin = self.to_linear();
+        let x = (0.4124564 * lin.r + 0.3575761 * lin.g + 0.1804375 * lin.b) / WHITE_X;
+        let y = 0.2126729 * lin.r + 0.7151522 * lin.g + 0.072175 * lin.b;
+        let z = (0.0193339 * lin.r + 0.119192 * lin.g + 0.9503041 * lin.b) / WHITE_Z;
+
+        let f = |t: f32| {
+            if t > 216.0 / 24389.0 {
+                t.cbrt()
+            } else {
+                (24389.0 / 27.0 * t + 16.0) / 116.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Vec3::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+    }
+
+    /// Creates a color from CIE L*a*b*, L is in 0..100.
+    pub fn from_lab(l: f32, a: f32, b: f32) -> Self {
+        let [x, y, z] = lab_to_xyz(l, a, b, WHITE_X, WHITE_Z);
+
+        Self::from_linear(
+            3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+            -0.969266 * x + 1.8760108 * y + 0.041556 * z,
+            0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+            1.0,
+        )
+    }
+
+    /// Creates a color from CIE L*a*b* relative to the D50 white point, L is in 0..100.
+    pub fn from_lab_d50(l: f32, a: f32, b: f32) -> Self {
+        let [x, y, z] = lab_to_xyz(l, a, b, D50_WHITE_X, D50_WHITE_Z);
+
+        // Bradford adapted D50 XYZ to linear sRGB
+        Self::from_linear(
+            3.1339 * x - 1.6169 * y - 0.4906 * z,
+            -0.9788 * x + 1.9161 * y + 0.0335 * z,
+            0.0719 * x - 0.2290 * y + 1.4052 * z,
+            1.0,
+        )
+    }
+
+    /// Convert the color to CIE LCh, the hue is in degrees.
+    pub fn as_lch(&self) -> Vec3<f32> {
+        rect_to_polar(self.as_lab())
+    }
+
+    /// Creates a color from CIE LCh, the hue is in degrees.
+    pub fn from_lch(l: f32, c: f32, h: f32) -> Self {
+        let lab = polar_to_rect(l, c, h);
+        Self::from_lab(lab.x, lab.y, lab.z)
+    }
+
+    /// Convert the color to OKLab, L is in 0..1.
+    pub fn as_oklab(&self) -> Vec3<f32> {
+        let lin = self.to_linear();
+        Vec3::from(linear_to_oklab([lin.r, lin.g, lin.b]))
+    }
+
+    /// Creates a color from OKLab, L is in 0..1.
+    pub fn from_oklab(l: f32, a: f32, b: f32) -> Self {
+        let [r, g, b] = oklab_to_linear([l, a, b]);
+        Self::from_linear(r, g, b, 1.0)
+    }
+
+    /// Convert the color to OKLCH, the hue is in degrees.
+    pub fn as_oklch(&self) -> Vec3<f32> {
+        rect_to_polar(self.as_oklab())
+    }
+
+    /// Creates a color from OKLCH, the hue is in degrees.
+    pub fn from_oklch(l: f32, c: f32, h: f32) -> Self {
+        let lab = polar_to_rect(l, c, h);
+        Self::from_oklab(lab.x, lab.y, lab.z)
+    }
+
+    /// Returns the color as coordinates in the given space. Hues are in degrees.
+    pub fn to_space(&self, space: TheColorSpace) -> Vec3<f32> {
+        match space {
+            TheColorSpace::Srgb => self.to_vec3(),
+            TheColorSpace::LinearRgb => self.to_linear().to_vec3(),
+            TheColorSpace::Hsl => {
+                let hsl = self.as_hsl();
+                Vec3::new(hsl.x * 360.0, hsl.y, hsl.z)
+            }
+            TheColorSpace::Hsv => {
+                let hsv = self.as_hsv();
+                Vec3::new(hsv.x * 360.0, hsv.y, hsv.z)
+            }
+            TheColorSpace::Lab => self.as_lab(),
+            TheColorSpace::Lch => self.as_lch(),
+            TheColorSpace::OkLab => self.as_oklab(),
+            TheColorSpace::OkLch => self.as_oklch(),
+        }
+    }
+
+    /// Creates a color from coordinates in the given space. Hues are in degrees.
+    pub fn from_space(v: Vec3<f32>, space: TheColorSpace) -> Self {
+        match space {
+            TheColorSpace::Srgb => Self::new(v.x, v.y, v.z, 1.0),
+            TheColorSpace::LinearRgb => Self::from_linear(v.x, v.y, v.z, 1.0),
+            TheColorSpace::Hsl => Self::from_hsl(v.x.rem_euclid(360.0), v.y, v.z),
+            TheColorSpace::Hsv => Self::from_hsv(v.x, v.y, v.z),
+            TheColorSpace::Lab => Self::from_lab(v.x, v.y, v.z),
+            TheColorSpace::Lch => Self::from_lch(v.x, v.y, v.z),
+            TheColorSpace::OkLab => Self::from_oklab(v.x, v.y, v.z),
+            TheColorSpace::OkLch => Self::from_oklch(v.x, v.y, v.z),
+        }
+    }
+
+    /// Mixes self and other in the given space. Hues take the shorter way around the circle.
+    pub fn mix_in(&self, other: &TheColor, v: f32, space: TheColorSpace) -> TheColor {
+        let a = self.to_space(space);
+        let b = other.to_space(space);
+
+        let mut mixed = a * (1.0 - v) + b * v;
+        let hue = match space {
+            TheColorSpace::Hsl | TheColorSpace::Hsv => Some(0),
+            TheColorSpace::Lch | TheColorSpace::OkLch => Some(2),
+            _ => None,
+        };
+        if let Some(i) = hue {
+            // Achromatic colors have no meaningful hue, use the one of the other color
+            let (mut a, mut b) = (a, b);
+            if a[1] < 1e-4 {
+                a[i] = b[i];
+            } else if b[1] < 1e-4 {
+                b[i] = a[i];
+            }
+            let delta = (b[i] - a[i] + 180.0).rem_euclid(360.0) - 180.0;
+            mixed[i] = (a[i] + delta * v).rem_euclid(360.0);
+        }
+
+        let mut color = Self::from_space(mixed, space);
+        color.a = (1.0 - v) * self.a + other.a * v;
+        color
+    }
+
+    /// The CIE76 color difference, the euclidean distance in Lab.
+    pub fn delta_e_76(&self, other: &TheColor) -> f32 {
+        self.as_lab().distance(other.as_lab())
+    }
+
+    /// The CIEDE2000 color difference. A value below 1 is not perceptible, around 2.3 is a just
+    /// noticeable difference.
+    pub fn delta_e(&self, other: &TheColor) -> f32 {
+        let (lab1, lab2) = (self.as_lab(), other.as_lab());
+
+        let c1 = (lab1.y * lab1.y + lab1.z * lab1.z).sqrt();
+        let c2 = (lab2.y * lab2.y + lab2.z * lab2.z).sqrt();
+        let c_mean7 = ((c1 + c2) / 2.0).powi(7);
+        let g = 0.5 * (1.0 - (c_mean7 / (c_mean7 + 25.0f32.powi(7))).sqrt());
+
+        let a1 = lab1.y * (1.0 + g);
+        let a2 = lab2.y * (1.0 + g);
+        let c1 = (a1 * a1 + lab1.z * lab1.z).sqrt();
+        let c2 = (a2 * a2 + lab2.z * lab2.z).sqrt();
+        let hue = |b: f32, a: f32| {
+            if a == 0.0 && b == 0.0 {
+                0.0
+            } else {
+                b.atan2(a).to_degrees().rem_euclid(360.0)
+            }
+        };
+        let h1 = hue(lab1.z, a1);
+        let h2 = hue(lab2.z, a2);
+
+        let dl = lab2.x - lab1.x;
+        let dc = c2 - c1;
+        let dh = if c1 * c2 == 0.0 {
+            0.0
+        } else {
+            (h2 - h1 + 180.0).rem_euclid(360.0) - 180.0
+        };
+        let dh = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).to_radians().sin();
+
+        let l_mean = (lab1.x + lab2.x) / 2.0;
+        let c_mean = (c1 + c2) / 2.0;
+        let h_mean = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) / 2.0
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+
+        let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos()
+            + 0.24 * (2.0 * h_mean).to_radians().cos()
+            + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+            - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+        let l50 = (l_mean - 50.0).powi(2);
+        let sl = 1.0 + 0.015 * l50 / (20.0 + l50).sqrt();
+        let sc = 1.0 + 0.045 * c_mean;
+        let sh = 1.0 + 0.015 * c_mean * t;
+        let c_mean7 = c_mean.powi(7);
+        let rc = 2.0 * (c_mean7 / (c_mean7 + 25.0f32.powi(7))).sqrt();
+        let d_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+        let rt = -rc * (2.0 * d_theta).to_radians().sin();
+
+        let (l, c, h) = (dl / sl, dc / sc, dh / sh);
+        (l * l + c * c + h * h + rt * c * h).sqrt()
+    }
+
+    /// The euclidean distance in OKLab (deltaEOK in CSS Color 4), a just noticeable difference is
+    /// around 0.02.
+    pub fn delta_e_ok(&self, other: &TheColor) -> f32 {
+        self.as_oklab().distance(other.as_oklab())
+    }
+
+    /// The WCAG relative luminance of the color.
+    pub fn relative_luminance(&self) -> f32 {
+        let lin = self.to_linear();
+        0.2126 * lin.r + 0.7152 * lin.g + 0.0722 * lin.b
+    }
+
+    /// The WCAG contrast ratio between the two colors, from 1 to 21. Text needs at least 4.5 (AA)
+    /// or 7 (AAA) against its background.
+    pub fn contrast_ratio(&self, other: &TheColor) -> f32 {
+        let l1 = self.relative_luminance();
+        let l2 = other.relative_luminance();
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
+    /// Returns count tints, the color mixed towards white in OKLab, lightest last.
+    pub fn tints(&self, count: usize) -> Vec<TheColor> {
+        self.ramp_to(&TheColor::white(), count)
+    }
+
+    /// Returns count shades, the color mixed towards black in OKLab, darkest last.
+    pub fn shades(&self, count: usize) -> Vec<TheColor> {
+        self.ramp_to(&TheColor::black(), count)
+    }
+
+    /// Returns count tones, the color mixed towards the gray of the same lightness in OKLab.
+    pub fn tones(&self, count: usize) -> Vec<TheColor> {
+        let lab = self.as_oklab();
+        self.ramp_to(&TheColor::from_oklab(lab.x, 0.0, 0.0), count)
+    }
+
+    /// Returns the colors of the harmony, keeping the lightness and chroma of the color.
+    pub fn harmony(&self, harmony: TheColorHarmony) -> Vec<TheColor> {
+        let lch = self.as_oklch();
+        harmony
+            .hue_offsets()
+            .iter()
+            .map(|offset| {
+                let mut color = Self::from_oklch(lch.x, lch.y, lch.z + offset);
+                color.a = self.a;
+                color
+            })
+            .collect()
+    }
+
+    /// Mixes towards the target in count even steps, excluding both self and the target.
+    fn ramp_to(&self, target: &TheColor, count: usize) -> Vec<TheColor> {
+        (1..=count)
+            .map(|i| {
+                let v = i as f32 / (count + 1) as f32;
+                let mut color = self.mix_in(target, v, TheColorSpace::OkLab);
+                color.a = self.a;
+                color
+            })
+            .collect()
+    }
+}
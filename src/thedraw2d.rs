@@ -7,6 +7,7 @@ use fontdue::layout::{
 use fontdue::{Font, Metrics};
 use vek::*;
 
+use crate::theblendmode::TheBlendMode;
 use crate::Embedded;
 
 #[derive(Default, Clone)]
@@ -156,6 +157,25 @@ impl TheDraw2D {
         }
     }
 
+    /// Blend the given rectangle using the given blend mode
+    pub fn blend_rect_mode(
+        &self,
+        frame: &mut [u8],
+        rect: &(usize, usize, usize, usize),
+        stride: usize,
+        color: &[u8; 4],
+        mode: TheBlendMode,
+    ) {
+        for y in rect.1..rect.1 + rect.3 {
+            for x in rect.0..rect.0 + rect.2 {
+                let i = x * 4 + y * stride * 4;
+
+                let background = &[frame[i], frame[i + 1], frame[i + 2], frame[i + 3]];
+                frame[i..i + 4].copy_from_slice(&mode.blend_pixel(background, color, 1.0));
+            }
+        }
+    }
+
     /// Draws the outline of a given rectangle
     pub fn rect_outline(
         &self,
@@ -1117,6 +1137,31 @@ impl TheDraw2D {
         }
     }
 
+    /// Blends rect from the source frame into the dest frame using the given blend mode and opacity
+    pub fn blend_slice_mode(
+        &self,
+        dest: &mut [u8],
+        source: &[u8],
+        rect: &(usize, usize, usize, usize),
+        dest_stride: usize,
+        mode: TheBlendMode,
+        opacity: f32,
+    ) {
+        for y in 0..rect.3 {
+            let d = rect.0 * 4 + (y + rect.1) * dest_stride * 4;
+            let s = y * rect.2 * 4;
+
+            for x in 0..rect.2 {
+                let dd = d + x * 4;
+                let ss = s + x * 4;
+
+                let background = &[dest[dd], dest[dd + 1], dest[dd + 2], dest[dd + 3]];
+                let color = &[source[ss], source[ss + 1], source[ss + 2], source[ss + 3]];
+                dest[dd..dd + 4].copy_from_slice(&mode.blend_pixel(background, color, opacity));
+            }
+        }
+    }
+
     /// Blends rect from the source frame into the dest frame
     pub fn blend_slice_alpha(
         &self,
@@ -0,0 +1,356 @@
+use crate::prelude::*;
+use crate::theblendmode::{premultiply, unpremultiply};
+use rayon::prelude::*;
+
+/// A square convolution kernel (3x3 or 5x5) applied by TheRGBABuffer::convolve.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TheConvolutionKernel {
+    size: usize,
+    weights: Vec<f32>,
+    /// The weighted sum is divided by the divisor.
+    pub divisor: f32,
+    /// The bias is added to the color channels after dividing, in 0..1.
+    pub bias: f32,
+}
+
+impl TheConvolutionKernel {
+    /// Creates a 3x3 kernel, the divisor is the sum of the weights (or 1 if the sum is zero).
+    pub fn new_3x3(weights: [f32; 9]) -> Self {
+        Self::from_weights(3, weights.to_vec())
+    }
+
+    /// Creates a 5x5 kernel, the divisor is the sum of the weights (or 1 if the sum is zero).
+    pub fn new_5x5(weights: [f32; 25]) -> Self {
+        Self::from_weights(5, weights.to_vec())
+    }
+
+    fn from_weights(size: usize, weights: Vec<f32>) -> Self {
+        let sum: f32 = weights.iter().sum();
+        Self {
+            size,
+            weights,
+            divisor: if sum.abs() < f32::EPSILON { 1.0 } else { sum },
+            bias: 0.0,
+        }
+    }
+
+    /// Sets the bias, useful for kernels summing to zero like emboss or edge detection.
+    pub fn with_bias(mut self, bias: f32) -> Self {
+        self.bias = bias;
+        self
+    }
+
+    /// The width and height of the kernel.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The row major weights of the kernel.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// A sharpen kernel of the given strength.
+    pub fn sharpen(amount: f32) -> Self {
+        Self::new_3x3([
+            0.0,
+            -amount,
+            0.0,
+            -amount,
+            1.0 + 4.0 * amount,
+            -amount,
+            0.0,
+            -amount,
+            0.0,
+        ])
+    }
+
+    /// A laplacian edge detection kernel.
+    pub fn edge_detect() -> Self {
+        Self::new_3x3([-1.0, -1.0, -1.0, -1.0, 8.0, -1.0, -1.0, -1.0, -1.0])
+    }
+
+    /// An emboss kernel.
+    pub fn emboss() -> Self {
+        Self::new_3x3([-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0])
+    }
+
+    /// A 5x5 gaussian approximation.
+    pub fn gaussian_5x5() -> Self {
+        Self::new_5x5([
+            1.0, 4.0, 6.0, 4.0, 1.0, 4.0, 16.0, 24.0, 16.0, 4.0, 6.0, 24.0, 36.0, 24.0, 6.0, 4.0,
+            16.0, 24.0, 16.0, 4.0, 1.0, 4.0, 6.0, 4.0, 1.0,
+        ])
+    }
+}
+
+/// Returns the normalized 1D gaussian kernel for the given sigma, covering three standard deviations.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let radius = (sigma * 3.0).ceil() as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    for w in kernel.iter_mut() {
+        *w /= sum;
+    }
+    kernel
+}
+
+/// Image filters for TheRGBABuffer. Blurs and convolutions operate on premultiplied alpha so that
+/// transparent pixels do not bleed into the result, color adjustments operate on straight colors.
+impl TheRGBABuffer {
+    /// Blurs the buffer with a gaussian of the given standard deviation in pixels.
+    pub fn gaussian_blur(&mut self, sigma: f32) {
+        if sigma > 0.0 {
+            self.convolve_separable(&gaussian_kernel(sigma));
+        }
+    }
+
+    /// Blurs the buffer with a box filter of the given radius in pixels.
+    pub fn box_blur(&mut self, radius: usize) {
+        if radius > 0 {
+            let size = radius * 2 + 1;
+            self.convolve_separable(&vec![1.0 / size as f32; size]);
+        }
+    }
+
+    /// Sharpens the buffer, an amount of 1.0 is a strong sharpen.
+    pub fn sharpen(&mut self, amount: f32) {
+        if amount > 0.0 {
+            self.convolve(&TheConvolutionKernel::sharpen(amount));
+        }
+    }
+
+    /// Applies a separable kernel horizontally and then vertically. Edges are clamped.
+    pub fn convolve_separable(&mut self, kernel: &[f32]) {
+        let width = self.dim().width.max(0) as usize;
+        let height = self.dim().height.max(0) as usize;
+        if width == 0 || height == 0 || kernel.is_empty() {
+            return;
+        }
+        let radius = (kernel.len() / 2) as i32;
+        let premultiplied = self.is_premultiplied();
+        let src = to_premultiplied_f32(self.pixels(), premultiplied);
+
+        // Horizontal pass
+        let mut tmp = vec![0.0_f32; width * height * 4];
+        tmp.par_chunks_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let src_row = &src[y * width * 4..(y + 1) * width * 4];
+                for x in 0..width {
+                    let mut acc = [0.0_f32; 4];
+                    for (k, w) in kernel.iter().enumerate() {
+                        let sx = (x as i32 + k as i32 - radius).clamp(0, width as i32 - 1) as usize;
+                        for c in 0..4 {
+                            acc[c] += src_row[sx * 4 + c] * w;
+                        }
+                    }
+                    row[x * 4..x * 4 + 4].copy_from_slice(&acc);
+                }
+            });
+
+        // Vertical pass
+        self.pixels_mut()
+            .par_chunks_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let mut acc = [0.0_f32; 4];
+                    for (k, w) in kernel.iter().enumerate() {
+                        let sy =
+                            (y as i32 + k as i32 - radius).clamp(0, height as i32 - 1) as usize;
+                        let o = (sy * width + x) * 4;
+                        for c in 0..4 {
+                            acc[c] += tmp[o + c] * w;
+                        }
+                    }
+                    row[x * 4..x * 4 + 4]
+                        .copy_from_slice(&from_premultiplied_f32(acc, premultiplied));
+                }
+            });
+    }
+
+    /// Applies a 3x3 or 5x5 convolution kernel. Edges are clamped, alpha is preserved.
+    pub fn convolve(&mut self, kernel: &TheConvolutionKernel) {
+        let width = self.dim().width.max(0) as usize;
+        let height = self.dim().height.max(0) as usize;
+        if width == 0 || height == 0 || kernel.weights.len() != kernel.size * kernel.size {
+            return;
+        }
+        let radius = (kernel.size / 2) as i32;
+        let premultiplied = self.is_premultiplied();
+        let src = to_premultiplied_f32(self.pixels(), premultiplied);
+
+        self.pixels_mut()
+            .par_chunks_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let center = (y * width + x) * 4;
+                    let alpha = src[center + 3];
+                    let mut acc = [0.0_f32; 3];
+                    for ky in 0..kernel.size {
+                        let sy =
+                            (y as i32 + ky as i32 - radius).clamp(0, height as i32 - 1) as usize;
+                        for kx in 0..kernel.size {
+                            let sx =
+                                (x as i32 + kx as i32 - radius).clamp(0, width as i32 - 1) as usize;
+                            let w = kernel.weights[ky * kernel.size + kx];
+                            let o = (sy * width + sx) * 4;
+                            for c in 0..3 {
+                                acc[c] += src[o + c] * w;
+                            }
+                        }
+                    }
+                    let mut out = [0.0, 0.0, 0.0, alpha];
+                    for c in 0..3 {
+                        out[c] = (acc[c] / kernel.divisor + kernel.bias * alpha).clamp(0.0, alpha);
+                    }
+                    row[x * 4..x * 4 + 4]
+                        .copy_from_slice(&from_premultiplied_f32(out, premultiplied));
+                }
+            });
+    }
+
+    /// Calls the function for every pixel with its straight alpha color and stores the result.
+    pub fn map_colors<F>(&mut self, f: F)
+    where
+        F: Fn([u8; 4]) -> [u8; 4] + Sync,
+    {
+        let premultiplied = self.is_premultiplied();
+        self.pixels_mut().par_chunks_exact_mut(4).for_each(|p| {
+            let mut color = [p[0], p[1], p[2], p[3]];
+            if premultiplied {
+                color = unpremultiply(&color);
+            }
+            color = f(color);
+            if premultiplied {
+                color = premultiply(&color);
+            }
+            p.copy_from_slice(&color);
+        });
+    }
+
+    /// Adjusts brightness and contrast, both in -1..1 where 0 leaves the image unchanged.
+    pub fn brightness_contrast(&mut self, brightness: f32, contrast: f32) {
+        let contrast = contrast.clamp(-1.0, 1.0);
+        let factor = if contrast >= 0.0 {
+            1.0 / (1.0 - contrast * 0.99)
+        } else {
+            1.0 + contrast
+        };
+        let lut: [u8; 256] = std::array::from_fn(|i| {
+            let v = i as f32 / 255.0 + brightness;
+            let v = (v - 0.5) * factor + 0.5;
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        });
+        self.map_colors(|c| {
+            [
+                lut[c[0] as usize],
+                lut[c[1] as usize],
+                lut[c[2] as usize],
+                c[3],
+            ]
+        });
+    }
+
+    /// Rotates the hue by the given degrees, scales the saturation and adds to the lightness (-1..1).
+    pub fn hue_saturation(&mut self, hue: f32, saturation: f32, lightness: f32) {
+        self.map_colors(|c| {
+            let hsl = TheColor::from_u8_array(c).as_hsl();
+            let h = (hsl.x * 360.0 + hue).rem_euclid(360.0);
+            let s = (hsl.y * saturation).clamp(0.0, 1.0);
+            let l = (hsl.z + lightness).clamp(0.0, 1.0);
+            let rgb = TheColor::from_hsl(h, s, l);
+            [
+                (rgb.r * 255.0).round() as u8,
+                (rgb.g * 255.0).round() as u8,
+                (rgb.b * 255.0).round() as u8,
+                c[3],
+            ]
+        });
+    }
+
+    /// Remaps the input range to the output range with the given gamma correction.
+    pub fn levels(&mut self, in_black: u8, in_white: u8, gamma: f32, out_black: u8, out_white: u8) {
+        let in_range = (in_white as f32 - in_black as f32).max(1.0);
+        let inv_gamma = 1.0 / gamma.max(0.01);
+        let lut: [u8; 256] = std::array::from_fn(|i| {
+            let v = ((i as f32 - in_black as f32) / in_range).clamp(0.0, 1.0);
+            let v = v.powf(inv_gamma);
+            let v = out_black as f32 + v * (out_white as f32 - out_black as f32);
+            v.round().clamp(0.0, 255.0) as u8
+        });
+        self.map_colors(|c| {
+            [
+                lut[c[0] as usize],
+                lut[c[1] as usize],
+                lut[c[2] as usize],
+                c[3],
+            ]
+        });
+    }
+
+    /// Inverts the color channels.
+    pub fn invert(&mut self) {
+        self.map_colors(|c| [255 - c[0], 255 - c[1], 255 - c[2], c[3]]);
+    }
+
+    /// Converts the colors to grayscale using the Rec. 709 luma weights.
+    pub fn grayscale(&mut self) {
+        self.map_colors(|c| {
+            let l = luma(&c);
+            [l, l, l, c[3]]
+        });
+    }
+
+    /// Sets colors with a luma above or equal to the level to white, all others to black.
+    pub fn threshold(&mut self, level: u8) {
+        self.map_colors(|c| {
+            let v = if luma(&c) >= level { 255 } else { 0 };
+            [v, v, v, c[3]]
+        });
+    }
+}
+
+#[inline]
+fn luma(c: &[u8; 4]) -> u8 {
+    (0.2126 * c[0] as f32 + 0.7152 * c[1] as f32 + 0.0722 * c[2] as f32).round() as u8
+}
+
+/// Converts the pixels to premultiplied f32 values in 0..1.
+fn to_premultiplied_f32(pixels: &[u8], premultiplied: bool) -> Vec<f32> {
+    let mut out = vec![0.0_f32; pixels.len()];
+    out.par_chunks_exact_mut(4)
+        .zip(pixels.par_chunks_exact(4))
+        .for_each(|(o, p)| {
+            let a = p[3] as f32 / 255.0;
+            let m = if premultiplied { 1.0 } else { a };
+            o[0] = p[0] as f32 / 255.0 * m;
+            o[1] = p[1] as f32 / 255.0 * m;
+            o[2] = p[2] as f32 / 255.0 * m;
+            o[3] = a;
+        });
+    out
+}
+
+/// Converts a premultiplied f32 pixel back to u8, either premultiplied or straight.
+#[inline]
+fn from_premultiplied_f32(p: [f32; 4], premultiplied: bool) -> [u8; 4] {
+    let a = p[3].clamp(0.0, 1.0);
+    if a <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let d = if premultiplied { 1.0 } else { a };
+    [
+        ((p[0] / d).clamp(0.0, 1.0) * 255.0).round() as u8,
+        ((p[1] / d).clamp(0.0, 1.0) * 255.0).round() as u8,
+        ((p[2] / d).clamp(0.0, 1.0) * 255.0).round() as u8,
+        (a * 255.0).round() as u8,
+    ]
+}
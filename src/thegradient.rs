@@ -0,0 +1,155 @@
+use crate::prelude::*;
+
+/// A color at a position (0..1) of a gradient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TheGradientStop {
+    pub position: f32,
+    pub color: TheColor,
+}
+
+impl TheGradientStop {
+    pub fn new(position: f32, color: TheColor) -> Self {
+        Self {
+            position: position.clamp(0.0, 1.0),
+            color,
+        }
+    }
+}
+
+/// A color gradient. The stops are kept sorted by position and are interpolated in the given color space.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TheGradient {
+    pub stops: Vec<TheGradientStop>,
+    #[serde(default)]
+    pub space: TheColorSpace,
+}
+
+impl Default for TheGradient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TheGradient {
+    /// A black to white gradient.
+    pub fn new() -> Self {
+        Self::two(TheColor::black(), TheColor::white())
+    }
+
+    /// A gradient between two colors.
+    pub fn two(start: TheColor, end: TheColor) -> Self {
+        Self {
+            stops: vec![
+                TheGradientStop::new(0.0, start),
+                TheGradientStop::new(1.0, end),
+            ],
+            space: TheColorSpace::default(),
+        }
+    }
+
+    /// Adds a stop and returns its index.
+    pub fn add_stop(&mut self, position: f32, color: TheColor) -> usize {
+        let stop = TheGradientStop::new(position, color);
+        let index = self
+            .stops
+            .iter()
+            .position(|s| s.position > stop.position)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(index, stop);
+        index
+    }
+
+    /// Removes the stop at the index.
+    pub fn remove_stop(&mut self, index: usize) -> Option<TheGradientStop> {
+        if index < self.stops.len() {
+            Some(self.stops.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Moves the stop at the index to the position and returns its new index.
+    pub fn set_stop_position(&mut self, index: usize, position: f32) -> usize {
+        if let Some(stop) = self.remove_stop(index) {
+            self.add_stop(position, stop.color)
+        } else {
+            index
+        }
+    }
+
+    /// Sets the color of the stop at the index.
+    pub fn set_stop_color(&mut self, index: usize, color: TheColor) {
+        if let Some(stop) = self.stops.get_mut(index) {
+            stop.color = color;
+        }
+    }
+
+    /// Sorts the stops by position.
+    pub fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    /// Returns the color of the gradient at t (0..1).
+    pub fn sample(&self, t: f32) -> TheColor {
+        let Some(first) = self.stops.first() else {
+            return TheColor::default();
+        };
+
+        let t = t.clamp(0.0, 1.0);
+        if t <= first.position {
+            return first.color.clone();
+        }
+
+        for pair in self.stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if t <= b.position {
+                let span = b.position - a.position;
+                if span <= f32::EPSILON {
+                    return b.color.clone();
+                }
+                return a
+                    .color
+                    .mix_in(&b.color, (t - a.position) / span, self.space);
+            }
+        }
+
+        self.stops[self.stops.len() - 1].color.clone()
+    }
+
+    /// Returns `count` evenly spaced samples of the gradient.
+    pub fn colors(&self, count: usize) -> Vec<TheColor> {
+        let div = count.saturating_sub(1).max(1) as f32;
+        (0..count).map(|i| self.sample(i as f32 / div)).collect()
+    }
+
+    /// Blends two gradients. The result has a stop at every position of both gradients.
+    pub fn lerp(&self, other: &TheGradient, t: f32) -> TheGradient {
+        let mut positions: Vec<f32> = self
+            .stops
+            .iter()
+            .chain(other.stops.iter())
+            .map(|s| s.position)
+            .collect();
+        positions.sort_by(|a, b| a.total_cmp(b));
+        positions.dedup_by(|a, b| (*a - *b).abs() < 0.0001);
+
+        let space = if t < 0.5 { self.space } else { other.space };
+        TheGradient {
+            stops: positions
+                .into_iter()
+                .map(|p| TheGradientStop::new(p, self.sample(p).mix_in(&other.sample(p), t, space)))
+                .collect(),
+            space,
+        }
+    }
+
+    /// Load a gradient from json.
+    pub fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_default()
+    }
+
+    /// Convert the gradient to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+}
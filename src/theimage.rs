@@ -0,0 +1,392 @@
+use crate::prelude::*;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Errors which can occur while decoding an image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TheImageError {
+    /// The file could not be read.
+    Io(String),
+    /// The data is not in a supported image format.
+    UnsupportedFormat,
+    /// The decoder rejected the data.
+    Decoding(String),
+}
+
+impl std::fmt::Display for TheImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::UnsupportedFormat => write!(f, "Unsupported image format"),
+            Self::Decoding(err) => write!(f, "Decoding error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TheImageError {}
+
+impl From<png::DecodingError> for TheImageError {
+    fn from(err: png::DecodingError) -> Self {
+        Self::Decoding(err.to_string())
+    }
+}
+
+impl From<gif::DecodingError> for TheImageError {
+    fn from(err: gif::DecodingError) -> Self {
+        Self::Decoding(err.to_string())
+    }
+}
+
+/// The image formats TheFramework can decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TheImageFormat {
+    Png,
+    Gif,
+}
+
+impl TheImageFormat {
+    /// Detects the format from the file signature.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+            Some(Self::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else {
+            None
+        }
+    }
+}
+
+/// A single decoded frame of an image sequence.
+#[derive(Clone, Debug)]
+pub struct TheImageFrame {
+    /// The fully composited frame.
+    pub buffer: TheRGBABuffer,
+    /// The display time of the frame in milliseconds.
+    pub delay: u32,
+}
+
+/// TheImageSequence holds the frames of an animated GIF or APNG. Still images decode into a single frame.
+#[derive(Clone, Debug)]
+pub struct TheImageSequence {
+    pub width: i32,
+    pub height: i32,
+    pub frames: Vec<TheImageFrame>,
+    /// How often the animation should be played, None for infinite looping.
+    pub loop_count: Option<u32>,
+}
+
+impl TheImageSequence {
+    /// Decodes a PNG (including APNG) or GIF from memory.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        match TheImageFormat::detect(data) {
+            Some(TheImageFormat::Png) => Self::from_png_bytes(data),
+            Some(TheImageFormat::Gif) => Self::from_gif_bytes(data),
+            None => Err(TheImageError::UnsupportedFormat),
+        }
+    }
+
+    /// Decodes a PNG (including APNG) or GIF file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, TheImageError> {
+        let data = std::fs::read(path).map_err(|err| TheImageError::Io(err.to_string()))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Decodes a PNG or an animated PNG. Frames are composited according to their blend and dispose operations.
+    pub fn from_png_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        let mut decoder = png::Decoder::new(Cursor::new(data));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+
+        let (width, height) = reader.info().size();
+        let (width, height) = (width as i32, height as i32);
+
+        let Some(animation) = reader.info().animation_control else {
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf)?;
+            let buffer = png_output_to_rgba(&buf[..info.buffer_size()], info.color_type)?;
+            return Ok(Self {
+                width,
+                height,
+                frames: vec![TheImageFrame {
+                    buffer: TheRGBABuffer::from(buffer, info.width, info.height),
+                    delay: 0,
+                }],
+                loop_count: Some(1),
+            });
+        };
+
+        // If the default image has no fcTL chunk it is not part of the animation and is skipped.
+        let skip_default = reader.info().frame_control.is_none();
+        let count = animation.num_frames as usize + usize::from(skip_default);
+
+        let mut canvas = TheRGBABuffer::new(TheDim::new(0, 0, width, height));
+        let mut frames = vec![];
+        let mut buf = vec![0; reader.output_buffer_size()];
+
+        for index in 0..count {
+            let info = reader.next_frame(&mut buf)?;
+            if index == 0 && skip_default {
+                continue;
+            }
+
+            let control = reader.info().frame_control.unwrap_or_default();
+            let pixels = png_output_to_rgba(&buf[..info.buffer_size()], info.color_type)?;
+            let sub = TheRGBABuffer::from(pixels, info.width, info.height);
+
+            let rect = TheDim::new(
+                control.x_offset as i32,
+                control.y_offset as i32,
+                info.width as i32,
+                info.height as i32,
+            );
+
+            let previous = if control.dispose_op == png::DisposeOp::Previous {
+                Some(canvas.extract(&rect))
+            } else {
+                None
+            };
+
+            match control.blend_op {
+                png::BlendOp::Source => canvas.copy_into(rect.x, rect.y, &sub),
+                png::BlendOp::Over => composite_over(&mut canvas, rect.x, rect.y, &sub),
+            }
+
+            let den = if control.delay_den == 0 {
+                100
+            } else {
+                control.delay_den as u32
+            };
+            frames.push(TheImageFrame {
+                buffer: canvas.clone(),
+                delay: control.delay_num as u32 * 1000 / den,
+            });
+
+            match control.dispose_op {
+                png::DisposeOp::None => {}
+                png::DisposeOp::Background => canvas.copy_into(
+                    rect.x,
+                    rect.y,
+                    &TheRGBABuffer::new(TheDim::sized(rect.width, rect.height)),
+                ),
+                png::DisposeOp::Previous => {
+                    if let Some(previous) = previous {
+                        canvas.copy_into(rect.x, rect.y, &previous);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            frames,
+            loop_count: if animation.num_plays == 0 {
+                None
+            } else {
+                Some(animation.num_plays)
+            },
+        })
+    }
+
+    /// Decodes a (possibly animated) GIF. Frames are composited according to their disposal methods.
+    pub fn from_gif_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(Cursor::new(data))?;
+
+        let width = decoder.width() as i32;
+        let height = decoder.height() as i32;
+
+        let mut canvas = TheRGBABuffer::new(TheDim::new(0, 0, width, height));
+        let mut frames = vec![];
+
+        while let Some(frame) = decoder.read_next_frame()? {
+            let rect = TheDim::new(
+                frame.left as i32,
+                frame.top as i32,
+                frame.width as i32,
+                frame.height as i32,
+            );
+            let sub = TheRGBABuffer::from(
+                frame.buffer.to_vec(),
+                frame.width as u32,
+                frame.height as u32,
+            );
+
+            let previous = if frame.dispose == gif::DisposalMethod::Previous {
+                Some(canvas.extract(&rect))
+            } else {
+                None
+            };
+
+            // GIF pixels are either fully transparent or opaque.
+            composite_over(&mut canvas, rect.x, rect.y, &sub);
+
+            frames.push(TheImageFrame {
+                buffer: canvas.clone(),
+                delay: frame.delay as u32 * 10,
+            });
+
+            match frame.dispose {
+                gif::DisposalMethod::Background => canvas.copy_into(
+                    rect.x,
+                    rect.y,
+                    &TheRGBABuffer::new(TheDim::sized(rect.width, rect.height)),
+                ),
+                gif::DisposalMethod::Previous => {
+                    if let Some(previous) = previous {
+                        canvas.copy_into(rect.x, rect.y, &previous);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if frames.is_empty() {
+            return Err(TheImageError::Decoding(
+                "GIF contains no frames".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            frames,
+            loop_count: match decoder.repeat() {
+                gif::Repeat::Infinite => None,
+                gif::Repeat::Finite(count) => Some(count as u32 + 1),
+            },
+        })
+    }
+
+    /// Returns true if the sequence contains more than one frame.
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// Returns the frame delays in milliseconds.
+    pub fn delays(&self) -> Vec<u32> {
+        self.frames.iter().map(|frame| frame.delay).collect()
+    }
+
+    /// Packs the frames into a tile which can be played by TheIconView.
+    pub fn to_tile(&self, name: &str) -> TheRGBATile {
+        let mut tile = TheRGBATile::new();
+        tile.name = name.to_string();
+        tile.buffer = self.frames.iter().map(|f| f.buffer.clone()).collect();
+        tile.delays = self.delays();
+        tile
+    }
+
+    /// Packs the frames horizontally into a sprite sheet and returns the sheet with the region of each frame.
+    pub fn to_sheet(&self) -> (TheRGBABuffer, TheRGBARegionSequence) {
+        let mut sheet = TheRGBABuffer::new(TheDim::new(
+            0,
+            0,
+            self.width * self.frames.len() as i32,
+            self.height,
+        ));
+        let mut sequence = TheRGBARegionSequence::new();
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            let x = index as i32 * self.width;
+            sheet.copy_into(x, 0, &frame.buffer);
+            sequence.regions.push(TheRGBARegion::new(
+                x as usize,
+                0,
+                self.width as usize,
+                self.height as usize,
+            ));
+        }
+
+        (sheet, sequence)
+    }
+}
+
+impl TheRGBABuffer {
+    /// Decodes a PNG or GIF from memory. For animations the first frame is returned.
+    pub fn from_image_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        match TheImageFormat::detect(data) {
+            Some(TheImageFormat::Png) => Self::from_png_bytes(data),
+            Some(TheImageFormat::Gif) => Self::from_gif_bytes(data),
+            None => Err(TheImageError::UnsupportedFormat),
+        }
+    }
+
+    /// Decodes a PNG or GIF file. For animations the first frame is returned.
+    pub fn from_image_path(path: impl AsRef<Path>) -> Result<Self, TheImageError> {
+        let data = std::fs::read(path).map_err(|err| TheImageError::Io(err.to_string()))?;
+        Self::from_image_bytes(&data)
+    }
+
+    /// Decodes a PNG from memory. Grayscale, RGB, indexed and 16-bit images are converted to 8-bit RGBA,
+    /// palette transparency is preserved.
+    pub fn from_png_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        let mut decoder = png::Decoder::new(Cursor::new(data));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let rgba = png_output_to_rgba(&buf[..info.buffer_size()], info.color_type)?;
+
+        Ok(Self::from(rgba, info.width, info.height))
+    }
+
+    /// Decodes the first frame of a GIF from memory.
+    pub fn from_gif_bytes(data: &[u8]) -> Result<Self, TheImageError> {
+        TheImageSequence::from_gif_bytes(data).map(|mut sequence| sequence.frames.remove(0).buffer)
+    }
+}
+
+/// Converts the normalized 8-bit output of the PNG decoder to RGBA.
+fn png_output_to_rgba(bytes: &[u8], color_type: png::ColorType) -> Result<Vec<u8>, TheImageError> {
+    let rgba = match color_type {
+        png::ColorType::Rgba => bytes.to_vec(),
+        png::ColorType::Rgb => bytes
+            .chunks_exact(3)
+            .flat_map(|c| [c[0], c[1], c[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => bytes
+            .chunks_exact(2)
+            .flat_map(|c| [c[0], c[0], c[0], c[1]])
+            .collect(),
+        png::ColorType::Grayscale => bytes.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(TheImageError::Decoding(
+                "Indexed image was not expanded".to_string(),
+            ))
+        }
+    };
+    Ok(rgba)
+}
+
+/// Composites `other` over `canvas` at the given position using straight alpha.
+fn composite_over(canvas: &mut TheRGBABuffer, x: i32, y: i32, other: &TheRGBABuffer) {
+    for sy in 0..other.dim().height {
+        for sx in 0..other.dim().width {
+            let Some(src) = other.get_pixel(sx, sy) else {
+                continue;
+            };
+            if src[3] == 0 {
+                continue;
+            }
+            if src[3] == 255 {
+                canvas.set_pixel(x + sx, y + sy, &src);
+                continue;
+            }
+            if let Some(dst) = canvas.get_pixel(x + sx, y + sy) {
+                let sa = src[3] as f32 / 255.0;
+                let da = dst[3] as f32 / 255.0;
+                let out_a = sa + da * (1.0 - sa);
+                let mut out = [0, 0, 0, (out_a * 255.0).round() as u8];
+                for i in 0..3 {
+                    out[i] = ((src[i] as f32 * sa + dst[i] as f32 * da * (1.0 - sa)) / out_a)
+                        .round() as u8;
+                }
+                canvas.set_pixel(x + sx, y + sy, &out);
+            }
+        }
+    }
+}
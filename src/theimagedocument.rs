@@ -0,0 +1,450 @@
+use crate::prelude::*;
+
+/// The content of a layer, either pixels or a group of child layers.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum TheLayerKind {
+    /// The pixels of the layer, always the size of the document.
+    Pixels(TheRGBABuffer),
+    /// Child layers ordered bottom to top, composited in isolation before being blended.
+    Group(Vec<TheLayer>),
+}
+
+/// A layer of a TheImageDocument.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TheLayer {
+    pub id: Uuid,
+    pub name: String,
+    pub opacity: f32,
+    pub blend_mode: TheBlendMode,
+    pub visible: bool,
+    pub locked: bool,
+    pub kind: TheLayerKind,
+}
+
+impl TheLayer {
+    /// Creates a transparent pixel layer of the given size.
+    pub fn new(name: &str, width: i32, height: i32) -> Self {
+        Self::with_buffer(name, TheRGBABuffer::new(TheDim::sized(width, height)))
+    }
+
+    /// Creates a pixel layer from the given buffer.
+    pub fn with_buffer(name: &str, buffer: TheRGBABuffer) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            opacity: 1.0,
+            blend_mode: TheBlendMode::Normal,
+            visible: true,
+            locked: false,
+            kind: TheLayerKind::Pixels(buffer),
+        }
+    }
+
+    /// Creates an empty layer group.
+    pub fn new_group(name: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            opacity: 1.0,
+            blend_mode: TheBlendMode::Normal,
+            visible: true,
+            locked: false,
+            kind: TheLayerKind::Group(vec![]),
+        }
+    }
+
+    /// Returns true if the layer is a group.
+    pub fn is_group(&self) -> bool {
+        matches!(self.kind, TheLayerKind::Group(_))
+    }
+
+    /// The pixels of the layer, None for groups.
+    pub fn buffer(&self) -> Option<&TheRGBABuffer> {
+        match &self.kind {
+            TheLayerKind::Pixels(buffer) => Some(buffer),
+            TheLayerKind::Group(_) => None,
+        }
+    }
+
+    /// The mutable pixels of the layer, None for groups.
+    pub fn buffer_mut(&mut self) -> Option<&mut TheRGBABuffer> {
+        match &mut self.kind {
+            TheLayerKind::Pixels(buffer) => Some(buffer),
+            TheLayerKind::Group(_) => None,
+        }
+    }
+
+    /// The child layers of a group, None for pixel layers.
+    pub fn children(&self) -> Option<&Vec<TheLayer>> {
+        match &self.kind {
+            TheLayerKind::Group(children) => Some(children),
+            TheLayerKind::Pixels(_) => None,
+        }
+    }
+
+    /// The mutable child layers of a group, None for pixel layers.
+    pub fn children_mut(&mut self) -> Option<&mut Vec<TheLayer>> {
+        match &mut self.kind {
+            TheLayerKind::Group(children) => Some(children),
+            TheLayerKind::Pixels(_) => None,
+        }
+    }
+}
+
+/// A layered image. Layers are ordered bottom to top and composited into a cached buffer, only
+/// the regions marked dirty are composited again. The layer pixels are serialized zlib compressed.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TheImageDocument {
+    width: i32,
+    height: i32,
+    layers: Vec<TheLayer>,
+    active: Option<Uuid>,
+
+    #[serde(skip)]
+    composite: TheRGBABuffer,
+    #[serde(skip)]
+    dirty: Option<TheDim>,
+}
+
+impl Default for TheImageDocument {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl TheImageDocument {
+    /// Creates an empty document of the given size.
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            layers: vec![],
+            active: None,
+
+            composite: TheRGBABuffer::empty(),
+            dirty: None,
+        }
+    }
+
+    /// Creates a document with a single layer holding the buffer.
+    pub fn from_buffer(name: &str, buffer: TheRGBABuffer) -> Self {
+        let mut document = Self::new(buffer.dim().width, buffer.dim().height);
+        document.insert_layer(None, 0, TheLayer::with_buffer(name, buffer));
+        document
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The top level layers, ordered bottom to top.
+    pub fn layers(&self) -> &Vec<TheLayer> {
+        &self.layers
+    }
+
+    /// Adds a transparent pixel layer above the active layer and makes it active.
+    pub fn add_layer(&mut self, name: &str) -> Uuid {
+        self.add_above_active(TheLayer::new(name, self.width, self.height))
+    }
+
+    /// Adds an empty group above the active layer and makes it active.
+    pub fn add_group(&mut self, name: &str) -> Uuid {
+        self.add_above_active(TheLayer::new_group(name))
+    }
+
+    fn add_above_active(&mut self, layer: TheLayer) -> Uuid {
+        let id = layer.id;
+        let (parent, index) = match self.active.and_then(|active| self.path_of(active)) {
+            Some(path) => {
+                let (index, parent) = path.split_last().unwrap();
+                (self.layer_at_path(parent).map(|l| l.id), index + 1)
+            }
+            None => (None, self.layers.len()),
+        };
+        self.insert_layer(parent, index, layer);
+        id
+    }
+
+    /// Inserts the layer into the given group (or the top level) at the index and makes it active.
+    /// Pixel layers are resized to the document size. Returns false if the parent is not a group.
+    pub fn insert_layer(
+        &mut self,
+        parent: Option<Uuid>,
+        index: usize,
+        mut layer: TheLayer,
+    ) -> bool {
+        let (width, height) = (self.width, self.height);
+        if let Some(buffer) = layer.buffer_mut() {
+            if buffer.dim().width != width || buffer.dim().height != height {
+                let mut resized = TheRGBABuffer::new(TheDim::sized(width, height));
+                resized.copy_into(0, 0, buffer);
+                *buffer = resized;
+            }
+        }
+        let id = layer.id;
+        let Some(container) = self.container_mut(parent) else {
+            return false;
+        };
+        let index = index.min(container.len());
+        container.insert(index, layer);
+        self.active = Some(id);
+        self.mark_all_dirty();
+        true
+    }
+
+    /// Removes the layer (or group) with the given id.
+    pub fn remove_layer(&mut self, id: Uuid) -> Option<TheLayer> {
+        let path = self.path_of(id)?;
+        let (index, parent) = path.split_last()?;
+        let parent_id = self.layer_at_path(parent).map(|l| l.id);
+        let layer = self.container_mut(parent_id)?.remove(*index);
+        if self.active.is_some_and(|active| {
+            layer.id == active
+                || layer
+                    .children()
+                    .is_some_and(|c| Self::find(c, active).is_some())
+        }) {
+            self.active = None;
+        }
+        self.mark_all_dirty();
+        Some(layer)
+    }
+
+    /// Moves the layer into the given group (or the top level) at the index.
+    pub fn move_layer(&mut self, id: Uuid, parent: Option<Uuid>, index: usize) -> bool {
+        // A group can not be moved into itself or its children
+        if let (Some(parent), Some(layer)) = (parent, self.layer(id)) {
+            if parent == id
+                || layer
+                    .children()
+                    .is_some_and(|c| Self::find(c, parent).is_some())
+            {
+                return false;
+            }
+        }
+        if parent.is_some_and(|p| !self.layer(p).is_some_and(|l| l.is_group())) {
+            return false;
+        }
+        let active = self.active;
+        if let Some(layer) = self.remove_layer(id) {
+            self.insert_layer(parent, index, layer);
+            self.active = active;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Merges the pixel layer into the pixel layer directly below it. Returns the id of the merged layer.
+    pub fn merge_down(&mut self, id: Uuid) -> Option<Uuid> {
+        let path = self.path_of(id)?;
+        let (index, parent) = path.split_last()?;
+        if *index == 0 {
+            return None;
+        }
+        let parent_id = self.layer_at_path(parent).map(|l| l.id);
+        let container = self.container_mut(parent_id)?;
+        if container[*index].is_group() || container[index - 1].is_group() {
+            return None;
+        }
+        let upper = container.remove(*index);
+        let lower = &mut container[index - 1];
+        if upper.visible {
+            if let (Some(dst), Some(src)) = (lower.buffer_mut(), upper.buffer()) {
+                dst.blend_into_mode_par(0, 0, src, upper.blend_mode, upper.opacity);
+            }
+        }
+        let lower_id = lower.id;
+        if self.active == Some(id) {
+            self.active = Some(lower_id);
+        }
+        self.mark_all_dirty();
+        Some(lower_id)
+    }
+
+    /// Returns the layer with the given id.
+    pub fn layer(&self, id: Uuid) -> Option<&TheLayer> {
+        Self::find(&self.layers, id)
+    }
+
+    /// Returns the mutable layer with the given id and marks the document dirty.
+    pub fn layer_mut(&mut self, id: Uuid) -> Option<&mut TheLayer> {
+        self.mark_all_dirty();
+        Self::find_mut(&mut self.layers, id)
+    }
+
+    pub fn active_layer_id(&self) -> Option<Uuid> {
+        self.active
+    }
+
+    /// Sets the active layer, returns false if there is no layer with the id.
+    pub fn set_active_layer(&mut self, id: Uuid) -> bool {
+        if self.layer(id).is_some() {
+            self.active = Some(id);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn active_layer(&self) -> Option<&TheLayer> {
+        self.active.and_then(|id| self.layer(id))
+    }
+
+    /// The pixels of the active layer.
+    pub fn active_buffer(&self) -> Option<&TheRGBABuffer> {
+        self.active_layer().and_then(|layer| layer.buffer())
+    }
+
+    /// The mutable pixels of the active layer, call mark_dirty() for the changed region afterwards.
+    pub fn active_buffer_mut(&mut self) -> Option<&mut TheRGBABuffer> {
+        let id = self.active?;
+        Self::find_mut(&mut self.layers, id).and_then(|layer| layer.buffer_mut())
+    }
+
+    /// The mutable pixels of the layer with the given id, call mark_dirty() for the changed region afterwards.
+    pub fn layer_buffer_mut(&mut self, id: Uuid) -> Option<&mut TheRGBABuffer> {
+        Self::find_mut(&mut self.layers, id).and_then(|layer| layer.buffer_mut())
+    }
+
+    /// Returns true if the active layer is a visible, unlocked pixel layer.
+    pub fn is_active_editable(&self) -> bool {
+        self.active_layer()
+            .is_some_and(|layer| !layer.is_group() && !layer.locked && layer.visible)
+    }
+
+    /// Marks the region to be composited again.
+    pub fn mark_dirty(&mut self, region: &TheDim) {
+        let region = TheDim::new(region.x, region.y, region.width, region.height);
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => {
+                let min_x = dirty.x.min(region.x);
+                let min_y = dirty.y.min(region.y);
+                let max_x = (dirty.x + dirty.width).max(region.x + region.width);
+                let max_y = (dirty.y + dirty.height).max(region.y + region.height);
+                TheDim::new(min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+            None => region,
+        });
+    }
+
+    /// Marks the whole document to be composited again.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = Some(TheDim::sized(self.width, self.height));
+    }
+
+    /// The region which will be composited again by the next call to composite().
+    pub fn dirty_region(&self) -> Option<TheDim> {
+        self.dirty
+    }
+
+    /// Composites the dirty region of the layers and returns the cached composite.
+    pub fn composite(&mut self) -> &TheRGBABuffer {
+        if self.composite.dim().width != self.width || self.composite.dim().height != self.height {
+            self.composite = TheRGBABuffer::new(TheDim::sized(self.width, self.height));
+            self.mark_all_dirty();
+        }
+
+        if let Some(dirty) = self.dirty.take() {
+            // Clip the dirty region to the document
+            let x0 = dirty.x.max(0);
+            let y0 = dirty.y.max(0);
+            let x1 = (dirty.x + dirty.width).min(self.width);
+            let y1 = (dirty.y + dirty.height).min(self.height);
+            if x1 > x0 && y1 > y0 {
+                let region = TheDim::new(x0, y0, x1 - x0, y1 - y0);
+                let mut buffer = TheRGBABuffer::new(TheDim::sized(region.width, region.height));
+                Self::composite_layers(&self.layers, &region, &mut buffer);
+                self.composite.copy_into(region.x, region.y, &buffer);
+            }
+        }
+
+        &self.composite
+    }
+
+    /// Returns the flattened image.
+    pub fn flatten(&mut self) -> TheRGBABuffer {
+        self.composite().clone()
+    }
+
+    /// Blends the region of the layers into the buffer, which has the size of the region.
+    fn composite_layers(layers: &[TheLayer], region: &TheDim, into: &mut TheRGBABuffer) {
+        for layer in layers {
+            if !layer.visible || layer.opacity <= 0.0 {
+                continue;
+            }
+            match &layer.kind {
+                TheLayerKind::Pixels(buffer) => {
+                    let part = buffer.extract(region);
+                    into.blend_into_mode_par(0, 0, &part, layer.blend_mode, layer.opacity);
+                }
+                TheLayerKind::Group(children) => {
+                    let mut group = TheRGBABuffer::new(TheDim::sized(region.width, region.height));
+                    Self::composite_layers(children, region, &mut group);
+                    into.blend_into_mode_par(0, 0, &group, layer.blend_mode, layer.opacity);
+                }
+            }
+        }
+    }
+
+    fn find(layers: &[TheLayer], id: Uuid) -> Option<&TheLayer> {
+        for layer in layers {
+            if layer.id == id {
+                return Some(layer);
+            }
+            if let Some(found) = layer.children().and_then(|c| Self::find(c, id)) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn find_mut(layers: &mut [TheLayer], id: Uuid) -> Option<&mut TheLayer> {
+        for layer in layers {
+            if layer.id == id {
+                return Some(layer);
+            }
+            if let Some(found) = layer.children_mut().and_then(|c| Self::find_mut(c, id)) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// The indices leading from the top level to the layer.
+    fn path_of(&self, id: Uuid) -> Option<Vec<usize>> {
+        fn search(layers: &[TheLayer], id: Uuid, path: &mut Vec<usize>) -> bool {
+            for (index, layer) in layers.iter().enumerate() {
+                path.push(index);
+                if layer.id == id || layer.children().is_some_and(|c| search(c, id, path)) {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = vec![];
+        search(&self.layers, id, &mut path).then_some(path)
+    }
+
+    fn layer_at_path(&self, path: &[usize]) -> Option<&TheLayer> {
+        let (first, rest) = path.split_first()?;
+        let mut layer = self.layers.get(*first)?;
+        for index in rest {
+            layer = layer.children()?.get(*index)?;
+        }
+        Some(layer)
+    }
+
+    /// The layer list of the group with the given id, or the top level list.
+    fn container_mut(&mut self, parent: Option<Uuid>) -> Option<&mut Vec<TheLayer>> {
+        match parent {
+            Some(id) => Self::find_mut(&mut self.layers, id)?.children_mut(),
+            None => Some(&mut self.layers),
+        }
+    }
+}
@@ -1,17 +1,194 @@
 use crate::prelude::*;
 
+/// A connection between two nodes identified by their ids: Source node id, source terminal, dest node id, dest terminal.
+pub type TheNodeConnectionId = (Uuid, u8, Uuid, u8);
+
+/// The data type of a node terminal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TheNodeDataType {
+    /// Accepts and provides every type.
+    #[default]
+    Any,
+    Bool,
+    Int,
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    Color,
+    Text,
+    Image,
+    Gradient,
+    /// An application defined type.
+    Custom(String),
+}
+
+impl std::fmt::Display for TheNodeDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "Any"),
+            Self::Bool => write!(f, "Bool"),
+            Self::Int => write!(f, "Int"),
+            Self::Float => write!(f, "Float"),
+            Self::Float2 => write!(f, "Float2"),
+            Self::Float3 => write!(f, "Float3"),
+            Self::Float4 => write!(f, "Float4"),
+            Self::Color => write!(f, "Color"),
+            Self::Text => write!(f, "Text"),
+            Self::Image => write!(f, "Image"),
+            Self::Gradient => write!(f, "Gradient"),
+            Self::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl TheNodeDataType {
+    /// Returns true if a value of this type can be connected to a terminal of the other type,
+    /// either directly or by one of the built-in implicit conversions.
+    pub fn converts_to(&self, other: &TheNodeDataType) -> bool {
+        use TheNodeDataType::*;
+        if self == other || *self == Any || *other == Any {
+            return true;
+        }
+        matches!(
+            (self, other),
+            (Bool, Int)
+                | (Bool, Float)
+                | (Int, Float)
+                | (Float, Int)
+                | (Float, Float2)
+                | (Float, Float3)
+                | (Float, Float4)
+                | (Float3, Float4)
+                | (Float4, Color)
+                | (Color, Float4)
+                | (Bool | Int | Float | Float2 | Float3 | Float4 | Color, Text)
+        )
+    }
+
+    /// The color of the connections carrying this type. None for Any and custom types, which
+    /// use the color of the terminal category.
+    pub fn color(&self) -> Option<TheColor> {
+        use TheNodeDataType::*;
+        let rgb = match self {
+            Any | Custom(_) => return None,
+            Bool => [204, 102, 102],
+            Int => [87, 166, 125],
+            Float => [140, 140, 140],
+            Float2 => [99, 150, 199],
+            Float3 => [110, 120, 210],
+            Float4 => [150, 110, 210],
+            Color => [214, 180, 72],
+            Text => [196, 120, 190],
+            Image => [224, 140, 70],
+            Gradient => [80, 190, 190],
+        };
+        Some(TheColor::from_u8(rgb[0], rgb[1], rgb[2], 255))
+    }
+}
+
+/// How TheNodeCanvasView draws the connections.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum TheNodeWireStyle {
+    #[default]
+    Bezier,
+    Straight,
+    Orthogonal,
+}
+
+impl TheNodeWireStyle {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Bezier => "Bezier",
+            Self::Straight => "Straight",
+            Self::Orthogonal => "Orthogonal",
+        }
+    }
+    pub fn iterator() -> impl Iterator<Item = TheNodeWireStyle> {
+        [Self::Bezier, Self::Straight, Self::Orthogonal]
+            .iter()
+            .copied()
+    }
+}
+
+/// The reasons TheNodeCanvas::validate_connection() refuses a connection.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TheNodeConnectionError {
+    /// A node can not be connected to itself.
+    SameNode,
+    /// Both terminals are inputs or both are outputs.
+    SameDirection,
+    /// The node or terminal does not exist.
+    InvalidTerminal,
+    /// The connection already exists.
+    Duplicate,
+    /// The data types of the terminals are not compatible: Source type, dest type.
+    Incompatible(TheNodeDataType, TheNodeDataType),
+    /// The terminal already has its maximum number of connections.
+    TerminalFull(usize),
+}
+
+impl std::fmt::Display for TheNodeConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SameNode => write!(f, "A node can not be connected to itself"),
+            Self::SameDirection => write!(f, "Outputs can only be connected to inputs"),
+            Self::InvalidTerminal => write!(f, "Invalid terminal"),
+            Self::Duplicate => write!(f, "The terminals are already connected"),
+            Self::Incompatible(src, dest) => write!(f, "Can not convert {} to {}", src, dest),
+            Self::TerminalFull(max) => {
+                write!(f, "The terminal only accepts {} connection(s)", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TheNodeConnectionError {}
+
+/// A node input or output. Create terminals with new(), or end struct literals with
+/// `..Default::default()` as more fields may be added.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
 pub struct TheNodeTerminal {
     pub name: String,
     pub category_name: String,
+
+    #[serde(default)]
+    pub data_type: TheNodeDataType,
+
+    /// The maximum number of connections. None allows any number of connections.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+}
+
+impl TheNodeTerminal {
+    pub fn new(name: &str, category_name: &str, data_type: TheNodeDataType) -> Self {
+        Self {
+            name: name.to_string(),
+            category_name: category_name.to_string(),
+            data_type,
+            max_connections: None,
+        }
+    }
+
+    /// The maximum number of connections of the terminal.
+    pub fn connection_limit(&self) -> usize {
+        self.max_connections.unwrap_or(usize::MAX)
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TheNode {
+    /// The stable id of the node, it does not change when other nodes are added or deleted.
+    #[serde(default = "Uuid::new_v4")]
+    pub id: Uuid,
+
     pub name: String,
     pub position: Vec2<i32>,
 
     pub inputs: Vec<TheNodeTerminal>,
     pub outputs: Vec<TheNodeTerminal>,
 
+    #[serde(skip)]
     pub preview: TheRGBABuffer,
 
     pub supports_preview: bool,
@@ -20,6 +197,194 @@ pub struct TheNode {
     pub can_be_deleted: bool,
 }
 
+impl Default for TheNode {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
+}
+
+impl TheNode {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            position: Vec2::zero(),
+            inputs: vec![],
+            outputs: vec![],
+            preview: TheRGBABuffer::empty(),
+            supports_preview: false,
+            preview_is_open: false,
+            can_be_deleted: true,
+        }
+    }
+
+    /// The height of the node as drawn by TheNodeCanvasView.
+    pub fn height(&self) -> i32 {
+        let max_terminals = self.inputs.len().max(self.outputs.len()) as i32;
+        let body_height = 7 + max_terminals * 10 + (max_terminals - 1) * 4 + 7;
+        let preview_height = if self.supports_preview && self.preview_is_open {
+            118
+        } else {
+            0
+        };
+        19 + body_height + 19 + preview_height
+    }
+}
+
+/// A labelled, colored comment frame. Nodes inside the frame move with it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TheNodeFrame {
+    pub id: Uuid,
+    pub label: String,
+    pub color: TheColor,
+
+    pub position: Vec2<i32>,
+    pub size: Vec2<i32>,
+}
+
+impl TheNodeFrame {
+    pub fn new(label: String, color: TheColor, position: Vec2<i32>, size: Vec2<i32>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            label,
+            color,
+            position,
+            size,
+        }
+    }
+
+    /// Returns true if the canvas coordinate is inside the frame.
+    pub fn contains(&self, coord: Vec2<i32>) -> bool {
+        coord.x >= self.position.x
+            && coord.y >= self.position.y
+            && coord.x < self.position.x + self.size.x
+            && coord.y < self.position.y + self.size.y
+    }
+}
+
+/// The reroute dots of a connection, in order from the output to the input.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TheNodeReroute {
+    pub connection: TheNodeConnectionId,
+    pub points: Vec<Vec2<i32>>,
+}
+
+/// An undoable edit of a TheNodeCanvas. Nodes are referenced by id so that commands stay valid
+/// when other nodes are deleted or inserted.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum TheNodeCommand {
+    /// Inserts the node at the index together with its connections and their reroute dots.
+    AddNode {
+        index: usize,
+        node: TheNode,
+        connections: Vec<TheNodeConnectionId>,
+        #[serde(default)]
+        reroutes: Vec<TheNodeReroute>,
+    },
+    /// Removes the node, its connections and their reroute dots.
+    RemoveNode {
+        index: usize,
+        node: TheNode,
+        connections: Vec<TheNodeConnectionId>,
+        #[serde(default)]
+        reroutes: Vec<TheNodeReroute>,
+    },
+    /// Moves nodes: Node id, old position, new position.
+    MoveNodes(Vec<(Uuid, Vec2<i32>, Vec2<i32>)>),
+    /// Adds the connection with the given reroute dots.
+    Connect(TheNodeConnectionId, Vec<Vec2<i32>>),
+    /// Removes the connection and its reroute dots, which are restored by the inverse.
+    Disconnect(TheNodeConnectionId, Vec<Vec2<i32>>),
+    AddFrame {
+        index: usize,
+        frame: TheNodeFrame,
+    },
+    RemoveFrame {
+        index: usize,
+        frame: TheNodeFrame,
+    },
+    /// Replaces a frame (old, new), used for moving, resizing and relabelling.
+    ChangeFrame(TheNodeFrame, TheNodeFrame),
+    /// Replaces the reroute dots of a connection.
+    SetReroutes {
+        connection: TheNodeConnectionId,
+        from: Vec<Vec2<i32>>,
+        to: Vec<Vec2<i32>>,
+    },
+    /// Several commands applied as one edit.
+    Batch(Vec<TheNodeCommand>),
+}
+
+impl TheNodeCommand {
+    /// Returns the command which reverts this command.
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::AddNode {
+                index,
+                node,
+                connections,
+                reroutes,
+            } => Self::RemoveNode {
+                index: *index,
+                node: node.clone(),
+                connections: connections.clone(),
+                reroutes: reroutes.clone(),
+            },
+            Self::RemoveNode {
+                index,
+                node,
+                connections,
+                reroutes,
+            } => Self::AddNode {
+                index: *index,
+                node: node.clone(),
+                connections: connections.clone(),
+                reroutes: reroutes.clone(),
+            },
+            Self::MoveNodes(moves) => Self::MoveNodes(
+                moves
+                    .iter()
+                    .map(|(id, from, to)| (*id, *to, *from))
+                    .collect(),
+            ),
+            Self::Connect(connection, points) => Self::Disconnect(*connection, points.clone()),
+            Self::Disconnect(connection, points) => Self::Connect(*connection, points.clone()),
+            Self::AddFrame { index, frame } => Self::RemoveFrame {
+                index: *index,
+                frame: frame.clone(),
+            },
+            Self::RemoveFrame { index, frame } => Self::AddFrame {
+                index: *index,
+                frame: frame.clone(),
+            },
+            Self::ChangeFrame(old, new) => Self::ChangeFrame(new.clone(), old.clone()),
+            Self::SetReroutes {
+                connection,
+                from,
+                to,
+            } => Self::SetReroutes {
+                connection: *connection,
+                from: to.clone(),
+                to: from.clone(),
+            },
+            Self::Batch(commands) => {
+                Self::Batch(commands.iter().rev().map(|c| c.inverse()).collect())
+            }
+        }
+    }
+
+    /// Load a command from json.
+    pub fn from_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    /// Convert the command to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TheNodeCanvas {
     /// The nodes in the canvas, identified by their index.
     pub nodes: Vec<TheNode>,
@@ -27,8 +392,9 @@ pub struct TheNodeCanvas {
     /// The width of a node.
     pub node_width: i32,
 
-    /// The node connections: Source node index, source terminal, dest node index, dest terminal
-    pub connections: Vec<(u16, u8, u16, u8)>,
+    /// The node connections by node id: Source node id, source terminal, dest node id, dest
+    /// terminal. They stay valid when nodes are inserted or removed.
+    pub connections: Vec<TheNodeConnectionId>,
 
     /// The scroll offset.
     pub offset: Vec2<i32>,
@@ -36,11 +402,31 @@ pub struct TheNodeCanvas {
     /// The currently selected node.
     pub selected_node: Option<usize>,
 
+    /// The ids of all selected nodes, including the selected node.
+    #[serde(default)]
+    pub selection: Vec<Uuid>,
+
+    /// The comment frames, drawn behind the nodes.
+    #[serde(default)]
+    pub frames: Vec<TheNodeFrame>,
+
     /// The zoom level.
     pub zoom: f32,
 
     /// The node categories
     pub categories: FxHashMap<String, TheColor>,
+
+    /// Additional implicit conversions between data types: From, to.
+    #[serde(default)]
+    pub conversions: Vec<(TheNodeDataType, TheNodeDataType)>,
+
+    /// The reroute dots of the connections.
+    #[serde(default)]
+    pub reroutes: Vec<TheNodeReroute>,
+
+    /// How the connections are drawn.
+    #[serde(default)]
+    pub wire_style: TheNodeWireStyle,
 }
 
 impl Default for TheNodeCanvas {
@@ -57,8 +443,508 @@ impl TheNodeCanvas {
             connections: Vec::new(),
             offset: Vec2::zero(),
             selected_node: None,
+            selection: Vec::new(),
+            frames: Vec::new(),
             zoom: 1.0,
             categories: FxHashMap::default(),
+            conversions: Vec::new(),
+            reroutes: Vec::new(),
+            wire_style: TheNodeWireStyle::default(),
+        }
+    }
+
+    /// Returns the index of the node with the given id.
+    pub fn node_index(&self, id: &Uuid) -> Option<usize> {
+        self.nodes.iter().position(|node| node.id == *id)
+    }
+
+    /// Returns the node with the given id.
+    pub fn node(&self, id: &Uuid) -> Option<&TheNode> {
+        self.nodes.iter().find(|node| node.id == *id)
+    }
+
+    /// Returns the node with the given id, mutable.
+    pub fn node_mut(&mut self, id: &Uuid) -> Option<&mut TheNode> {
+        self.nodes.iter_mut().find(|node| node.id == *id)
+    }
+
+    /// Converts an index based connection to an id based one.
+    pub fn connection_id(&self, connection: &(u16, u8, u16, u8)) -> Option<TheNodeConnectionId> {
+        let (src, src_terminal, dest, dest_terminal) = *connection;
+        Some((
+            self.nodes.get(src as usize)?.id,
+            src_terminal,
+            self.nodes.get(dest as usize)?.id,
+            dest_terminal,
+        ))
+    }
+
+    /// Converts an id based connection to an index based one.
+    pub fn connection_from_id(
+        &self,
+        connection: &TheNodeConnectionId,
+    ) -> Option<(u16, u8, u16, u8)> {
+        let (src, src_terminal, dest, dest_terminal) = *connection;
+        Some((
+            self.node_index(&src)? as u16,
+            src_terminal,
+            self.node_index(&dest)? as u16,
+            dest_terminal,
+        ))
+    }
+
+    /// Returns the connections by node index: Source node index, source terminal, dest node
+    /// index, dest terminal.
+    pub fn index_connections(&self) -> Vec<(u16, u8, u16, u8)> {
+        self.connections
+            .iter()
+            .filter_map(|connection| self.connection_from_id(connection))
+            .collect()
+    }
+
+    /// Adds the connection between the nodes at the indices, see validate_connection().
+    pub fn connect(&mut self, connection: &(u16, u8, u16, u8)) {
+        if let Some(id) = self.connection_id(connection) {
+            if !self.connections.contains(&id) {
+                self.connections.push(id);
+            }
+        }
+    }
+
+    /// Returns true if a value of the first type can be connected to a terminal of the second.
+    pub fn can_convert(&self, from: &TheNodeDataType, to: &TheNodeDataType) -> bool {
+        from.converts_to(to) || self.conversions.contains(&(from.clone(), to.clone()))
+    }
+
+    /// Returns the number of connections of a terminal.
+    pub fn connection_count(&self, node: usize, output: bool, terminal: u8) -> usize {
+        let Some(node) = self.nodes.get(node) else {
+            return 0;
+        };
+        self.connections
+            .iter()
+            .filter(|(src, src_terminal, dest, dest_terminal)| {
+                if output {
+                    *src == node.id && *src_terminal == terminal
+                } else {
+                    *dest == node.id && *dest_terminal == terminal
+                }
+            })
+            .count()
+    }
+
+    /// Checks if the connection (source node, output, dest node, input) can be added.
+    pub fn validate_connection(
+        &self,
+        connection: &(u16, u8, u16, u8),
+    ) -> Result<(), TheNodeConnectionError> {
+        let (src, src_terminal, dest, dest_terminal) = *connection;
+        if src == dest {
+            return Err(TheNodeConnectionError::SameNode);
+        }
+        let output = self
+            .nodes
+            .get(src as usize)
+            .and_then(|node| node.outputs.get(src_terminal as usize))
+            .ok_or(TheNodeConnectionError::InvalidTerminal)?;
+        let input = self
+            .nodes
+            .get(dest as usize)
+            .and_then(|node| node.inputs.get(dest_terminal as usize))
+            .ok_or(TheNodeConnectionError::InvalidTerminal)?;
+
+        if self
+            .connection_id(connection)
+            .is_some_and(|id| self.connections.contains(&id))
+        {
+            return Err(TheNodeConnectionError::Duplicate);
+        }
+        if !self.can_convert(&output.data_type, &input.data_type) {
+            return Err(TheNodeConnectionError::Incompatible(
+                output.data_type.clone(),
+                input.data_type.clone(),
+            ));
+        }
+        let max = output.connection_limit();
+        if self.connection_count(src as usize, true, src_terminal) >= max {
+            return Err(TheNodeConnectionError::TerminalFull(max));
+        }
+        let max = input.connection_limit();
+        if self.connection_count(dest as usize, false, dest_terminal) >= max {
+            return Err(TheNodeConnectionError::TerminalFull(max));
+        }
+        Ok(())
+    }
+
+    /// Inserts a node at the index and adjusts the selected node.
+    pub fn insert_node(&mut self, index: usize, node: TheNode) -> usize {
+        let index = index.min(self.nodes.len());
+        self.nodes.insert(index, node);
+        if let Some(selected) = &mut self.selected_node {
+            if *selected >= index {
+                *selected += 1;
+            }
+        }
+        index
+    }
+
+    /// Returns the connections of the node with the given id.
+    pub fn node_connections(&self, id: &Uuid) -> Vec<TheNodeConnectionId> {
+        self.connections
+            .iter()
+            .filter(|(src, _, dest, _)| src == id || dest == id)
+            .copied()
+            .collect()
+    }
+
+    /// Removes the node at the index together with its connections and their reroute dots.
+    /// Returns the node and its removed connections.
+    pub fn remove_node(&mut self, index: usize) -> Option<(TheNode, Vec<TheNodeConnectionId>)> {
+        let id = self.nodes.get(index)?.id;
+
+        let removed = self.node_connections(&id);
+        self.connections
+            .retain(|(src, _, dest, _)| *src != id && *dest != id);
+        self.reroutes.retain(|r| !removed.contains(&r.connection));
+
+        self.selected_node = match self.selected_node {
+            Some(selected) if selected == index => None,
+            Some(selected) if selected > index => Some(selected - 1),
+            selected => selected,
+        };
+
+        let node = self.nodes.remove(index);
+        self.selection.retain(|id| *id != node.id);
+
+        Some((node, removed))
+    }
+
+    /// Returns true if the node at the index is selected.
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.nodes
+            .get(index)
+            .is_some_and(|node| self.selection.contains(&node.id))
+    }
+
+    /// Returns the indices of the selected nodes in ascending order.
+    pub fn selected_indices(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|index| self.is_selected(*index))
+            .collect()
+    }
+
+    /// Selects the node at the index and makes it the selected node. If add is false the
+    /// previous selection is cleared.
+    pub fn select(&mut self, index: usize, add: bool) {
+        if !add {
+            self.selection.clear();
+        }
+        if let Some(node) = self.nodes.get(index) {
+            if !self.selection.contains(&node.id) {
+                self.selection.push(node.id);
+            }
+            self.selected_node = Some(index);
         }
     }
+
+    /// Removes the node at the index from the selection.
+    pub fn deselect(&mut self, index: usize) {
+        if let Some(node) = self.nodes.get(index) {
+            let id = node.id;
+            self.selection.retain(|s| *s != id);
+            if self.selected_node == Some(index) {
+                self.selected_node = self.selection.last().and_then(|id| self.node_index(id));
+            }
+        }
+    }
+
+    /// Clears the selection.
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+        self.selected_node = None;
+    }
+
+    /// Returns the index of the frame with the given id.
+    pub fn frame_index(&self, id: &Uuid) -> Option<usize> {
+        self.frames.iter().position(|frame| frame.id == *id)
+    }
+
+    /// Returns the indices of the nodes inside the frame.
+    pub fn frame_nodes(&self, frame_index: usize) -> Vec<usize> {
+        if let Some(frame) = self.frames.get(frame_index) {
+            (0..self.nodes.len())
+                .filter(|index| frame.contains(self.nodes[*index].position))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Returns the nodes at the indices and their connections to each other as a new canvas,
+    /// used for the clipboard.
+    pub fn copy_nodes(&self, indices: &[usize]) -> TheNodeCanvas {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|index| *index < self.nodes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let mut copy = TheNodeCanvas::new();
+        copy.node_width = self.node_width;
+        copy.nodes = indices
+            .iter()
+            .map(|index| self.nodes[*index].clone())
+            .collect();
+        // The copied nodes keep their ids, so the copied connections are unchanged.
+        copy.connections = self
+            .connections
+            .iter()
+            .filter(|(src, _, dest, _)| copy.node(src).is_some() && copy.node(dest).is_some())
+            .copied()
+            .collect();
+        copy.reroutes = self
+            .reroutes
+            .iter()
+            .filter(|r| copy.connections.contains(&r.connection))
+            .cloned()
+            .collect();
+        copy
+    }
+
+    /// Creates the command which adds the nodes of the copied canvas with new ids, moved by the
+    /// offset. Returns the command and the ids of the new nodes.
+    pub fn paste_command(
+        &self,
+        copy: &TheNodeCanvas,
+        offset: Vec2<i32>,
+    ) -> (TheNodeCommand, Vec<Uuid>) {
+        let nodes: Vec<TheNode> = copy
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut node = node.clone();
+                node.id = Uuid::new_v4();
+                node.position += offset;
+                node
+            })
+            .collect();
+
+        let mut commands: Vec<TheNodeCommand> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| TheNodeCommand::AddNode {
+                index: self.nodes.len() + i,
+                node: node.clone(),
+                connections: vec![],
+                reroutes: vec![],
+            })
+            .collect();
+
+        for connection in &copy.connections {
+            let (src, src_terminal, dest, dest_terminal) = *connection;
+            let src = copy.node_index(&src).and_then(|index| nodes.get(index));
+            let dest = copy.node_index(&dest).and_then(|index| nodes.get(index));
+            if let (Some(src), Some(dest)) = (src, dest) {
+                let id = (src.id, src_terminal, dest.id, dest_terminal);
+                let points = copy.reroute_points(connection);
+                commands.push(TheNodeCommand::Connect(
+                    id,
+                    points.iter().map(|p| *p + offset).collect(),
+                ));
+            }
+        }
+
+        (
+            TheNodeCommand::Batch(commands),
+            nodes.iter().map(|node| node.id).collect(),
+        )
+    }
+
+    /// Returns the reroute dots of the connection.
+    pub fn reroute_points(&self, connection: &TheNodeConnectionId) -> &[Vec2<i32>] {
+        self.reroutes
+            .iter()
+            .find(|r| r.connection == *connection)
+            .map(|r| r.points.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Creates the command which replaces the reroute dots of the connection.
+    pub fn set_reroutes_command(
+        &self,
+        connection: &TheNodeConnectionId,
+        points: Vec<Vec2<i32>>,
+    ) -> TheNodeCommand {
+        TheNodeCommand::SetReroutes {
+            connection: *connection,
+            from: self.reroute_points(connection).to_vec(),
+            to: points,
+        }
+    }
+
+    /// Creates the command which removes the deletable nodes at the indices.
+    pub fn remove_nodes_command(&self, indices: &[usize]) -> TheNodeCommand {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|index| self.nodes.get(*index).is_some_and(|n| n.can_be_deleted))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        // Remove from the back so that the indices and connections stay valid.
+        let mut canvas = self.clone();
+        let mut commands = vec![];
+        for index in indices.into_iter().rev() {
+            if let Some(command) = canvas.remove_node_command(index) {
+                canvas.remove_node(index);
+                commands.push(command);
+            }
+        }
+        TheNodeCommand::Batch(commands)
+    }
+
+    /// Creates the command which removes the node at the index, see apply().
+    pub fn remove_node_command(&self, index: usize) -> Option<TheNodeCommand> {
+        let node = self.nodes.get(index)?;
+        let connections = self.node_connections(&node.id);
+        Some(TheNodeCommand::RemoveNode {
+            index,
+            node: node.clone(),
+            reroutes: self
+                .reroutes
+                .iter()
+                .filter(|r| connections.contains(&r.connection))
+                .cloned()
+                .collect(),
+            connections,
+        })
+    }
+
+    /// Applies the command. Returns false if it does not match the canvas, i.e. a node is missing.
+    pub fn apply(&mut self, command: &TheNodeCommand) -> bool {
+        match command {
+            TheNodeCommand::AddNode {
+                index,
+                node,
+                connections,
+                reroutes,
+            } => {
+                if self.node_index(&node.id).is_some() {
+                    return false;
+                }
+                self.insert_node(*index, node.clone());
+                for connection in connections {
+                    if self.connection_from_id(connection).is_some()
+                        && !self.connections.contains(connection)
+                    {
+                        self.connections.push(*connection);
+                    }
+                }
+                for reroute in reroutes {
+                    if self.connections.contains(&reroute.connection) {
+                        self.set_reroute_points(&reroute.connection, reroute.points.clone());
+                    }
+                }
+                true
+            }
+            TheNodeCommand::RemoveNode { node, .. } => {
+                if let Some(index) = self.node_index(&node.id) {
+                    self.remove_node(index);
+                    true
+                } else {
+                    false
+                }
+            }
+            TheNodeCommand::MoveNodes(moves) => {
+                let mut rc = true;
+                for (id, _, to) in moves {
+                    if let Some(node) = self.node_mut(id) {
+                        node.position = *to;
+                    } else {
+                        rc = false;
+                    }
+                }
+                rc
+            }
+            TheNodeCommand::Connect(connection, points) => {
+                if self.connection_from_id(connection).is_some() {
+                    if !self.connections.contains(connection) {
+                        self.connections.push(*connection);
+                    }
+                    self.set_reroute_points(connection, points.clone());
+                    true
+                } else {
+                    false
+                }
+            }
+            TheNodeCommand::Disconnect(connection, _) => {
+                if self.connection_from_id(connection).is_some() {
+                    self.connections.retain(|c| c != connection);
+                    self.set_reroute_points(connection, vec![]);
+                    true
+                } else {
+                    false
+                }
+            }
+            TheNodeCommand::AddFrame { index, frame } => {
+                if self.frame_index(&frame.id).is_some() {
+                    return false;
+                }
+                let index = (*index).min(self.frames.len());
+                self.frames.insert(index, frame.clone());
+                true
+            }
+            TheNodeCommand::RemoveFrame { frame, .. } => {
+                if let Some(index) = self.frame_index(&frame.id) {
+                    self.frames.remove(index);
+                    true
+                } else {
+                    false
+                }
+            }
+            TheNodeCommand::ChangeFrame(_, new) => {
+                if let Some(index) = self.frame_index(&new.id) {
+                    self.frames[index] = new.clone();
+                    true
+                } else {
+                    false
+                }
+            }
+            TheNodeCommand::SetReroutes { connection, to, .. } => {
+                self.set_reroute_points(connection, to.clone());
+                true
+            }
+            TheNodeCommand::Batch(commands) => {
+                let mut rc = true;
+                for command in commands {
+                    rc &= self.apply(command);
+                }
+                rc
+            }
+        }
+    }
+
+    /// Replaces the reroute dots of the connection, an empty list removes them.
+    fn set_reroute_points(&mut self, connection: &TheNodeConnectionId, points: Vec<Vec2<i32>>) {
+        self.reroutes.retain(|r| r.connection != *connection);
+        if !points.is_empty() {
+            self.reroutes.push(TheNodeReroute {
+                connection: *connection,
+                points,
+            });
+        }
+    }
+
+    /// Load a canvas from json.
+    pub fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_default()
+    }
+
+    /// Convert the canvas to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
 }
@@ -0,0 +1,407 @@
+use crate::prelude::*;
+
+/// How TheNodeCanvas::align_command() arranges nodes.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum TheNodeAlignment {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+    DistributeHorizontally,
+    DistributeVertically,
+}
+
+impl TheNodeAlignment {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Left => "Left",
+            Self::Center => "Center",
+            Self::Right => "Right",
+            Self::Top => "Top",
+            Self::Middle => "Middle",
+            Self::Bottom => "Bottom",
+            Self::DistributeHorizontally => "Distribute Horizontally",
+            Self::DistributeVertically => "Distribute Vertically",
+        }
+    }
+    pub fn iterator() -> impl Iterator<Item = TheNodeAlignment> {
+        [
+            Self::Left,
+            Self::Center,
+            Self::Right,
+            Self::Top,
+            Self::Middle,
+            Self::Bottom,
+            Self::DistributeHorizontally,
+            Self::DistributeVertically,
+        ]
+        .iter()
+        .copied()
+    }
+}
+
+impl TheNodeCanvas {
+    /// Returns the top left and bottom right corner of all nodes and frames, in canvas coordinates.
+    pub fn bounds(&self) -> Option<(Vec2<i32>, Vec2<i32>)> {
+        let rects = self
+            .nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.position,
+                    node.position + Vec2::new(self.node_width, node.height()),
+                )
+            })
+            .chain(
+                self.frames
+                    .iter()
+                    .map(|frame| (frame.position, frame.position + frame.size)),
+            );
+
+        rects.reduce(|(min, max), (rmin, rmax)| {
+            (
+                Vec2::new(min.x.min(rmin.x), min.y.min(rmin.y)),
+                Vec2::new(max.x.max(rmax.x), max.y.max(rmax.y)),
+            )
+        })
+    }
+
+    /// Creates the command which arranges the nodes at the indices in layers from left to right
+    /// (Sugiyama style): cycles are broken, nodes are assigned to layers by their longest path,
+    /// and the order inside the layers is chosen by barycenter sweeps to keep connection
+    /// crossings low. The layout starts at the top left corner of the nodes.
+    pub fn layout_command(&self, indices: &[usize], spacing: Vec2<i32>) -> TheNodeCommand {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|index| *index < self.nodes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let count = indices.len();
+        if count == 0 {
+            return TheNodeCommand::MoveNodes(vec![]);
+        }
+
+        let local = |index: usize| indices.iter().position(|i| *i == index);
+        let mut edges: Vec<(usize, usize)> = self
+            .index_connections()
+            .iter()
+            .filter_map(|(src, _, dest, _)| Some((local(*src as usize)?, local(*dest as usize)?)))
+            .filter(|(src, dest)| src != dest)
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        let edges = Self::acyclic_edges(count, edges);
+
+        // Layer assignment by longest path.
+        let mut layer = vec![0; count];
+        if let Ok(order) = Self::topological(count, &edges) {
+            for src in order {
+                for (_, dest) in edges.iter().filter(|(s, _)| *s == src) {
+                    layer[*dest] = layer[*dest].max(layer[src] + 1);
+                }
+            }
+        }
+        let layer_count = layer.iter().max().copied().unwrap_or(0) + 1;
+
+        // Long connections get a dummy vertex in every layer they cross.
+        let mut vertex_layer = layer.clone();
+        let mut vertex_key: Vec<f32> = indices
+            .iter()
+            .map(|index| self.nodes[*index].position.y as f32)
+            .collect();
+        let mut layered_edges = vec![];
+        for (src, dest) in &edges {
+            let mut prev = *src;
+            for l in layer[*src] + 1..layer[*dest] {
+                let dummy = vertex_layer.len();
+                vertex_layer.push(l);
+                vertex_key.push(vertex_key[*src]);
+                layered_edges.push((prev, dummy));
+                prev = dummy;
+            }
+            layered_edges.push((prev, *dest));
+        }
+
+        let mut layers: Vec<Vec<usize>> = vec![vec![]; layer_count];
+        for (vertex, l) in vertex_layer.iter().enumerate() {
+            layers[*l].push(vertex);
+        }
+        for layer in &mut layers {
+            layer.sort_by(|a, b| vertex_key[*a].total_cmp(&vertex_key[*b]));
+        }
+
+        // Crossing reduction, alternating downward and upward sweeps.
+        let mut best = layers.clone();
+        let mut best_crossings = Self::crossings(&layers, &vertex_layer, &layered_edges);
+        for iteration in 0..12 {
+            let mut position = vec![0.0; vertex_layer.len()];
+            for layer in &layers {
+                for (i, vertex) in layer.iter().enumerate() {
+                    position[*vertex] = i as f32;
+                }
+            }
+
+            let down = iteration % 2 == 0;
+            let range: Vec<usize> = if down {
+                (1..layer_count).collect()
+            } else {
+                (0..layer_count.saturating_sub(1)).rev().collect()
+            };
+            for l in range {
+                let mut keys = FxHashMap::default();
+                for vertex in &layers[l] {
+                    let neighbours: Vec<f32> = layered_edges
+                        .iter()
+                        .filter_map(|(src, dest)| {
+                            if down && dest == vertex {
+                                Some(position[*src])
+                            } else if !down && src == vertex {
+                                Some(position[*dest])
+                            } else {
+                                None
+                            }
+                        })
+                        .collect();
+                    let key = if neighbours.is_empty() {
+                        position[*vertex]
+                    } else {
+                        neighbours.iter().sum::<f32>() / neighbours.len() as f32
+                    };
+                    keys.insert(*vertex, key);
+                }
+                layers[l].sort_by(|a, b| keys[a].total_cmp(&keys[b]));
+                for (i, vertex) in layers[l].iter().enumerate() {
+                    position[*vertex] = i as f32;
+                }
+            }
+
+            let crossings = Self::crossings(&layers, &vertex_layer, &layered_edges);
+            if crossings < best_crossings {
+                best_crossings = crossings;
+                best = layers.clone();
+            }
+        }
+
+        // Coordinates, every layer is a column centered on the tallest one.
+        let origin = indices
+            .iter()
+            .map(|index| self.nodes[*index].position)
+            .reduce(|a, b| Vec2::new(a.x.min(b.x), a.y.min(b.y)))
+            .unwrap_or_default();
+        let layer_height = |layer: &Vec<usize>| {
+            let nodes: Vec<i32> = layer
+                .iter()
+                .filter(|vertex| **vertex < count)
+                .map(|vertex| self.nodes[indices[*vertex]].height())
+                .collect();
+            nodes.iter().sum::<i32>() + (nodes.len() as i32 - 1).max(0) * spacing.y
+        };
+        let max_height = best.iter().map(layer_height).max().unwrap_or(0);
+
+        let mut moves = vec![];
+        for (l, layer) in best.iter().enumerate() {
+            let x = origin.x + l as i32 * (self.node_width + spacing.x);
+            let mut y = origin.y + (max_height - layer_height(layer)) / 2;
+            for vertex in layer.iter().filter(|vertex| **vertex < count) {
+                let node = &self.nodes[indices[*vertex]];
+                let to = Vec2::new(x, y);
+                if to != node.position {
+                    moves.push((node.id, node.position, to));
+                }
+                y += node.height() + spacing.y;
+            }
+        }
+
+        TheNodeCommand::MoveNodes(moves)
+    }
+
+    /// Creates the command which aligns or distributes the nodes at the indices.
+    pub fn align_command(&self, indices: &[usize], alignment: TheNodeAlignment) -> TheNodeCommand {
+        let mut nodes: Vec<&TheNode> = indices
+            .iter()
+            .filter_map(|index| self.nodes.get(*index))
+            .collect();
+        if nodes.len() < 2 {
+            return TheNodeCommand::MoveNodes(vec![]);
+        }
+
+        let count = nodes.len() as i32;
+        let min_x = nodes.iter().map(|n| n.position.x).min().unwrap_or(0);
+        let max_x = nodes.iter().map(|n| n.position.x).max().unwrap_or(0);
+        let min_y = nodes.iter().map(|n| n.position.y).min().unwrap_or(0);
+        let max_bottom = nodes
+            .iter()
+            .map(|n| n.position.y + n.height())
+            .max()
+            .unwrap_or(0);
+
+        let mut targets: Vec<(&TheNode, Vec2<i32>)> = vec![];
+        match alignment {
+            TheNodeAlignment::Left | TheNodeAlignment::Right | TheNodeAlignment::Center => {
+                // All nodes have the same width, so only the reference x differs.
+                let x = match alignment {
+                    TheNodeAlignment::Left => min_x,
+                    TheNodeAlignment::Right => max_x,
+                    _ => nodes.iter().map(|n| n.position.x).sum::<i32>() / count,
+                };
+                for node in &nodes {
+                    targets.push((node, Vec2::new(x, node.position.y)));
+                }
+            }
+            TheNodeAlignment::Top => {
+                for node in &nodes {
+                    targets.push((node, Vec2::new(node.position.x, min_y)));
+                }
+            }
+            TheNodeAlignment::Bottom => {
+                for node in &nodes {
+                    targets.push((node, Vec2::new(node.position.x, max_bottom - node.height())));
+                }
+            }
+            TheNodeAlignment::Middle => {
+                let middle = nodes
+                    .iter()
+                    .map(|n| n.position.y + n.height() / 2)
+                    .sum::<i32>()
+                    / count;
+                for node in &nodes {
+                    targets.push((node, Vec2::new(node.position.x, middle - node.height() / 2)));
+                }
+            }
+            TheNodeAlignment::DistributeHorizontally => {
+                nodes.sort_by_key(|n| n.position.x);
+                let step = (max_x - min_x) as f32 / (count - 1) as f32;
+                for (i, node) in nodes.iter().enumerate() {
+                    let x = min_x + (i as f32 * step).round() as i32;
+                    targets.push((node, Vec2::new(x, node.position.y)));
+                }
+            }
+            TheNodeAlignment::DistributeVertically => {
+                nodes.sort_by_key(|n| n.position.y);
+                let heights: i32 = nodes.iter().map(|n| n.height()).sum();
+                // Overlapping nodes are stacked.
+                let gap = ((max_bottom - min_y - heights) as f32 / (count - 1) as f32).max(0.0);
+                let mut y = min_y as f32;
+                for node in &nodes {
+                    targets.push((node, Vec2::new(node.position.x, y.round() as i32)));
+                    y += node.height() as f32 + gap;
+                }
+            }
+        }
+
+        TheNodeCommand::MoveNodes(
+            targets
+                .into_iter()
+                .filter(|(node, to)| node.position != *to)
+                .map(|(node, to)| (node.id, node.position, to))
+                .collect(),
+        )
+    }
+
+    /// Reverses the edges which close a cycle, found by a depth first search.
+    fn acyclic_edges(count: usize, edges: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        // 0: unvisited, 1: on the stack, 2: done
+        let mut state = vec![0u8; count];
+        let mut reversed = FxHashSet::default();
+
+        for start in 0..count {
+            if state[start] != 0 {
+                continue;
+            }
+            let mut stack = vec![(start, 0)];
+            state[start] = 1;
+            while let Some((vertex, next)) = stack.pop() {
+                let outgoing: Vec<(usize, usize)> = edges
+                    .iter()
+                    .filter(|(src, _)| *src == vertex)
+                    .copied()
+                    .collect();
+                if let Some(edge) = outgoing.get(next) {
+                    stack.push((vertex, next + 1));
+                    match state[edge.1] {
+                        0 => {
+                            state[edge.1] = 1;
+                            stack.push((edge.1, 0));
+                        }
+                        1 => {
+                            reversed.insert(*edge);
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[vertex] = 2;
+                }
+            }
+        }
+
+        let mut edges: Vec<(usize, usize)> = edges
+            .into_iter()
+            .map(|edge| {
+                if reversed.contains(&edge) {
+                    (edge.1, edge.0)
+                } else {
+                    edge
+                }
+            })
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    /// Kahn's algorithm, returns the unsorted vertices on a cycle.
+    fn topological(count: usize, edges: &[(usize, usize)]) -> Result<Vec<usize>, Vec<usize>> {
+        let mut in_degree = vec![0; count];
+        for (_, dest) in edges {
+            in_degree[*dest] += 1;
+        }
+        let mut queue: Vec<usize> = (0..count).filter(|v| in_degree[*v] == 0).collect();
+        let mut order = vec![];
+        while let Some(vertex) = queue.pop() {
+            order.push(vertex);
+            for (_, dest) in edges.iter().filter(|(src, _)| *src == vertex) {
+                in_degree[*dest] -= 1;
+                if in_degree[*dest] == 0 {
+                    queue.push(*dest);
+                }
+            }
+        }
+        if order.len() == count {
+            Ok(order)
+        } else {
+            Err((0..count).filter(|v| in_degree[*v] > 0).collect())
+        }
+    }
+
+    /// Counts the crossings of the edges between adjacent layers.
+    fn crossings(layers: &[Vec<usize>], vertex_layer: &[usize], edges: &[(usize, usize)]) -> usize {
+        let mut position = vec![0; vertex_layer.len()];
+        for layer in layers {
+            for (i, vertex) in layer.iter().enumerate() {
+                position[*vertex] = i;
+            }
+        }
+
+        let mut crossings = 0;
+        for l in 0..layers.len().saturating_sub(1) {
+            let between: Vec<(usize, usize)> = edges
+                .iter()
+                .filter(|(src, _)| vertex_layer[*src] == l)
+                .map(|(src, dest)| (position[*src], position[*dest]))
+                .collect();
+            for (i, a) in between.iter().enumerate() {
+                for b in &between[i + 1..] {
+                    if (a.0 < b.0 && a.1 > b.1) || (a.0 > b.0 && a.1 < b.1) {
+                        crossings += 1;
+                    }
+                }
+            }
+        }
+        crossings
+    }
+}
@@ -1,9 +1,32 @@
 pub use crate::prelude::*;
 use std::ops::{Index, IndexMut};
 
+/// The keys a palette can be sorted by.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum ThePaletteSort {
+    Hue,
+    Lightness,
+    Usage,
+}
+
+impl ThePaletteSort {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Hue => "Hue",
+            Self::Lightness => "Lightness",
+            Self::Usage => "Usage",
+        }
+    }
+    pub fn iterator() -> impl Iterator<Item = ThePaletteSort> {
+        [Self::Hue, Self::Lightness, Self::Usage].iter().copied()
+    }
+}
+
 /// Holds an array of colors.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct ThePalette {
+    #[serde(default)]
+    pub name: String,
     #[serde(default)]
     pub current_index: u16,
     pub colors: Vec<Option<TheColor>>,
@@ -18,6 +41,7 @@ impl Default for ThePalette {
 impl ThePalette {
     pub fn new(colors: Vec<Option<TheColor>>) -> Self {
         Self {
+            name: String::default(),
             current_index: 0,
             colors,
         }
@@ -29,6 +53,7 @@ impl ThePalette {
             colors.push(None);
         }
         Self {
+            name: String::default(),
             current_index: 0,
             colors,
         }
@@ -56,70 +81,20 @@ impl ThePalette {
         }
     }
 
-    /// Load the palette from a Paint.net TXT file
+    /// Load the colors of a Paint.net TXT file into the palette, starting at the current index.
+    /// Malformed files are ignored, use `try_load_from_txt` to get the parse error.
     pub fn load_from_txt(&mut self, txt: String) {
-        let mut index = self.current_index as usize;
-        for line in txt.lines() {
-            // Ignore comments
-            if line.starts_with(';') {
-                continue;
-            }
-
-            let mut chars = line.chars();
-
-            // Skip Alpha
-            if chars.next().is_none() {
-                return;
-            }
-            if chars.next().is_none() {
-                return;
-            }
-
-            // R
-            let mut r_string = "".to_string();
-            if let Some(c) = chars.next() {
-                r_string.push(c);
-            }
-            if let Some(c) = chars.next() {
-                r_string.push(c);
-            }
-
-            let r = u8::from_str_radix(&r_string, 16);
-
-            // G
-            let mut g_string = "".to_string();
-            if let Some(c) = chars.next() {
-                g_string.push(c);
-            }
-            if let Some(c) = chars.next() {
-                g_string.push(c);
-            }
-
-            let g = u8::from_str_radix(&g_string, 16);
-
-            // B
-            let mut b_string = "".to_string();
-            if let Some(c) = chars.next() {
-                b_string.push(c);
-            }
-            if let Some(c) = chars.next() {
-                b_string.push(c);
-            }
-
-            let b = u8::from_str_radix(&b_string, 16);
-
-            if r.is_ok() && g.is_ok() && b.is_ok() {
-                let r = r.ok().unwrap();
-                let g = g.ok().unwrap();
-                let b = b.ok().unwrap();
-
-                if index < self.colors.len() {
-                    self.colors[index] = Some(TheColor::from_u8(r, g, b, 0xFF));
-                }
+        _ = self.try_load_from_txt(&txt);
+    }
 
-                index += 1;
-            }
+    /// Load the colors of a Paint.net TXT file into the palette, starting at the current index.
+    pub fn try_load_from_txt(&mut self, txt: &str) -> Result<(), ThePaletteError> {
+        let loaded = Self::from_paint_net(txt)?;
+        let start = self.current_index as usize;
+        for (slot, color) in self.colors.iter_mut().skip(start).zip(loaded.colors) {
+            *slot = color;
         }
+        Ok(())
     }
 
     /// Adds a color to the palette if it doesn't already exist.
@@ -146,6 +121,114 @@ impl ThePalette {
         None
     }
 
+    /// Swaps the entries at the two indices.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if a < self.colors.len() && b < self.colors.len() {
+            self.colors.swap(a, b);
+        }
+    }
+
+    /// Moves the entry at `from` to `to`, shifting the entries in between.
+    pub fn move_color(&mut self, from: usize, to: usize) {
+        if from < self.colors.len() && to < self.colors.len() {
+            let color = self.colors.remove(from);
+            self.colors.insert(to, color);
+        }
+    }
+
+    /// Inserts an empty entry at the index. The palette keeps its size if the last entry is empty.
+    pub fn insert_empty(&mut self, index: usize) {
+        if index <= self.colors.len() {
+            self.colors.insert(index, None);
+            if self.colors.last() == Some(&None) {
+                self.colors.pop();
+            }
+        }
+    }
+
+    /// Removes the entry at the index, shifting the following entries and appending an empty entry.
+    pub fn remove_color(&mut self, index: usize) {
+        if index < self.colors.len() {
+            self.colors.remove(index);
+            self.colors.push(None);
+        }
+    }
+
+    /// Fills the entries between `from` and `to` with a ramp between their colors, mixed in OKLab.
+    /// Returns false if one of the two entries is empty.
+    pub fn ramp(&mut self, from: usize, to: usize) -> bool {
+        let (from, to) = (from.min(to), from.max(to));
+        let (Some(Some(start)), Some(Some(end))) =
+            (self.colors.get(from).cloned(), self.colors.get(to).cloned())
+        else {
+            return false;
+        };
+
+        let steps = (to - from) as f32;
+        for i in from + 1..to {
+            let v = (i - from) as f32 / steps;
+            self.colors[i] = Some(start.mix_in(&end, v, TheColorSpace::OkLab));
+        }
+        true
+    }
+
+    /// Sorts the colors in the given slots by the key, empty entries go last. `usage` holds the use
+    /// count per entry for [`ThePaletteSort::Usage`]. Returns the previous index of each slot in order.
+    pub fn sort(&mut self, slots: &[usize], key: ThePaletteSort, usage: &[u32]) -> Vec<usize> {
+        let mut slots: Vec<usize> = slots
+            .iter()
+            .copied()
+            .filter(|i| *i < self.colors.len())
+            .collect();
+        slots.sort_unstable();
+        slots.dedup();
+
+        let sort_key = |index: usize| -> (u8, f32, f32) {
+            match &self.colors[index] {
+                Some(color) => match key {
+                    ThePaletteSort::Hue => {
+                        let hsl = color.as_hsl();
+                        // Grays first, ordered by lightness.
+                        if hsl.y < 0.001 {
+                            (0, 0.0, hsl.z)
+                        } else {
+                            (1, hsl.x, hsl.z)
+                        }
+                    }
+                    ThePaletteSort::Lightness => (0, color.as_oklab().x, 0.0),
+                    ThePaletteSort::Usage => {
+                        (0, -(usage.get(index).copied().unwrap_or(0) as f32), 0.0)
+                    }
+                },
+                None => (2, 0.0, 0.0),
+            }
+        };
+
+        let mut order = slots.clone();
+        order.sort_by(|a, b| {
+            let (ka, kb) = (sort_key(*a), sort_key(*b));
+            ka.0.cmp(&kb.0)
+                .then(ka.1.total_cmp(&kb.1))
+                .then(ka.2.total_cmp(&kb.2))
+        });
+
+        let colors: Vec<Option<TheColor>> = order.iter().map(|i| self.colors[*i].clone()).collect();
+        for (slot, color) in slots.iter().zip(colors) {
+            self.colors[*slot] = color;
+        }
+        order
+    }
+
+    /// Load a palette from json.
+    pub fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_default()
+    }
+
+    /// Convert the palette to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+
     /// Returns the index of the closest matching color in the palette.
     /// Returns `None` if the palette is empty.
     pub fn find_closest_color_index(&self, color: &TheColor) -> Option<usize> {
@@ -0,0 +1,597 @@
+use crate::prelude::*;
+use std::path::Path;
+
+/// Errors which can occur while reading or writing palette files.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThePaletteError {
+    /// The file could not be read or written.
+    Io(String),
+    /// The data is not in a supported palette format.
+    UnsupportedFormat,
+    /// The data is malformed. Holds the line (or byte offset for binary formats) and a description.
+    Parse(usize, String),
+    /// The palette strip image could not be decoded or encoded.
+    Image(String),
+}
+
+impl std::fmt::Display for ThePaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::UnsupportedFormat => write!(f, "Unsupported palette format"),
+            Self::Parse(line, err) => write!(f, "Parse error at {}: {}", line, err),
+            Self::Image(err) => write!(f, "Image error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ThePaletteError {}
+
+/// The palette file formats ThePalette can read and write.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThePaletteFormat {
+    /// GIMP palette (.gpl), keeps the palette and color names.
+    Gimp,
+    /// JASC / Paint Shop Pro palette (.pal).
+    Jasc,
+    /// Adobe Swatch Exchange (.ase), keeps the group and color names.
+    Ase,
+    /// Plain list of RRGGBB values (.hex).
+    Hex,
+    /// Paint.net palette of AARRGGBB values (.txt).
+    PaintNet,
+    /// Image strip of swatches (.png).
+    Png,
+}
+
+impl ThePaletteFormat {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Gimp => "GIMP Palette",
+            Self::Jasc => "JASC Palette",
+            Self::Ase => "Adobe Swatch Exchange",
+            Self::Hex => "Hex List",
+            Self::PaintNet => "Paint.net Palette",
+            Self::Png => "PNG Strip",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = ThePaletteFormat> {
+        [
+            Self::Gimp,
+            Self::Jasc,
+            Self::Ase,
+            Self::Hex,
+            Self::PaintNet,
+            Self::Png,
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// The file extension of the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gimp => "gpl",
+            Self::Jasc => "pal",
+            Self::Ase => "ase",
+            Self::Hex => "hex",
+            Self::PaintNet => "txt",
+            Self::Png => "png",
+        }
+    }
+
+    /// Returns the format for the given file extension.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_lowercase();
+        Self::iterator().find(|format| format.extension() == extension)
+    }
+
+    /// Detects the format from the file contents.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"GIMP Palette") {
+            return Some(Self::Gimp);
+        }
+        if data.starts_with(b"JASC-PAL") {
+            return Some(Self::Jasc);
+        }
+        if data.starts_with(b"ASEF") {
+            return Some(Self::Ase);
+        }
+        if TheImageFormat::detect(data) == Some(TheImageFormat::Png) {
+            return Some(Self::Png);
+        }
+
+        // Text lists, decide on the first line which is not a comment
+        let text = std::str::from_utf8(data).ok()?;
+        if text.trim_start().starts_with(';') {
+            return Some(Self::PaintNet);
+        }
+        let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+        let digits = line.strip_prefix('#').unwrap_or(line);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        // Paint.net files always start with a comment header, bare 8 digit values are RRGGBBAA
+        match digits.len() {
+            6 | 8 => Some(Self::Hex),
+            _ => None,
+        }
+    }
+}
+
+/// Palette file import and export.
+impl ThePalette {
+    /// Reads a palette, the format is detected from the contents.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ThePaletteError> {
+        let format = ThePaletteFormat::detect(data).ok_or(ThePaletteError::UnsupportedFormat)?;
+        Self::from_format_bytes(data, format)
+    }
+
+    /// Reads a palette in the given format.
+    pub fn from_format_bytes(
+        data: &[u8],
+        format: ThePaletteFormat,
+    ) -> Result<Self, ThePaletteError> {
+        match format {
+            ThePaletteFormat::Ase => Self::from_ase(data),
+            ThePaletteFormat::Png => Self::from_png_strip(data),
+            _ => {
+                let text = std::str::from_utf8(data)
+                    .map_err(|err| ThePaletteError::Parse(1, err.to_string()))?;
+                match format {
+                    ThePaletteFormat::Gimp => Self::from_gpl(text),
+                    ThePaletteFormat::Jasc => Self::from_jasc(text),
+                    ThePaletteFormat::Hex => Self::from_hex_list(text),
+                    _ => Self::from_paint_net(text),
+                }
+            }
+        }
+    }
+
+    /// Reads a palette file. The format is detected from the contents, falling back to the extension.
+    /// Hex lists inside a .txt file are read as Paint.net palettes without a header.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ThePaletteError> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|err| ThePaletteError::Io(err.to_string()))?;
+        let by_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ThePaletteFormat::from_extension);
+        let format = match (ThePaletteFormat::detect(&data), by_extension) {
+            (Some(ThePaletteFormat::Hex), Some(ThePaletteFormat::PaintNet)) => {
+                ThePaletteFormat::PaintNet
+            }
+            (detected, by_extension) => detected
+                .or(by_extension)
+                .ok_or(ThePaletteError::UnsupportedFormat)?,
+        };
+        Self::from_format_bytes(&data, format)
+    }
+
+    /// Writes the palette in the given format. Empty palette slots are skipped.
+    pub fn to_bytes(&self, format: ThePaletteFormat) -> Result<Vec<u8>, ThePaletteError> {
+        match format {
+            ThePaletteFormat::Gimp => Ok(self.to_gpl().into_bytes()),
+            ThePaletteFormat::Jasc => Ok(self.to_jasc().into_bytes()),
+            ThePaletteFormat::Ase => Ok(self.to_ase()),
+            ThePaletteFormat::Hex => Ok(self.to_hex_list().into_bytes()),
+            ThePaletteFormat::PaintNet => Ok(self.to_paint_net().into_bytes()),
+            ThePaletteFormat::Png => self.to_png_strip(1),
+        }
+    }
+
+    /// Writes the palette to a file, the format is chosen by the extension.
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), ThePaletteError> {
+        let path = path.as_ref();
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ThePaletteFormat::from_extension)
+            .ok_or(ThePaletteError::UnsupportedFormat)?;
+        let data = self.to_bytes(format)?;
+        std::fs::write(path, data).map_err(|err| ThePaletteError::Io(err.to_string()))
+    }
+
+    /// Parses a GIMP palette.
+    pub fn from_gpl(text: &str) -> Result<Self, ThePaletteError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == "GIMP Palette" => {}
+            _ => return Err(parse_error(0, "Missing GIMP Palette header")),
+        }
+
+        let mut palette = Self::new(vec![]);
+        for (index, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("Name:") {
+                palette.name = name.trim().to_string();
+                continue;
+            }
+            if line.starts_with("Columns:") {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let mut channel = || -> Result<u8, ThePaletteError> {
+                parts
+                    .next()
+                    .and_then(|value| value.parse::<u8>().ok())
+                    .ok_or_else(|| parse_error(index, "Expected three values between 0 and 255"))
+            };
+            let (r, g, b) = (channel()?, channel()?, channel()?);
+            let mut color = TheColor::from_u8(r, g, b, 255);
+            color.name = parts.collect::<Vec<_>>().join(" ");
+            if color.name == "Untitled" {
+                color.name.clear();
+            }
+            palette.colors.push(Some(color));
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as a GIMP palette.
+    pub fn to_gpl(&self) -> String {
+        let mut text = String::from("GIMP Palette\n");
+        if !self.name.is_empty() {
+            text += &format!("Name: {}\n", self.name);
+        }
+        text += "#\n";
+        for color in self.colors.iter().flatten() {
+            let [r, g, b, _] = rounded_u8(color);
+            let name = if color.name.is_empty() {
+                "Untitled"
+            } else {
+                &color.name
+            };
+            text += &format!("{:3} {:3} {:3}\t{}\n", r, g, b, name);
+        }
+        text
+    }
+
+    /// Parses a JASC palette. An optional fourth value per line is read as alpha.
+    pub fn from_jasc(text: &str) -> Result<Self, ThePaletteError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, header)) if header.trim() == "JASC-PAL" => {}
+            _ => return Err(parse_error(0, "Missing JASC-PAL header")),
+        }
+        match lines.next() {
+            Some((_, version)) if version.trim() == "0100" => {}
+            Some((index, _)) => return Err(parse_error(index, "Unsupported version")),
+            None => return Err(parse_error(1, "Missing version")),
+        }
+        let count = match lines.next() {
+            Some((index, count)) => count
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| parse_error(index, "Invalid color count"))?,
+            None => return Err(parse_error(2, "Missing color count")),
+        };
+
+        let mut palette = Self::new(vec![]);
+        let mut last = 2;
+        for (index, line) in lines {
+            let values: Vec<u8> = line
+                .split_whitespace()
+                .map(|value| value.parse::<u8>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| parse_error(index, "Expected values between 0 and 255"))?;
+            let color = match values[..] {
+                [r, g, b] => TheColor::from_u8(r, g, b, 255),
+                [r, g, b, a] => TheColor::from_u8(r, g, b, a),
+                _ => return Err(parse_error(index, "Expected three or four values")),
+            };
+            palette.colors.push(Some(color));
+            last = index;
+        }
+        if palette.colors.len() != count {
+            return Err(parse_error(
+                last,
+                &format!("Expected {} colors, found {}", count, palette.colors.len()),
+            ));
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as a JASC palette.
+    pub fn to_jasc(&self) -> String {
+        let colors: Vec<&TheColor> = self.colors.iter().flatten().collect();
+        let mut text = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+        for color in colors {
+            let [r, g, b, _] = rounded_u8(color);
+            text += &format!("{} {} {}\r\n", r, g, b);
+        }
+        text
+    }
+
+    /// Parses a list of RRGGBB or RRGGBBAA values, optionally prefixed by #. Lines starting with
+    /// ; or // are comments.
+    pub fn from_hex_list(text: &str) -> Result<Self, ThePaletteError> {
+        let mut palette = Self::new(vec![]);
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with("//") {
+                continue;
+            }
+            let digits = line.strip_prefix('#').unwrap_or(line);
+            let color = parse_hex_digits(digits, false)
+                .ok_or_else(|| parse_error(index, "Expected RRGGBB or RRGGBBAA"))?;
+            palette.colors.push(Some(color));
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as a list of hex values. Alpha is only written for translucent colors.
+    pub fn to_hex_list(&self) -> String {
+        let mut text = String::new();
+        for color in self.colors.iter().flatten() {
+            let [r, g, b, a] = rounded_u8(color);
+            if a == 255 {
+                text += &format!("{:02x}{:02x}{:02x}\n", r, g, b);
+            } else {
+                text += &format!("{:02x}{:02x}{:02x}{:02x}\n", r, g, b, a);
+            }
+        }
+        text
+    }
+
+    /// Parses a Paint.net palette of AARRGGBB values.
+    pub fn from_paint_net(text: &str) -> Result<Self, ThePaletteError> {
+        let mut palette = Self::new(vec![]);
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if let Some(name) = line.strip_prefix(";Palette Name:") {
+                palette.name = name.trim().to_string();
+                continue;
+            }
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let color = parse_hex_digits(line, true)
+                .filter(|_| line.len() == 8)
+                .ok_or_else(|| parse_error(index, "Expected AARRGGBB"))?;
+            palette.colors.push(Some(color));
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as a Paint.net palette.
+    pub fn to_paint_net(&self) -> String {
+        let mut text = String::from(";paint.net Palette File\n");
+        if !self.name.is_empty() {
+            text += &format!(";Palette Name: {}\n", self.name);
+        }
+        let count = self.colors.iter().flatten().count();
+        text += &format!(";Colors: {}\n", count);
+        for color in self.colors.iter().flatten() {
+            let [r, g, b, a] = rounded_u8(color);
+            text += &format!("{:02X}{:02X}{:02X}{:02X}\n", a, r, g, b);
+        }
+        text
+    }
+
+    /// Parses an Adobe Swatch Exchange file. RGB, CMYK, LAB and gray swatches are converted to RGB,
+    /// the name of the first group becomes the palette name.
+    pub fn from_ase(data: &[u8]) -> Result<Self, ThePaletteError> {
+        let mut reader = TheAseReader { data, offset: 0 };
+        if reader.bytes(4)? != b"ASEF" {
+            return Err(ThePaletteError::Parse(
+                0,
+                "Missing ASEF signature".to_string(),
+            ));
+        }
+        let _version = (reader.u16()?, reader.u16()?);
+        let blocks = reader.u32()?;
+
+        let mut palette = Self::new(vec![]);
+        for _ in 0..blocks {
+            let block_type = reader.u16()?;
+            let length = reader.u32()? as usize;
+            let start = reader.offset;
+            match block_type {
+                // Group start
+                0xC001 => {
+                    let name = reader.utf16_string()?;
+                    if palette.name.is_empty() {
+                        palette.name = name;
+                    }
+                }
+                // Color entry
+                0x0001 => {
+                    let name = reader.utf16_string()?;
+                    let model = reader.bytes(4)?;
+                    let mut color = match model {
+                        b"RGB " => {
+                            let (r, g, b) = (reader.f32()?, reader.f32()?, reader.f32()?);
+                            TheColor::new(r, g, b, 1.0)
+                        }
+                        b"CMYK" => {
+                            let (c, m, y, k) =
+                                (reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?);
+                            TheColor::new(
+                                (1.0 - c) * (1.0 - k),
+                                (1.0 - m) * (1.0 - k),
+                                (1.0 - y) * (1.0 - k),
+                                1.0,
+                            )
+                        }
+                        b"LAB " => {
+                            let (l, a, b) = (reader.f32()?, reader.f32()?, reader.f32()?);
+                            TheColor::from_lab_d50(l * 100.0, a, b)
+                        }
+                        b"Gray" => {
+                            let v = reader.f32()?;
+                            TheColor::new(v, v, v, 1.0)
+                        }
+                        _ => {
+                            return Err(ThePaletteError::Parse(
+                                start,
+                                "Unsupported color model".to_string(),
+                            ))
+                        }
+                    };
+                    color.name = name;
+                    palette.colors.push(Some(color));
+                }
+                _ => {}
+            }
+            // Skip the remainder, including the color type and unknown blocks
+            reader.offset = start + length;
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as an Adobe Swatch Exchange file with RGB swatches. Named palettes are
+    /// written as a group.
+    pub fn to_ase(&self) -> Vec<u8> {
+        let colors: Vec<&TheColor> = self.colors.iter().flatten().collect();
+        let grouped = !self.name.is_empty();
+
+        let mut data = b"ASEF".to_vec();
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        let blocks = colors.len() + if grouped { 2 } else { 0 };
+        data.extend_from_slice(&(blocks as u32).to_be_bytes());
+
+        let write_block = |data: &mut Vec<u8>, block_type: u16, body: &[u8]| {
+            data.extend_from_slice(&block_type.to_be_bytes());
+            data.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            data.extend_from_slice(body);
+        };
+
+        if grouped {
+            write_block(&mut data, 0xC001, &utf16_string(&self.name));
+        }
+        for color in colors {
+            let mut body = utf16_string(&color.name);
+            body.extend_from_slice(b"RGB ");
+            for value in [color.r, color.g, color.b] {
+                body.extend_from_slice(&value.to_be_bytes());
+            }
+            // Normal (non global, non spot) color
+            body.extend_from_slice(&2u16.to_be_bytes());
+            write_block(&mut data, 0x0001, &body);
+        }
+        if grouped {
+            write_block(&mut data, 0xC002, &[]);
+        }
+        data
+    }
+
+    /// Reads the colors of a palette strip image in reading order. Every distinct color becomes one
+    /// entry, so both one pixel and larger swatches work. Fully transparent pixels are ignored.
+    pub fn from_png_strip(data: &[u8]) -> Result<Self, ThePaletteError> {
+        let buffer = TheRGBABuffer::from_png_bytes(data)
+            .map_err(|err| ThePaletteError::Image(err.to_string()))?;
+        let mut palette = Self::new(vec![]);
+        let mut seen = FxHashSet::default();
+        for pixel in buffer.pixels().chunks_exact(4) {
+            let pixel = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            if pixel[3] > 0 && seen.insert(pixel) {
+                palette.colors.push(Some(TheColor::from_u8_array(pixel)));
+            }
+        }
+        Ok(palette)
+    }
+
+    /// Writes the palette as a horizontal PNG strip with square swatches of the given size.
+    pub fn to_png_strip(&self, swatch_size: i32) -> Result<Vec<u8>, ThePaletteError> {
+        let colors: Vec<&TheColor> = self.colors.iter().flatten().collect();
+        let size = swatch_size.max(1);
+        let mut buffer =
+            TheRGBABuffer::new(TheDim::sized((colors.len() as i32 * size).max(1), size));
+        for (index, color) in colors.iter().enumerate() {
+            let rect = TheDim::new(index as i32 * size, 0, size, size);
+            buffer.paint_rect(&rect, size, &rounded_u8(color), true);
+        }
+        buffer
+            .to_png()
+            .map_err(|err| ThePaletteError::Image(err.to_string()))
+    }
+}
+
+/// Creates a parse error for the zero based line index.
+fn parse_error(index: usize, message: &str) -> ThePaletteError {
+    ThePaletteError::Parse(index + 1, message.to_string())
+}
+
+/// Converts the color to rounded 8-bit channels.
+fn rounded_u8(color: &TheColor) -> [u8; 4] {
+    [color.r, color.g, color.b, color.a].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Parses RRGGBB, RRGGBBAA or (if alpha_first) AARRGGBB digits.
+fn parse_hex_digits(digits: &str, alpha_first: bool) -> Option<TheColor> {
+    if !digits.is_ascii() || (digits.len() != 6 && digits.len() != 8) {
+        return None;
+    }
+    let values: Vec<u8> = (0..digits.len() / 2)
+        .map(|i| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok())
+        .collect::<Option<_>>()?;
+    Some(match values[..] {
+        [r, g, b] => TheColor::from_u8(r, g, b, 255),
+        [a, r, g, b] if alpha_first => TheColor::from_u8(r, g, b, a),
+        [r, g, b, a] => TheColor::from_u8(r, g, b, a),
+        _ => return None,
+    })
+}
+
+/// Encodes a string as length prefixed, zero terminated UTF-16BE as used by ASE files.
+fn utf16_string(text: &str) -> Vec<u8> {
+    let units: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut data = (units.len() as u16).to_be_bytes().to_vec();
+    for unit in units {
+        data.extend_from_slice(&unit.to_be_bytes());
+    }
+    data
+}
+
+/// Big endian reader for ASE files.
+struct TheAseReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> TheAseReader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ThePaletteError> {
+        let end = self.offset + count;
+        if end > self.data.len() {
+            return Err(ThePaletteError::Parse(
+                self.offset,
+                "Unexpected end of file".to_string(),
+            ));
+        }
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, ThePaletteError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ThePaletteError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, ThePaletteError> {
+        let b = self.bytes(4)?;
+        Ok(f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn utf16_string(&mut self) -> Result<String, ThePaletteError> {
+        let length = self.u16()? as usize;
+        let units: Vec<u16> = (0..length).map(|_| self.u16()).collect::<Result<_, _>>()?;
+        let units = units.split(|unit| *unit == 0).next().unwrap_or(&[]);
+        Ok(String::from_utf16_lossy(units))
+    }
+}
@@ -0,0 +1,755 @@
+use crate::prelude::*;
+use crate::theblendmode::{premultiply, unpremultiply};
+use crate::thecolorspace::{linear_to_srgb, srgb_to_linear};
+use crate::{compress, decompress};
+use ::serde::{Deserializer, Serializer};
+use half::f16;
+use rayon::prelude::*;
+
+/// The pixel formats of ThePixelBuffer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThePixelFormat {
+    Rgba8,
+    Rgb8,
+    Gray8,
+    Rgba16F,
+    Rgba32F,
+}
+
+impl ThePixelFormat {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Rgba8 => "RGBA8",
+            Self::Rgb8 => "RGB8",
+            Self::Gray8 => "Gray8",
+            Self::Rgba16F => "RGBA16F",
+            Self::Rgba32F => "RGBA32F",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = ThePixelFormat> {
+        [
+            Self::Rgba8,
+            Self::Rgb8,
+            Self::Gray8,
+            Self::Rgba16F,
+            Self::Rgba32F,
+        ]
+        .iter()
+        .copied()
+    }
+
+    /// The number of channels of a pixel.
+    pub const fn channels(self) -> usize {
+        match self {
+            Self::Rgb8 => 3,
+            Self::Gray8 => 1,
+            _ => 4,
+        }
+    }
+
+    /// The size of a pixel in bytes.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Rgb8 => 3,
+            Self::Gray8 => 1,
+            Self::Rgba16F => 8,
+            Self::Rgba32F => 16,
+        }
+    }
+
+    /// Float formats hold linear, unbounded (HDR) values. 8-bit formats hold sRGB values.
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Self::Rgba16F | Self::Rgba32F)
+    }
+}
+
+/// How HDR values are mapped to the displayable 0..1 range.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum TheToneMapping {
+    /// Values above 1 are clipped.
+    Clamp,
+    #[default]
+    Reinhard,
+    /// The ACES filmic curve (Narkowicz fit).
+    Aces,
+}
+
+impl TheToneMapping {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Clamp => "Clamp",
+            Self::Reinhard => "Reinhard",
+            Self::Aces => "ACES",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheToneMapping> {
+        [Self::Clamp, Self::Reinhard, Self::Aces].iter().copied()
+    }
+
+    /// Maps a linear channel value to 0..1.
+    #[inline]
+    pub fn map(self, c: f32) -> f32 {
+        let c = c.max(0.0);
+        match self {
+            Self::Clamp => c.min(1.0),
+            Self::Reinhard => c / (1.0 + c),
+            Self::Aces => {
+                ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// A pixel of ThePixelBuffer.
+pub trait ThePixel:
+    Copy + Default + PartialEq + PartialOrd + Send + Sync + std::fmt::Debug
+{
+    const FORMAT: ThePixelFormat;
+
+    /// The type of a single channel of the pixel.
+    type Channel: Copy
+        + Default
+        + PartialEq
+        + PartialOrd
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + Serialize
+        + for<'de> Deserialize<'de>;
+
+    /// Returns the pixel as RGBA, 8-bit formats are normalized to 0..1.
+    fn to_rgba(&self) -> [f32; 4];
+    /// Creates the pixel from RGBA, 8-bit formats are clamped to 0..1.
+    fn from_rgba(rgba: [f32; 4]) -> Self;
+
+    /// Reads the pixel from its channels.
+    fn from_channels(channels: &[Self::Channel]) -> Self;
+    /// Writes the pixel into its channels.
+    fn to_channels(&self, channels: &mut [Self::Channel]);
+
+    /// Multiplies the color channels by alpha. Formats without alpha are unchanged.
+    fn premultiplied(&self) -> Self {
+        *self
+    }
+    /// Divides the color channels by alpha. Formats without alpha are unchanged.
+    fn unpremultiplied(&self) -> Self {
+        *self
+    }
+
+    /// Serializes the channels of a buffer.
+    fn serialize_channels<S: Serializer>(
+        channels: &[Self::Channel],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        channels.serialize(serializer)
+    }
+    /// Deserializes the channels of a buffer.
+    fn deserialize_channels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Self::Channel>, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
+
+#[inline]
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Premultiplies a float RGBA pixel.
+#[inline]
+fn premultiply_f32(rgba: [f32; 4]) -> [f32; 4] {
+    let a = rgba[3];
+    [rgba[0] * a, rgba[1] * a, rgba[2] * a, a]
+}
+
+/// Unpremultiplies a float RGBA pixel.
+#[inline]
+fn unpremultiply_f32(rgba: [f32; 4]) -> [f32; 4] {
+    let a = rgba[3];
+    if a <= 0.0 {
+        return [0.0; 4];
+    }
+    [rgba[0] / a, rgba[1] / a, rgba[2] / a, a]
+}
+
+impl ThePixel for [u8; 4] {
+    const FORMAT: ThePixelFormat = ThePixelFormat::Rgba8;
+    type Channel = u8;
+
+    fn to_rgba(&self) -> [f32; 4] {
+        self.map(|c| c as f32 / 255.0)
+    }
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        rgba.map(to_u8)
+    }
+    fn from_channels(channels: &[u8]) -> Self {
+        [channels[0], channels[1], channels[2], channels[3]]
+    }
+    fn to_channels(&self, channels: &mut [u8]) {
+        channels.copy_from_slice(self);
+    }
+    fn premultiplied(&self) -> Self {
+        premultiply(self)
+    }
+    fn unpremultiplied(&self) -> Self {
+        unpremultiply(self)
+    }
+    fn serialize_channels<S: Serializer>(
+        channels: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        compress(channels, serializer)
+    }
+    fn deserialize_channels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        decompress(deserializer)
+    }
+}
+
+impl ThePixel for [u8; 3] {
+    const FORMAT: ThePixelFormat = ThePixelFormat::Rgb8;
+    type Channel = u8;
+
+    fn to_rgba(&self) -> [f32; 4] {
+        let [r, g, b] = self.map(|c| c as f32 / 255.0);
+        [r, g, b, 1.0]
+    }
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        [to_u8(rgba[0]), to_u8(rgba[1]), to_u8(rgba[2])]
+    }
+    fn from_channels(channels: &[u8]) -> Self {
+        [channels[0], channels[1], channels[2]]
+    }
+    fn to_channels(&self, channels: &mut [u8]) {
+        channels.copy_from_slice(self);
+    }
+    fn serialize_channels<S: Serializer>(
+        channels: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        compress(channels, serializer)
+    }
+    fn deserialize_channels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        decompress(deserializer)
+    }
+}
+
+impl ThePixel for u8 {
+    const FORMAT: ThePixelFormat = ThePixelFormat::Gray8;
+    type Channel = u8;
+
+    fn to_rgba(&self) -> [f32; 4] {
+        let v = *self as f32 / 255.0;
+        [v, v, v, 1.0]
+    }
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        to_u8(0.2126 * rgba[0] + 0.7152 * rgba[1] + 0.0722 * rgba[2])
+    }
+    fn from_channels(channels: &[u8]) -> Self {
+        channels[0]
+    }
+    fn to_channels(&self, channels: &mut [u8]) {
+        channels[0] = *self;
+    }
+    fn serialize_channels<S: Serializer>(
+        channels: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        compress(channels, serializer)
+    }
+    fn deserialize_channels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        decompress(deserializer)
+    }
+}
+
+impl ThePixel for [f16; 4] {
+    const FORMAT: ThePixelFormat = ThePixelFormat::Rgba16F;
+    type Channel = f16;
+
+    fn to_rgba(&self) -> [f32; 4] {
+        self.map(f16::to_f32)
+    }
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        rgba.map(f16::from_f32)
+    }
+    fn from_channels(channels: &[f16]) -> Self {
+        [channels[0], channels[1], channels[2], channels[3]]
+    }
+    fn to_channels(&self, channels: &mut [f16]) {
+        channels.copy_from_slice(self);
+    }
+    fn premultiplied(&self) -> Self {
+        Self::from_rgba(premultiply_f32(self.to_rgba()))
+    }
+    fn unpremultiplied(&self) -> Self {
+        Self::from_rgba(unpremultiply_f32(self.to_rgba()))
+    }
+}
+
+impl ThePixel for [f32; 4] {
+    const FORMAT: ThePixelFormat = ThePixelFormat::Rgba32F;
+    type Channel = f32;
+
+    fn to_rgba(&self) -> [f32; 4] {
+        *self
+    }
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        rgba
+    }
+    fn from_channels(channels: &[f32]) -> Self {
+        [channels[0], channels[1], channels[2], channels[3]]
+    }
+    fn to_channels(&self, channels: &mut [f32]) {
+        channels.copy_from_slice(self);
+    }
+    fn premultiplied(&self) -> Self {
+        premultiply_f32(*self)
+    }
+    fn unpremultiplied(&self) -> Self {
+        unpremultiply_f32(*self)
+    }
+}
+
+/// Converts a pixel between formats, applying the sRGB transfer when going between 8-bit and HDR.
+#[inline]
+fn convert_pixel<P: ThePixel, Q: ThePixel>(pixel: &P) -> Q {
+    let mut rgba = pixel.to_rgba();
+    if P::FORMAT.is_hdr() != Q::FORMAT.is_hdr() {
+        let transfer = if P::FORMAT.is_hdr() {
+            |c: f32| linear_to_srgb(c.clamp(0.0, 1.0))
+        } else {
+            srgb_to_linear
+        };
+        for c in rgba.iter_mut().take(3) {
+            *c = transfer(*c);
+        }
+    }
+    Q::from_rgba(rgba)
+}
+
+pub type TheGray8Buffer = ThePixelBuffer<u8>;
+pub type TheRGBA16FBuffer = ThePixelBuffer<[f16; 4]>;
+pub type TheRGBA32FBuffer = ThePixelBuffer<[f32; 4]>;
+
+/// A pixel buffer generic over its pixel format. The channels of all pixels are stored in a
+/// single row major Vec. TheRGBABuffer and TheRGBBuffer are the 8-bit RGBA and RGB variants.
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Clone, Debug)]
+#[serde(bound = "")]
+pub struct ThePixelBuffer<P: ThePixel> {
+    pub(crate) dim: TheDim,
+
+    #[serde(
+        serialize_with = "P::serialize_channels",
+        deserialize_with = "P::deserialize_channels"
+    )]
+    pub(crate) buffer: Vec<P::Channel>,
+
+    /// True if the color channels are premultiplied by alpha.
+    #[serde(default)]
+    pub(crate) premultiplied: bool,
+}
+
+impl<P: ThePixel> Default for ThePixelBuffer<P> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<P: ThePixel> ThePixelBuffer<P> {
+    /// The number of channels per pixel.
+    const CHANNELS: usize = P::FORMAT.channels();
+
+    /// Create an empty buffer.
+    pub fn empty() -> Self {
+        Self {
+            dim: TheDim::zero(),
+            buffer: vec![],
+            premultiplied: false,
+        }
+    }
+
+    /// Creates a buffer of the given dimension.
+    pub fn new(dim: TheDim) -> Self {
+        Self {
+            dim,
+            buffer: vec![
+                P::Channel::default();
+                dim.width as usize * dim.height as usize * Self::CHANNELS
+            ],
+            premultiplied: false,
+        }
+    }
+
+    /// Creates a buffer from existing channel data. Data which does not hold
+    /// width * height * channels values is truncated or padded with zeros.
+    pub fn from(mut buffer: Vec<P::Channel>, width: u32, height: u32) -> Self {
+        buffer.resize(
+            width as usize * height as usize * Self::CHANNELS,
+            P::Channel::default(),
+        );
+        Self {
+            dim: TheDim::new(0, 0, width as i32, height as i32),
+            buffer,
+            premultiplied: false,
+        }
+    }
+
+    /// The pixel format of the buffer.
+    pub fn format(&self) -> ThePixelFormat {
+        P::FORMAT
+    }
+
+    /// Resizes the buffer.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        if self.dim.width != width || self.dim.height != height {
+            self.dim.width = width;
+            self.dim.height = height;
+            self.allocate();
+        }
+    }
+
+    /// Check for size validity
+    pub fn is_valid(&self) -> bool {
+        self.dim.is_valid()
+    }
+
+    /// Returns true if the color channels of the buffer are premultiplied by alpha.
+    pub fn is_premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+
+    /// Marks the buffer as premultiplied or straight alpha without touching the pixels.
+    pub fn set_premultiplied(&mut self, premultiplied: bool) {
+        self.premultiplied = premultiplied;
+    }
+
+    /// Gets the dimension of the buffer.
+    pub fn dim(&self) -> &TheDim {
+        &self.dim
+    }
+
+    /// Gets the width (stride) of the buffer.
+    pub fn stride(&self) -> usize {
+        self.dim.width as usize
+    }
+
+    /// Gets a slice of the buffer.
+    pub fn pixels(&self) -> &[P::Channel] {
+        &self.buffer[..]
+    }
+
+    /// Gets a mutable slice of the buffer.
+    pub fn pixels_mut(&mut self) -> &mut [P::Channel] {
+        &mut self.buffer[..]
+    }
+
+    /// Set the dimension of the buffer.
+    pub fn set_dim(&mut self, dim: TheDim) {
+        if dim != self.dim {
+            self.dim = dim;
+            self.allocate();
+        }
+    }
+
+    /// Check if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        !self.is_valid()
+    }
+
+    /// Returns the number of channel values of the buffer.
+    pub fn len(&self) -> usize {
+        self.dim.width as usize * self.dim.height as usize * Self::CHANNELS
+    }
+
+    /// Allocates the buffer.
+    pub fn allocate(&mut self) {
+        if self.dim.is_valid() {
+            self.buffer = vec![
+                P::Channel::default();
+                self.dim.width as usize * self.dim.height as usize * Self::CHANNELS
+            ];
+        } else {
+            self.buffer = vec![];
+        }
+    }
+
+    /// Fills the entire buffer with the given pixel.
+    pub fn fill(&mut self, pixel: P) {
+        for channels in self.buffer.chunks_exact_mut(Self::CHANNELS) {
+            pixel.to_channels(channels);
+        }
+    }
+
+    /// Helper method to calculate the buffer index for a pixel at (x, y).
+    pub fn pixel_index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && x < self.dim.width && y >= 0 && y < self.dim.height {
+            Some((y as usize * self.dim.width as usize + x as usize) * Self::CHANNELS)
+        } else {
+            None
+        }
+    }
+
+    /// Get a pixel at (x, y).
+    pub fn get_pixel(&self, x: i32, y: i32) -> Option<P> {
+        self.pixel_index(x, y)
+            .map(|index| P::from_channels(&self.buffer[index..index + Self::CHANNELS]))
+    }
+
+    /// Sets the pixel at (x, y).
+    pub fn set_pixel(&mut self, x: i32, y: i32, pixel: &P) {
+        if let Some(index) = self.pixel_index(x, y) {
+            pixel.to_channels(&mut self.buffer[index..index + Self::CHANNELS]);
+        }
+    }
+
+    /// Extracts a sub-buffer of given dimensions from the current buffer.
+    pub fn extract(&self, dim: &TheDim) -> Self {
+        let mut new_buffer = Self::new(*dim);
+        new_buffer.premultiplied = self.premultiplied;
+        new_buffer.copy_into(-dim.x, -dim.y, self);
+        new_buffer
+    }
+
+    /// Copy the other buffer into this buffer at the given coordinates. The pixels are converted
+    /// if only one of the buffers is premultiplied.
+    pub fn copy_into(&mut self, mut x: i32, mut y: i32, other: &ThePixelBuffer<P>) {
+        let channels = Self::CHANNELS as i32;
+
+        // Early return if the whole other buffer is outside this buffer
+        if x + other.dim.width <= 0
+            || y + other.dim.height <= 0
+            || x >= self.dim.width
+            || y >= self.dim.height
+        {
+            return;
+        }
+
+        // Adjust source and destination coordinates and dimensions
+        let mut source_offset_x = 0;
+        let mut source_y_start = 0;
+        let mut copy_width = other.dim.width;
+        let mut copy_height = other.dim.height;
+
+        // Adjust for negative x
+        if x < 0 {
+            source_offset_x = (-x * channels) as usize;
+            copy_width += x;
+            x = 0;
+        }
+
+        // Adjust for negative y
+        if y < 0 {
+            source_y_start = -y;
+            copy_height += y;
+            y = 0;
+        }
+
+        // Adjust for width overflow
+        if x + copy_width > self.dim.width {
+            copy_width = self.dim.width - x;
+        }
+
+        // Adjust for height overflow
+        if y + copy_height > self.dim.height {
+            copy_height = self.dim.height - y;
+        }
+
+        // Calculate the channel width to copy per row
+        let row_width = (copy_width * channels) as usize;
+
+        // Convert the pixels if the alpha representations differ
+        let convert = self.premultiplied != other.premultiplied;
+        let premultiplied = self.premultiplied;
+
+        // Copy the buffer
+        for src_y in source_y_start..source_y_start + copy_height {
+            let src_start = (src_y * other.dim.width * channels) as usize + source_offset_x;
+            let dst_start =
+                ((src_y + y - source_y_start) * self.dim.width * channels + x * channels) as usize;
+
+            let src = &other.buffer[src_start..src_start + row_width];
+            let dst = &mut self.buffer[dst_start..dst_start + row_width];
+
+            // Perform the copy
+            if convert {
+                for (d, s) in dst
+                    .chunks_exact_mut(Self::CHANNELS)
+                    .zip(src.chunks_exact(Self::CHANNELS))
+                {
+                    let pixel = P::from_channels(s);
+                    if premultiplied {
+                        pixel.premultiplied().to_channels(d);
+                    } else {
+                        pixel.unpremultiplied().to_channels(d);
+                    }
+                }
+            } else {
+                dst.copy_from_slice(src);
+            }
+        }
+    }
+
+    /// Creates a scaled version of the buffer (nearest neighbor).
+    pub fn scaled(&self, new_width: i32, new_height: i32) -> Self {
+        let channels = Self::CHANNELS;
+        let scale_x = new_width as f32 / self.dim.width as f32;
+        let scale_y = new_height as f32 / self.dim.height as f32;
+
+        let mut new_buffer = Self::new(TheDim::new(0, 0, new_width, new_height));
+        new_buffer.premultiplied = self.premultiplied;
+
+        for y in 0..new_height {
+            for x in 0..new_width {
+                let src_x = (x as f32 / scale_x).round() as i32;
+                let src_y = (y as f32 / scale_y).round() as i32;
+
+                let pixel_index = (src_y * self.dim.width + src_x) as usize * channels;
+                let new_pixel_index = (y * new_width + x) as usize * channels;
+
+                if pixel_index < self.buffer.len() && new_pixel_index < new_buffer.buffer.len() {
+                    new_buffer.buffer[new_pixel_index..new_pixel_index + channels]
+                        .copy_from_slice(&self.buffer[pixel_index..pixel_index + channels]);
+                }
+            }
+        }
+
+        new_buffer
+    }
+
+    /// Converts the buffer to another pixel format. Premultiplied buffers are converted to
+    /// straight alpha.
+    pub fn convert<Q: ThePixel>(&self) -> ThePixelBuffer<Q> {
+        let channels = Q::FORMAT.channels();
+        let premultiplied = self.premultiplied;
+        let mut out = ThePixelBuffer::<Q>::new(self.dim);
+        out.buffer
+            .par_chunks_exact_mut(channels)
+            .zip(self.buffer.par_chunks_exact(Self::CHANNELS))
+            .for_each(|(out, pixel)| {
+                let mut pixel = P::from_channels(pixel);
+                if premultiplied {
+                    pixel = pixel.unpremultiplied();
+                }
+                convert_pixel::<P, Q>(&pixel).to_channels(out);
+            });
+        out
+    }
+
+    /// Creates the buffer from a TheRGBABuffer.
+    pub fn from_rgba_buffer(buffer: &TheRGBABuffer) -> Self {
+        buffer.convert()
+    }
+
+    /// Converts the buffer to a TheRGBABuffer. HDR values are clamped, see
+    /// [`ThePixelBuffer::tone_map`] to map them into the displayable range.
+    pub fn to_rgba_buffer(&self) -> TheRGBABuffer {
+        self.convert()
+    }
+
+    /// Tone maps the buffer for display. The exposure scales the linear values before mapping.
+    /// 8-bit buffers are already displayable and only converted.
+    pub fn tone_map(&self, mapping: TheToneMapping, exposure: f32) -> TheRGBABuffer {
+        if !P::FORMAT.is_hdr() {
+            return self.to_rgba_buffer();
+        }
+
+        let premultiplied = self.premultiplied;
+        let mut out = TheRGBABuffer::new(self.dim);
+        out.buffer
+            .par_chunks_exact_mut(4)
+            .zip(self.buffer.par_chunks_exact(Self::CHANNELS))
+            .for_each(|(out, pixel)| {
+                let mut pixel = P::from_channels(pixel);
+                if premultiplied {
+                    pixel = pixel.unpremultiplied();
+                }
+                let rgba = pixel.to_rgba();
+                for i in 0..3 {
+                    out[i] = to_u8(linear_to_srgb(mapping.map(rgba[i] * exposure)));
+                }
+                out[3] = to_u8(rgba[3]);
+            });
+
+        out
+    }
+
+    /// Blends the other float buffer into this buffer at the given coordinates using its alpha.
+    fn blend_float_into(&mut self, x: i32, y: i32, other: &ThePixelBuffer<P>) {
+        let channels = Self::CHANNELS;
+        let x0 = x.max(0);
+        let y0 = y.max(0);
+        let x1 = (x + other.dim.width).min(self.dim.width);
+        let y1 = (y + other.dim.height).min(self.dim.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let (src_x, src_y) = ((x0 - x) as usize, (y0 - y) as usize);
+        let width = (x1 - x0) as usize;
+        let premultiplied = self.premultiplied;
+        let convert = self.premultiplied != other.premultiplied;
+        let dst_stride = self.dim.width as usize * channels;
+        let src_stride = other.dim.width as usize * channels;
+
+        self.buffer
+            .par_chunks_exact_mut(dst_stride)
+            .skip(y0 as usize)
+            .take((y1 - y0) as usize)
+            .enumerate()
+            .for_each(|(row, dst_row)| {
+                let src = (src_y + row) * src_stride + src_x * channels;
+                let src_row = &other.buffer[src..src + width * channels];
+                let dst = x0 as usize * channels;
+                for (d, s) in dst_row[dst..dst + width * channels]
+                    .chunks_exact_mut(channels)
+                    .zip(src_row.chunks_exact(channels))
+                {
+                    let mut source = P::from_channels(s);
+                    if convert {
+                        source = if premultiplied {
+                            source.premultiplied()
+                        } else {
+                            source.unpremultiplied()
+                        };
+                    }
+                    let s = source.to_rgba();
+                    let b = P::from_channels(d).to_rgba();
+                    let a = s[3];
+                    let out = if premultiplied {
+                        [0, 1, 2, 3].map(|i| s[i] + b[i] * (1.0 - a))
+                    } else {
+                        [
+                            s[0] * a + b[0] * (1.0 - a),
+                            s[1] * a + b[1] * (1.0 - a),
+                            s[2] * a + b[2] * (1.0 - a),
+                            a + b[3] * (1.0 - a),
+                        ]
+                    };
+                    P::from_rgba(out).to_channels(d);
+                }
+            });
+    }
+}
+
+impl TheRGBA16FBuffer {
+    /// Blend the other buffer into this buffer at the given coordinates using its alpha.
+    pub fn blend_into(&mut self, x: i32, y: i32, other: &TheRGBA16FBuffer) {
+        self.blend_float_into(x, y, other);
+    }
+}
+
+impl TheRGBA32FBuffer {
+    /// Blend the other buffer into this buffer at the given coordinates using its alpha.
+    pub fn blend_into(&mut self, x: i32, y: i32, other: &TheRGBA32FBuffer) {
+        self.blend_float_into(x, y, other);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::prelude::*;
+use crate::thecolorspace::{linear_to_oklab, linear_to_srgb, oklab_to_linear, srgb_to_linear};
+
+/// The algorithm used to extract a palette from an image.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TheQuantizeMethod {
+    /// Median cut, fast and deterministic.
+    #[default]
+    MedianCut,
+    /// K-means in OKLab space seeded by median cut, slower but closer to the perceived colors.
+    KMeans,
+}
+
+impl TheQuantizeMethod {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::MedianCut => "Median Cut",
+            Self::KMeans => "K-Means",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheQuantizeMethod> {
+        [Self::MedianCut, Self::KMeans].iter().copied()
+    }
+}
+
+/// The dithering used when remapping an image onto a palette.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TheDitherMode {
+    #[default]
+    None,
+    /// Ordered dithering with a 4x4 Bayer matrix.
+    Bayer4x4,
+    /// Ordered dithering with an 8x8 Bayer matrix.
+    Bayer8x8,
+    /// Floyd–Steinberg error diffusion.
+    FloydSteinberg,
+}
+
+impl TheDitherMode {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Bayer4x4 => "Bayer 4x4",
+            Self::Bayer8x8 => "Bayer 8x8",
+            Self::FloydSteinberg => "Floyd-Steinberg",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = TheDitherMode> {
+        [
+            Self::None,
+            Self::Bayer4x4,
+            Self::Bayer8x8,
+            Self::FloydSteinberg,
+        ]
+        .iter()
+        .copied()
+    }
+}
+
+/// Palette extraction and palette remapping for TheRGBABuffer. Fully transparent pixels are
+/// ignored and keep their value, the alpha of all other pixels is preserved.
+impl TheRGBABuffer {
+    /// Extracts a palette of at most the given number of colors.
+    pub fn extract_palette(&self, count: usize, method: TheQuantizeMethod) -> ThePalette {
+        let histogram = self.color_histogram();
+        let mut colors = median_cut(&histogram, count);
+        if method == TheQuantizeMethod::KMeans {
+            colors = kmeans(&histogram, colors);
+        }
+        ThePalette::new(
+            colors
+                .into_iter()
+                .map(|[r, g, b]| Some(TheColor::from_u8(r, g, b, 255)))
+                .collect(),
+        )
+    }
+
+    /// Reduces the buffer to at most the given number of colors.
+    pub fn quantize(&mut self, count: usize, method: TheQuantizeMethod, dither: TheDitherMode) {
+        let palette = self.extract_palette(count, method);
+        self.remap_to_palette(&palette, dither);
+    }
+
+    /// Returns a copy of the buffer remapped onto the palette.
+    pub fn remapped_to_palette(&self, palette: &ThePalette, dither: TheDitherMode) -> Self {
+        let mut buffer = self.clone();
+        buffer.remap_to_palette(palette, dither);
+        buffer
+    }
+
+    /// Remaps every pixel onto the perceptually closest palette color.
+    pub fn remap_to_palette(&mut self, palette: &ThePalette, dither: TheDitherMode) {
+        let mut matcher = ThePaletteMatcher::new(palette);
+        if matcher.is_empty() {
+            return;
+        }
+        let width = self.dim().width as usize;
+        let height = self.dim().height as usize;
+
+        match dither {
+            TheDitherMode::None | TheDitherMode::Bayer4x4 | TheDitherMode::Bayer8x8 => {
+                let size = match dither {
+                    TheDitherMode::Bayer4x4 => 4,
+                    TheDitherMode::Bayer8x8 => 8,
+                    _ => 0,
+                };
+                let spread = matcher.spread();
+                for (index, pixel) in self.pixels_mut().chunks_exact_mut(4).enumerate() {
+                    if pixel[3] == 0 {
+                        continue;
+                    }
+                    let mut rgb = [pixel[0], pixel[1], pixel[2]];
+                    if size > 0 {
+                        let (x, y) = (index % width, index / width);
+                        let offset = (bayer(x, y, size) - 0.5) * spread;
+                        rgb = rgb.map(|c| (c as f32 + offset).round().clamp(0.0, 255.0) as u8);
+                    }
+                    pixel[0..3].copy_from_slice(&matcher.closest(rgb));
+                }
+            }
+            TheDitherMode::FloydSteinberg => {
+                // Error of the current and the next row, one extra pixel on each side
+                let mut current = vec![[0.0f32; 3]; width + 2];
+                let mut next = vec![[0.0f32; 3]; width + 2];
+                let pixels = self.pixels_mut();
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = (y * width + x) * 4;
+                        if pixels[i + 3] == 0 {
+                            continue;
+                        }
+                        let wanted: [f32; 3] =
+                            std::array::from_fn(|c| pixels[i + c] as f32 + current[x + 1][c]);
+                        let rgb = wanted.map(|c| c.round().clamp(0.0, 255.0) as u8);
+                        let found = matcher.closest(rgb);
+                        pixels[i..i + 3].copy_from_slice(&found);
+
+                        for c in 0..3 {
+                            let error = wanted[c] - found[c] as f32;
+                            current[x + 2][c] += error * 7.0 / 16.0;
+                            next[x][c] += error * 3.0 / 16.0;
+                            next[x + 1][c] += error * 5.0 / 16.0;
+                            next[x + 2][c] += error * 1.0 / 16.0;
+                        }
+                    }
+                    std::mem::swap(&mut current, &mut next);
+                    next.iter_mut().for_each(|e| *e = [0.0; 3]);
+                }
+            }
+        }
+    }
+
+    /// Returns the distinct opaque colors of the buffer and how often they occur.
+    fn color_histogram(&self) -> Vec<([u8; 3], u32)> {
+        let mut counts: FxHashMap<[u8; 3], u32> = FxHashMap::default();
+        for pixel in self.pixels().chunks_exact(4) {
+            if pixel[3] > 0 {
+                *counts.entry([pixel[0], pixel[1], pixel[2]]).or_default() += 1;
+            }
+        }
+        let mut histogram: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+        // Hash map order is arbitrary, sort to keep the results deterministic
+        histogram.sort_unstable();
+        histogram
+    }
+}
+
+/// Finds the closest palette color in OKLab space, caching the results.
+struct ThePaletteMatcher {
+    colors: Vec<([u8; 3], [f32; 3])>,
+    cache: FxHashMap<[u8; 3], [u8; 3]>,
+}
+
+impl ThePaletteMatcher {
+    fn new(palette: &ThePalette) -> Self {
+        let colors = palette
+            .colors
+            .iter()
+            .flatten()
+            .map(|color| {
+                let rgb =
+                    [color.r, color.g, color.b].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                (rgb, srgb_to_oklab(rgb))
+            })
+            .collect();
+        Self {
+            colors,
+            cache: FxHashMap::default(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    /// The strength of ordered dithering, smaller for larger palettes.
+    fn spread(&self) -> f32 {
+        255.0 / (self.colors.len() as f32).cbrt().max(1.0)
+    }
+
+    fn closest(&mut self, rgb: [u8; 3]) -> [u8; 3] {
+        if let Some(found) = self.cache.get(&rgb) {
+            return *found;
+        }
+        let lab = srgb_to_oklab(rgb);
+        let mut best = self.colors[0].0;
+        let mut best_distance = f32::MAX;
+        for (color, color_lab) in &self.colors {
+            let distance = distance_squared(&lab, color_lab);
+            if distance < best_distance {
+                best_distance = distance;
+                best = *color;
+            }
+        }
+        self.cache.insert(rgb, best);
+        best
+    }
+}
+
+/// Returns the normalized threshold of the Bayer matrix of the given size (a power of two).
+fn bayer(x: usize, y: usize, size: usize) -> f32 {
+    let bits = size.trailing_zeros() as usize;
+    let mut value = 0;
+    for i in 0..bits {
+        let (xb, yb) = ((x >> i) & 1, (y >> i) & 1);
+        value |= (((xb ^ yb) << 1) | yb) << (2 * (bits - 1 - i));
+    }
+    (value as f32 + 0.5) / (size * size) as f32
+}
+
+/// Median cut on the histogram, returns at most count colors.
+fn median_cut(histogram: &[([u8; 3], u32)], count: usize) -> Vec<[u8; 3]> {
+    if histogram.is_empty() || count == 0 {
+        return vec![];
+    }
+    let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![histogram.to_vec()];
+
+    while boxes.len() < count {
+        // Split the box with the largest channel range weighted by its pixel count
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                let pixels: u64 = b.iter().map(|(_, n)| *n as u64).sum();
+                (i, channel, range as u64 * pixels)
+            })
+            .max_by_key(|(_, _, score)| *score);
+        let Some((index, channel, _)) = candidate else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let total: u64 = colors.iter().map(|(_, n)| *n as u64).sum();
+        let mut accumulated = 0;
+        let mut split = 1;
+        for (i, (_, n)) in colors.iter().enumerate() {
+            accumulated += *n as u64;
+            if accumulated * 2 >= total {
+                split = (i + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let total: u64 = b.iter().map(|(_, n)| *n as u64).sum();
+            let mut sum = [0u64; 3];
+            for (color, n) in b {
+                for c in 0..3 {
+                    sum[c] += color[c] as u64 * *n as u64;
+                }
+            }
+            sum.map(|s| ((s + total / 2) / total) as u8)
+        })
+        .collect()
+}
+
+/// Returns the channel with the largest value range in the colors and the range.
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    let mut min = [255u8; 3];
+    let mut max = [0u8; 3];
+    for (color, _) in colors {
+        for c in 0..3 {
+            min[c] = min[c].min(color[c]);
+            max[c] = max[c].max(color[c]);
+        }
+    }
+    (0..3)
+        .map(|c| (c, max[c] - min[c]))
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+/// Refines the seed colors with weighted k-means in OKLab space.
+fn kmeans(histogram: &[([u8; 3], u32)], seeds: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+    if seeds.is_empty() {
+        return seeds;
+    }
+    let samples: Vec<([f32; 3], f32)> = histogram
+        .iter()
+        .map(|(color, n)| (srgb_to_oklab(*color), *n as f32))
+        .collect();
+    let mut centers: Vec<[f32; 3]> = seeds.iter().map(|c| srgb_to_oklab(*c)).collect();
+
+    for _ in 0..16 {
+        let mut sums = vec![([0.0f32; 3], 0.0f32); centers.len()];
+        for (lab, weight) in &samples {
+            let closest = (0..centers.len())
+                .min_by(|a, b| {
+                    distance_squared(lab, &centers[*a])
+                        .total_cmp(&distance_squared(lab, &centers[*b]))
+                })
+                .unwrap_or(0);
+            for (sum, value) in sums[closest].0.iter_mut().zip(lab) {
+                *sum += value * weight;
+            }
+            sums[closest].1 += weight;
+        }
+
+        let mut moved = 0.0f32;
+        for (center, (sum, weight)) in centers.iter_mut().zip(&sums) {
+            if *weight > 0.0 {
+                let updated = sum.map(|s| s / weight);
+                moved = moved.max(distance_squared(center, &updated));
+                *center = updated;
+            }
+        }
+        if moved < 1e-8 {
+            break;
+        }
+    }
+
+    let mut colors: Vec<[u8; 3]> = centers.iter().map(oklab_to_srgb).collect();
+    colors.dedup();
+    colors
+}
+
+fn distance_squared(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+/// Converts an sRGB color to OKLab.
+fn srgb_to_oklab(rgb: [u8; 3]) -> [f32; 3] {
+    linear_to_oklab(rgb.map(|c| srgb_to_linear(c as f32 / 255.0)))
+}
+
+/// Converts an OKLab color to sRGB.
+fn oklab_to_srgb(lab: &[f32; 3]) -> [u8; 3] {
+    oklab_to_linear(*lab).map(|c| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8)
+}
@@ -1,5 +1,4 @@
 use crate::prelude::*;
-use crate::{compress, decompress};
 use fontdue::layout::{
     CoordinateSystem, HorizontalAlign, Layout, LayoutSettings, TextStyle, VerticalAlign,
 };
@@ -9,190 +8,18 @@ use std::ops::{Index, IndexMut, Range};
 use rayon::prelude::*;
 use rayon::slice::ParallelSliceMut;
 
-#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Clone, Debug)]
-pub struct TheRGBABuffer {
-    dim: TheDim,
-
-    #[serde(serialize_with = "compress", deserialize_with = "decompress")]
-    buffer: Vec<u8>,
-}
-
-impl Default for TheRGBABuffer {
-    fn default() -> Self {
-        Self::empty()
-    }
-}
-
 /// TheRGBABuffer contains the pixel buffer for a canvas or icon.
-impl TheRGBABuffer {
-    /// Create an empty buffer.
-    pub fn empty() -> Self {
-        Self {
-            dim: TheDim::zero(),
-            buffer: vec![],
-        }
-    }
-
-    /// Creates a buffer of the given dimension.
-    pub fn new(dim: TheDim) -> Self {
-        Self {
-            dim,
-            buffer: vec![0; dim.width as usize * dim.height as usize * 4],
-        }
-    }
-
-    /// Creates a buffer from existing data.
-    pub fn from(buffer: Vec<u8>, width: u32, height: u32) -> Self {
-        Self {
-            dim: TheDim::new(0, 0, width as i32, height as i32),
-            buffer,
-        }
-    }
-
-    /// Resizes the buffer.
-    pub fn resize(&mut self, width: i32, height: i32) {
-        if self.dim.width != width || self.dim.height != height {
-            self.dim.width = width;
-            self.dim.height = height;
-            self.allocate();
-        }
-    }
-
-    /// Check for size validity
-    pub fn is_valid(&self) -> bool {
-        self.dim.is_valid()
-    }
-
-    /// Gets the width (stride) of the buffer.
-    pub fn dim(&self) -> &TheDim {
-        &self.dim
-    }
-
-    /// Gets the width (stride) of the buffer.
-    pub fn stride(&self) -> usize {
-        self.dim.width as usize
-    }
-
-    /// Gets a slice of the buffer.
-    pub fn pixels(&self) -> &[u8] {
-        &self.buffer[..]
-    }
-
-    /// Gets a mutable slice of the buffer.
-    pub fn pixels_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer[..]
-    }
-
-    /// Set the dimension of the buffer.
-    pub fn set_dim(&mut self, dim: TheDim) {
-        if dim != self.dim {
-            self.dim = dim;
-            self.allocate();
-        }
-    }
-
-    /// Check if the buffer is empty
-    pub fn is_empty(&self) -> bool {
-        !self.is_valid()
-    }
-
-    /// Returns the len of the underlying Vec<u8>
-    pub fn len(&self) -> usize {
-        self.dim.width as usize * self.dim.height as usize * 4
-    }
-
-    /// Allocates the buffer.
-    pub fn allocate(&mut self) {
-        if self.dim.is_valid() {
-            self.buffer = vec![0; self.dim.width as usize * self.dim.height as usize * 4];
-        } else {
-            self.buffer = vec![];
-        }
-    }
-
-    /// Extracts a sub-buffer of given dimensions from the current buffer.
-    pub fn extract(&self, dim: &TheDim) -> Self {
-        let mut new_buffer = Self::new(*dim);
-
-        for y in 0..dim.height {
-            for x in 0..dim.width {
-                let src_x = dim.x + x;
-                let src_y = dim.y + y;
-
-                if src_x >= 0 && src_x < self.dim.width && src_y >= 0 && src_y < self.dim.height {
-                    let src_index = ((src_y * self.dim.width) + src_x) as usize * 4;
-                    let dest_index = ((y * dim.width) + x) as usize * 4;
-
-                    if src_index + 3 < self.buffer.len() && dest_index + 3 < new_buffer.buffer.len()
-                    {
-                        new_buffer.buffer[dest_index..dest_index + 4]
-                            .copy_from_slice(&self.buffer[src_index..src_index + 4]);
-                    }
-                }
-            }
-        }
-
-        new_buffer
-    }
-
-    /// Copy the other buffer into this buffer at the given coordinates.
-    pub fn copy_into(&mut self, mut x: i32, mut y: i32, other: &TheRGBABuffer) {
-        // Early return if the whole other buffer is outside this buffer
-        if x + other.dim.width <= 0
-            || y + other.dim.height <= 0
-            || x >= self.dim.width
-            || y >= self.dim.height
-        {
-            return;
-        }
-
-        // Adjust source and destination coordinates and dimensions
-        let mut source_offset_x = 0;
-        let mut source_y_start = 0;
-        let mut copy_width = other.dim.width;
-        let mut copy_height = other.dim.height;
-
-        // Adjust for negative x
-        if x < 0 {
-            source_offset_x = (-x * 4) as usize;
-            copy_width += x;
-            x = 0;
-        }
-
-        // Adjust for negative y
-        if y < 0 {
-            source_y_start = -y;
-            copy_height += y;
-            y = 0;
-        }
-
-        // Adjust for width overflow
-        if x + copy_width > self.dim.width {
-            copy_width = self.dim.width - x;
-        }
-
-        // Adjust for height overflow
-        if y + copy_height > self.dim.height {
-            copy_height = self.dim.height - y;
-        }
-
-        // Calculate the byte width to copy per row
-        let byte_width = (copy_width * 4) as usize;
-
-        // Copy the buffer
-        for src_y in source_y_start..source_y_start + copy_height {
-            let src_start = (src_y * other.dim.width * 4) as usize + source_offset_x;
-            let dst_start = ((src_y + y - source_y_start) * self.dim.width * 4 + x * 4) as usize;
-
-            // Perform the copy
-            self.buffer[dst_start..dst_start + byte_width]
-                .copy_from_slice(&other.buffer[src_start..src_start + byte_width]);
-        }
-    }
+pub type TheRGBABuffer = ThePixelBuffer<[u8; 4]>;
 
+impl TheRGBABuffer {
     /// Parallel version of `copy_into` using Rayon. Has identical clipping/safety behavior.
     /// Enabled when the `rayon` feature is on. When the feature is off, it falls back to the serial version.
     pub fn copy_into_par(&mut self, mut x: i32, mut y: i32, other: &TheRGBABuffer) {
+        // Converting between alpha representations is handled by the serial path
+        if self.premultiplied != other.premultiplied {
+            return self.copy_into(x, y, other);
+        }
+
         // Early return if the whole other buffer is outside this buffer
         if x + other.dim.width <= 0
             || y + other.dim.height <= 0
@@ -291,7 +118,12 @@ impl TheRGBABuffer {
     }
 
     /// Blend the other buffer into this buffer at the given coordinates (single-threaded reference path).
+    /// Premultiplied buffers are composited with the premultiplied source-over operator.
     pub fn blend_into(&mut self, mut x: i32, mut y: i32, other: &TheRGBABuffer) {
+        if self.premultiplied || other.premultiplied {
+            return self.blend_into_mode(x, y, other, TheBlendMode::Normal, 1.0);
+        }
+
         // Early return if the whole other buffer is outside this buffer
         if x + other.dim.width <= 0
             || y + other.dim.height <= 0
@@ -361,6 +193,10 @@ impl TheRGBABuffer {
     /// Blend the other buffer into this buffer at the given coordinates (adaptive parallel version).
     /// Falls back to the single-threaded path for small regions to avoid overhead.
     pub fn blend_into_par(&mut self, mut x: i32, mut y: i32, other: &TheRGBABuffer) {
+        if self.premultiplied || other.premultiplied {
+            return self.blend_into_mode_par(x, y, other, TheBlendMode::Normal, 1.0);
+        }
+
         // Early out if completely outside
         if x + other.dim.width <= 0
             || y + other.dim.height <= 0
@@ -511,31 +347,6 @@ impl TheRGBABuffer {
         }
     }
 
-    /// Creates a scaled version of the buffer.
-    pub fn scaled(&self, new_width: i32, new_height: i32) -> Self {
-        let scale_x = new_width as f32 / self.dim.width as f32;
-        let scale_y = new_height as f32 / self.dim.height as f32;
-
-        let mut new_buffer = TheRGBABuffer::new(TheDim::new(0, 0, new_width, new_height));
-
-        for y in 0..new_height {
-            for x in 0..new_width {
-                let src_x = (x as f32 / scale_x).round() as i32;
-                let src_y = (y as f32 / scale_y).round() as i32;
-
-                let pixel_index = (src_y * self.dim.width + src_x) as usize * 4;
-                let new_pixel_index = (y * new_width + x) as usize * 4;
-
-                if pixel_index < self.buffer.len() && new_pixel_index < new_buffer.buffer.len() {
-                    new_buffer.buffer[new_pixel_index..new_pixel_index + 4]
-                        .copy_from_slice(&self.buffer[pixel_index..pixel_index + 4]);
-                }
-            }
-        }
-
-        new_buffer
-    }
-
     /// Creates a scaled version of the buffer by writing into the other buffer.
     pub fn scaled_into(&self, into: &mut TheRGBABuffer) {
         let new_width = into.dim().width;
@@ -730,19 +541,6 @@ impl TheRGBABuffer {
         }
     }
 
-    /// Fills the entire buffer with the given RGBA color.
-    pub fn fill(&mut self, color: [u8; 4]) {
-        for y in 0..self.dim.height {
-            for x in 0..self.dim.width {
-                let index = (y * self.dim.width + x) as usize * 4;
-                // Check to make sure we don't write out of bounds
-                if index < self.buffer.len() {
-                    self.buffer[index..index + 4].copy_from_slice(&color);
-                }
-            }
-        }
-    }
-
     /// Multiplies every pixel in the buffer by the given RGBA pixel (component-wise, 0-255 space).
     pub fn multiply_by_pixel(&mut self, add: [u8; 4], pixel: [u8; 4]) {
         if self.buffer.is_empty() {
@@ -1057,34 +855,6 @@ impl TheRGBABuffer {
         }
     }
 
-    /// Helper method to calculate the buffer index for a pixel at (x, y).
-    pub fn pixel_index(&self, x: i32, y: i32) -> Option<usize> {
-        if x >= 0 && x < self.dim.width && y >= 0 && y < self.dim.height {
-            Some((y as usize * self.dim.width as usize + x as usize) * 4)
-        } else {
-            None
-        }
-    }
-
-    /// Get a pixel at (x, y).
-    pub fn get_pixel(&self, x: i32, y: i32) -> Option<[u8; 4]> {
-        self.pixel_index(x, y).map(|index| {
-            [
-                self.buffer[index],
-                self.buffer[index + 1],
-                self.buffer[index + 2],
-                self.buffer[index + 3],
-            ]
-        })
-    }
-
-    /// Sets the color of a pixel at (x, y).
-    pub fn set_pixel(&mut self, x: i32, y: i32, color: &[u8; 4]) {
-        if let Some(index) = self.pixel_index(x, y) {
-            self.buffer[index..index + 4].copy_from_slice(color);
-        }
-    }
-
     /// Convert the buffer to an RGBA PNG image.
     pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
         let mut png_data = Vec::new();
@@ -1287,6 +1057,9 @@ pub struct TheRGBATile {
     pub render_mode: u8,
     pub blocking: bool,
     pub scale: f32,
+    /// Optional per frame display times in milliseconds, used for animations.
+    #[serde(default)]
+    pub delays: Vec<u32>,
 }
 
 impl Default for TheRGBATile {
@@ -1306,6 +1079,7 @@ impl TheRGBATile {
             render_mode: 0,
             blocking: false,
             scale: 1.0,
+            delays: vec![],
         }
     }
 
@@ -1318,6 +1092,7 @@ impl TheRGBATile {
             render_mode: 0,
             blocking: false,
             scale: 1.0,
+            delays: vec![],
         }
     }
 }
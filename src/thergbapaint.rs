@@ -0,0 +1,188 @@
+use crate::prelude::*;
+
+/// Pixel painting primitives for TheRGBABuffer, used by the pixel editor of TheRGBAView. All
+/// primitives replace pixels (no blending) and clip against the buffer bounds.
+impl TheRGBABuffer {
+    /// Paints a round brush of the given size (diameter in pixels) centered on the pixel.
+    pub fn paint_brush(&mut self, x: i32, y: i32, size: i32, color: &[u8; 4]) {
+        if size <= 1 {
+            self.set_pixel(x, y, color);
+            return;
+        }
+        let radius = size as f32 / 2.0;
+        let start_x = x - size / 2;
+        let start_y = y - size / 2;
+        // Even sizes are centered on the pixel corner, odd sizes on the pixel center
+        let offset = if size % 2 == 1 { 0.5 } else { 0.0 };
+        let cx = x as f32 + offset;
+        let cy = y as f32 + offset;
+        for py in start_y..start_y + size {
+            for px in start_x..start_x + size {
+                let dx = px as f32 + 0.5 - cx;
+                let dy = py as f32 + 0.5 - cy;
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set_pixel(px, py, color);
+                }
+            }
+        }
+    }
+
+    /// Paints a line between the two pixels with the given brush size.
+    pub fn paint_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, size: i32, color: &[u8; 4]) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.paint_brush(x, y, size, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Paints the rectangle, either filled or as an outline of the given thickness.
+    pub fn paint_rect(&mut self, rect: &TheDim, size: i32, color: &[u8; 4], filled: bool) {
+        let (min_x, max_x) = (rect.x, rect.x + rect.width - 1);
+        let (min_y, max_y) = (rect.y, rect.y + rect.height - 1);
+        let size = size.max(1);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let border =
+                    x < min_x + size || x > max_x - size || y < min_y + size || y > max_y - size;
+                if filled || border {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Paints the ellipse inscribed into the rectangle, either filled or as an outline of the given
+    /// thickness.
+    pub fn paint_ellipse(&mut self, rect: &TheDim, size: i32, color: &[u8; 4], filled: bool) {
+        let (min_x, max_x) = (rect.x, rect.x + rect.width - 1);
+        let (min_y, max_y) = (rect.y, rect.y + rect.height - 1);
+        let size = size.max(1) as f32;
+
+        let cx = (min_x + max_x + 1) as f32 / 2.0;
+        let cy = (min_y + max_y + 1) as f32 / 2.0;
+        let rx = (max_x - min_x + 1) as f32 / 2.0;
+        let ry = (max_y - min_y + 1) as f32 / 2.0;
+
+        let inside = |px: f32, py: f32, rx: f32, ry: f32| -> bool {
+            if rx <= 0.0 || ry <= 0.0 {
+                return false;
+            }
+            let nx = (px - cx) / rx;
+            let ny = (py - cy) / ry;
+            nx * nx + ny * ny <= 1.0
+        };
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                if inside(px, py, rx, ry) && (filled || !inside(px, py, rx - size, ry - size)) {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Flood fills the contiguous area of similar color starting at the given pixel. Channels may
+    /// differ by up to the tolerance. Returns the bounding rectangle of the filled pixels.
+    pub fn flood_fill(&mut self, x: i32, y: i32, color: &[u8; 4], tolerance: u8) -> Option<TheDim> {
+        let target = self.get_pixel(x, y)?;
+        if target == *color {
+            return None;
+        }
+
+        let width = self.dim().width;
+        let height = self.dim().height;
+        let matches = |pixel: &[u8]| -> bool {
+            pixel
+                .iter()
+                .zip(target.iter())
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+        };
+
+        let mut visited = vec![false; (width * height) as usize];
+        let mut stack = vec![(x, y)];
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
+
+        // Scanline fill, each popped seed fills its whole horizontal run
+        while let Some((sx, sy)) = stack.pop() {
+            if visited[(sy * width + sx) as usize] {
+                continue;
+            }
+            let pixels = self.pixels();
+            let is_fillable = |px: i32| {
+                let i = (sy * width + px) as usize;
+                !visited[i] && matches(&pixels[i * 4..i * 4 + 4])
+            };
+            if !is_fillable(sx) {
+                continue;
+            }
+
+            let mut left = sx;
+            while left > 0 && is_fillable(left - 1) {
+                left -= 1;
+            }
+            let mut right = sx;
+            while right < width - 1 && is_fillable(right + 1) {
+                right += 1;
+            }
+
+            for px in left..=right {
+                visited[(sy * width + px) as usize] = true;
+                self.set_pixel(px, sy, color);
+            }
+
+            min_x = min_x.min(left);
+            max_x = max_x.max(right);
+            min_y = min_y.min(sy);
+            max_y = max_y.max(sy);
+
+            for ny in [sy - 1, sy + 1] {
+                if ny < 0 || ny >= height {
+                    continue;
+                }
+                let pixels = self.pixels();
+                let mut px = left;
+                while px <= right {
+                    let i = (ny * width + px) as usize;
+                    if !visited[i] && matches(&pixels[i * 4..i * 4 + 4]) {
+                        stack.push((px, ny));
+                        // Skip the rest of this run, it gets filled from the seed
+                        while px <= right {
+                            let i = (ny * width + px) as usize;
+                            if visited[i] || !matches(&pixels[i * 4..i * 4 + 4]) {
+                                break;
+                            }
+                            px += 1;
+                        }
+                    }
+                    px += 1;
+                }
+            }
+        }
+
+        Some(TheDim::new(
+            min_x,
+            min_y,
+            max_x - min_x + 1,
+            max_y - min_y + 1,
+        ))
+    }
+}
@@ -0,0 +1,552 @@
+use crate::prelude::*;
+use rayon::prelude::*;
+
+/// The reconstruction filter used when resampling or transforming TheRGBABuffer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TheResampleFilter {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom bicubic.
+    Bicubic,
+    /// Lanczos with a 3 lobe window.
+    Lanczos3,
+}
+
+impl TheResampleFilter {
+    /// The radius of the filter kernel in source pixels.
+    fn support(&self) -> f32 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Bilinear => 1.0,
+            Self::Bicubic => 2.0,
+            Self::Lanczos3 => 3.0,
+        }
+    }
+
+    /// Evaluates the filter kernel at the given distance.
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Self::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Bilinear => (1.0 - x).max(0.0),
+            Self::Bicubic => {
+                const A: f32 = -0.5;
+                if x < 1.0 {
+                    ((A + 2.0) * x - (A + 3.0)) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((A * x - 5.0 * A) * x + 8.0 * A) * x - 4.0 * A
+                } else {
+                    0.0
+                }
+            }
+            Self::Lanczos3 => {
+                if x < 1e-6 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = std::f32::consts::PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// A 2D affine transformation, mapping (x, y) to (a * x + c * y + e, b * x + d * y + f).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TheTransform2D {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Default for TheTransform2D {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl TheTransform2D {
+    pub fn identity() -> Self {
+        Self {
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 1.0,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self {
+            e: x,
+            f: y,
+            ..Self::identity()
+        }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::identity()
+        }
+    }
+
+    /// A clockwise rotation (in screen space, y pointing down) by the given angle in degrees.
+    pub fn rotation(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    /// Returns the transform which applies self first and then other.
+    pub fn then(&self, other: &TheTransform2D) -> Self {
+        Self {
+            a: other.a * self.a + other.c * self.b,
+            b: other.b * self.a + other.d * self.b,
+            c: other.a * self.c + other.c * self.d,
+            d: other.b * self.c + other.d * self.d,
+            e: other.a * self.e + other.c * self.f + other.e,
+            f: other.b * self.e + other.d * self.f + other.f,
+        }
+    }
+
+    /// Returns the inverse transform, or None if the transform is not invertible.
+    pub fn inverted(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv = 1.0 / det;
+        Some(Self {
+            a: self.d * inv,
+            b: -self.b * inv,
+            c: -self.c * inv,
+            d: self.a * inv,
+            e: (self.c * self.f - self.d * self.e) * inv,
+            f: (self.b * self.e - self.a * self.f) * inv,
+        })
+    }
+
+    /// Transforms the given point.
+    pub fn apply(&self, p: Vec2<f32>) -> Vec2<f32> {
+        Vec2::new(
+            self.a * p.x + self.c * p.y + self.e,
+            self.b * p.x + self.d * p.y + self.f,
+        )
+    }
+}
+
+/// The precomputed filter taps of one output coordinate.
+struct TheFilterTaps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Computes the filter taps for resampling a line of src_len pixels to dst_len pixels.
+fn filter_taps(src_len: usize, dst_len: usize, filter: TheResampleFilter) -> Vec<TheFilterTaps> {
+    let scale = src_len as f32 / dst_len as f32;
+
+    if filter == TheResampleFilter::Nearest {
+        return (0..dst_len)
+            .map(|x| TheFilterTaps {
+                start: (((x as f32 + 0.5) * scale) as usize).min(src_len - 1),
+                weights: vec![1.0],
+            })
+            .collect();
+    }
+
+    // When downscaling the kernel is widened to avoid aliasing.
+    let filter_scale = scale.max(1.0);
+    let radius = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|x| {
+            let center = (x as f32 + 0.5) * scale - 0.5;
+            let left = ((center - radius).ceil() as i32).max(0) as usize;
+            let right = ((center + radius).floor() as i32).min(src_len as i32 - 1) as usize;
+
+            let mut weights: Vec<f32> = (left..=right)
+                .map(|i| filter.kernel((i as f32 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum.abs() > f32::EPSILON {
+                for w in &mut weights {
+                    *w /= sum;
+                }
+            }
+
+            TheFilterTaps {
+                start: left,
+                weights,
+            }
+        })
+        .collect()
+}
+
+/// Converts a straight alpha pixel to premultiplied f32.
+#[inline]
+fn premultiplied(p: &[u8]) -> [f32; 4] {
+    let a = p[3] as f32 / 255.0;
+    [
+        p[0] as f32 * a,
+        p[1] as f32 * a,
+        p[2] as f32 * a,
+        p[3] as f32,
+    ]
+}
+
+/// Converts a premultiplied f32 pixel back to straight alpha u8.
+#[inline]
+fn unpremultiplied(p: [f32; 4]) -> [u8; 4] {
+    let a = p[3].clamp(0.0, 255.0);
+    if a <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let inv = 255.0 / a;
+    [
+        (p[0] * inv).round().clamp(0.0, 255.0) as u8,
+        (p[1] * inv).round().clamp(0.0, 255.0) as u8,
+        (p[2] * inv).round().clamp(0.0, 255.0) as u8,
+        a.round() as u8,
+    ]
+}
+
+/// TheRGBABuffer resampling and geometric transforms. Alpha is premultiplied while filtering so that
+/// transparent pixels do not bleed their color into the result.
+impl TheRGBABuffer {
+    /// Returns a resampled copy of the buffer with the given size.
+    pub fn resampled(&self, width: i32, height: i32, filter: TheResampleFilter) -> Self {
+        let mut into = TheRGBABuffer::new(TheDim::sized(width, height));
+        self.resampled_into(&mut into, filter);
+        into
+    }
+
+    /// Resamples the buffer into the other buffer, using its dimensions as the target size.
+    pub fn resampled_into(&self, into: &mut TheRGBABuffer, filter: TheResampleFilter) {
+        if self.is_premultiplied() {
+            self.to_straight_alpha().resampled_into(into, filter);
+            return;
+        }
+        into.set_premultiplied(false);
+
+        let src_w = self.dim().width.max(0) as usize;
+        let src_h = self.dim().height.max(0) as usize;
+        let dst_w = into.dim().width.max(0) as usize;
+        let dst_h = into.dim().height.max(0) as usize;
+
+        if src_w == 0 || src_h == 0 || dst_w == 0 || dst_h == 0 {
+            return;
+        }
+
+        let taps_x = filter_taps(src_w, dst_w, filter);
+        let taps_y = filter_taps(src_h, dst_h, filter);
+        let src = self.pixels();
+
+        // Horizontal pass into a premultiplied intermediate buffer
+        let mut tmp = vec![0.0_f32; dst_w * src_h * 4];
+        tmp.par_chunks_mut(dst_w * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let src_row = &src[y * src_w * 4..(y + 1) * src_w * 4];
+                for (x, taps) in taps_x.iter().enumerate() {
+                    let mut acc = [0.0_f32; 4];
+                    for (i, w) in taps.weights.iter().enumerate() {
+                        let o = (taps.start + i) * 4;
+                        let p = premultiplied(&src_row[o..o + 4]);
+                        for c in 0..4 {
+                            acc[c] += p[c] * w;
+                        }
+                    }
+                    row[x * 4..x * 4 + 4].copy_from_slice(&acc);
+                }
+            });
+
+        // Vertical pass into the target
+        into.pixels_mut()
+            .par_chunks_mut(dst_w * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let taps = &taps_y[y];
+                for x in 0..dst_w {
+                    let mut acc = [0.0_f32; 4];
+                    for (i, w) in taps.weights.iter().enumerate() {
+                        let o = ((taps.start + i) * dst_w + x) * 4;
+                        for c in 0..4 {
+                            acc[c] += tmp[o + c] * w;
+                        }
+                    }
+                    row[x * 4..x * 4 + 4].copy_from_slice(&unpremultiplied(acc));
+                }
+            });
+    }
+
+    /// Returns a copy scaled down (never up) to fit into the given size while keeping the aspect ratio.
+    pub fn thumbnail(&self, max_width: i32, max_height: i32, filter: TheResampleFilter) -> Self {
+        if !self.is_valid() {
+            return self.clone();
+        }
+        let ratio = (max_width as f32 / self.dim().width as f32)
+            .min(max_height as f32 / self.dim().height as f32)
+            .min(1.0);
+        let width = ((self.dim().width as f32 * ratio).round() as i32).max(1);
+        let height = ((self.dim().height as f32 * ratio).round() as i32).max(1);
+        self.resampled(width, height, filter)
+    }
+
+    /// Returns a horizontally mirrored copy of the buffer.
+    pub fn flipped_horizontal(&self) -> Self {
+        let mut flipped = self.clone();
+        flipped.flip_horizontal();
+        flipped
+    }
+
+    /// Returns a vertically mirrored copy of the buffer.
+    pub fn flipped_vertical(&self) -> Self {
+        let mut flipped = self.clone();
+        flipped.flip_vertical();
+        flipped
+    }
+
+    /// Mirrors the buffer horizontally in place.
+    pub fn flip_horizontal(&mut self) {
+        let width = self.dim().width.max(0) as usize;
+        if width == 0 {
+            return;
+        }
+        self.pixels_mut().par_chunks_mut(width * 4).for_each(|row| {
+            for x in 0..width / 2 {
+                let (l, r) = (x * 4, (width - 1 - x) * 4);
+                for c in 0..4 {
+                    row.swap(l + c, r + c);
+                }
+            }
+        });
+    }
+
+    /// Mirrors the buffer vertically in place.
+    pub fn flip_vertical(&mut self) {
+        let stride = self.dim().width.max(0) as usize * 4;
+        let height = self.dim().height.max(0) as usize;
+        if stride == 0 {
+            return;
+        }
+        let pixels = self.pixels_mut();
+        for y in 0..height / 2 {
+            let (top, bottom) = pixels.split_at_mut((height - 1 - y) * stride);
+            top[y * stride..(y + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+        }
+    }
+
+    /// Returns a copy rotated by 90 degrees clockwise.
+    pub fn rotated_90(&self) -> Self {
+        let (w, h) = (self.dim().width, self.dim().height);
+        self.remapped(h, w, |x, y| (y, h - 1 - x))
+    }
+
+    /// Returns a copy rotated by 180 degrees.
+    pub fn rotated_180(&self) -> Self {
+        let (w, h) = (self.dim().width, self.dim().height);
+        self.remapped(w, h, |x, y| (w - 1 - x, h - 1 - y))
+    }
+
+    /// Returns a copy rotated by 270 degrees clockwise (90 degrees counter-clockwise).
+    pub fn rotated_270(&self) -> Self {
+        let (w, h) = (self.dim().width, self.dim().height);
+        self.remapped(h, w, |x, y| (w - 1 - y, x))
+    }
+
+    /// Creates a buffer of the given size where each pixel is copied from the source position returned by map.
+    fn remapped<F>(&self, width: i32, height: i32, map: F) -> Self
+    where
+        F: Fn(i32, i32) -> (i32, i32) + Sync,
+    {
+        let mut out = TheRGBABuffer::new(TheDim::sized(width, height));
+        out.set_premultiplied(self.is_premultiplied());
+        if !out.is_valid() {
+            return out;
+        }
+        let src = self.pixels();
+        let src_w = self.dim().width;
+        out.pixels_mut()
+            .par_chunks_mut(width as usize * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for x in 0..width {
+                    let (sx, sy) = map(x, y as i32);
+                    let s = ((sy * src_w + sx) * 4) as usize;
+                    row[x as usize * 4..x as usize * 4 + 4].copy_from_slice(&src[s..s + 4]);
+                }
+            });
+        out
+    }
+
+    /// Returns a copy rotated clockwise by an arbitrary angle in degrees. The result is enlarged to
+    /// fit the rotated image, uncovered areas are filled with the background color.
+    pub fn rotated(&self, degrees: f32, background: [u8; 4], filter: TheResampleFilter) -> Self {
+        let (w, h) = (self.dim().width as f32, self.dim().height as f32);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let new_w = (w * cos.abs() + h * sin.abs()).round().max(1.0) as i32;
+        let new_h = (w * sin.abs() + h * cos.abs()).round().max(1.0) as i32;
+
+        let transform = TheTransform2D::translation(-w / 2.0, -h / 2.0)
+            .then(&TheTransform2D::rotation(degrees))
+            .then(&TheTransform2D::translation(
+                new_w as f32 / 2.0,
+                new_h as f32 / 2.0,
+            ));
+
+        let mut out = TheRGBABuffer::new(TheDim::sized(new_w, new_h));
+        out.fill(background);
+        self.blit_affine(&mut out, &transform, filter);
+        out
+    }
+
+    /// Draws the buffer transformed by the affine transform into the other buffer, compositing it over
+    /// the existing content. The transform maps source pixel coordinates to target pixel coordinates.
+    pub fn blit_affine(
+        &self,
+        into: &mut TheRGBABuffer,
+        transform: &TheTransform2D,
+        filter: TheResampleFilter,
+    ) {
+        let Some(inverse) = transform.inverted() else {
+            return;
+        };
+        if self.is_premultiplied() {
+            self.to_straight_alpha()
+                .blit_affine(into, transform, filter);
+            return;
+        }
+        if into.is_premultiplied() {
+            into.unpremultiply_alpha();
+            self.blit_affine(into, transform, filter);
+            into.premultiply_alpha();
+            return;
+        }
+        if !self.is_valid() || !into.is_valid() {
+            return;
+        }
+
+        // Bounding box of the transformed source in target space
+        let (w, h) = (self.dim().width as f32, self.dim().height as f32);
+        let corners = [
+            transform.apply(Vec2::new(0.0, 0.0)),
+            transform.apply(Vec2::new(w, 0.0)),
+            transform.apply(Vec2::new(0.0, h)),
+            transform.apply(Vec2::new(w, h)),
+        ];
+        let min_x = corners.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+        let max_x = corners.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+        let min_y = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+        let max_y = corners.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+
+        let x0 = (min_x.floor() as i32).max(0);
+        let x1 = (max_x.ceil() as i32).min(into.dim().width);
+        let y0 = (min_y.floor() as i32).max(0);
+        let y1 = (max_y.ceil() as i32).min(into.dim().height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let stride = into.dim().width as usize * 4;
+        into.pixels_mut()[y0 as usize * stride..y1 as usize * stride]
+            .par_chunks_mut(stride)
+            .enumerate()
+            .for_each(|(row_index, row)| {
+                let y = y0 + row_index as i32;
+                for x in x0..x1 {
+                    let p = inverse.apply(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+                    let src = self.sample_premultiplied(p.x - 0.5, p.y - 0.5, filter);
+                    if src[3] <= 0.0 {
+                        continue;
+                    }
+
+                    let o = x as usize * 4;
+                    let dst = premultiplied(&row[o..o + 4]);
+                    let inv_a = 1.0 - src[3] / 255.0;
+                    let out = [
+                        src[0] + dst[0] * inv_a,
+                        src[1] + dst[1] * inv_a,
+                        src[2] + dst[2] * inv_a,
+                        src[3] + dst[3] * inv_a,
+                    ];
+                    row[o..o + 4].copy_from_slice(&unpremultiplied(out));
+                }
+            });
+    }
+
+    /// Samples the buffer at the given continuous pixel position (pixel centers are at integer
+    /// coordinates). Pixels outside of the buffer are transparent. Returns a premultiplied color.
+    fn sample_premultiplied(&self, x: f32, y: f32, filter: TheResampleFilter) -> [f32; 4] {
+        let (w, h) = (self.dim().width, self.dim().height);
+        let pixels = self.pixels();
+
+        if filter == TheResampleFilter::Nearest {
+            let (sx, sy) = (x.round() as i32, y.round() as i32);
+            if sx < 0 || sy < 0 || sx >= w || sy >= h {
+                return [0.0; 4];
+            }
+            let o = ((sy * w + sx) * 4) as usize;
+            return premultiplied(&pixels[o..o + 4]);
+        }
+
+        let support = filter.support();
+        let (x_start, x_end) = ((x - support).ceil() as i32, (x + support).floor() as i32);
+        let (y_start, y_end) = ((y - support).ceil() as i32, (y + support).floor() as i32);
+
+        let mut acc = [0.0_f32; 4];
+        let mut weight_sum = 0.0;
+        for sy in y_start..=y_end {
+            let wy = filter.kernel(sy as f32 - y);
+            if wy == 0.0 {
+                continue;
+            }
+            for sx in x_start..=x_end {
+                let weight = wy * filter.kernel(sx as f32 - x);
+                if weight == 0.0 {
+                    continue;
+                }
+                weight_sum += weight;
+                if sx < 0 || sy < 0 || sx >= w || sy >= h {
+                    continue;
+                }
+                let o = ((sy * w + sx) * 4) as usize;
+                let p = premultiplied(&pixels[o..o + 4]);
+                for c in 0..4 {
+                    acc[c] += p[c] * weight;
+                }
+            }
+        }
+
+        if weight_sum.abs() > f32::EPSILON {
+            for c in &mut acc {
+                *c /= weight_sum;
+            }
+        }
+        // Negative lobes can push colors outside of the valid premultiplied range
+        acc[3] = acc[3].clamp(0.0, 255.0);
+        for c in 0..3 {
+            acc[c] = acc[c].clamp(0.0, acc[3]);
+        }
+        acc
+    }
+}
@@ -5,12 +5,15 @@ pub mod thecontextmenu;
 pub mod thedrop;
 pub mod theflattenedmap;
 pub mod theid;
+pub mod theinspect;
 pub mod thelayout;
+pub mod thenodeevaluator;
 pub mod thenodeui;
 pub mod thergbbuffer;
 pub mod thesdf;
 pub mod thesizelimiter;
 pub mod thestyle;
+pub mod thesvgicon;
 pub mod thetheme;
 pub mod thetilemask;
 pub mod thetimeline;
@@ -21,37 +24,8 @@ pub mod thevalue;
 pub mod thevent;
 pub mod thewidget;
 
-use ::serde::de::{self, Deserializer};
-use ::serde::ser::{self, Serializer};
-use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
-use std::io::{Read, Write};
 use std::sync::mpsc::{self, Receiver, Sender};
 
-fn compress<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(data).map_err(ser::Error::custom)?;
-    let compressed_data = encoder.finish().map_err(ser::Error::custom)?;
-
-    serializer.serialize_bytes(&compressed_data)
-}
-
-fn decompress<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let data = Vec::<u8>::deserialize(deserializer)?;
-    let mut decoder = ZlibDecoder::new(&data[..]);
-    let mut decompressed_data = Vec::new();
-    decoder
-        .read_to_end(&mut decompressed_data)
-        .map_err(de::Error::custom)?;
-
-    Ok(decompressed_data)
-}
-
 pub use crate::prelude::*;
 
 pub type RGBA = [u8; 4];
@@ -76,6 +50,7 @@ pub mod prelude {
 
     pub use crate::theui::thergbbuffer::TheRGBBuffer;
     pub use crate::theui::thesizelimiter::TheSizeLimiter;
+    pub use crate::theui::thesvgicon::{TheSVGIcon, TheSVGPaint};
     pub use crate::theui::theuicontext::*;
     pub use crate::theui::TheUI;
 
@@ -105,13 +80,19 @@ pub mod prelude {
     pub use crate::str;
     pub use crate::theui::thecollection::TheCollection;
     pub use crate::theui::theflattenedmap::{TheFlattenedMap, TheFlattenedMap3D};
+    pub use crate::theui::thenodeevaluator::{TheNodeBehavior, TheNodeError, TheNodeEvaluator};
     pub use crate::theui::thetilemask::TheTileMask;
-    pub use crate::theui::thetimeline::{TheInterpolation, TheTimeline};
+    pub use crate::theui::thetimeline::{
+        TheInterpolation, TheKeyframe, TheTimeline, TheTimelineEdit,
+    };
     pub use crate::theui::TheAccelerator;
     pub use crate::theui::TheAcceleratorKey;
     pub use crate::theui::TheDialogButtonRole;
 
     pub use crate::theui::thenodeui::*;
+
+    pub use crate::theui::theinspect::{TheInspect, TheInspectAttrs, TheInspectField};
+    pub use theframework_derive::TheInspect;
 }
 
 // Define a macro named `str!`.
@@ -280,6 +261,14 @@ pub struct TheUI {
     pub context_menu: Option<TheContextMenu>,
     pub menu_widget_id: Option<TheId>,
 
+    /// The color popup opened by a color button, together with the button and popup layout ids.
+    pub color_popup: Option<TheCanvas>,
+    pub color_popup_widget_id: Option<TheId>,
+    color_popup_layout_id: Option<TheId>,
+
+    /// The widget which receives the color of the next click while the eyedropper is active.
+    pub eyedropper_widget_id: Option<TheId>,
+
     pub is_dirty: bool,
 
     // Modifiers
@@ -313,6 +302,13 @@ impl TheUI {
 
             context_menu: None,
             menu_widget_id: None,
+
+            color_popup: None,
+            color_popup_widget_id: None,
+            color_popup_layout_id: None,
+
+            eyedropper_widget_id: None,
+
             is_dirty: false,
 
             shift: false,
@@ -454,6 +450,9 @@ impl TheUI {
         if self.dialog.is_some() {
             self.draw_dialog(ctx);
         }
+        if self.color_popup.is_some() {
+            self.draw_color_popup(ctx);
+        }
         self.canvas.draw_overlay(&mut self.style, ctx);
         if let Some(drop) = &ctx.ui.drop {
             if let Some(position) = &drop.position {
@@ -475,6 +474,7 @@ impl TheUI {
 
     /// Processes widget state events, these are mostly send from TheUIContext based on state changes provided by the widgets.
     pub fn process_events(&mut self, ctx: &mut TheContext) {
+        let mut color_popup = None;
         if let Some(receiver) = &mut self.state_events_receiver {
             while let Ok(event) = receiver.try_recv() {
                 // Resend event to all app listeners
@@ -501,10 +501,35 @@ impl TheUI {
                         self.menu_widget_id = None;
                     }
                     TheEvent::RedirectWidgetValueToLayout(layout_id, widget_id, value) => {
-                        if let Some(layout) = self.canvas.get_layout(None, Some(&layout_id.uuid)) {
+                        if let Some(layout) = self
+                            .color_popup
+                            .as_mut()
+                            .and_then(|popup| popup.get_layout(None, Some(&layout_id.uuid)))
+                        {
+                            layout.redirected_widget_value(&widget_id, &value, ctx);
+                            self.is_dirty = true;
+                        } else if let Some(layout) =
+                            self.canvas.get_layout(None, Some(&layout_id.uuid))
+                        {
                             layout.redirected_widget_value(&widget_id, &value, ctx);
                         }
                     }
+                    TheEvent::ContextMenuSelected(widget_id, item_id) => {
+                        // Let the widget which opened the context menu handle its own items.
+                        if let Some(widget) = self.canvas.get_widget(None, Some(&widget_id.uuid)) {
+                            let event = TheEvent::ContextMenuSelected(widget_id, item_id);
+                            if widget.on_event(&event, ctx) {
+                                self.is_dirty = true;
+                            }
+                        }
+                    }
+                    TheEvent::ShowColorPopup(id, coord, color) => {
+                        color_popup = Some((id, coord, color));
+                    }
+                    TheEvent::StartEyedropper(id) => {
+                        self.eyedropper_widget_id = Some(id);
+                        ctx.set_cursor_icon(TheCursorIcon::Crosshair);
+                    }
                     TheEvent::DragStartedWithNoImage(drop) => {
                         let mut drop = drop.clone();
                         self.style.create_drop_image(&mut drop, ctx);
@@ -689,6 +714,30 @@ impl TheUI {
                             }
                         }
                     }
+                    TheEvent::ValueChanged(id, value)
+                        if Some(&id) == self.color_popup_layout_id.as_ref() =>
+                    {
+                        // Forward color popup changes to the widget which opened it and send
+                        // its resulting value.
+                        if let Some(button_id) = self.color_popup_widget_id.clone() {
+                            let mut widget_value = value.clone();
+                            if let Some(widget) =
+                                self.canvas.get_widget(None, Some(&button_id.uuid))
+                            {
+                                widget.set_value(value);
+                                widget_value = widget.value();
+                            } else if let Some(widget) = self
+                                .dialog
+                                .as_mut()
+                                .and_then(|dialog| dialog.get_widget(None, Some(&button_id.uuid)))
+                            {
+                                widget.set_value(value);
+                                widget_value = widget.value();
+                            }
+                            ctx.ui.send_widget_value_changed(&button_id, widget_value);
+                            self.is_dirty = true;
+                        }
+                    }
                     TheEvent::ValueChanged(id, value) => {
                         //println!("Widget Value changed {:?}: {:?}", id, value);
                     }
@@ -704,6 +753,9 @@ impl TheUI {
                 }
             }
         }
+        if let Some((id, coord, color)) = color_popup {
+            self.show_color_popup(id, coord, color, ctx);
+        }
     }
 
     /// Set the given id as disabled.
@@ -757,6 +809,18 @@ impl TheUI {
 
         //ctx.ui.clear_focus();
 
+        if let Some(id) = self.eyedropper_widget_id.take() {
+            ctx.set_cursor_icon(TheCursorIcon::Default);
+            if let Some(pixel) = self.canvas.buffer.get_pixel(coord.x, coord.y) {
+                let color = TheColor::from_u8_array(pixel);
+                if let Some(widget) = self.get_widget_abs(None, Some(&id.uuid)) {
+                    widget.on_event(&TheEvent::EyedropperColorPicked(id.clone(), color), ctx);
+                }
+            }
+            self.process_events(ctx);
+            return true;
+        }
+
         if let Some(context) = &mut self.context_menu {
             if context.contains(coord) {
                 let event = TheEvent::MouseDown(context.dim.to_local(coord));
@@ -798,6 +862,14 @@ impl TheUI {
             return redraw;
         }
 
+        // A click outside of the color popup closes it.
+        if let Some(popup) = &self.color_popup {
+            if !popup.dim.contains(coord) {
+                self.close_color_popup(ctx);
+                return true;
+            }
+        }
+
         if let Some(widget) = self.get_widget_at_coord(coord) {
             let event = TheEvent::MouseDown(widget.dim().to_local(coord));
             redraw = widget.on_event(&event, ctx);
@@ -882,6 +954,11 @@ impl TheUI {
         let mut redraw = false;
         let coord = Vec2::new(x as i32, y as i32);
 
+        if self.eyedropper_widget_id.is_some() {
+            ctx.set_cursor_icon(TheCursorIcon::Crosshair);
+            return redraw;
+        }
+
         if let Some(context) = &mut self.context_menu {
             if context.contains(coord) {
                 let event = TheEvent::Hover(context.dim.to_local(coord));
@@ -989,6 +1066,18 @@ impl TheUI {
                     return true;
                 }
 
+                if key == Some(TheKeyCode::Escape) {
+                    if self.eyedropper_widget_id.is_some() {
+                        self.eyedropper_widget_id = None;
+                        ctx.set_cursor_icon(TheCursorIcon::Default);
+                        return true;
+                    }
+                    if self.color_popup.is_some() {
+                        self.close_color_popup(ctx);
+                        return true;
+                    }
+                }
+
                 TheEvent::KeyCodeDown(TheValue::KeyCode(key.unwrap()))
             };
             ctx.ui.send(event.clone());
@@ -1056,6 +1145,13 @@ impl TheUI {
 
     /// Returns the absolute widget at the given position.
     pub fn get_widget_at_coord(&mut self, coord: Vec2<i32>) -> Option<&mut Box<dyn TheWidget>> {
+        if let Some(popup) = self
+            .color_popup
+            .as_mut()
+            .filter(|popup| popup.dim.contains(coord))
+        {
+            return popup.get_widget_at_coord(coord);
+        }
         if let Some(dialog) = &mut self.dialog {
             if let Some(widget) = dialog.get_widget_at_coord(coord) {
                 return Some(widget);
@@ -1071,6 +1167,16 @@ impl TheUI {
         name: Option<&String>,
         uuid: Option<&Uuid>,
     ) -> Option<&mut Box<dyn TheWidget>> {
+        if self
+            .color_popup
+            .as_mut()
+            .is_some_and(|popup| popup.get_widget(name, uuid).is_some())
+        {
+            return self
+                .color_popup
+                .as_mut()
+                .and_then(|popup| popup.get_widget(name, uuid));
+        }
         if let Some(dialog) = &mut self.dialog {
             dialog.get_widget(name, uuid)
         } else {
@@ -1176,6 +1282,17 @@ impl TheUI {
         None
     }
 
+    /// Gets a given TheColorPickerLayout by name
+    pub fn get_color_picker_layout(
+        &mut self,
+        name: &str,
+    ) -> Option<&mut dyn TheColorPickerLayoutTrait> {
+        if let Some(layout) = self.canvas.get_layout(Some(&name.to_string()), None) {
+            return layout.as_color_picker_layout();
+        }
+        None
+    }
+
     /// Gets a given layout by name
     pub fn get_layout(&mut self, name: &str) -> Option<&mut Box<dyn TheLayout>> {
         self.canvas.get_layout(Some(&name.to_string()), None)
@@ -1446,6 +1563,14 @@ impl TheUI {
 
             tuple = border_dim.to_buffer_utuple();
 
+            self.style.draw_drop_shadow(
+                self.canvas.buffer.pixels_mut(),
+                &tuple,
+                ctx.width,
+                10.0,
+                ctx,
+            );
+
             ctx.draw.rect_outline(
                 self.canvas.buffer.pixels_mut(),
                 &tuple,
@@ -1524,4 +1649,74 @@ impl TheUI {
             );
         }
     }
+
+    #[cfg(feature = "ui")]
+    /// Opens a color popup for the given widget below the given (global) coordinates.
+    pub fn show_color_popup(
+        &mut self,
+        widget_id: TheId,
+        coord: Vec2<i32>,
+        color: TheColor,
+        ctx: &mut TheContext,
+    ) {
+        let mut layout = TheColorPickerLayout::new(TheId::named("Color Popup"));
+        layout.set_color(color);
+        layout.set_continuous(true);
+
+        let width = layout.limiter().get_max_width();
+        let height = layout.limiter().get_max_height();
+
+        self.color_popup_layout_id = Some(layout.id().clone());
+        self.color_popup_widget_id = Some(widget_id);
+
+        let mut canvas = TheCanvas::new();
+        canvas.set_layout(layout);
+
+        // Keep the popup (and its border) inside the window.
+        let x = coord.x.min(ctx.width as i32 - width - 1).max(1);
+        let mut y = coord.y;
+        if y + height + 1 > ctx.height as i32 {
+            y = ctx.height as i32 - height - 1;
+        }
+        let y = y.max(1);
+
+        let mut dim = TheDim::new(x, y, width, height);
+        dim.buffer_x = x;
+        dim.buffer_y = y;
+        canvas.set_dim(dim, ctx);
+
+        self.color_popup = Some(canvas);
+        ctx.ui.redraw_all = true;
+    }
+
+    #[cfg(feature = "ui")]
+    /// Closes the color popup.
+    pub fn close_color_popup(&mut self, ctx: &mut TheContext) {
+        self.color_popup = None;
+        self.color_popup_widget_id = None;
+        self.color_popup_layout_id = None;
+        ctx.ui.clear_focus();
+        ctx.ui.clear_hover();
+        ctx.ui.redraw_all = true;
+    }
+
+    #[cfg(feature = "ui")]
+    /// Draws the color popup.
+    pub fn draw_color_popup(&mut self, ctx: &mut TheContext) {
+        if let Some(popup) = &mut self.color_popup {
+            popup.draw(&mut self.style, ctx);
+
+            self.canvas
+                .buffer
+                .copy_into(popup.dim.buffer_x, popup.dim.buffer_y, &popup.buffer);
+
+            let tuple = popup.dim.to_buffer_utuple();
+            ctx.draw.rect_outline(
+                self.canvas.buffer.pixels_mut(),
+                &(tuple.0 - 1, tuple.1 - 1, tuple.2 + 2, tuple.3 + 2),
+                ctx.width,
+                self.style.theme().color(WindowBorderOuter),
+            );
+        }
+    }
 }
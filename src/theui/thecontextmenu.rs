@@ -247,6 +247,8 @@ impl TheContextMenu {
         let mut tuple = self.dim.to_buffer_utuple();
         let mut shrinker = TheDimShrinker::zero();
 
+        style.draw_drop_shadow(pixels, &tuple, ctx.width, 6.0, ctx);
+
         ctx.draw.rect_outline(
             pixels,
             &tuple,
@@ -0,0 +1,243 @@
+use crate::prelude::*;
+
+/// A type which can be edited in a TheNodeUI property inspector. Usually derived with
+/// `#[derive(TheInspect)]`.
+pub trait TheInspect {
+    /// Adds the items of the fields to the UI, with the ids prefixed by the given prefix.
+    fn add_to_node_ui(&self, prefix: &str, ui: &mut TheNodeUI);
+
+    /// Reads the fields back from the items of the UI, with the ids prefixed by the given prefix.
+    fn read_from_node_ui(&mut self, prefix: &str, ui: &TheNodeUI);
+
+    /// Creates the property inspector.
+    fn to_node_ui(&self) -> TheNodeUI {
+        let mut ui = TheNodeUI::default();
+        self.add_to_node_ui("", &mut ui);
+        ui
+    }
+
+    /// Applies the values of the property inspector.
+    fn apply_from_node_ui(&mut self, ui: &TheNodeUI) {
+        self.read_from_node_ui("", ui);
+    }
+}
+
+/// The options of an inspected field, set by the `#[inspect(...)]` attribute.
+#[derive(Clone, Debug, Default)]
+pub struct TheInspectAttrs {
+    pub label: String,
+    pub status: String,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub continuous: bool,
+    pub slider: bool,
+}
+
+impl TheInspectAttrs {
+    /// The float range, 0..=1 by default.
+    pub fn range_f32(&self) -> std::ops::RangeInclusive<f32> {
+        self.min.unwrap_or(0.0)..=self.max.unwrap_or(1.0)
+    }
+
+    /// The int range, 0..=100 by default.
+    pub fn range_i32(&self) -> std::ops::RangeInclusive<i32> {
+        self.min.map(|v| v as i32).unwrap_or(0)..=self.max.map(|v| v as i32).unwrap_or(100)
+    }
+}
+
+/// A field type of an inspected struct, mapped to a TheNodeUIItem.
+pub trait TheInspectField {
+    /// Adds the item of the field to the UI.
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI);
+
+    /// Reads the field back from its item.
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI);
+}
+
+impl TheInspectField for f32 {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        if attrs.slider {
+            ui.add_item(TheNodeUIItem::FloatSlider(
+                id.into(),
+                attrs.label.clone(),
+                attrs.status.clone(),
+                *self,
+                attrs.range_f32(),
+                *self,
+                attrs.continuous,
+            ));
+        } else {
+            ui.add_item(TheNodeUIItem::FloatEditSlider(
+                id.into(),
+                attrs.label.clone(),
+                attrs.status.clone(),
+                *self,
+                attrs.range_f32(),
+                attrs.continuous,
+            ));
+        }
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(TheValue::Float(value)) = ui.get_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for i32 {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        if attrs.slider {
+            ui.add_item(TheNodeUIItem::IntSlider(
+                id.into(),
+                attrs.label.clone(),
+                attrs.status.clone(),
+                *self,
+                attrs.range_i32(),
+                *self,
+                attrs.continuous,
+            ));
+        } else {
+            ui.add_item(TheNodeUIItem::IntEditSlider(
+                id.into(),
+                attrs.label.clone(),
+                attrs.status.clone(),
+                *self,
+                attrs.range_i32(),
+                attrs.continuous,
+            ));
+        }
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(TheValue::Int(value)) = ui.get_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for bool {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Checkbox(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            *self,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_bool_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for String {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Text(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            self.clone(),
+            None,
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_text_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for TheColor {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::ColorPicker(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            self.clone(),
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(TheValue::ColorObject(value)) = ui.get_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for TheGradient {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Gradient(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            self.clone(),
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_gradient_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for Vec2<f32> {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Vec2Edit(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            *self,
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_vec2_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for Vec3<f32> {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Vec3Edit(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            *self,
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_vec3_value(id) {
+            *self = value;
+        }
+    }
+}
+
+impl TheInspectField for Vec4<f32> {
+    fn add_item(&self, id: &str, attrs: &TheInspectAttrs, ui: &mut TheNodeUI) {
+        ui.add_item(TheNodeUIItem::Vec4Edit(
+            id.into(),
+            attrs.label.clone(),
+            attrs.status.clone(),
+            *self,
+            attrs.continuous,
+        ));
+    }
+
+    fn read_item(&mut self, id: &str, ui: &TheNodeUI) {
+        if let Some(value) = ui.get_vec4_value(id) {
+            *self = value;
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::prelude::*;
 
+pub mod thecolorpickerlayout;
 pub mod thehlayout;
 pub mod thelistlayout;
 pub mod thergbalayout;
@@ -14,6 +15,9 @@ pub mod thetreelayout;
 pub mod thevlayout;
 
 pub mod prelude {
+    pub use crate::theui::thelayout::thecolorpickerlayout::{
+        TheColorPickerLayout, TheColorPickerLayoutTrait,
+    };
     pub use crate::theui::thelayout::thehlayout::{TheHLayout, TheHLayoutMode, TheHLayoutTrait};
     pub use crate::theui::thelayout::thelistlayout::{TheListLayout, TheListLayoutTrait};
     pub use crate::theui::thelayout::thergbalayout::{TheRGBALayout, TheRGBALayoutTrait};
@@ -167,4 +171,9 @@ pub trait TheLayout: Send {
     fn as_tree_layout(&mut self) -> Option<&mut dyn TheTreeLayoutTrait> {
         None
     }
+
+    /// Attempts to cast to TheColorPickerLayoutTrait. Only valid for TheColorPickerLayout.
+    fn as_color_picker_layout(&mut self) -> Option<&mut dyn TheColorPickerLayoutTrait> {
+        None
+    }
 }
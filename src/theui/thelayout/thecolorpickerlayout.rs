@@ -0,0 +1,344 @@
+use crate::prelude::*;
+
+/// The names of the numeric fields, in widget order after the picker.
+const FIELDS: [(&str, &str); 8] = [
+    ("R", "Color R"),
+    ("G", "Color G"),
+    ("B", "Color B"),
+    ("H", "Color H"),
+    ("S", "Color S"),
+    ("V", "Color V"),
+    ("Hex", "Color Hex"),
+    ("A", "Color A"),
+];
+
+/// A color picker together with RGB, HSV, hex and alpha text fields which are kept in sync.
+pub struct TheColorPickerLayout {
+    id: TheId,
+    limiter: TheSizeLimiter,
+
+    dim: TheDim,
+
+    widgets: Vec<Box<dyn TheWidget>>,
+
+    margin: Vec4<i32>,
+    padding: i32,
+
+    background: Option<TheThemeColors>,
+
+    color: TheColor,
+}
+
+impl TheLayout for TheColorPickerLayout {
+    fn new(id: TheId) -> Self
+    where
+        Self: Sized,
+    {
+        let mut widgets: Vec<Box<dyn TheWidget>> = vec![];
+
+        let mut picker = TheColorPicker::new(TheId::named("Color Picker"));
+        picker.set_associated_layout(id.clone());
+        widgets.push(Box::new(picker));
+
+        for (label, name) in FIELDS {
+            let mut text = TheText::new(TheId::empty());
+            text.set_text(label.to_string());
+            widgets.push(Box::new(text));
+
+            let mut edit = TheTextLineEdit::new(TheId::named(name));
+            match name {
+                "Color R" | "Color G" | "Color B" => edit.set_range(TheValue::RangeI32(0..=255)),
+                "Color H" => edit.set_range(TheValue::RangeI32(0..=360)),
+                "Color S" | "Color V" | "Color A" => edit.set_range(TheValue::RangeI32(0..=100)),
+                _ => {}
+            }
+            edit.set_associated_layout(id.clone());
+            widgets.push(Box::new(edit));
+        }
+
+        let mut layout = Self {
+            id,
+            limiter: TheSizeLimiter::new(),
+
+            dim: TheDim::zero(),
+
+            widgets,
+
+            margin: Vec4::new(8, 8, 8, 8),
+            padding: 4,
+
+            background: Some(DefaultWidgetBackground),
+
+            color: TheColor::black(),
+        };
+        layout.limiter.set_max_size(Vec2::new(220, 290));
+        layout.sync_fields();
+        layout
+    }
+
+    fn id(&self) -> &TheId {
+        &self.id
+    }
+
+    fn set_margin(&mut self, margin: Vec4<i32>) {
+        self.margin = margin;
+    }
+
+    fn set_padding(&mut self, padding: i32) {
+        self.padding = padding;
+    }
+
+    fn set_background_color(&mut self, color: Option<TheThemeColors>) {
+        self.background = color;
+    }
+
+    fn widgets(&mut self) -> &mut Vec<Box<dyn TheWidget>> {
+        &mut self.widgets
+    }
+
+    fn get_widget_at_coord(&mut self, coord: Vec2<i32>) -> Option<&mut Box<dyn TheWidget>> {
+        self.widgets.iter_mut().find(|w| w.dim().contains(coord))
+    }
+
+    fn get_widget(
+        &mut self,
+        name: Option<&String>,
+        uuid: Option<&Uuid>,
+    ) -> Option<&mut Box<dyn TheWidget>> {
+        self.widgets.iter_mut().find(|w| w.id().matches(name, uuid))
+    }
+
+    fn dim(&self) -> &TheDim {
+        &self.dim
+    }
+
+    fn dim_mut(&mut self) -> &mut TheDim {
+        &mut self.dim
+    }
+
+    fn set_dim(&mut self, dim: TheDim, ctx: &mut TheContext) {
+        if self.dim != dim || ctx.ui.relayout {
+            self.dim = dim;
+
+            let row_height = 20;
+            let width = dim.width - self.margin.x - self.margin.z;
+            let picker_height =
+                dim.height - self.margin.y - self.margin.w - (row_height + self.padding) * 3;
+
+            let mut place = |index: usize, x: i32, y: i32, w: i32, h: i32| {
+                let widget = &mut self.widgets[index];
+                widget.set_dim(TheDim::new(dim.x + x, dim.y + y, w, h), ctx);
+                widget
+                    .dim_mut()
+                    .set_buffer_offset(dim.buffer_x + x, dim.buffer_y + y);
+            };
+
+            place(0, self.margin.x, self.margin.y, width, picker_height.max(0));
+
+            // Three rows: R G B, H S V and Hex A, each as label / edit pairs.
+            let mut y = self.margin.y + picker_height + self.padding;
+            for row in 0..3 {
+                let pairs: Vec<(usize, i32)> = if row < 2 {
+                    (0..3).map(|i| (row * 3 + i, 14)).collect()
+                } else {
+                    vec![(6, 28), (7, 14)]
+                };
+
+                let label_width: i32 = pairs.iter().map(|(_, l)| l).sum();
+                let edit_width =
+                    (width - label_width - self.padding * (pairs.len() as i32 * 2 - 1))
+                        / pairs.len() as i32;
+
+                let mut x = self.margin.x;
+                for (field, label) in pairs {
+                    let edit_width = if field == 6 {
+                        edit_width * 3 / 2
+                    } else if field == 7 {
+                        edit_width / 2
+                    } else {
+                        edit_width
+                    };
+                    place(1 + field * 2, x, y, label, row_height);
+                    x += label + self.padding;
+                    place(2 + field * 2, x, y, edit_width, row_height);
+                    x += edit_width + self.padding;
+                }
+                y += row_height + self.padding;
+            }
+        }
+    }
+
+    fn relayout(&mut self, ctx: &mut TheContext) {
+        let dim = self.dim;
+        self.dim = TheDim::zero();
+        self.set_dim(dim, ctx);
+    }
+
+    fn limiter(&self) -> &TheSizeLimiter {
+        &self.limiter
+    }
+
+    fn limiter_mut(&mut self) -> &mut TheSizeLimiter {
+        &mut self.limiter
+    }
+
+    fn redirected_widget_value(
+        &mut self,
+        widget_id: &TheId,
+        value: &TheValue,
+        ctx: &mut TheContext,
+    ) {
+        let mut color = self.color.clone();
+        let [r, g, b, _] = color.to_u8_array();
+        let hsv = color.as_hsv();
+
+        let component = |v: &TheValue, max: i32| v.to_i32().map(|v| v.clamp(0, max));
+
+        match widget_id.name.as_str() {
+            "Color Picker" => {
+                if let TheValue::ColorObject(c) = value {
+                    color = c.clone();
+                }
+            }
+            "Color R" | "Color G" | "Color B" => {
+                if let Some(v) = component(value, 255) {
+                    let mut rgb = [r, g, b];
+                    let index = match widget_id.name.as_str() {
+                        "Color R" => 0,
+                        "Color G" => 1,
+                        _ => 2,
+                    };
+                    rgb[index] = v as u8;
+                    color = TheColor::from_u8(rgb[0], rgb[1], rgb[2], 255);
+                    color.a = self.color.a;
+                }
+            }
+            "Color H" | "Color S" | "Color V" => {
+                if let Some(v) = component(value, 360) {
+                    let (mut h, mut s, mut v_) = (hsv.x * 360.0, hsv.y, hsv.z);
+                    match widget_id.name.as_str() {
+                        "Color H" => h = v as f32,
+                        "Color S" => s = v.min(100) as f32 / 100.0,
+                        _ => v_ = v.min(100) as f32 / 100.0,
+                    }
+                    color = TheColor::from_hsv(h, s, v_);
+                    color.a = self.color.a;
+                }
+            }
+            "Color A" => {
+                if let Some(v) = component(value, 100) {
+                    color.a = v as f32 / 100.0;
+                }
+            }
+            "Color Hex" => {
+                if let Some(text) = value.to_string() {
+                    let text = text.trim().trim_start_matches('#');
+                    if (text.len() == 6 || text.len() == 8)
+                        && text.chars().all(|c| c.is_ascii_hexdigit())
+                    {
+                        color = TheColor::from_hex(&format!("#{}", text));
+                    }
+                }
+            }
+            _ => return,
+        }
+
+        self.color = color.clone();
+        self.sync_fields();
+
+        if widget_id.name != "Color Picker" {
+            if let Some(picker) = self.widgets[0].as_color_picker() {
+                picker.update_color(color.clone());
+            }
+            ctx.ui.add_recent_color(color.clone());
+        }
+
+        ctx.ui.send(TheEvent::ValueChanged(
+            self.id.clone(),
+            TheValue::ColorObject(color),
+        ));
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &mut TheRGBABuffer,
+        style: &mut Box<dyn TheStyle>,
+        ctx: &mut TheContext,
+    ) {
+        if let Some(background) = self.background {
+            let stride = buffer.stride();
+
+            ctx.draw.rect(
+                buffer.pixels_mut(),
+                &self.dim.to_buffer_utuple(),
+                stride,
+                style.theme().color(background),
+            );
+        }
+
+        for w in &mut self.widgets {
+            w.draw(buffer, style, ctx);
+        }
+    }
+
+    fn as_color_picker_layout(&mut self) -> Option<&mut dyn TheColorPickerLayoutTrait> {
+        Some(self)
+    }
+}
+
+impl TheColorPickerLayout {
+    /// Writes the current color into the text fields.
+    fn sync_fields(&mut self) {
+        let [r, g, b, _] = self.color.to_u8_array();
+        let hsv = self.color.as_hsv();
+        let texts = [
+            r.to_string(),
+            g.to_string(),
+            b.to_string(),
+            ((hsv.x * 360.0).round() as i32).to_string(),
+            ((hsv.y * 100.0).round() as i32).to_string(),
+            ((hsv.z * 100.0).round() as i32).to_string(),
+            self.color.to_hex(),
+            ((self.color.a * 100.0).round() as i32).to_string(),
+        ];
+
+        for (index, text) in texts.into_iter().enumerate() {
+            if let Some(edit) = self.widgets[2 + index * 2].as_text_line_edit() {
+                edit.set_text(text);
+            }
+        }
+    }
+}
+
+/// TheColorPickerLayout specific functions.
+pub trait TheColorPickerLayoutTrait {
+    /// Returns the current color.
+    fn color(&self) -> TheColor;
+    /// Sets the color of the picker and the text fields.
+    fn set_color(&mut self, color: TheColor);
+    /// Send value changes while dragging in the picker.
+    fn set_continuous(&mut self, continuous: bool);
+    /// Show or hide the alpha bar of the picker.
+    fn set_alpha_enabled(&mut self, alpha_enabled: bool);
+}
+
+impl TheColorPickerLayoutTrait for TheColorPickerLayout {
+    fn color(&self) -> TheColor {
+        self.color.clone()
+    }
+    fn set_color(&mut self, color: TheColor) {
+        self.widgets[0].set_value(TheValue::ColorObject(color.clone()));
+        self.color = color;
+        self.sync_fields();
+    }
+    fn set_continuous(&mut self, continuous: bool) {
+        if let Some(picker) = self.widgets[0].as_color_picker() {
+            picker.set_continuous(continuous);
+        }
+    }
+    fn set_alpha_enabled(&mut self, alpha_enabled: bool) {
+        if let Some(picker) = self.widgets[0].as_color_picker() {
+            picker.set_alpha_enabled(alpha_enabled);
+        }
+    }
+}
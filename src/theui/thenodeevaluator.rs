@@ -0,0 +1,409 @@
+use crate::prelude::*;
+use std::collections::VecDeque;
+
+/// The errors of a node evaluation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TheNodeError {
+    /// The node is part of a cycle in the connections.
+    Cycle,
+    /// No behavior was set for the node.
+    MissingBehavior,
+    /// The input node with the given id failed, so this node could not be evaluated.
+    Upstream(Uuid),
+    /// The behavior of the node failed.
+    Evaluation(String),
+}
+
+impl std::fmt::Display for TheNodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cycle => write!(f, "Node is part of a cycle"),
+            Self::MissingBehavior => write!(f, "Node has no behavior"),
+            Self::Upstream(index) => write!(f, "Input node {} failed", index),
+            Self::Evaluation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TheNodeError {}
+
+/// The behavior of a node, computes the values of its output terminals from its input terminals.
+pub trait TheNodeBehavior {
+    /// Evaluates the node. Returns one value per output terminal.
+    fn evaluate(&mut self, inputs: &[TheValue]) -> Result<Vec<TheValue>, TheNodeError>;
+
+    /// The value of an input terminal which is not connected and has no value set.
+    fn default_input(&self, _index: usize) -> TheValue {
+        TheValue::Empty
+    }
+
+    /// Volatile nodes (time, random, external input) are evaluated on every pass.
+    fn is_volatile(&self) -> bool {
+        false
+    }
+}
+
+/// Evaluates the dataflow of a TheNodeCanvas. Nodes are scheduled in topological order and only
+/// re-evaluated if they, one of their inputs or an upstream output changed. The state of a node is
+/// keyed by its id, so it stays attached to the node when nodes are inserted or removed, for
+/// example by undo and redo. Removed nodes keep their state until remove_node() is called.
+#[derive(Default)]
+pub struct TheNodeEvaluator {
+    behaviors: FxHashMap<Uuid, Box<dyn TheNodeBehavior>>,
+    /// Values of unconnected inputs: (node id, input terminal).
+    inputs: FxHashMap<(Uuid, u8), TheValue>,
+    outputs: FxHashMap<Uuid, Vec<TheValue>>,
+    errors: FxHashMap<Uuid, TheNodeError>,
+    dirty: FxHashSet<Uuid>,
+
+    /// The nodes and connections of the last evaluation, used to detect changes.
+    nodes: FxHashSet<Uuid>,
+    connections: Vec<TheNodeConnectionId>,
+}
+
+impl TheNodeEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the behavior of the node with the given id.
+    pub fn set_behavior(&mut self, id: Uuid, behavior: Box<dyn TheNodeBehavior>) {
+        self.behaviors.insert(id, behavior);
+        self.dirty.insert(id);
+    }
+
+    /// Sets the value of an unconnected input terminal.
+    pub fn set_input(&mut self, id: Uuid, terminal: u8, value: TheValue) {
+        if self.inputs.get(&(id, terminal)) != Some(&value) {
+            self.inputs.insert((id, terminal), value);
+            self.dirty.insert(id);
+        }
+    }
+
+    /// Marks the node as changed, it and everything depending on it is evaluated on the next pass.
+    pub fn mark_dirty(&mut self, id: Uuid) {
+        self.dirty.insert(id);
+    }
+
+    /// Marks all nodes as changed.
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.extend(self.behaviors.keys().copied());
+        self.dirty.extend(self.nodes.iter().copied());
+    }
+
+    /// Returns true if the node will be evaluated on the next pass.
+    pub fn is_dirty(&self, id: &Uuid) -> bool {
+        self.dirty.contains(id)
+    }
+
+    /// Removes the behavior, the input values and the results of the node with the given id.
+    pub fn remove_node(&mut self, id: &Uuid) {
+        self.behaviors.remove(id);
+        self.inputs.retain(|(node, _), _| node != id);
+        self.outputs.remove(id);
+        self.errors.remove(id);
+        self.dirty.remove(id);
+    }
+
+    /// Returns the output values of the node from the last evaluation.
+    pub fn outputs(&self, id: &Uuid) -> Option<&[TheValue]> {
+        self.outputs.get(id).map(|outputs| outputs.as_slice())
+    }
+
+    /// Returns the value of an output terminal from the last evaluation.
+    pub fn output(&self, id: &Uuid, terminal: u8) -> Option<&TheValue> {
+        self.outputs(id)?.get(terminal as usize)
+    }
+
+    /// Returns the error of the node, if its last evaluation failed.
+    pub fn error(&self, id: &Uuid) -> Option<&TheNodeError> {
+        self.errors.get(id)
+    }
+
+    /// Returns all node errors.
+    pub fn errors(&self) -> &FxHashMap<Uuid, TheNodeError> {
+        &self.errors
+    }
+
+    /// Applies the implicit conversion of a value to the data type of an input terminal.
+    /// Values which have no conversion to the type are passed through unchanged.
+    pub fn convert(value: TheValue, data_type: &TheNodeDataType) -> TheValue {
+        use TheNodeDataType::*;
+        match (value, data_type) {
+            (TheValue::Bool(v), Int) => TheValue::Int(v as i32),
+            (TheValue::Bool(v), Float) => TheValue::Float(if v { 1.0 } else { 0.0 }),
+            (TheValue::Int(v), Float) => TheValue::Float(v as f32),
+            (TheValue::Float(v), Int) => TheValue::Int(v as i32),
+            (TheValue::Float(v), Float2) => TheValue::Float2(Vec2::broadcast(v)),
+            (TheValue::Float(v), Float3) => TheValue::Float3(Vec3::broadcast(v)),
+            (TheValue::Float(v), Float4) => TheValue::Float4(Vec4::broadcast(v)),
+            (TheValue::Float3(v), Float4) => TheValue::Float4(Vec4::new(v.x, v.y, v.z, 1.0)),
+            (TheValue::Float4(v), Color) => TheValue::ColorObject(TheColor::from_vec4f(v)),
+            (TheValue::ColorObject(c), Float4) => TheValue::Float4(c.to_vec4()),
+            (TheValue::ColorObject(c), Text) => TheValue::Text(c.to_hex()),
+            (
+                value @ (TheValue::Bool(_)
+                | TheValue::Int(_)
+                | TheValue::Float(_)
+                | TheValue::Float2(_)
+                | TheValue::Float3(_)
+                | TheValue::Float4(_)),
+                Text,
+            ) => TheValue::Text(value.describe()),
+            (value, _) => value,
+        }
+    }
+
+    /// Returns the errors of the nodes of the canvas as text by node index, for
+    /// TheNodeCanvasViewTrait::set_node_errors. Failed input nodes are named.
+    pub fn error_messages(&self, canvas: &TheNodeCanvas) -> FxHashMap<usize, String> {
+        canvas
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| {
+                let message = match self.errors.get(&node.id)? {
+                    TheNodeError::Upstream(id) => match canvas.node(id) {
+                        Some(input) => format!("Input node {} failed", input.name),
+                        None => TheNodeError::Upstream(*id).to_string(),
+                    },
+                    err => err.to_string(),
+                };
+                Some((index, message))
+            })
+            .collect()
+    }
+
+    /// Returns the nodes in evaluation order, or the nodes which are part of a cycle.
+    pub fn schedule(canvas: &TheNodeCanvas) -> Result<Vec<usize>, Vec<usize>> {
+        Self::topological_order(canvas.nodes.len(), &canvas.index_connections())
+    }
+
+    /// Kahn's algorithm over the connections.
+    fn topological_order(
+        count: usize,
+        connections: &[(u16, u8, u16, u8)],
+    ) -> Result<Vec<usize>, Vec<usize>> {
+        let mut in_degree = vec![0; count];
+        let mut edges: Vec<Vec<usize>> = vec![vec![]; count];
+
+        for (src, _, dest, _) in connections {
+            let (src, dest) = (*src as usize, *dest as usize);
+            if src < count && dest < count {
+                edges[src].push(dest);
+                in_degree[dest] += 1;
+            }
+        }
+
+        let mut queue: VecDeque<usize> = (0..count).filter(|i| in_degree[*i] == 0).collect();
+        let mut order = Vec::with_capacity(count);
+
+        while let Some(index) = queue.pop_front() {
+            order.push(index);
+            for dest in &edges[index] {
+                in_degree[*dest] -= 1;
+                if in_degree[*dest] == 0 {
+                    queue.push_back(*dest);
+                }
+            }
+        }
+
+        if order.len() == count {
+            Ok(order)
+        } else {
+            Err((0..count).filter(|i| in_degree[*i] > 0).collect())
+        }
+    }
+
+    /// Evaluates the changed nodes of the canvas and everything depending on them.
+    /// Returns the indices of the evaluated nodes in evaluation order.
+    pub fn evaluate(&mut self, canvas: &TheNodeCanvas) -> Vec<usize> {
+        let count = canvas.nodes.len();
+        self.sync_nodes(canvas);
+        let connections = canvas.index_connections();
+        let id = |index: usize| canvas.nodes[index].id;
+
+        // Nodes in a cycle and the nodes downstream of them can not be scheduled.
+        let order = match Self::topological_order(count, &connections) {
+            Ok(order) => order,
+            Err(blocked) => {
+                let blocked: FxHashSet<usize> = blocked.into_iter().collect();
+                let cycle = Self::cycle_nodes(&blocked, &connections);
+                for index in &blocked {
+                    let err = if cycle.contains(index) {
+                        TheNodeError::Cycle
+                    } else {
+                        let src = connections
+                            .iter()
+                            .find(|(src, _, dest, _)| {
+                                *dest as usize == *index && blocked.contains(&(*src as usize))
+                            })
+                            .map(|(src, _, _, _)| *src as usize)
+                            .unwrap_or(*index);
+                        TheNodeError::Upstream(id(src))
+                    };
+                    self.errors.insert(id(*index), err);
+                    self.outputs.remove(&id(*index));
+                    self.dirty.insert(id(*index));
+                }
+                let unblocked: Vec<_> = connections
+                    .iter()
+                    .filter(|(src, _, dest, _)| {
+                        !blocked.contains(&(*src as usize)) && !blocked.contains(&(*dest as usize))
+                    })
+                    .copied()
+                    .collect();
+                Self::topological_order(count, &unblocked)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|index| !blocked.contains(index))
+                    .collect()
+            }
+        };
+
+        let mut evaluated = vec![];
+
+        for index in order {
+            let node_id = id(index);
+            let volatile = self
+                .behaviors
+                .get(&node_id)
+                .is_some_and(|behavior| behavior.is_volatile());
+            if !self.dirty.contains(&node_id) && !volatile {
+                continue;
+            }
+
+            let previous = self.outputs.get(&node_id).cloned();
+            let result = self.evaluate_node(canvas, &connections, index);
+            evaluated.push(index);
+            self.dirty.remove(&node_id);
+
+            match result {
+                Ok(outputs) => {
+                    self.errors.remove(&node_id);
+                    self.outputs.insert(node_id, outputs);
+                }
+                Err(err) => {
+                    self.errors.insert(node_id, err);
+                    self.outputs.remove(&node_id);
+                }
+            }
+
+            // Only propagate when the outputs actually changed.
+            if self.outputs.get(&node_id) != previous.as_ref() || self.errors.contains_key(&node_id)
+            {
+                for (src, _, dest, _) in &connections {
+                    if *src as usize == index {
+                        self.dirty.insert(id(*dest as usize));
+                    }
+                }
+            }
+        }
+
+        evaluated
+    }
+
+    /// Gathers the inputs of the node and runs its behavior.
+    fn evaluate_node(
+        &mut self,
+        canvas: &TheNodeCanvas,
+        connections: &[(u16, u8, u16, u8)],
+        index: usize,
+    ) -> Result<Vec<TheValue>, TheNodeError> {
+        let node_id = canvas.nodes[index].id;
+        let Some(behavior) = self.behaviors.get(&node_id) else {
+            return Err(TheNodeError::MissingBehavior);
+        };
+
+        let mut inputs = vec![];
+        for terminal in 0..canvas.nodes[index].inputs.len() {
+            let source = connections
+                .iter()
+                .rev()
+                .find(|(_, _, dest, dest_terminal)| {
+                    *dest as usize == index && *dest_terminal as usize == terminal
+                });
+
+            let value = if let Some((src, src_terminal, _, _)) = source {
+                let src_id = canvas.nodes[*src as usize].id;
+                if self.errors.contains_key(&src_id) {
+                    return Err(TheNodeError::Upstream(src_id));
+                }
+                let value = self
+                    .output(&src_id, *src_terminal)
+                    .cloned()
+                    .unwrap_or(TheValue::Empty);
+                Self::convert(value, &canvas.nodes[index].inputs[terminal].data_type)
+            } else if let Some(value) = self.inputs.get(&(node_id, terminal as u8)) {
+                value.clone()
+            } else {
+                behavior.default_input(terminal)
+            };
+            inputs.push(value);
+        }
+
+        if let Some(behavior) = self.behaviors.get_mut(&node_id) {
+            behavior.evaluate(&inputs)
+        } else {
+            Err(TheNodeError::MissingBehavior)
+        }
+    }
+
+    /// Of the nodes Kahn's algorithm could not schedule, returns the ones on a cycle by
+    /// repeatedly removing the nodes without outgoing connections.
+    fn cycle_nodes(
+        blocked: &FxHashSet<usize>,
+        connections: &[(u16, u8, u16, u8)],
+    ) -> FxHashSet<usize> {
+        let mut cycle = blocked.clone();
+        loop {
+            let sinks: Vec<usize> = cycle
+                .iter()
+                .filter(|index| {
+                    !connections.iter().any(|(src, _, dest, _)| {
+                        *src as usize == **index && cycle.contains(&(*dest as usize))
+                    })
+                })
+                .copied()
+                .collect();
+            if sinks.is_empty() {
+                return cycle;
+            }
+            for index in sinks {
+                cycle.remove(&index);
+            }
+        }
+    }
+
+    /// Marks the nodes which were added to the canvas and the destination of every added or
+    /// removed connection as dirty.
+    fn sync_nodes(&mut self, canvas: &TheNodeCanvas) {
+        let nodes: FxHashSet<Uuid> = canvas.nodes.iter().map(|node| node.id).collect();
+        self.dirty.extend(nodes.difference(&self.nodes).copied());
+        self.nodes = nodes;
+
+        if self.connections == canvas.connections {
+            return;
+        }
+        for connection in self
+            .connections
+            .iter()
+            .filter(|c| !canvas.connections.contains(c))
+            .chain(
+                canvas
+                    .connections
+                    .iter()
+                    .filter(|c| !self.connections.contains(c)),
+            )
+        {
+            self.dirty.insert(connection.2);
+        }
+        // Nodes which were in a cycle may be schedulable again.
+        for (id, err) in &self.errors {
+            if *err == TheNodeError::Cycle {
+                self.dirty.insert(*id);
+            }
+        }
+        self.connections = canvas.connections.clone();
+    }
+}
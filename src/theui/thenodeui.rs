@@ -2,6 +2,12 @@ use crate::prelude::*;
 use indexmap::IndexMap;
 use std::ops::RangeInclusive;
 
+/// Validates the new value of an item. The error message is shown as the status text of the item.
+pub type TheNodeUIValidator = fn(&TheValue) -> Result<(), String>;
+
+/// The names of the vector components, used for the ids of the component editors: "id.x".
+const COMPONENTS: [&str; 4] = ["x", "y", "z", "w"];
+
 /// The items that can be added to TheNodeUI
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum TheNodeUIItem {
@@ -25,12 +31,56 @@ pub enum TheNodeUIItem {
     Button(String, String, String, String),
     /// Text: Id, Name, Status, Value, DefaultValue, Continuous
     ColorPicker(String, String, String, TheColor, bool),
+    /// Gradient: Id, Name, Status, Value, Continuous
+    Gradient(String, String, String, TheGradient, bool),
     /// Checkbox: Id, Name, Status, Value,
     Checkbox(String, String, String, bool),
     /// Separator: Name
     Separator(String),
     /// Icons: Id, Name, Status, (Buffer, Name, Id)
     Icons(String, String, String, Vec<(TheRGBABuffer, String, Uuid)>),
+    /// Vec2 Edit: Id, Name, Status, Value, Continuous
+    Vec2Edit(String, String, String, Vec2<f32>, bool),
+    /// Vec3 Edit: Id, Name, Status, Value, Continuous
+    Vec3Edit(String, String, String, Vec3<f32>, bool),
+    /// Vec4 Edit: Id, Name, Status, Value, Continuous
+    Vec4Edit(String, String, String, Vec4<f32>, bool),
+    /// Float Range: Id, Name, Status, Value (Min, Max), Range, Continuous
+    FloatRange(
+        String,
+        String,
+        String,
+        (f32, f32),
+        RangeInclusive<f32>,
+        bool,
+    ),
+    /// Int Range: Id, Name, Status, Value (Min, Max), Range, Continuous
+    IntRange(
+        String,
+        String,
+        String,
+        (i32, i32),
+        RangeInclusive<i32>,
+        bool,
+    ),
+    /// File Path with a browse button: Id, Name, Status, Value, Extension Name, Extensions
+    FilePath(String, String, String, String, String, Vec<String>),
+    /// Multi-line Text: Id, Name, Status, Value, Continuous
+    TextArea(String, String, String, String, bool),
+    /// Curve: Id, Name, Status, Control Points, Continuous. See TheCurveEditor::sample().
+    Curve(String, String, String, Vec<Vec2<f32>>, bool),
+    /// Tile Picker: Id, Name, Status, (Buffer, Name, Id), Value
+    TilePicker(
+        String,
+        String,
+        String,
+        Vec<(TheRGBABuffer, String, Uuid)>,
+        Option<Uuid>,
+    ),
+    /// Collapsible Group: Id, Name, Collapsed. Contains the items up to the matching GroupEnd.
+    Group(String, String, bool),
+    /// End of a Group: Id
+    GroupEnd(String),
 }
 
 impl TheNodeUIItem {
@@ -47,19 +97,45 @@ impl TheNodeUIItem {
             TheNodeUIItem::IntSlider(id, _, _, _, _, _, _) => id,
             TheNodeUIItem::Button(id, _, _, _) => id,
             TheNodeUIItem::ColorPicker(id, _, _, _, _) => id,
+            TheNodeUIItem::Gradient(id, _, _, _, _) => id,
             TheNodeUIItem::Checkbox(id, _, _, _) => id,
             TheNodeUIItem::Separator(name) => name,
             TheNodeUIItem::Icons(id, _, _, _) => id,
+            TheNodeUIItem::Vec2Edit(id, _, _, _, _) => id,
+            TheNodeUIItem::Vec3Edit(id, _, _, _, _) => id,
+            TheNodeUIItem::Vec4Edit(id, _, _, _, _) => id,
+            TheNodeUIItem::FloatRange(id, _, _, _, _, _) => id,
+            TheNodeUIItem::IntRange(id, _, _, _, _, _) => id,
+            TheNodeUIItem::FilePath(id, _, _, _, _, _) => id,
+            TheNodeUIItem::TextArea(id, _, _, _, _) => id,
+            TheNodeUIItem::Curve(id, _, _, _, _) => id,
+            TheNodeUIItem::TilePicker(id, _, _, _, _) => id,
+            TheNodeUIItem::Group(id, _, _) => id,
+            TheNodeUIItem::GroupEnd(id) => id,
         }
     }
 }
 
 use TheNodeUIItem::*;
 
+/// Editors with their labels, used for items with one editor per component.
+type LabelledEdits = Vec<(String, Box<dyn TheWidget>)>;
+
 /// A container for UI items. Supports adding them to a text layout or handling events for updating the values.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TheNodeUI {
     items: IndexMap<String, TheNodeUIItem>,
+
+    #[serde(skip)]
+    validators: FxHashMap<String, TheNodeUIValidator>,
+    #[serde(skip)]
+    errors: FxHashMap<String, String>,
+
+    /// Item id: The id of the controlling item and the values for which the item is visible.
+    #[serde(default)]
+    conditions: FxHashMap<String, (String, Vec<TheValue>)>,
+    #[serde(default)]
+    tooltips: FxHashMap<String, String>,
 }
 
 impl Default for TheNodeUI {
@@ -72,6 +148,12 @@ impl TheNodeUI {
     pub fn new() -> Self {
         Self {
             items: IndexMap::new(),
+
+            validators: FxHashMap::default(),
+            errors: FxHashMap::default(),
+
+            conditions: FxHashMap::default(),
+            tooltips: FxHashMap::default(),
         }
     }
 
@@ -115,7 +197,9 @@ impl TheNodeUI {
         for (item_id, item) in &self.items {
             if id == item_id {
                 match item {
-                    Text(_, _, _, value, _, _) => {
+                    Text(_, _, _, value, _, _)
+                    | TextArea(_, _, _, value, _)
+                    | FilePath(_, _, _, value, _, _) => {
                         return Some(value.clone());
                     }
                     _ => {}
@@ -140,6 +224,21 @@ impl TheNodeUI {
         None
     }
 
+    /// Get a gradient value.
+    pub fn get_gradient_value(&self, id: &str) -> Option<TheGradient> {
+        if let Some(Gradient(_, _, _, value, _)) = self.items.get(id) {
+            return Some(value.clone());
+        }
+        None
+    }
+
+    /// Set a gradient value.
+    pub fn set_gradient_value(&mut self, id: &str, val: TheGradient) {
+        if let Some(Gradient(_, _, _, value, _)) = self.items.get_mut(id) {
+            *value = val;
+        }
+    }
+
     /// Get a tile id for the given index
     pub fn get_tile_id(&self, id: &str, index: usize) -> Option<Uuid> {
         for (item_id, item) in &self.items {
@@ -238,7 +337,9 @@ impl TheNodeUI {
         for (item_id, item) in &mut self.items {
             if id == item_id {
                 match item {
-                    Text(_, _, _, value, _, _) => {
+                    Text(_, _, _, value, _, _)
+                    | TextArea(_, _, _, value, _)
+                    | FilePath(_, _, _, value, _, _) => {
                         *value = val.clone();
                     }
                     _ => {}
@@ -247,14 +348,413 @@ impl TheNodeUI {
         }
     }
 
-    /// Add the items to the given text layout.
+    /// Get a Vec2 value.
+    pub fn get_vec2_value(&self, id: &str) -> Option<Vec2<f32>> {
+        if let Some(Vec2Edit(_, _, _, value, _)) = self.items.get(id) {
+            return Some(*value);
+        }
+        None
+    }
+
+    /// Get a Vec3 value.
+    pub fn get_vec3_value(&self, id: &str) -> Option<Vec3<f32>> {
+        if let Some(Vec3Edit(_, _, _, value, _)) = self.items.get(id) {
+            return Some(*value);
+        }
+        None
+    }
+
+    /// Get a Vec4 value.
+    pub fn get_vec4_value(&self, id: &str) -> Option<Vec4<f32>> {
+        if let Some(Vec4Edit(_, _, _, value, _)) = self.items.get(id) {
+            return Some(*value);
+        }
+        None
+    }
+
+    /// Get a float range value (min, max).
+    pub fn get_float_range_value(&self, id: &str) -> Option<(f32, f32)> {
+        if let Some(FloatRange(_, _, _, value, _, _)) = self.items.get(id) {
+            return Some(*value);
+        }
+        None
+    }
+
+    /// Get an int range value (min, max).
+    pub fn get_int_range_value(&self, id: &str) -> Option<(i32, i32)> {
+        if let Some(IntRange(_, _, _, value, _, _)) = self.items.get(id) {
+            return Some(*value);
+        }
+        None
+    }
+
+    /// Get the control points of a curve.
+    pub fn get_curve_value(&self, id: &str) -> Option<Vec<Vec2<f32>>> {
+        if let Some(Curve(_, _, _, value, _)) = self.items.get(id) {
+            return Some(value.clone());
+        }
+        None
+    }
+
+    /// Get the id of the picked tile.
+    pub fn get_tile_value(&self, id: &str) -> Option<Uuid> {
+        if let Some(TilePicker(_, _, _, _, value)) = self.items.get(id) {
+            return *value;
+        }
+        None
+    }
+
+    /// Returns true if the group is collapsed.
+    pub fn is_collapsed(&self, id: &str) -> bool {
+        matches!(self.items.get(id), Some(Group(_, _, true)))
+    }
+
+    /// Collapses or expands a group.
+    pub fn set_collapsed(&mut self, id: &str, collapsed: bool) {
+        if let Some(Group(_, _, value)) = self.items.get_mut(id) {
+            *value = collapsed;
+        }
+    }
+
+    /// Get the value of an item as TheValue. Ranges are returned as RangeF32 / RangeI32, curves
+    /// as a List of Float2 and the picked tile as an Id.
+    pub fn get_value(&self, id: &str) -> Option<TheValue> {
+        match self.items.get(id)? {
+            Text(_, _, _, value, _, _)
+            | TextArea(_, _, _, value, _)
+            | FilePath(_, _, _, value, _, _) => Some(TheValue::Text(value.clone())),
+            Selector(_, _, _, _, value)
+            | IntEditSlider(_, _, _, value, _, _)
+            | PaletteSlider(_, _, _, value, _, _)
+            | IntSlider(_, _, _, value, _, _, _) => Some(TheValue::Int(*value)),
+            FloatEditSlider(_, _, _, value, _, _) | FloatSlider(_, _, _, value, _, _, _) => {
+                Some(TheValue::Float(*value))
+            }
+            ColorPicker(_, _, _, value, _) => Some(TheValue::ColorObject(value.clone())),
+            Gradient(_, _, _, value, _) => Some(TheValue::Gradient(value.clone())),
+            Checkbox(_, _, _, value) => Some(TheValue::Bool(*value)),
+            Vec2Edit(_, _, _, value, _) => Some(TheValue::Float2(*value)),
+            Vec3Edit(_, _, _, value, _) => Some(TheValue::Float3(*value)),
+            Vec4Edit(_, _, _, value, _) => Some(TheValue::Float4(*value)),
+            FloatRange(_, _, _, (min, max), _, _) => Some(TheValue::RangeF32(*min..=*max)),
+            IntRange(_, _, _, (min, max), _, _) => Some(TheValue::RangeI32(*min..=*max)),
+            Curve(_, _, _, points, _) => Some(TheValue::List(
+                points.iter().map(|p| TheValue::Float2(*p)).collect(),
+            )),
+            TilePicker(_, _, _, _, value) => {
+                Some(value.map(TheValue::Id).unwrap_or(TheValue::Empty))
+            }
+            _ => None,
+        }
+    }
+
+    /// Set the value of an item from TheValue, in the format of get_value(). Returns true if the
+    /// value type matched the item.
+    pub fn set_value(&mut self, id: &str, val: TheValue) -> bool {
+        let Some(item) = self.items.get_mut(id) else {
+            return false;
+        };
+        match (item, val) {
+            (
+                Text(_, _, _, value, _, _)
+                | TextArea(_, _, _, value, _)
+                | FilePath(_, _, _, value, _, _),
+                TheValue::Text(v),
+            ) => *value = v,
+            (
+                Selector(_, _, _, _, value)
+                | IntEditSlider(_, _, _, value, _, _)
+                | PaletteSlider(_, _, _, value, _, _)
+                | IntSlider(_, _, _, value, _, _, _),
+                TheValue::Int(v),
+            ) => *value = v,
+            (
+                FloatEditSlider(_, _, _, value, _, _) | FloatSlider(_, _, _, value, _, _, _),
+                TheValue::Float(v),
+            ) => *value = v,
+            (ColorPicker(_, _, _, value, _), TheValue::ColorObject(v)) => *value = v,
+            (Gradient(_, _, _, value, _), TheValue::Gradient(v)) => *value = v,
+            (Checkbox(_, _, _, value), TheValue::Bool(v)) => *value = v,
+            (Vec2Edit(_, _, _, value, _), TheValue::Float2(v)) => *value = v,
+            (Vec3Edit(_, _, _, value, _), TheValue::Float3(v)) => *value = v,
+            (Vec4Edit(_, _, _, value, _), TheValue::Float4(v)) => *value = v,
+            (FloatRange(_, _, _, value, _, _), TheValue::RangeF32(v)) => {
+                *value = (*v.start(), *v.end())
+            }
+            (IntRange(_, _, _, value, _, _), TheValue::RangeI32(v)) => {
+                *value = (*v.start(), *v.end())
+            }
+            (Curve(_, _, _, points, _), TheValue::List(list)) => {
+                *points = list.iter().filter_map(|v| v.to_vec2f()).collect()
+            }
+            (TilePicker(_, _, _, _, value), TheValue::Id(v)) => *value = Some(v),
+            (TilePicker(_, _, _, _, value), TheValue::Empty) => *value = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Sets the validator of an item, which is called with the new value of the item before
+    /// it is applied. Rejected values are not applied and the error is shown in the status text.
+    pub fn set_validator(&mut self, id: &str, validator: TheNodeUIValidator) {
+        self.validators.insert(id.into(), validator);
+    }
+
+    /// Validates the current values of all items with a validator. Returns true if all are valid.
+    pub fn validate(&mut self) -> bool {
+        self.errors.clear();
+        for (id, validator) in &self.validators {
+            if let Some(value) = self.get_value(id) {
+                if let Err(err) = validator(&value) {
+                    self.errors.insert(id.clone(), err);
+                }
+            }
+        }
+        self.errors.is_empty()
+    }
+
+    /// Returns the validation error of the item.
+    pub fn error(&self, id: &str) -> Option<&String> {
+        self.errors.get(id)
+    }
+
+    /// Returns true if any item has a validation error.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Shows the item only while the value of the controlling item is one of the given values.
+    pub fn set_visibility_condition(&mut self, id: &str, controller: &str, values: Vec<TheValue>) {
+        self.conditions
+            .insert(id.into(), (controller.into(), values));
+    }
+
+    /// Removes the visibility condition of the item.
+    pub fn remove_visibility_condition(&mut self, id: &str) {
+        self.conditions.remove(id);
+    }
+
+    /// Returns true if the visibility conditions of the item and of its controlling items are met.
+    pub fn is_visible(&self, id: &str) -> bool {
+        let mut id = id;
+        // Bounded to not loop forever on circular conditions.
+        for _ in 0..=self.items.len() {
+            let Some((controller, values)) = self.conditions.get(id) else {
+                return true;
+            };
+            match self.get_value(controller) {
+                Some(value) if values.contains(&value) => id = controller,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Sets the help text of the item. There are no tooltip popups, the text replaces the
+    /// status text of the item which is shown in the status bar while it is hovered.
+    pub fn set_tooltip(&mut self, id: &str, tooltip: &str) {
+        self.tooltips.insert(id.into(), tooltip.into());
+    }
+
+    /// The status text shown for the item: The validation error, the tooltip or the status.
+    fn status_text(&self, id: &str, status: &str) -> String {
+        if let Some(error) = self.errors.get(id) {
+            error.clone()
+        } else if let Some(tooltip) = self.tooltips.get(id) {
+            tooltip.clone()
+        } else {
+            status.to_string()
+        }
+    }
+
+    /// The items to display: Without hidden items and hidden groups and, if requested, without
+    /// the content of collapsed groups.
+    fn visible_items(&self, skip_collapsed: bool) -> Vec<&TheNodeUIItem> {
+        let mut items = vec![];
+        let mut depth = 0;
+        // The group depth at which the skipped group started.
+        let mut skip: Option<usize> = None;
+        for item in self.items.values() {
+            match item {
+                Group(id, _, collapsed) => {
+                    if skip.is_none() {
+                        if !self.is_visible(id) {
+                            skip = Some(depth);
+                        } else {
+                            items.push(item);
+                            if skip_collapsed && *collapsed {
+                                skip = Some(depth);
+                            }
+                        }
+                    }
+                    depth += 1;
+                }
+                GroupEnd(_) => {
+                    depth = depth.saturating_sub(1);
+                    if skip == Some(depth) {
+                        skip = None;
+                    } else if skip.is_none() {
+                        items.push(item);
+                    }
+                }
+                _ => {
+                    if skip.is_none() && self.is_visible(item.id()) {
+                        items.push(item);
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    /// Returns the item id and the component of the widget id: "id" or "id.component".
+    fn item_for_widget<'a>(&self, name: &'a str) -> Option<(String, Option<&'a str>)> {
+        if self.items.contains_key(name) {
+            return Some((name.to_string(), None));
+        }
+        let (id, component) = name.rsplit_once('.')?;
+        self.items
+            .contains_key(id)
+            .then(|| (id.to_string(), Some(component)))
+    }
+
+    /// The new value of the item for the value sent by one of its widgets.
+    fn event_value(
+        &self,
+        id: &str,
+        component: Option<&str>,
+        event_value: &TheValue,
+    ) -> Option<TheValue> {
+        let index = component.and_then(|c| COMPONENTS.iter().position(|n| *n == c));
+        match self.items.get(id)? {
+            Text(..) | TextArea(..) | FilePath(..) => match event_value {
+                TheValue::Text(v) => Some(TheValue::Text(v.clone())),
+                _ => None,
+            },
+            Selector(..) | IntSlider(..) => match event_value {
+                TheValue::Int(v) => Some(TheValue::Int(*v)),
+                _ => None,
+            },
+            IntEditSlider(..) => match event_value {
+                TheValue::Int(v) | TheValue::IntRange(v, _) => Some(TheValue::Int(*v)),
+                _ => None,
+            },
+            FloatEditSlider(..) => event_value.to_f32().map(TheValue::Float),
+            FloatSlider(..) => match event_value {
+                TheValue::Float(v) => Some(TheValue::Float(*v)),
+                _ => None,
+            },
+            ColorPicker(..) => match event_value {
+                TheValue::ColorObject(v) => Some(TheValue::ColorObject(v.clone())),
+                _ => None,
+            },
+            Gradient(..) => match event_value {
+                TheValue::Gradient(v) => Some(TheValue::Gradient(v.clone())),
+                _ => None,
+            },
+            Checkbox(..) => match event_value {
+                TheValue::Bool(v) => Some(TheValue::Bool(*v)),
+                _ => None,
+            },
+            Vec2Edit(_, _, _, value, _) => {
+                let mut value = *value;
+                *value.as_mut_slice().get_mut(index?)? = event_value.to_f32()?;
+                Some(TheValue::Float2(value))
+            }
+            Vec3Edit(_, _, _, value, _) => {
+                let mut value = *value;
+                *value.as_mut_slice().get_mut(index?)? = event_value.to_f32()?;
+                Some(TheValue::Float3(value))
+            }
+            Vec4Edit(_, _, _, value, _) => {
+                let mut value = *value;
+                *value.as_mut_slice().get_mut(index?)? = event_value.to_f32()?;
+                Some(TheValue::Float4(value))
+            }
+            FloatRange(_, _, _, (min, max), range, _) => {
+                let v = event_value.to_f32()?.clamp(*range.start(), *range.end());
+                match component? {
+                    "min" => Some(TheValue::RangeF32(v..=max.max(v))),
+                    "max" => Some(TheValue::RangeF32(min.min(v)..=v)),
+                    _ => None,
+                }
+            }
+            IntRange(_, _, _, (min, max), range, _) => {
+                let v = event_value.to_i32()?.clamp(*range.start(), *range.end());
+                match component? {
+                    "min" => Some(TheValue::RangeI32(v..=*max.max(&v))),
+                    "max" => Some(TheValue::RangeI32(*min.min(&v)..=v)),
+                    _ => None,
+                }
+            }
+            Curve(..) => match event_value {
+                TheValue::List(v) => Some(TheValue::List(v.clone())),
+                _ => None,
+            },
+            // The text layout picks tiles with a dropdown menu.
+            TilePicker(_, _, _, tiles, _) => {
+                let index = event_value.to_i32()?;
+                tiles.get(index as usize).map(|tile| TheValue::Id(tile.2))
+            }
+            _ => None,
+        }
+    }
+
+    /// Validates and applies a new value of the item. Returns true if the value was applied or
+    /// rejected or if the error of the item was cleared, as the layout then has to show the
+    /// current value and status text again.
+    fn update_value(&mut self, id: &str, value: TheValue) -> bool {
+        if let Some(validator) = self.validators.get(id) {
+            if let Err(err) = validator(&value) {
+                self.errors.insert(id.to_string(), err);
+                return true;
+            }
+        }
+        let cleared = self.errors.remove(id).is_some();
+        self.set_value(id, value) || cleared
+    }
+
+    /// Add the items to the given tree node. Groups are added as child nodes.
     pub fn apply_to_tree_node(&self, node: &mut TheTreeNode) {
         node.widgets.clear();
         node.childs.clear();
 
-        for (_, item) in &self.items {
+        // The open groups
+        let mut groups: Vec<TheTreeNode> = vec![];
+
+        for item in self.visible_items(false) {
+            match item {
+                Group(id, name, collapsed) => {
+                    let mut group = TheTreeNode::new(TheId::named(id));
+                    if let Some(snapper) = group.widget.as_any().downcast_mut::<TheSnapperbar>() {
+                        snapper.set_text(name.clone());
+                    }
+                    group.set_open(!collapsed);
+                    groups.push(group);
+                    continue;
+                }
+                GroupEnd(_) => {
+                    if let Some(group) = groups.pop() {
+                        if let Some(parent) = groups.last_mut() {
+                            parent.add_child(group);
+                        } else {
+                            node.add_child(group);
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+
+            let node = if let Some(group) = groups.last_mut() {
+                group
+            } else {
+                &mut *node
+            };
+
             match item {
                 Text(id, name, status, value, default_value, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut edit = TheTextLineEdit::new(TheId::named(id));
                     edit.set_text(value.clone());
                     edit.set_continuous(*continous);
@@ -270,7 +770,7 @@ impl TheNodeUI {
                 }
                 Icons(id, _name, status, vec) => {
                     let mut item = TheTreeIcons::new(TheId::named(id));
-                    item.set_status_text(status);
+                    item.set_status_text(&self.status_text(id, status));
                     item.set_icon_size(32);
                     item.set_icon_count(vec.len());
                     item.set_selected_index(Some(0));
@@ -296,6 +796,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 Selector(id, name, status, values, value) => {
+                    let status = &self.status_text(id, status);
                     let mut dropdown = TheDropdownMenu::new(TheId::named(id));
                     for item in values {
                         dropdown.add_option(item.clone());
@@ -311,6 +812,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 FloatEditSlider(id, name, status, value, range, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut slider = TheTextLineEdit::new(TheId::named(id));
                     slider.set_value(TheValue::Float(*value));
                     slider.set_range(TheValue::RangeF32(range.clone()));
@@ -325,6 +827,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 FloatSlider(id, name, status, value, range, default_value, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut slider = TheSlider::new(TheId::named(id));
                     slider.set_value(TheValue::Float(*value));
                     slider.set_default_value(TheValue::Float(*default_value));
@@ -340,6 +843,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 IntEditSlider(id, name, status, value, range, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut slider = TheTextLineEdit::new(TheId::named(id));
                     slider.set_value(TheValue::Int(*value));
                     slider.set_range(TheValue::RangeI32(range.clone()));
@@ -354,6 +858,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 PaletteSlider(id, name, status, value, palette, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut slider = TheTextLineEdit::new(TheId::named(id));
                     slider.set_value(TheValue::Int(*value));
                     slider.set_range(TheValue::RangeI32(0..=255));
@@ -369,6 +874,7 @@ impl TheNodeUI {
                     node.add_widget(Box::new(item));
                 }
                 IntSlider(id, name, status, value, range, default_value, continous) => {
+                    let status = &self.status_text(id, status);
                     let mut slider = TheSlider::new(TheId::named(id));
                     slider.set_value(TheValue::Int(*value));
                     slider.set_default_value(TheValue::Int(*default_value));
@@ -397,7 +903,22 @@ impl TheNodeUI {
                 //     picker.limiter_mut().set_max_size(Vec2::new(200, 200));
                 //     layout.add_pair(name.clone(), Box::new(picker));
                 // }
+                Gradient(id, name, status, value, continuous) => {
+                    let status = &self.status_text(id, status);
+                    let mut editor = TheGradientEditor::new(TheId::named(id));
+                    editor.set_gradient(value.clone());
+                    editor.set_continuous(*continuous);
+                    editor.set_status_text(status);
+
+                    let mut item = TheTreeItem::new(TheId::named("Gradient"));
+                    item.set_text(name.clone());
+                    item.add_widget_column(200, Box::new(editor));
+                    item.set_status_text(status);
+
+                    node.add_widget(Box::new(item));
+                }
                 Checkbox(id, name, status, value) => {
+                    let status = &self.status_text(id, status);
                     let mut cb = TheCheckButton::new(TheId::named(id));
                     cb.set_value(TheValue::Bool(*value));
                     cb.set_status_text(status);
@@ -413,21 +934,107 @@ impl TheNodeUI {
                 //     let sep = TheSeparator::new(TheId::named_with_id("Separator", Uuid::new_v4()));
                 //     layout.add_pair(name.clone(), Box::new(sep));
                 // }
+                Vec2Edit(id, _, status, ..)
+                | Vec3Edit(id, _, status, ..)
+                | Vec4Edit(id, _, status, ..)
+                | FloatRange(id, _, status, ..)
+                | IntRange(id, _, status, ..) => {
+                    let status = self.status_text(id, status);
+                    let (kind, edits) = self.compound_edits(item);
+                    for (label, widget) in edits {
+                        let mut item = TheTreeItem::new(TheId::named(kind));
+                        item.set_text(label);
+                        item.add_widget_column(200, widget);
+                        item.set_status_text(&status);
+                        node.add_widget(Box::new(item));
+                    }
+                }
+                FilePath(id, name, status, value, _, _) => {
+                    let status = &self.status_text(id, status);
+                    let mut edit = TheTextLineEdit::new(TheId::named(id));
+                    edit.set_text(value.clone());
+                    edit.set_status_text(status);
+
+                    let mut item = TheTreeItem::new(TheId::named("FilePath"));
+                    item.set_text(name.clone());
+                    item.add_widget_column(200, Box::new(edit));
+                    item.set_status_text(status);
+                    node.add_widget(Box::new(item));
+
+                    let mut item = TheTreeItem::new(TheId::named("FilePath"));
+                    item.add_widget_column(200, Self::browse_button(id));
+                    item.set_status_text(status);
+                    node.add_widget(Box::new(item));
+                }
+                TextArea(id, name, status, value, continuous) => {
+                    let status = &self.status_text(id, status);
+                    let mut edit = TheTextAreaEdit::new(TheId::named(id));
+                    edit.set_text(value.clone());
+                    edit.set_continuous(*continuous);
+                    edit.set_status_text(status);
+                    edit.limiter_mut().set_max_height(80);
+
+                    let mut item = TheTreeItem::new(TheId::named("TextArea"));
+                    item.set_text(name.clone());
+                    item.set_size(84);
+                    item.add_widget_column(200, Box::new(edit));
+                    item.set_status_text(status);
+
+                    node.add_widget(Box::new(item));
+                }
+                Curve(id, name, status, points, continuous) => {
+                    let status = &self.status_text(id, status);
+                    let mut editor = TheCurveEditor::new(TheId::named(id));
+                    editor.set_points(points.clone());
+                    editor.set_continuous(*continuous);
+                    editor.set_status_text(status);
+
+                    let mut item = TheTreeItem::new(TheId::named("Curve"));
+                    item.set_text(name.clone());
+                    item.set_size(124);
+                    item.add_widget_column(200, Box::new(editor));
+                    item.set_status_text(status);
+
+                    node.add_widget(Box::new(item));
+                }
+                TilePicker(id, _name, status, tiles, value) => {
+                    let mut item = TheTreeIcons::new(TheId::named(id));
+                    item.set_status_text(&self.status_text(id, status));
+                    item.set_icon_size(32);
+                    item.set_icon_count(tiles.len());
+                    item.set_selected_index(tiles.iter().position(|tile| Some(tile.2) == *value));
+
+                    for (index, tile) in tiles.iter().enumerate() {
+                        item.set_text(index, tile.1.clone());
+                        item.set_icon(index, tile.0.clone());
+                    }
+
+                    node.add_widget(Box::new(item));
+                }
                 _ => {}
             }
         }
+
+        // Groups without a GroupEnd
+        while let Some(group) = groups.pop() {
+            if let Some(parent) = groups.last_mut() {
+                parent.add_child(group);
+            } else {
+                node.add_child(group);
+            }
+        }
     }
 
     /// Add the items to the given text layout.
     pub fn apply_to_text_layout(&self, layout: &mut dyn TheTextLayoutTrait) {
         layout.clear();
-        for (_, item) in &self.items {
+        for item in self.visible_items(true) {
             match item {
                 Text(id, name, status, value, default_value, continous) => {
                     let mut edit = TheTextLineEdit::new(TheId::named(id));
                     edit.set_text(value.clone());
                     edit.set_continuous(*continous);
-                    edit.set_status_text(status);
+                    edit.set_status_text(&self.status_text(id, status));
                     edit.set_info_text(default_value.clone());
                     layout.add_pair(name.clone(), Box::new(edit));
                 }
@@ -444,7 +1051,7 @@ impl TheNodeUI {
                         dropdown.add_option(item.clone());
                     }
                     dropdown.set_selected_index(*value);
-                    dropdown.set_status_text(status);
+                    dropdown.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(dropdown));
                 }
                 FloatEditSlider(id, name, status, value, range, continous) => {
@@ -452,7 +1059,7 @@ impl TheNodeUI {
                     slider.set_value(TheValue::Float(*value));
                     slider.set_range(TheValue::RangeF32(range.clone()));
                     slider.set_continuous(*continous);
-                    slider.set_status_text(status);
+                    slider.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(slider));
                 }
                 FloatSlider(id, name, status, value, range, default_value, continous) => {
@@ -461,7 +1068,7 @@ impl TheNodeUI {
                     slider.set_default_value(TheValue::Float(*default_value));
                     slider.set_range(TheValue::RangeF32(range.clone()));
                     slider.set_continuous(*continous);
-                    slider.set_status_text(status);
+                    slider.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(slider));
                 }
                 IntEditSlider(id, name, status, value, range, continous) => {
@@ -469,7 +1076,7 @@ impl TheNodeUI {
                     slider.set_value(TheValue::Int(*value));
                     slider.set_range(TheValue::RangeI32(range.clone()));
                     slider.set_continuous(*continous);
-                    slider.set_status_text(status);
+                    slider.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(slider));
                 }
                 PaletteSlider(id, name, status, value, palette, continous) => {
@@ -477,7 +1084,7 @@ impl TheNodeUI {
                     slider.set_value(TheValue::Int(*value));
                     slider.set_range(TheValue::RangeI32(0..=255));
                     slider.set_continuous(*continous);
-                    slider.set_status_text(status);
+                    slider.set_status_text(&self.status_text(id, status));
                     slider.set_palette(palette.clone());
                     layout.add_pair(name.clone(), Box::new(slider));
                 }
@@ -487,96 +1094,254 @@ impl TheNodeUI {
                     slider.set_default_value(TheValue::Int(*default_value));
                     slider.set_range(TheValue::RangeI32(range.clone()));
                     slider.set_continuous(*continous);
-                    slider.set_status_text(status);
+                    slider.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(slider));
                 }
                 Button(id, name, status, layout_text) => {
                     let mut button = TheTraybarButton::new(TheId::named(id));
                     button.set_text(name.clone());
-                    button.set_status_text(status);
+                    button.set_status_text(&self.status_text(id, status));
                     layout.add_pair(layout_text.clone(), Box::new(button));
                 }
                 ColorPicker(id, name, status, value, continuous) => {
                     let mut picker = TheColorPicker::new(TheId::named(id));
                     picker.set_value(TheValue::ColorObject(value.clone()));
-                    picker.set_status_text(status);
+                    picker.set_status_text(&self.status_text(id, status));
                     picker.set_continuous(*continuous);
                     picker.limiter_mut().set_max_size(Vec2::new(200, 200));
                     layout.add_pair(name.clone(), Box::new(picker));
                 }
+                Gradient(id, name, status, value, continuous) => {
+                    let mut editor = TheGradientEditor::new(TheId::named(id));
+                    editor.set_gradient(value.clone());
+                    editor.set_continuous(*continuous);
+                    editor.set_status_text(&self.status_text(id, status));
+                    layout.add_pair(name.clone(), Box::new(editor));
+                }
                 Checkbox(id, name, status, value) => {
                     let mut cb = TheCheckButton::new(TheId::named(id));
                     cb.set_value(TheValue::Bool(*value));
-                    cb.set_status_text(status);
+                    cb.set_status_text(&self.status_text(id, status));
                     layout.add_pair(name.clone(), Box::new(cb));
                 }
                 Separator(name) => {
                     let sep = TheSeparator::new(TheId::named_with_id("Separator", Uuid::new_v4()));
                     layout.add_pair(name.clone(), Box::new(sep));
                 }
+                Vec2Edit(..) | Vec3Edit(..) | Vec4Edit(..) | FloatRange(..) | IntRange(..) => {
+                    for (label, widget) in self.compound_edits(item).1 {
+                        layout.add_pair(label, widget);
+                    }
+                }
+                FilePath(id, name, status, value, _, _) => {
+                    let mut edit = TheTextLineEdit::new(TheId::named(id));
+                    edit.set_text(value.clone());
+                    edit.set_status_text(&self.status_text(id, status));
+                    layout.add_pair(name.clone(), Box::new(edit));
+                    layout.add_pair("".into(), Self::browse_button(id));
+                }
+                TextArea(id, name, status, value, continuous) => {
+                    let mut edit = TheTextAreaEdit::new(TheId::named(id));
+                    edit.set_text(value.clone());
+                    edit.set_continuous(*continuous);
+                    edit.set_status_text(&self.status_text(id, status));
+                    edit.limiter_mut().set_max_height(120);
+                    layout.add_pair(name.clone(), Box::new(edit));
+                }
+                Curve(id, name, status, points, continuous) => {
+                    let mut editor = TheCurveEditor::new(TheId::named(id));
+                    editor.set_points(points.clone());
+                    editor.set_continuous(*continuous);
+                    editor.set_status_text(&self.status_text(id, status));
+                    layout.add_pair(name.clone(), Box::new(editor));
+                }
+                TilePicker(id, name, status, tiles, value) => {
+                    let mut dropdown = TheDropdownMenu::new(TheId::named(id));
+                    for tile in tiles {
+                        dropdown.add_option(tile.1.clone());
+                    }
+                    if let Some(index) = tiles.iter().position(|tile| Some(tile.2) == *value) {
+                        dropdown.set_selected_index(index as i32);
+                    }
+                    dropdown.set_status_text(&self.status_text(id, status));
+                    layout.add_pair(name.clone(), Box::new(dropdown));
+                }
+                Group(id, name, collapsed) => {
+                    let mut button = TheTraybarButton::new(TheId::named(id));
+                    button.set_text(if *collapsed { "Show" } else { "Hide" }.into());
+                    button.set_status_text(&self.status_text(id, "Show or hide the group."));
+                    layout.add_pair(name.clone(), Box::new(button));
+                }
                 _ => {}
             }
         }
     }
 
-    /// Handle an event and update the item values if necessary
+    /// The kind name and the labelled editors of the vector and range items, one editor per
+    /// component. Empty for other items.
+    fn compound_edits(&self, item: &TheNodeUIItem) -> (&'static str, LabelledEdits) {
+        match item {
+            Vec2Edit(id, name, status, value, continuous) => (
+                "Vec2Edit",
+                self.component_edits(id, name, status, value.as_slice(), *continuous),
+            ),
+            Vec3Edit(id, name, status, value, continuous) => (
+                "Vec3Edit",
+                self.component_edits(id, name, status, value.as_slice(), *continuous),
+            ),
+            Vec4Edit(id, name, status, value, continuous) => (
+                "Vec4Edit",
+                self.component_edits(id, name, status, value.as_slice(), *continuous),
+            ),
+            FloatRange(id, name, status, value, range, continuous) => (
+                "FloatRange",
+                self.range_edits(
+                    id,
+                    name,
+                    status,
+                    [TheValue::Float(value.0), TheValue::Float(value.1)],
+                    TheValue::RangeF32(range.clone()),
+                    *continuous,
+                ),
+            ),
+            IntRange(id, name, status, value, range, continuous) => (
+                "IntRange",
+                self.range_edits(
+                    id,
+                    name,
+                    status,
+                    [TheValue::Int(value.0), TheValue::Int(value.1)],
+                    TheValue::RangeI32(range.clone()),
+                    *continuous,
+                ),
+            ),
+            _ => ("", vec![]),
+        }
+    }
+
+    /// The editors of the vector components, with ids "id.x", "id.y", ...
+    fn component_edits(
+        &self,
+        id: &str,
+        name: &str,
+        status: &str,
+        value: &[f32],
+        continuous: bool,
+    ) -> LabelledEdits {
+        value
+            .iter()
+            .zip(COMPONENTS)
+            .map(|(v, component)| {
+                let mut edit = TheTextLineEdit::new(TheId::named(&format!("{id}.{component}")));
+                edit.set_value(TheValue::Float(*v));
+                edit.set_continuous(continuous);
+                edit.set_status_text(&self.status_text(id, status));
+                (
+                    format!("{} {}", name, component.to_uppercase()),
+                    Box::new(edit) as Box<dyn TheWidget>,
+                )
+            })
+            .collect()
+    }
+
+    /// The min and max editors of a range, with ids "id.min" and "id.max".
+    fn range_edits(
+        &self,
+        id: &str,
+        name: &str,
+        status: &str,
+        value: [TheValue; 2],
+        range: TheValue,
+        continuous: bool,
+    ) -> LabelledEdits {
+        value
+            .into_iter()
+            .zip(["min", "max"])
+            .map(|(v, component)| {
+                let mut edit = TheTextLineEdit::new(TheId::named(&format!("{id}.{component}")));
+                edit.set_value(v);
+                edit.set_range(range.clone());
+                edit.set_continuous(continuous);
+                edit.set_status_text(&self.status_text(id, status));
+                let label = if component == "min" { "Min" } else { "Max" };
+                (
+                    format!("{} {}", name, label),
+                    Box::new(edit) as Box<dyn TheWidget>,
+                )
+            })
+            .collect()
+    }
+
+    /// The browse button of a FilePath item, with the id "id.browse".
+    fn browse_button(id: &str) -> Box<dyn TheWidget> {
+        let mut button = TheTraybarButton::new(TheId::named(&format!("{id}.browse")));
+        button.set_text("Browse...".into());
+        button.set_status_text("Choose a file.");
+        Box::new(button)
+    }
+
+    /// Opens the file requester of a FilePath item when its browse button is clicked. The
+    /// chosen path is applied by handle_event().
+    pub fn handle_browse(&self, event: &TheEvent, ctx: &mut TheContext) -> bool {
+        if let TheEvent::StateChanged(id, TheWidgetState::Clicked) = event {
+            if let Some((item_id, Some("browse"))) = self.item_for_widget(&id.name) {
+                if let Some(FilePath(_, name, _, _, extension_name, extensions)) =
+                    self.items.get(&item_id)
+                {
+                    ctx.ui.open_file_requester(
+                        id.clone(),
+                        name.clone(),
+                        TheFileExtension::new(extension_name.clone(), extensions.clone()),
+                    );
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Handle an event and update the item values if necessary. Returns true if a value was
+    /// updated or rejected by its validator, or a group of the text layout was collapsed or
+    /// expanded, in which case the items should be applied to the layout again. This reverts
+    /// the widget of a rejected value and shows the error in its status text.
     pub fn handle_event(&mut self, event: &TheEvent) -> bool {
-        let mut updated = false;
         match event {
             TheEvent::ValueChanged(id, event_value) => {
-                if let Some(item) = self.get_item_mut(&id.name) {
-                    match item {
-                        Text(_, _, _, value, _, _) => {
-                            if let TheValue::Text(v) = event_value {
-                                *value = v.clone();
-                                updated = true;
-                            }
-                        }
-                        Selector(_, _, _, _, value) => {
-                            if let TheValue::Int(v) = event_value {
-                                *value = *v;
-                                updated = true;
-                            }
-                        }
-                        FloatEditSlider(_, _, _, value, _, _) => {
-                            if let Some(v) = event_value.to_f32() {
-                                *value = v;
-                                updated = true;
-                            }
-                        }
-                        FloatSlider(_, _, _, value, _, _, _) => {
-                            if let TheValue::Float(v) = event_value {
-                                *value = *v;
-                                updated = true;
-                            }
-                        }
-                        IntEditSlider(_, _, _, value, _, _) => {
-                            if let TheValue::Int(v) = event_value {
-                                *value = *v;
-                                updated = true;
-                            } else if let TheValue::IntRange(v, _) = event_value {
-                                *value = *v;
-                                updated = true;
-                            }
-                        }
-                        IntSlider(_, _, _, value, _, _, _) => {
-                            if let TheValue::Int(v) = event_value {
-                                *value = *v;
-                                updated = true;
-                            }
-                        }
-                        Checkbox(_, _, _, value) => {
-                            if let TheValue::Bool(v) = event_value {
-                                *value = *v;
-                                updated = true;
-                            }
-                        }
-                        _ => {}
+                if let Some((item_id, component)) = self.item_for_widget(&id.name) {
+                    if let Some(value) = self.event_value(&item_id, component, event_value) {
+                        return self.update_value(&item_id, value);
+                    }
+                }
+            }
+            TheEvent::IndexChanged(id, index) => {
+                if let Some(TilePicker(_, _, _, tiles, _)) = self.items.get(&id.name) {
+                    if let Some(tile) = tiles.get(*index) {
+                        let value = TheValue::Id(tile.2);
+                        return self.update_value(&id.name.clone(), value);
                     }
                 }
             }
+            TheEvent::FileRequesterResult(id, paths) => {
+                if let Some((item_id, Some("browse"))) = self.item_for_widget(&id.name) {
+                    if let Some(path) = paths.first() {
+                        let value = TheValue::Text(path.to_string_lossy().to_string());
+                        return self.update_value(&item_id, value);
+                    }
+                }
+            }
+            TheEvent::StateChanged(id, TheWidgetState::Clicked) => {
+                if let Some(Group(_, _, collapsed)) = self.items.get_mut(&id.name) {
+                    *collapsed = !*collapsed;
+                    return true;
+                }
+            }
+            TheEvent::SnapperStateChanged(id, _, open) => {
+                if let Some(Group(_, _, collapsed)) = self.items.get_mut(&id.name) {
+                    *collapsed = !*open;
+                }
+            }
             _ => {}
         }
-        updated
+        false
     }
 }
@@ -50,6 +50,65 @@ pub trait TheStyle: Send {
     ) {
     }
 
+    /// Draws a soft drop shadow for the given rectangle into the frame. The shadow is offset
+    /// downwards and blurred with the given sigma, the rectangle itself should be drawn afterwards.
+    fn draw_drop_shadow(
+        &mut self,
+        pixels: &mut [u8],
+        rect: &(usize, usize, usize, usize),
+        stride: usize,
+        sigma: f32,
+        ctx: &mut TheContext,
+    ) {
+        let color = *self.theme().color(DropShadow);
+        if stride == 0 || rect.2 == 0 || rect.3 == 0 || color[3] == 0 {
+            return;
+        }
+        let frame_height = (pixels.len() / (stride * 4)) as i32;
+
+        // The blur of a rectangle is separable, so the shadow is the product of two blurred 1D profiles
+        let kernel = crate::thefilter::gaussian_kernel(sigma);
+        let radius = (kernel.len() / 2) as i32;
+        let profile = |length: usize| -> Vec<f32> {
+            (0..length as i32 + radius * 2)
+                .map(|i| {
+                    kernel
+                        .iter()
+                        .enumerate()
+                        .filter(|(k, _)| {
+                            let p = i + *k as i32 - radius * 2;
+                            p >= 0 && p < length as i32
+                        })
+                        .map(|(_, w)| *w)
+                        .sum()
+                })
+                .collect()
+        };
+        let profile_x = profile(rect.2);
+        let profile_y = profile(rect.3);
+
+        let offset = (sigma / 2.0).ceil() as i32;
+        let x0 = rect.0 as i32 - radius;
+        let y0 = rect.1 as i32 - radius + offset;
+
+        for (py, wy) in profile_y.iter().enumerate() {
+            let y = y0 + py as i32;
+            if y < 0 || y >= frame_height {
+                continue;
+            }
+            for (px, wx) in profile_x.iter().enumerate() {
+                let x = x0 + px as i32;
+                if x < 0 || x >= stride as i32 {
+                    continue;
+                }
+                let alpha = wx * wy * color[3] as f32 / 255.0;
+                let i = (y as usize * stride + x as usize) * 4;
+                let background = [pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3]];
+                pixels[i..i + 4].copy_from_slice(&ctx.draw.mix_color(&background, &color, alpha));
+            }
+        }
+    }
+
     /// Creates a preview image for the drop.
     fn create_drop_image(&mut self, drop: &mut TheDrop, ctx: &mut TheContext) {
         let mut width: i32 = 120;
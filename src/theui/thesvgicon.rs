@@ -0,0 +1,561 @@
+use crate::prelude::*;
+use zeno::{Angle, Cap, Fill, Join, Mask, Stroke, Transform};
+
+/// The paint of an SVG fill or stroke.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TheSVGPaint {
+    /// Replaced by the tint color at rasterization time.
+    CurrentColor,
+    Color(RGBA),
+}
+
+/// A single drawable shape of an SVG icon, converted to SVG path data.
+#[derive(Clone, Debug)]
+struct TheSVGShape {
+    path: String,
+    fill: Option<TheSVGPaint>,
+    even_odd: bool,
+    stroke: Option<TheSVGPaint>,
+    stroke_width: f32,
+    cap: Cap,
+    join: Join,
+    opacity: f32,
+    transform: Transform,
+}
+
+/// The inheritable presentation attributes of an element.
+#[derive(Clone, Debug)]
+struct TheSVGStyle {
+    fill: Option<TheSVGPaint>,
+    even_odd: bool,
+    stroke: Option<TheSVGPaint>,
+    stroke_width: f32,
+    cap: Cap,
+    join: Join,
+    opacity: f32,
+    transform: Transform,
+}
+
+impl Default for TheSVGStyle {
+    fn default() -> Self {
+        Self {
+            // Shapes without an explicit fill follow the tint, so monochrome icon sets can be themed.
+            fill: Some(TheSVGPaint::CurrentColor),
+            even_odd: false,
+            stroke: None,
+            stroke_width: 1.0,
+            cap: Cap::Butt,
+            join: Join::Miter,
+            opacity: 1.0,
+            transform: Transform::IDENTITY,
+        }
+    }
+}
+
+/// TheSVGIcon holds a parsed SVG document which can be rasterized into TheRGBABuffer at any scale.
+///
+/// Only the subset of SVG commonly used by icon sets is supported: `path`, `rect`, `circle`, `ellipse`,
+/// `line`, `polyline` and `polygon` elements, nested `g` groups, `transform` attributes and the
+/// fill, stroke and opacity presentation attributes (also inside `style`).
+#[derive(Clone, Debug)]
+pub struct TheSVGIcon {
+    width: f32,
+    height: f32,
+    view_box: (f32, f32, f32, f32),
+    shapes: Vec<TheSVGShape>,
+}
+
+impl TheSVGIcon {
+    /// Parses an SVG icon from the given bytes.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        std::str::from_utf8(data).ok().and_then(Self::parse)
+    }
+
+    /// Parses an SVG icon from the given source text.
+    pub fn parse(source: &str) -> Option<Self> {
+        let mut width = None;
+        let mut height = None;
+        let mut view_box = None;
+        let mut shapes = vec![];
+        let mut found_root = false;
+
+        let mut stack: Vec<TheSVGStyle> = vec![TheSVGStyle::default()];
+
+        let mut rest = source;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+
+            // Skip comments, declarations and processing instructions
+            if rest.starts_with("!--") {
+                rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+                continue;
+            }
+            if rest.starts_with('!') || rest.starts_with('?') {
+                rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+                continue;
+            }
+
+            let end = rest.find('>')?;
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim();
+                if (name == "g" || name == "svg" || name == "a") && stack.len() > 1 {
+                    stack.pop();
+                }
+                continue;
+            }
+
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(tag.len());
+            let name = &tag[..name_end];
+            let attributes = parse_attributes(&tag[name_end..]);
+
+            let parent = stack.last().cloned().unwrap_or_default();
+            let style = apply_style(&parent, &attributes);
+
+            match name {
+                "svg" | "g" | "a" => {
+                    if name == "svg" && !found_root {
+                        found_root = true;
+                        width = attribute(&attributes, "width").and_then(parse_length);
+                        height = attribute(&attributes, "height").and_then(parse_length);
+                        if let Some(vb) = attribute(&attributes, "viewBox") {
+                            let v = parse_numbers(vb);
+                            if v.len() == 4 && v[2] > 0.0 && v[3] > 0.0 {
+                                view_box = Some((v[0], v[1], v[2], v[3]));
+                            }
+                        }
+                    }
+                    if !self_closing {
+                        stack.push(style);
+                    }
+                }
+                "defs" | "clipPath" | "mask" | "symbol" => {
+                    // Skip the content of non rendering containers
+                    if !self_closing {
+                        let close = format!("</{}", name);
+                        rest = rest.find(&close).map(|end| &rest[end..]).unwrap_or("");
+                        if let Some(end) = rest.find('>') {
+                            rest = &rest[end + 1..];
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(path) = shape_to_path(name, &attributes) {
+                        shapes.push(TheSVGShape {
+                            path,
+                            fill: style.fill,
+                            even_odd: style.even_odd,
+                            stroke: style.stroke,
+                            stroke_width: style.stroke_width,
+                            cap: style.cap,
+                            join: style.join,
+                            opacity: style.opacity,
+                            transform: style.transform,
+                        });
+                    }
+                }
+            }
+        }
+
+        if !found_root {
+            return None;
+        }
+
+        let view_box =
+            view_box.unwrap_or((0.0, 0.0, width.unwrap_or(16.0), height.unwrap_or(16.0)));
+
+        Some(Self {
+            width: width.unwrap_or(view_box.2),
+            height: height.unwrap_or(view_box.3),
+            view_box,
+            shapes,
+        })
+    }
+
+    /// Returns the logical size of the icon, i.e. its size at a scale factor of 1.0.
+    pub fn size(&self) -> Vec2<f32> {
+        Vec2::new(self.width, self.height)
+    }
+
+    /// Rasterizes the icon at the given scale factor. `currentColor` paints are replaced by the tint.
+    pub fn rasterize(&self, scale: f32, tint: &RGBA) -> TheRGBABuffer {
+        let width = (self.width * scale).round().max(1.0) as i32;
+        let height = (self.height * scale).round().max(1.0) as i32;
+        self.rasterize_to_size(width, height, tint)
+    }
+
+    /// Rasterizes the icon into a buffer of the given pixel size, stretching the view box to fit.
+    pub fn rasterize_to_size(&self, width: i32, height: i32, tint: &RGBA) -> TheRGBABuffer {
+        let mut buffer = TheRGBABuffer::new(TheDim::new(0, 0, width, height));
+        if !buffer.is_valid() {
+            return buffer;
+        }
+
+        let (vx, vy, vw, vh) = self.view_box;
+        let view =
+            Transform::translation(-vx, -vy).then_scale(width as f32 / vw, height as f32 / vh);
+
+        let mut mask = vec![0_u8; width as usize * height as usize];
+
+        for shape in &self.shapes {
+            let transform = shape.transform.then(&view);
+
+            if let Some(fill) = shape.fill {
+                mask.fill(0);
+                Mask::new(shape.path.as_str())
+                    .size(width as u32, height as u32)
+                    .transform(Some(transform))
+                    .style(if shape.even_odd {
+                        Fill::EvenOdd
+                    } else {
+                        Fill::NonZero
+                    })
+                    .render_into(&mut mask, None);
+                Self::composite(&mut buffer, &mask, &resolve(fill, tint), shape.opacity);
+            }
+
+            if let Some(stroke) = shape.stroke {
+                if shape.stroke_width > 0.0 {
+                    mask.fill(0);
+                    Mask::new(shape.path.as_str())
+                        .size(width as u32, height as u32)
+                        .transform(Some(transform))
+                        .style(
+                            Stroke::new(shape.stroke_width)
+                                .cap(shape.cap)
+                                .join(shape.join),
+                        )
+                        .render_into(&mut mask, None);
+                    Self::composite(&mut buffer, &mask, &resolve(stroke, tint), shape.opacity);
+                }
+            }
+        }
+
+        buffer
+    }
+
+    /// Composites the color through the coverage mask onto the buffer (source over, straight alpha).
+    fn composite(buffer: &mut TheRGBABuffer, mask: &[u8], color: &RGBA, opacity: f32) {
+        let alpha = color[3] as f32 / 255.0 * opacity;
+        for (pixel, coverage) in buffer.pixels_mut().chunks_exact_mut(4).zip(mask) {
+            if *coverage == 0 {
+                continue;
+            }
+            let sa = *coverage as f32 / 255.0 * alpha;
+            let da = pixel[3] as f32 / 255.0;
+            let out_a = sa + da * (1.0 - sa);
+            if out_a <= 0.0 {
+                continue;
+            }
+            for i in 0..3 {
+                let c = (color[i] as f32 * sa + pixel[i] as f32 * da * (1.0 - sa)) / out_a;
+                pixel[i] = c.round().clamp(0.0, 255.0) as u8;
+            }
+            pixel[3] = (out_a * 255.0).round() as u8;
+        }
+    }
+}
+
+/// Resolves a paint against the tint color.
+fn resolve(paint: TheSVGPaint, tint: &RGBA) -> RGBA {
+    match paint {
+        TheSVGPaint::CurrentColor => *tint,
+        TheSVGPaint::Color(color) => color,
+    }
+}
+
+/// Splits the attribute section of a tag into name / value pairs.
+fn parse_attributes(source: &str) -> Vec<(&str, &str)> {
+    let mut attributes = vec![];
+    let mut rest = source;
+
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let after = rest[eq + 1..].trim_start();
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let after = &after[1..];
+        let Some(end) = after.find(quote) else {
+            break;
+        };
+        attributes.push((name, &after[..end]));
+        rest = &after[end + 1..];
+    }
+
+    attributes
+}
+
+/// Returns the value of the given attribute.
+fn attribute<'a>(attributes: &[(&'a str, &'a str)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, value)| *value)
+}
+
+/// Applies the presentation attributes (and the `style` attribute) of an element to the inherited style.
+fn apply_style(parent: &TheSVGStyle, attributes: &[(&str, &str)]) -> TheSVGStyle {
+    let mut style = parent.clone();
+    // Opacity and transforms are not inherited but accumulate down the tree.
+    let mut local_transform = Transform::IDENTITY;
+
+    let mut properties: Vec<(&str, &str)> = attributes.to_vec();
+    if let Some(inline) = attribute(attributes, "style") {
+        for declaration in inline.split(';') {
+            if let Some((name, value)) = declaration.split_once(':') {
+                properties.push((name.trim(), value.trim()));
+            }
+        }
+    }
+
+    for (name, value) in properties {
+        let value = value.trim();
+        match name {
+            "fill" => style.fill = parse_paint(value, parent.fill),
+            "fill-rule" => style.even_odd = value == "evenodd",
+            "stroke" => style.stroke = parse_paint(value, parent.stroke),
+            "stroke-width" => {
+                if let Some(width) = parse_length(value) {
+                    style.stroke_width = width;
+                }
+            }
+            "stroke-linecap" => {
+                style.cap = match value {
+                    "round" => Cap::Round,
+                    "square" => Cap::Square,
+                    _ => Cap::Butt,
+                }
+            }
+            "stroke-linejoin" => {
+                style.join = match value {
+                    "round" => Join::Round,
+                    "bevel" => Join::Bevel,
+                    _ => Join::Miter,
+                }
+            }
+            "opacity" => {
+                if let Ok(opacity) = value.parse::<f32>() {
+                    style.opacity = parent.opacity * opacity.clamp(0.0, 1.0);
+                }
+            }
+            "transform" => local_transform = parse_transform(value),
+            _ => {}
+        }
+    }
+
+    style.transform = local_transform.then(&parent.transform);
+    style
+}
+
+/// Parses a fill or stroke paint. Returns None for `none`.
+fn parse_paint(value: &str, inherited: Option<TheSVGPaint>) -> Option<TheSVGPaint> {
+    match value {
+        "none" | "transparent" => None,
+        "currentColor" => Some(TheSVGPaint::CurrentColor),
+        "inherit" => inherited,
+        _ => parse_color(value).map(TheSVGPaint::Color).or(inherited),
+    }
+}
+
+/// Parses hex, `rgb()` and a few named colors.
+fn parse_color(value: &str) -> Option<RGBA> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let hex = if hex.len() == 3 {
+            hex.chars().flat_map(|c| [c, c]).collect::<String>()
+        } else {
+            hex.to_string()
+        };
+        if hex.len() == 6 || hex.len() == 8 {
+            return Some(TheColor::from_hex(&format!("#{}", hex)).to_u8_array());
+        }
+        return None;
+    }
+
+    if let Some(inner) = value
+        .strip_prefix("rgb(")
+        .or_else(|| value.strip_prefix("rgba("))
+    {
+        let v = parse_numbers(inner.trim_end_matches(')'));
+        if v.len() >= 3 {
+            let a = v.get(3).map(|a| (a * 255.0) as u8).unwrap_or(255);
+            return Some([v[0] as u8, v[1] as u8, v[2] as u8, a]);
+        }
+        return None;
+    }
+
+    match value.to_ascii_lowercase().as_str() {
+        "black" => Some(BLACK),
+        "white" => Some(WHITE),
+        "red" => Some([255, 0, 0, 255]),
+        "green" => Some([0, 128, 0, 255]),
+        "blue" => Some([0, 0, 255, 255]),
+        "gray" | "grey" => Some([128, 128, 128, 255]),
+        _ => None,
+    }
+}
+
+/// Parses a length, ignoring `px` units. Percentages are not supported.
+fn parse_length(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches("px").parse::<f32>().ok()
+}
+
+/// Parses a list of numbers separated by whitespace and / or commas.
+fn parse_numbers(value: &str) -> Vec<f32> {
+    let mut numbers = vec![];
+    let mut current = String::new();
+
+    let flush = |current: &mut String, numbers: &mut Vec<f32>| {
+        if let Ok(v) = current.parse::<f32>() {
+            numbers.push(v);
+        }
+        current.clear();
+    };
+
+    for c in value.chars() {
+        match c {
+            '-' | '+' if !current.is_empty() && !current.ends_with(['e', 'E']) => {
+                flush(&mut current, &mut numbers);
+                current.push(c);
+            }
+            '.' if current.contains('.') && !current.contains(['e', 'E']) => {
+                flush(&mut current, &mut numbers);
+                current.push(c);
+            }
+            '0'..='9' | '.' | '-' | '+' | 'e' | 'E' => current.push(c),
+            _ => flush(&mut current, &mut numbers),
+        }
+    }
+    flush(&mut current, &mut numbers);
+
+    numbers
+}
+
+/// Parses an SVG transform list.
+fn parse_transform(value: &str) -> Transform {
+    let mut transform = Transform::IDENTITY;
+    let mut rest = value;
+
+    while let Some(open) = rest.find('(') {
+        let name = rest[..open].trim().trim_start_matches(',').trim();
+        let Some(close) = rest.find(')') else {
+            break;
+        };
+        let v = parse_numbers(&rest[open + 1..close]);
+        rest = &rest[close + 1..];
+
+        let t = match (name, v.len()) {
+            ("matrix", 6) => Transform::new(v[0], v[1], v[2], v[3], v[4], v[5]),
+            ("translate", 1) => Transform::translation(v[0], 0.0),
+            ("translate", 2) => Transform::translation(v[0], v[1]),
+            ("scale", 1) => Transform::scale(v[0], v[0]),
+            ("scale", 2) => Transform::scale(v[0], v[1]),
+            ("rotate", 1) => Transform::rotation(Angle::from_degrees(v[0])),
+            ("rotate", 3) => Transform::rotation_about((v[1], v[2]), Angle::from_degrees(v[0])),
+            _ => Transform::IDENTITY,
+        };
+
+        // The rightmost transform of the list is applied first.
+        transform = t.then(&transform);
+    }
+
+    transform
+}
+
+/// Converts a basic shape element to SVG path data.
+fn shape_to_path(name: &str, attributes: &[(&str, &str)]) -> Option<String> {
+    let num = |name: &str| {
+        attribute(attributes, name)
+            .and_then(parse_length)
+            .unwrap_or(0.0)
+    };
+
+    match name {
+        "path" => attribute(attributes, "d").map(|d| d.to_string()),
+        "rect" => {
+            let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+            if w <= 0.0 || h <= 0.0 {
+                return None;
+            }
+            let mut rx = attribute(attributes, "rx").and_then(parse_length);
+            let mut ry = attribute(attributes, "ry").and_then(parse_length);
+            if rx.is_none() {
+                rx = ry;
+            }
+            if ry.is_none() {
+                ry = rx;
+            }
+            let rx = rx.unwrap_or(0.0).min(w / 2.0);
+            let ry = ry.unwrap_or(0.0).min(h / 2.0);
+            if rx > 0.0 && ry > 0.0 {
+                Some(format!(
+                    "M {},{} H {} A {},{} 0 0 1 {},{} V {} A {},{} 0 0 1 {},{} H {} A {},{} 0 0 1 {},{} V {} A {},{} 0 0 1 {},{} Z",
+                    x + rx, y,
+                    x + w - rx,
+                    rx, ry, x + w, y + ry,
+                    y + h - ry,
+                    rx, ry, x + w - rx, y + h,
+                    x + rx,
+                    rx, ry, x, y + h - ry,
+                    y + ry,
+                    rx, ry, x + rx, y
+                ))
+            } else {
+                Some(format!("M {},{} H {} V {} H {} Z", x, y, x + w, y + h, x))
+            }
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (num("cx"), num("cy"));
+            let (rx, ry) = if name == "circle" {
+                (num("r"), num("r"))
+            } else {
+                (num("rx"), num("ry"))
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return None;
+            }
+            Some(format!(
+                "M {},{} A {},{} 0 1 0 {},{} A {},{} 0 1 0 {},{} Z",
+                cx - rx,
+                cy,
+                rx,
+                ry,
+                cx + rx,
+                cy,
+                rx,
+                ry,
+                cx - rx,
+                cy
+            ))
+        }
+        "line" => Some(format!(
+            "M {},{} L {},{}",
+            num("x1"),
+            num("y1"),
+            num("x2"),
+            num("y2")
+        )),
+        "polyline" | "polygon" => {
+            let points = parse_numbers(attribute(attributes, "points")?);
+            if points.len() < 4 {
+                return None;
+            }
+            let mut path = String::new();
+            for (index, point) in points.chunks_exact(2).enumerate() {
+                let command = if index == 0 { "M" } else { "L" };
+                path += &format!("{} {},{} ", command, point[0], point[1]);
+            }
+            if name == "polygon" {
+                path += "Z";
+            }
+            Some(path)
+        }
+        _ => None,
+    }
+}
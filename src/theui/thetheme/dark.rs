@@ -130,6 +130,8 @@ impl TheTheme for TheDarkTheme {
         colors.insert(ContextMenuTextDisabled, [100, 100, 100, 255]);
         colors.insert(ContextMenuTextHighlight, [82, 82, 82, 255]);
 
+        colors.insert(DropShadow, [0, 0, 0, 120]);
+
         colors.insert(WindowBorderOuter, [147, 147, 147, 255]);
         colors.insert(WindowBorderInner, [197, 197, 197, 255]);
         colors.insert(WindowHeaderBackground, [148, 148, 148, 255]);
@@ -173,6 +175,8 @@ impl TheTheme for TheDarkTheme {
 
         colors.insert(TreeViewNodePlusMinus, [227, 227, 227, 255]);
 
+        colors.insert(SVGIconTint, [227, 227, 227, 255]);
+
         Self {
             temp_color: BLACK,
             colors,
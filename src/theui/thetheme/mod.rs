@@ -144,6 +144,8 @@ pub enum TheThemeColors {
     ContextMenuTextHighlight,
     ContextMenuSeparator,
 
+    DropShadow,
+
     WindowBorderOuter,
     WindowBorderInner,
     WindowHeaderBackground,
@@ -182,4 +184,6 @@ pub enum TheThemeColors {
     TreeViewNodeSelected,
     TreeViewNodeText,
     TreeViewNodePlusMinus,
+
+    SVGIconTint,
 }
@@ -1,11 +1,22 @@
 pub use crate::prelude::*;
 use std::collections::BTreeMap;
 
+/// The in and out tangents of a keyframe.
+pub type TheTangents = (Vec2<f32>, Vec2<f32>);
+
 /// Represents a collection of TheValues.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct TheTimeline {
     #[serde(with = "vectorize")]
     pub events: BTreeMap<TheTime, Vec<TheCollection>>,
+    /// The interpolation towards the next keyframe, by time, collection and key. Keyframes
+    /// without an entry interpolate linearly.
+    #[serde(default, with = "vectorize")]
+    pub interpolations: BTreeMap<(TheTime, String, String), TheInterpolation>,
+    /// The in and out tangents of the bezier handles, by time, collection and key. Keyframes
+    /// without an entry use the handles of TheKeyframe::DEFAULT_TANGENTS.
+    #[serde(default, with = "vectorize")]
+    pub tangents: BTreeMap<(TheTime, String, String), TheTangents>,
 }
 
 impl Default for TheTimeline {
@@ -18,13 +29,19 @@ impl TheTimeline {
     pub fn new() -> Self {
         Self {
             events: BTreeMap::default(),
+            interpolations: BTreeMap::default(),
+            tangents: BTreeMap::default(),
         }
     }
 
     pub fn collection(coll: TheCollection) -> Self {
         let mut events: BTreeMap<TheTime, Vec<TheCollection>> = BTreeMap::default();
         events.insert(TheTime::default(), vec![coll]);
-        Self { events }
+        Self {
+            events,
+            interpolations: BTreeMap::default(),
+            tangents: BTreeMap::default(),
+        }
     }
 
     /// Returns true if the timeline is empty.
@@ -35,6 +52,8 @@ impl TheTimeline {
     /// Clears the timeline.
     pub fn clear(&mut self) {
         self.events.clear();
+        self.interpolations.clear();
+        self.tangents.clear();
     }
 
     /// Gets the value for the given key at the given time.
@@ -228,56 +247,475 @@ impl TheTimeline {
     /// Removes the event at the given time position.
     pub fn remove(&mut self, time: &TheTime) {
         self.events.remove(time);
+        self.interpolations.retain(|(t, _, _), _| t != time);
+        self.tangents.retain(|(t, _, _), _| t != time);
+    }
+
+    /// Returns the keyframe tracks as (collection, key) pairs, in order of appearance.
+    pub fn tracks(&self) -> Vec<(String, String)> {
+        let mut tracks: Vec<(String, String)> = vec![];
+        for list in self.events.values() {
+            for collection in list {
+                for key in collection.keys.keys() {
+                    if !tracks
+                        .iter()
+                        .any(|(c, k)| *c == collection.name && k == key)
+                    {
+                        tracks.push((collection.name.clone(), key.clone()));
+                    }
+                }
+            }
+        }
+        tracks
+    }
+
+    /// Returns all keyframes, sorted by time.
+    pub fn keyframes(&self) -> Vec<TheKeyframe> {
+        let mut keyframes = vec![];
+        for (time, list) in &self.events {
+            for collection in list {
+                for (key, value) in &collection.keys {
+                    let (in_tangent, out_tangent) = self.tangents(time, &collection.name, key);
+                    keyframes.push(TheKeyframe {
+                        time: *time,
+                        collection: collection.name.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                        interpolation: self.interpolation(time, &collection.name, key),
+                        in_tangent,
+                        out_tangent,
+                    });
+                }
+            }
+        }
+        keyframes
+    }
+
+    /// Returns the keyframes of the track, sorted by time.
+    pub fn track_keyframes(&self, collection: &str, key: &str) -> Vec<TheKeyframe> {
+        self.keyframes()
+            .into_iter()
+            .filter(|k| k.collection == collection && k.key == key)
+            .collect()
+    }
+
+    /// Returns the keyframe of the track at the given time.
+    pub fn keyframe(&self, time: &TheTime, collection: &str, key: &str) -> Option<TheKeyframe> {
+        let value = self
+            .events
+            .get(time)?
+            .iter()
+            .find(|c| c.name == collection)?
+            .get(key)?;
+        let (in_tangent, out_tangent) = self.tangents(time, collection, key);
+        Some(TheKeyframe {
+            time: *time,
+            collection: collection.to_string(),
+            key: key.to_string(),
+            value: value.clone(),
+            interpolation: self.interpolation(time, collection, key),
+            in_tangent,
+            out_tangent,
+        })
+    }
+
+    /// Returns the interpolation of the keyframe towards the next keyframe.
+    pub fn interpolation(&self, time: &TheTime, collection: &str, key: &str) -> TheInterpolation {
+        self.interpolations
+            .get(&(*time, collection.to_string(), key.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the in and out tangents of the bezier handles of the keyframe.
+    pub fn tangents(&self, time: &TheTime, collection: &str, key: &str) -> TheTangents {
+        self.tangents
+            .get(&(*time, collection.to_string(), key.to_string()))
+            .copied()
+            .unwrap_or(TheKeyframe::DEFAULT_TANGENTS)
+    }
+
+    /// Inserts or replaces the keyframe.
+    pub fn insert_keyframe(&mut self, keyframe: &TheKeyframe) {
+        let list = self.events.entry(keyframe.time).or_default();
+        if let Some(collection) = list.iter_mut().find(|c| c.name == keyframe.collection) {
+            collection
+                .keys
+                .insert(keyframe.key.clone(), keyframe.value.clone());
+        } else {
+            let mut collection = TheCollection::named(keyframe.collection.clone());
+            collection
+                .keys
+                .insert(keyframe.key.clone(), keyframe.value.clone());
+            list.push(collection);
+        }
+
+        let id = (
+            keyframe.time,
+            keyframe.collection.clone(),
+            keyframe.key.clone(),
+        );
+        let tangents = (keyframe.in_tangent, keyframe.out_tangent);
+        if tangents == TheKeyframe::DEFAULT_TANGENTS {
+            self.tangents.remove(&id);
+        } else {
+            self.tangents.insert(id.clone(), tangents);
+        }
+        if keyframe.interpolation == TheInterpolation::Linear {
+            self.interpolations.remove(&id);
+        } else {
+            self.interpolations.insert(id, keyframe.interpolation);
+        }
+    }
+
+    /// Removes the keyframe and returns it. Collections and events without keys are removed.
+    pub fn remove_keyframe(
+        &mut self,
+        time: &TheTime,
+        collection: &str,
+        key: &str,
+    ) -> Option<TheKeyframe> {
+        let keyframe = self.keyframe(time, collection, key)?;
+        if let Some(list) = self.events.get_mut(time) {
+            for c in list.iter_mut() {
+                if c.name == collection {
+                    c.keys.shift_remove(key);
+                }
+            }
+            list.retain(|c| !c.keys.is_empty());
+            if list.is_empty() {
+                self.events.remove(time);
+            }
+        }
+        let id = (*time, collection.to_string(), key.to_string());
+        self.interpolations.remove(&id);
+        self.tangents.remove(&id);
+        Some(keyframe)
+    }
+
+    /// Creates the edit which removes and adds the given keyframes. Existing keyframes which
+    /// would be replaced by the added ones are removed by the edit too, so that it can be undone.
+    pub fn make_edit(&self, removed: Vec<TheKeyframe>, added: Vec<TheKeyframe>) -> TheTimelineEdit {
+        let mut removed = removed;
+        for keyframe in &added {
+            if let Some(existing) =
+                self.keyframe(&keyframe.time, &keyframe.collection, &keyframe.key)
+            {
+                if !removed.iter().any(|k| k.same_key(&existing)) {
+                    removed.push(existing);
+                }
+            }
+        }
+        TheTimelineEdit { removed, added }
+    }
+
+    /// Applies the edit: Removes its removed keyframes and then inserts its added keyframes.
+    pub fn apply_edit(&mut self, edit: &TheTimelineEdit) {
+        for keyframe in &edit.removed {
+            self.remove_keyframe(&keyframe.time, &keyframe.collection, &keyframe.key);
+        }
+        for keyframe in &edit.added {
+            self.insert_keyframe(keyframe);
+        }
+    }
+
+    /// Gets the value of the track at the given time, using the interpolation of the keyframe
+    /// which starts the segment, see TheKeyframe::interpolate(). Before the first and after the last keyframe their values are
+    /// returned.
+    pub fn evaluate(&self, collection: &str, key: &str, at: &TheTime) -> Option<TheValue> {
+        let keyframes = self.track_keyframes(collection, key);
+        let index = keyframes.partition_point(|k| k.time <= *at);
+        if index == 0 {
+            return keyframes.first().map(|k| k.value.clone());
+        }
+        let start = &keyframes[index - 1];
+        let Some(end) = keyframes.get(index) else {
+            return Some(start.value.clone());
+        };
+        let span = end.time.to_total_seconds() as f32 - start.time.to_total_seconds() as f32;
+        let t = (at.to_total_seconds() as f32 - start.time.to_total_seconds() as f32) / span;
+        Some(start.interpolate(end, t))
     }
 }
 
-// TheInterpolation
+/// A value of a collection key at a time in a TheTimeline.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct TheKeyframe {
+    pub time: TheTime,
+    pub collection: String,
+    pub key: String,
+    pub value: TheValue,
+    /// The interpolation towards the next keyframe of the track.
+    #[serde(default)]
+    pub interpolation: TheInterpolation,
+    /// The bezier handle towards the previous keyframe, relative to this keyframe. Handles are
+    /// in segment space, where the segment spans 1 in time and the value change from 0 to 1.
+    #[serde(default = "TheKeyframe::default_in_tangent")]
+    pub in_tangent: Vec2<f32>,
+    /// The bezier handle towards the next keyframe, relative to this keyframe.
+    #[serde(default = "TheKeyframe::default_out_tangent")]
+    pub out_tangent: Vec2<f32>,
+}
+
+impl TheKeyframe {
+    /// The in and out tangents of the ease in out preset.
+    pub const DEFAULT_TANGENTS: TheTangents = (Vec2::new(-0.42, 0.0), Vec2::new(0.42, 0.0));
+
+    fn default_in_tangent() -> Vec2<f32> {
+        Self::DEFAULT_TANGENTS.0
+    }
+
+    fn default_out_tangent() -> Vec2<f32> {
+        Self::DEFAULT_TANGENTS.1
+    }
+
+    /// Interpolates from this keyframe to the next keyframe of the track at t (0..1) of the
+    /// segment. Bezier curves use the out tangent of this and the in tangent of the next keyframe.
+    pub fn interpolate(&self, next: &TheKeyframe, t: f32) -> TheValue {
+        if self.interpolation == TheInterpolation::Bezier {
+            let eased = TheInterpolation::bezier_ease(
+                self.out_tangent,
+                Vec2::one() + next.in_tangent,
+                t.clamp(0.0, 1.0),
+            );
+            TheInterpolation::mix(&self.value, &next.value, eased)
+        } else {
+            self.interpolation.interpolate(&self.value, &next.value, t)
+        }
+    }
+
+    /// Returns true if both keyframes are at the same time of the same track.
+    pub fn same_key(&self, other: &TheKeyframe) -> bool {
+        self.time == other.time && self.collection == other.collection && self.key == other.key
+    }
+}
+
+/// An undoable edit of a TheTimeline, see TheTimeline::make_edit() and apply_edit().
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct TheTimelineEdit {
+    pub removed: Vec<TheKeyframe>,
+    pub added: Vec<TheKeyframe>,
+}
+
+impl TheTimelineEdit {
+    /// Returns the edit which reverts this edit.
+    pub fn inverse(&self) -> Self {
+        Self {
+            removed: self.added.clone(),
+            added: self.removed.clone(),
+        }
+    }
+
+    /// Returns true if the edit does not change anything.
+    pub fn is_empty(&self) -> bool {
+        self.removed == self.added
+    }
+
+    /// Load the edit from json.
+    pub fn from_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    /// Convert the edit to json.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self).unwrap_or_default()
+    }
+}
+
+// TheInterpolation
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
 pub enum TheInterpolation {
+    #[default]
     Linear,
     Spline, // Smoothstep
     Switch,
     EaseIn,
     EaseOut,
     EaseInOut,
+    /// A cubic bezier from (0, 0) to (1, 1) over the segment, given by the out tangent of the
+    /// keyframe and the in tangent of the next keyframe, see TheKeyframe::interpolate().
+    Bezier,
 }
 
 impl TheInterpolation {
-    pub fn interpolate(&self, start: &TheValue, end: &TheValue, t: f32) -> TheValue {
-        let t = t.clamp(0.0, 1.0);
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Spline => "Spline",
+            Self::Switch => "Switch",
+            Self::EaseIn => "Ease In",
+            Self::EaseOut => "Ease Out",
+            Self::EaseInOut => "Ease In Out",
+            Self::Bezier => "Bezier",
+        }
+    }
+    pub fn iterator() -> impl Iterator<Item = TheInterpolation> {
+        [
+            Self::Linear,
+            Self::Spline,
+            Self::Switch,
+            Self::EaseIn,
+            Self::EaseOut,
+            Self::EaseInOut,
+            Self::Bezier,
+        ]
+        .into_iter()
+    }
 
-        match (start.as_f32(), end.as_f32()) {
-            (Some(s), Some(e)) => match self {
-                TheInterpolation::Linear => TheValue::Float(s + (e - s) * t),
-                TheInterpolation::Spline => {
-                    let t = t * t * (3.0 - 2.0 * t); // Smoothstep
-                    TheValue::Float(s + (e - s) * t)
-                }
-                TheInterpolation::Switch => {
-                    if t < 0.5 {
-                        start.clone()
-                    } else {
-                        end.clone()
-                    }
-                }
-                TheInterpolation::EaseIn => {
-                    let t = t * t;
-                    TheValue::Float(s + (e - s) * t)
-                }
-                TheInterpolation::EaseOut => {
-                    let t = t * (2.0 - t);
-                    TheValue::Float(s + (e - s) * t)
+    /// Applies the easing curve to t (0..1). Switch returns 0 or 1. Without keyframe tangents
+    /// Bezier uses TheKeyframe::DEFAULT_TANGENTS.
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            TheInterpolation::Linear => t,
+            TheInterpolation::Spline => t * t * (3.0 - 2.0 * t),
+            TheInterpolation::Switch => {
+                if t < 0.5 {
+                    0.0
+                } else {
+                    1.0
                 }
-                TheInterpolation::EaseInOut => {
-                    let t = if t < 0.5 {
-                        2.0 * t * t
-                    } else {
-                        -1.0 + (4.0 - 2.0 * t) * t
-                    };
-                    TheValue::Float(s + (e - s) * t)
+            }
+            TheInterpolation::EaseIn => t * t,
+            TheInterpolation::EaseOut => t * (2.0 - t),
+            TheInterpolation::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
                 }
+            }
+            TheInterpolation::Bezier => {
+                let (in_tangent, out_tangent) = TheKeyframe::DEFAULT_TANGENTS;
+                Self::bezier_ease(out_tangent, Vec2::one() + in_tangent, t)
+            }
+        }
+    }
+
+    /// Evaluates the cubic bezier from (0, 0) over the control points p1 and p2 to (1, 1) at
+    /// x = t. The curve may overshoot.
+    fn bezier_ease(p1: Vec2<f32>, p2: Vec2<f32>, t: f32) -> f32 {
+        // The handles can not go back in time, so that x(s) is monotonic.
+        let (x1, x2) = (p1.x.clamp(0.0, 1.0), p2.x.clamp(0.0, 1.0));
+        let cubic = |a: f32, b: f32, s: f32| {
+            let r = 1.0 - s;
+            3.0 * r * r * s * a + 3.0 * r * s * s * b + s * s * s
+        };
+
+        // Bisection for the curve parameter s with x(s) = t.
+        let (mut low, mut high) = (0.0, 1.0);
+        let mut s = t;
+        for _ in 0..24 {
+            let x = cubic(x1, x2, s);
+            if (x - t).abs() < 1e-5 {
+                break;
+            }
+            if x < t {
+                low = s;
+            } else {
+                high = s;
+            }
+            s = (low + high) / 2.0;
+        }
+        cubic(p1.y, p2.y, s)
+    }
+
+    /// Interpolates between the values: Floats and vectors component-wise, ints rounded, colors
+    /// in OKLab, directions spherically and gradients by their stops. With Switch all values
+    /// switch from start to end at t = 0.5, otherwise values which can not be interpolated
+    /// return the end value.
+    pub fn interpolate(&self, start: &TheValue, end: &TheValue, t: f32) -> TheValue {
+        let t = t.clamp(0.0, 1.0);
+        if *self == TheInterpolation::Switch {
+            return if t < 0.5 { start } else { end }.clone();
+        }
+        Self::mix(start, end, self.ease(t))
+    }
+
+    /// Mixes the values by the eased t, see interpolate().
+    fn mix(start: &TheValue, end: &TheValue, t: f32) -> TheValue {
+        let f = |s: f32, e: f32| s + (e - s) * t;
+        let i = |s: i32, e: i32| f(s as f32, e as f32).round() as i32;
+
+        match (start, end) {
+            (TheValue::Gradient(s), TheValue::Gradient(e)) => TheValue::Gradient(s.lerp(e, t)),
+            (TheValue::Float(s), TheValue::Float(e)) => TheValue::Float(f(*s, *e)),
+            (TheValue::FloatRange(s, range), TheValue::FloatRange(e, _)) => {
+                TheValue::FloatRange(f(*s, *e), range.clone())
+            }
+            (TheValue::Int(s), TheValue::Int(e)) => TheValue::Int(i(*s, *e)),
+            (TheValue::IntRange(s, range), TheValue::IntRange(e, _)) => {
+                TheValue::IntRange(i(*s, *e), range.clone())
+            }
+            (TheValue::Float2(s), TheValue::Float2(e)) => {
+                TheValue::Float2(Vec2::new(f(s.x, e.x), f(s.y, e.y)))
+            }
+            (TheValue::Float3(s), TheValue::Float3(e)) => {
+                TheValue::Float3(Vec3::new(f(s.x, e.x), f(s.y, e.y), f(s.z, e.z)))
+            }
+            (TheValue::Float4(s), TheValue::Float4(e)) => TheValue::Float4(Vec4::new(
+                f(s.x, e.x),
+                f(s.y, e.y),
+                f(s.z, e.z),
+                f(s.w, e.w),
+            )),
+            (TheValue::Int2(s), TheValue::Int2(e)) => {
+                TheValue::Int2(Vec2::new(i(s.x, e.x), i(s.y, e.y)))
+            }
+            (TheValue::Int3(s), TheValue::Int3(e)) => {
+                TheValue::Int3(Vec3::new(i(s.x, e.x), i(s.y, e.y), i(s.z, e.z)))
+            }
+            (TheValue::Int4(s), TheValue::Int4(e)) => TheValue::Int4(Vec4::new(
+                i(s.x, e.x),
+                i(s.y, e.y),
+                i(s.z, e.z),
+                i(s.w, e.w),
+            )),
+            (TheValue::Position(s), TheValue::Position(e)) => {
+                TheValue::Position(Vec3::new(f(s.x, e.x), f(s.y, e.y), f(s.z, e.z)))
+            }
+            (TheValue::Direction(s), TheValue::Direction(e)) => {
+                TheValue::Direction(Self::slerp(*s, *e, t))
+            }
+            (TheValue::ColorObject(s), TheValue::ColorObject(e)) => {
+                TheValue::ColorObject(s.mix_in(e, t, TheColorSpace::OkLab))
+            }
+            _ => match (start.as_f32(), end.as_f32()) {
+                (Some(s), Some(e)) => TheValue::Float(f(s, e)),
+                _ => end.clone(),
             },
-            _ => end.clone(),
         }
     }
+
+    /// Spherical interpolation of the directions, with the length interpolated linearly.
+    fn slerp(start: Vec3<f32>, end: Vec3<f32>, t: f32) -> Vec3<f32> {
+        let (start_length, end_length) = (start.magnitude(), end.magnitude());
+        if start_length < f32::EPSILON || end_length < f32::EPSILON {
+            return start + (end - start) * t;
+        }
+        let (a, b) = (start / start_length, end / end_length);
+        let length = start_length + (end_length - start_length) * t;
+
+        let dot = a.dot(b).clamp(-1.0, 1.0);
+        if dot > 0.9995 {
+            // Nearly parallel
+            let v = a + (b - a) * t;
+            return v.normalized() * length;
+        }
+
+        // The direction perpendicular to a towards b. For opposite directions any perpendicular
+        // direction is valid.
+        let perpendicular = if dot < -0.9995 {
+            let axis = if a.x.abs() < 0.9 {
+                Vec3::unit_x()
+            } else {
+                Vec3::unit_y()
+            };
+            a.cross(axis).normalized()
+        } else {
+            (b - a * dot).normalized()
+        };
+        let theta = dot.acos() * t;
+        (a * theta.cos() + perpendicular * theta.sin()) * length
+    }
 }
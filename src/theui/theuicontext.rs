@@ -1,6 +1,7 @@
 use crate::prelude::*;
 use crate::Embedded;
 use fontdue::Font;
+use rust_embed::RustEmbed;
 
 use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, Sender};
@@ -20,10 +21,16 @@ impl TheFileExtension {
     }
 }
 
+/// The maximum number of tints cached for an SVG icon at a single scale.
+const SVG_ICON_CACHE_TINTS: usize = 4;
+
 pub struct TheUIContext {
     pub font: Option<Font>,
     icons: FxHashMap<String, TheRGBABuffer>,
 
+    svg_icons: FxHashMap<String, TheSVGIcon>,
+    svg_icon_cache: FxHashMap<(String, u32), Vec<(RGBA, TheRGBABuffer)>>,
+
     pub focus: Option<TheId>,
     pub keyboard_focus: Option<TheId>,
     pub hover: Option<TheId>,
@@ -47,6 +54,9 @@ pub struct TheUIContext {
 
     pub clipboard: Option<TheValue>,
     pub clipboard_app_type: Option<String>,
+
+    /// The most recently picked colors, newest first.
+    pub recent_colors: Vec<TheColor>,
 }
 
 impl Default for TheUIContext {
@@ -59,6 +69,7 @@ impl TheUIContext {
     pub fn new() -> Self {
         let mut font: Option<Font> = None;
         let mut icons: FxHashMap<String, TheRGBABuffer> = FxHashMap::default();
+        let mut svg_icons: FxHashMap<String, TheSVGIcon> = FxHashMap::default();
 
         for file in Embedded::iter() {
             let name = file.as_ref();
@@ -71,39 +82,19 @@ impl TheUIContext {
                         font = Some(f);
                     }
                 }
+            } else if name.starts_with("icons/") && name.ends_with(".svg") {
+                if let Some(file) = Embedded::get(name) {
+                    if let Some(icon) = TheSVGIcon::from_bytes(&file.data) {
+                        let cut_name = name.replace("icons/", "").replace(".svg", "");
+                        svg_icons.insert(cut_name, icon);
+                    }
+                }
             } else if name.starts_with("icons/") {
                 if let Some(file) = Embedded::get(name) {
-                    let data = std::io::Cursor::new(file.data);
-
-                    let decoder = png::Decoder::new(data);
-                    if let Ok(mut reader) = decoder.read_info() {
-                        let mut buf = vec![0; reader.output_buffer_size()];
-                        let info = reader.next_frame(&mut buf).unwrap();
-                        let bytes = &buf[..info.buffer_size()];
-
-                        // Ensure the image data has 4 channels (RGBA)
-                        let rgba_bytes = if info.color_type.samples() == 3 {
-                            // Image is RGB, expand to RGBA
-                            let mut expanded_buf =
-                                Vec::with_capacity(info.width as usize * info.height as usize * 4);
-                            for chunk in bytes.chunks(3) {
-                                expanded_buf.push(chunk[0]); // R
-                                expanded_buf.push(chunk[1]); // G
-                                expanded_buf.push(chunk[2]); // B
-                                expanded_buf.push(255); // A (opaque)
-                            }
-                            expanded_buf
-                        } else {
-                            // Image is already RGBA
-                            bytes.to_vec()
-                        };
-
+                    if let Ok(buffer) = TheRGBABuffer::from_png_bytes(&file.data) {
                         let mut cut_name = name.replace("icons/", "");
                         cut_name = cut_name.replace(".png", "");
-                        icons.insert(
-                            cut_name.to_string(),
-                            TheRGBABuffer::from(rgba_bytes, info.width, info.height),
-                        );
+                        icons.insert(cut_name.to_string(), buffer);
                     }
                 }
             }
@@ -119,6 +110,9 @@ impl TheUIContext {
             font,
             icons,
 
+            svg_icons,
+            svg_icon_cache: FxHashMap::default(),
+
             disabled_ids: FxHashSet::default(),
             state_events_sender: None,
 
@@ -135,9 +129,19 @@ impl TheUIContext {
 
             clipboard: None,
             clipboard_app_type: None,
+
+            recent_colors: vec![],
         }
     }
 
+    /// Adds a color to the front of the recent colors, removing duplicates.
+    pub fn add_recent_color(&mut self, color: TheColor) {
+        let rgba = color.to_u8_array();
+        self.recent_colors.retain(|c| c.to_u8_array() != rgba);
+        self.recent_colors.insert(0, color);
+        self.recent_colors.truncate(12);
+    }
+
     /// Set the given id as disabled.
     pub fn set_disabled(&mut self, id: &str) {
         self.disabled_ids.insert(id.to_string());
@@ -168,6 +172,88 @@ impl TheUIContext {
         None
     }
 
+    /// Adds an SVG icon to the library. Replaces any cached rasterizations of an icon with the same name.
+    pub fn add_svg_icon(&mut self, name: String, icon: TheSVGIcon) {
+        self.svg_icon_cache.retain(|(n, _), _| *n != name);
+        self.svg_icons.insert(name, icon);
+    }
+
+    /// Adds all SVG files inside the given folder of a RustEmbed asset struct as icons.
+    /// Icons are named by their path relative to the folder, without the extension.
+    pub fn add_svg_icons<E: RustEmbed>(&mut self, folder: &str) {
+        let prefix = if folder.is_empty() || folder.ends_with('/') {
+            folder.to_string()
+        } else {
+            format!("{}/", folder)
+        };
+
+        for file in E::iter() {
+            let name = file.as_ref();
+            if let Some(cut_name) = name
+                .strip_prefix(&prefix)
+                .and_then(|n| n.strip_suffix(".svg"))
+            {
+                if let Some(data) = E::get(name) {
+                    if let Some(icon) = TheSVGIcon::from_bytes(&data.data) {
+                        self.add_svg_icon(cut_name.to_string(), icon);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the SVG icon of the given name.
+    pub fn svg_icon(&self, name: &str) -> Option<&TheSVGIcon> {
+        self.svg_icons.get(name)
+    }
+
+    /// Returns the SVG icon of the given name rasterized at the given scale factor, with `currentColor`
+    /// replaced by the icon tint of the current theme.
+    pub fn scaled_svg_icon(
+        &mut self,
+        name: &str,
+        scale_factor: f32,
+        style: &mut Box<dyn TheStyle>,
+    ) -> Option<&TheRGBABuffer> {
+        let tint = *style.theme().color(TheThemeColors::SVGIconTint);
+        self.scaled_svg_icon_tinted(name, scale_factor, &tint)
+    }
+
+    /// Returns the SVG icon of the given name rasterized at the given scale factor, with `currentColor`
+    /// replaced by the given tint. Only the most recently used tints are cached for each scale.
+    pub fn scaled_svg_icon_tinted(
+        &mut self,
+        name: &str,
+        scale_factor: f32,
+        tint: &RGBA,
+    ) -> Option<&TheRGBABuffer> {
+        let key = (name.to_string(), (scale_factor * 100.0).round() as u32);
+
+        let cached = self
+            .svg_icon_cache
+            .get(&key)
+            .and_then(|entries| entries.iter().position(|(t, _)| t == tint));
+
+        let entry = match cached {
+            Some(index) => self.svg_icon_cache.get_mut(&key)?.remove(index),
+            None => (
+                *tint,
+                self.svg_icons.get(name)?.rasterize(scale_factor, tint),
+            ),
+        };
+
+        let entries = self.svg_icon_cache.entry(key).or_default();
+        entries.insert(0, entry);
+        entries.truncate(SVG_ICON_CACHE_TINTS);
+
+        entries.first().map(|(_, buffer)| buffer)
+    }
+
+    /// Clears all cached SVG icon rasterizations, i.e. after a theme or scale factor change.
+    pub fn clear_svg_icon_cache(&mut self) {
+        self.svg_icon_cache.clear();
+    }
+
     /// Sets the focus to the given widget
     pub fn set_focus(&mut self, id: &TheId) {
         if !id.equals(&self.focus) {
@@ -307,62 +393,14 @@ impl TheUIContext {
         self.file_requester_receiver = Some((id, rx));
     }
 
-    /// Decode image
+    /// Decodes a PNG or GIF image. Upon success a TheEvent::ImageDecodeResult event will be send.
     pub fn decode_image(&mut self, id: TheId, path: PathBuf) {
-        if let Ok(data) = std::fs::File::open(path.clone()) {
-            let decoder = png::Decoder::new(data);
-            if let Ok(mut reader) = decoder.read_info() {
-                let mut buf = vec![0; reader.output_buffer_size()];
-                let info = reader.next_frame(&mut buf).unwrap();
-                let bytes = &buf[..info.buffer_size()];
-
-                // Ensure the image data has 4 channels (RGBA)
-                let rgba_bytes = match info.color_type {
-                    png::ColorType::Rgb => {
-                        // Image is RGB, expand to RGBA
-                        let mut expanded_buf =
-                            Vec::with_capacity(info.width as usize * info.height as usize * 4);
-                        for chunk in bytes.chunks(3) {
-                            expanded_buf.push(chunk[0]); // R
-                            expanded_buf.push(chunk[1]); // G
-                            expanded_buf.push(chunk[2]); // B
-                            expanded_buf.push(255); // A (opaque)
-                        }
-                        expanded_buf
-                    }
-                    png::ColorType::Rgba => {
-                        // Image is already RGBA
-                        bytes.to_vec()
-                    }
-                    png::ColorType::Indexed => {
-                        // Image is Indexed, decode using the palette
-                        if let Some(palette) = reader.info().palette.as_ref() {
-                            let mut expanded_buf =
-                                Vec::with_capacity(info.width as usize * info.height as usize * 4);
-                            for &index in bytes {
-                                let palette_index = index as usize * 3;
-                                expanded_buf.push(palette[palette_index]); // R
-                                expanded_buf.push(palette[palette_index + 1]); // G
-                                expanded_buf.push(palette[palette_index + 2]); // B
-                                expanded_buf.push(255); // A (opaque)
-                            }
-                            expanded_buf
-                        } else {
-                            eprintln!("Indexed image does not have a palette");
-                            return;
-                        }
-                    }
-                    _ => {
-                        eprintln!("Unsupported color type: {:?}", info.color_type);
-                        return;
-                    }
-                };
-
-                let buffer = TheRGBABuffer::from(rgba_bytes, info.width, info.height);
+        match TheRGBABuffer::from_image_path(&path) {
+            Ok(buffer) => {
                 let name = path.file_stem().and_then(|f| f.to_str()).unwrap_or("");
-
                 self.send(TheEvent::ImageDecodeResult(id, name.to_string(), buffer));
             }
+            Err(err) => eprintln!("Failed to decode {:?}: {}", path, err),
         }
     }
 }
@@ -102,6 +102,7 @@ pub enum TheValue {
     TimeDuration(TheTime, TheTime),
     TileMask(TheTileMask),
     Image(TheRGBABuffer),
+    Gradient(TheGradient),
 }
 
 use TheValue::*;
@@ -193,6 +194,13 @@ impl TheValue {
         }
     }
 
+    pub fn to_gradient(&self) -> Option<TheGradient> {
+        match self {
+            Gradient(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
     /// Returns the value as f32 if possible. Used for comparison.
     pub fn as_f32(&self) -> Option<f32> {
         match self {
@@ -278,6 +286,7 @@ impl TheValue {
             TimeDuration(s, e) => format!("Time Duration: {:?} {:?}", s.to_time24(), e.to_time24()),
             TileMask(_) => str!("Pixels in a tile"),
             Image(b) => format!("Image ({}, {})", b.dim().width, b.dim().height),
+            Gradient(g) => format!("Gradient: {} stops", g.stops.len()),
         }
     }
 
@@ -325,6 +334,7 @@ impl TheValue {
             TimeDuration(s, e) => format!("{} - {}", s.to_time24(), e.to_time24()),
             TileMask(_) => str!("Pixels"),
             Image(b) => format!("Image ({}, {})", b.dim().width, b.dim().height),
+            Gradient(_) => "Gradient".to_string(),
         }
     }
 }
@@ -47,8 +47,18 @@ pub enum TheEvent {
 
     // The index of the palette has changed.
     PaletteIndexChanged(TheId, u16),
+    // The palette was edited, the undo holds the palette json before and after the edit.
+    // Widget side undo / redo sends it with the id "Palette Undo" / "Palette Redo".
+    PaletteChanged(TheId, TheUndo),
     ColorButtonClicked(TheId),
 
+    // Color picker
+    // Show the color popup for the given widget at the given (global) coordinates.
+    ShowColorPopup(TheId, Vec2<i32>, TheColor),
+    // The widget requests the next click to sample a color from the window.
+    StartEyedropper(TheId),
+    EyedropperColorPicked(TheId, TheColor),
+
     // Tile / Code Editor
     TileSelectionChanged(TheId),
     TilePicked(TheId, Vec2<i32>),
@@ -61,6 +71,11 @@ pub enum TheEvent {
     TileEditorUp(TheId),
     TileZoomBy(TheId, f32),
 
+    // Pixel Editor
+    PixelEditorChanged(TheId, TheDim),
+    PixelEditorColorPicked(TheId, TheColor),
+    PixelEditorSelectionChanged(TheId, Option<TheDim>),
+
     RenderViewClicked(TheId, Vec2<i32>),
     RenderViewDragged(TheId, Vec2<i32>),
     RenderViewHoverChanged(TheId, Vec2<i32>),
@@ -72,6 +87,9 @@ pub enum TheEvent {
 
     // Timeline
     TimelineMarkerSelected(TheId, TheTime),
+    // An undoable edit of the timeline, the undo / redo data is a TheTimelineEdit as json.
+    // Widget side undo / redo sends it with the id "Timeline Undo" / "Timeline Redo".
+    TimelineChanged(TheId, TheUndo),
 
     // SDF
     SDFIndexChanged(TheId, u32),
@@ -84,11 +102,20 @@ pub enum TheEvent {
 
     // Nodes
     NodeSelectedIndexChanged(TheId, Option<usize>),
+    NodeSelectionChanged(TheId, Vec<usize>),
     NodeDragged(TheId, usize, Vec2<i32>),
     NodeConnectionAdded(TheId, Vec<(u16, u8, u16, u8)>),
     NodeConnectionRemoved(TheId, Vec<(u16, u8, u16, u8)>),
+    NodeConnectionRejected(TheId, (u16, u8, u16, u8), TheNodeConnectionError),
     NodeDeleted(TheId, usize, Vec<(u16, u8, u16, u8)>),
+    // A node was added by paste, duplicate, undo or redo: Node index, all connections.
+    NodeAdded(TheId, usize, Vec<(u16, u8, u16, u8)>),
     NodeViewScrolled(TheId, Vec2<i32>),
+    NodeViewZoomed(TheId, f32),
+    // An undoable edit of the node canvas, the undo / redo data is a TheNodeCommand as json.
+    // Widget side undo / redo sends it with the id "Node Undo" / "Node Redo", after the node
+    // and connection events of the applied edit.
+    NodeCanvasChanged(TheId, TheUndo),
 
     //
     DialogValueOnClose(TheDialogButtonRole, String, Uuid, TheValue),
@@ -3,8 +3,10 @@ use crate::prelude::*;
 pub mod thecheckbutton;
 pub mod thecolorbutton;
 pub mod thecolorpicker;
+pub mod thecurveeditor;
 pub mod thedirectionpicker;
 pub mod thedropdownmenu;
+pub mod thegradienteditor;
 pub mod thegroupbutton;
 pub mod thehdivider;
 pub mod thehorizontalscrollbar;
@@ -35,6 +37,7 @@ pub mod thetextareaedit;
 pub mod thetextedit;
 pub mod thetextlineedit;
 pub mod thetextview;
+pub mod thetimelineeditor;
 pub mod thetimeslider;
 pub mod thetoolbar;
 pub mod thetoolbarbutton;
@@ -92,7 +95,7 @@ pub mod prelude {
         TheMarkdownStyles, TheMarkdownView, TheMarkdownViewTrait,
     };
     pub use crate::theui::thewidget::thergbaview::{
-        TheRGBAView, TheRGBAViewMode, TheRGBAViewTrait,
+        ThePixelTool, TheRGBAView, TheRGBAViewMode, TheRGBAViewTrait,
     };
     pub use crate::theui::thewidget::thesdfview::{TheSDFView, TheSDFViewTrait};
     pub use crate::theui::thewidget::thespacer::TheSpacer;
@@ -107,12 +110,19 @@ pub mod prelude {
     pub use crate::theui::thewidget::thetraybarbutton::{TheTraybarButton, TheTraybarButtonTrait};
 
     pub use crate::theui::thewidget::thecolorpicker::{TheColorPicker, TheColorPickerTrait};
+    pub use crate::theui::thewidget::thecurveeditor::{TheCurveEditor, TheCurveEditorTrait};
     pub use crate::theui::thewidget::thedirectionpicker::TheDirectionPicker;
+    pub use crate::theui::thewidget::thegradienteditor::{
+        TheGradientEditor, TheGradientEditorTrait,
+    };
     pub use crate::theui::thewidget::thenodecanvasview::{
         TheNodeCanvasView, TheNodeCanvasViewTrait,
     };
     pub use crate::theui::thewidget::thepalettepicker::{ThePalettePicker, ThePalettePickerTrait};
     pub use crate::theui::thewidget::theseparator::TheSeparator;
+    pub use crate::theui::thewidget::thetimelineeditor::{
+        TheTimelineEditor, TheTimelineEditorTrait,
+    };
     pub use crate::theui::thewidget::thetimeslider::{TheTimeSlider, TheTimeSliderTrait};
     pub use crate::theui::thewidget::TheWidget;
     pub use crate::theui::thewidget::TheWidgetState;
@@ -379,6 +389,26 @@ pub trait TheWidget: Send {
         None
     }
 
+    /// Attempts to cast to TheColorPickerTrait. Only valid for TheColorPicker.
+    fn as_color_picker(&mut self) -> Option<&mut dyn TheColorPickerTrait> {
+        None
+    }
+
+    /// Attempts to cast to TheGradientEditorTrait. Only valid for TheGradientEditor.
+    fn as_gradient_editor(&mut self) -> Option<&mut dyn TheGradientEditorTrait> {
+        None
+    }
+
+    /// Attempts to cast to TheCurveEditorTrait. Only valid for TheCurveEditor.
+    fn as_curve_editor(&mut self) -> Option<&mut dyn TheCurveEditorTrait> {
+        None
+    }
+
+    /// Attempts to cast to TheTimelineEditorTrait. Only valid for TheTimelineEditor.
+    fn as_timeline_editor(&mut self) -> Option<&mut dyn TheTimelineEditorTrait> {
+        None
+    }
+
     /// Attempts to cast to ThePalettePickerTrait. Only valid for ThePalettePicker.
     fn as_palette_picker(&mut self) -> Option<&mut dyn ThePalettePickerTrait> {
         None
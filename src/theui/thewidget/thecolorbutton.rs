@@ -12,6 +12,8 @@ pub struct TheColorButton {
     color: RGBA,
     is_dirty: bool,
     cursor_icon: Option<TheCursorIcon>,
+
+    popup: bool,
 }
 
 impl TheWidget for TheColorButton {
@@ -32,6 +34,8 @@ impl TheWidget for TheColorButton {
             color: BLACK,
             is_dirty: false,
             cursor_icon: Some(TheCursorIcon::Hand),
+
+            popup: false,
         }
     }
 
@@ -65,6 +69,13 @@ impl TheWidget for TheColorButton {
                 ctx.ui.send_widget_state_changed(self.id(), self.state);
                 self.is_dirty = true;
                 ctx.ui.send(TheEvent::ColorButtonClicked(self.id.clone()));
+                if self.popup {
+                    ctx.ui.send(TheEvent::ShowColorPopup(
+                        self.id.clone(),
+                        Vec2::new(self.dim.x, self.dim.y + self.dim.height),
+                        TheColor::from_u8_array(self.color),
+                    ));
+                }
                 redraw = true;
             }
             TheEvent::MouseUp(_coord) => {
@@ -125,6 +136,10 @@ impl TheWidget for TheColorButton {
         true
     }
 
+    fn value(&self) -> TheValue {
+        TheValue::ColorObject(TheColor::from_u8_array(self.color))
+    }
+
     fn set_value(&mut self, value: TheValue) {
         if let TheValue::ColorObject(color) = value {
             self.color = color.to_u8_array();
@@ -183,10 +198,15 @@ impl TheWidget for TheColorButton {
 
 pub trait TheColorColorButtonTrait {
     fn set_color(&mut self, color: RGBA);
+    /// Open a color popup when clicked. By default only ColorButtonClicked is sent.
+    fn set_popup(&mut self, popup: bool);
 }
 
 impl TheColorColorButtonTrait for TheColorButton {
     fn set_color(&mut self, color: RGBA) {
         self.color = color;
     }
+    fn set_popup(&mut self, popup: bool) {
+        self.popup = popup;
+    }
 }
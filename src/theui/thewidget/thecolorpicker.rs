@@ -1,73 +1,97 @@
 use crate::prelude::*;
-use maths_rs::prelude::*;
 use rayon::prelude::*;
 
+/// The part of the picker which is currently dragged.
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum TheColorPickerDrag {
+    None,
+    SaturationValue,
+    Hue,
+    Alpha,
+}
+
 pub struct TheColorPicker {
     id: TheId,
     limiter: TheSizeLimiter,
     state: TheWidgetState,
 
+    status: Option<String>,
+
     dim: TheDim,
     is_dirty: bool,
 
     background: Option<RGBA>,
     border: Option<RGBA>,
 
-    color: Vec3f,
-
+    // The current color in HSV, the hue is in degrees.
     h: f32,
     s: f32,
-    l: f32,
+    v: f32,
+    alpha: f32,
 
-    ls1_pt: Vec2f,
-    ls2_pt: Vec2f,
-    ls3_pt: Vec2f,
+    // The color the picker was set to, shown in the preview.
+    previous: TheColor,
 
-    hue_dot: Vec2f,
-    sl_dot: Vec2f,
+    alpha_enabled: bool,
+    continuous: bool,
+    drag: TheColorPickerDrag,
 
-    dot_size: f32,
-    inside: bool,
+    // Local regions of the individual parts.
+    sv_rect: TheDim,
+    hue_rect: TheDim,
+    alpha_rect: TheDim,
+    preview_rect: TheDim,
+    dropper_rect: TheDim,
+    swatch_rect: TheDim,
 
-    continuous: bool,
+    layout_id: Option<TheId>,
 }
 
+const BAR_WIDTH: i32 = 14;
+const GAP: i32 = 6;
+const PREVIEW_HEIGHT: i32 = 18;
+const SWATCH_SIZE: i32 = 14;
+
 impl TheWidget for TheColorPicker {
     fn new(id: TheId) -> Self
     where
         Self: Sized,
     {
         let mut limiter = TheSizeLimiter::new();
-        limiter.set_max_size(vek::Vec2::new(200, 200));
+        limiter.set_max_size(Vec2::new(200, 200));
 
         Self {
             id,
             limiter,
             state: TheWidgetState::None,
 
+            status: None,
+
             dim: TheDim::zero(),
             is_dirty: false,
 
             background: None,
             border: None,
 
-            color: Vec3f::zero(),
-
             h: 0.0,
             s: 0.0,
-            l: 0.0,
+            v: 0.0,
+            alpha: 1.0,
 
-            ls1_pt: Vec2f::zero(),
-            ls2_pt: Vec2f::zero(),
-            ls3_pt: Vec2f::zero(),
+            previous: TheColor::black(),
 
-            hue_dot: Vec2f::zero(),
-            sl_dot: Vec2f::zero(),
+            alpha_enabled: true,
+            continuous: false,
+            drag: TheColorPickerDrag::None,
 
-            dot_size: 0.0,
-            inside: false,
+            sv_rect: TheDim::zero(),
+            hue_rect: TheDim::zero(),
+            alpha_rect: TheDim::zero(),
+            preview_rect: TheDim::zero(),
+            dropper_rect: TheDim::zero(),
+            swatch_rect: TheDim::zero(),
 
-            continuous: false,
+            layout_id: None,
         }
     }
 
@@ -75,51 +99,85 @@ impl TheWidget for TheColorPicker {
         &self.id
     }
 
-    #[allow(clippy::single_match)]
+    fn status_text(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn set_status_text(&mut self, text: &str) {
+        self.status = Some(text.to_string());
+    }
+
     fn on_event(&mut self, event: &TheEvent, ctx: &mut TheContext) -> bool {
         let mut redraw = false;
         // println!("event ({}): {:?}", self.widget_id.name, event);
         match event {
             TheEvent::MouseDown(coord) => {
-                if self.state != TheWidgetState::Selected {
-                    self.state = TheWidgetState::Selected;
-                    ctx.ui.send_widget_state_changed(self.id(), self.state);
+                if self.sv_rect.contains(*coord) {
+                    self.drag = TheColorPickerDrag::SaturationValue;
+                } else if self.hue_rect.contains(*coord) {
+                    self.drag = TheColorPickerDrag::Hue;
+                } else if self.alpha_enabled && self.alpha_rect.contains(*coord) {
+                    self.drag = TheColorPickerDrag::Alpha;
+                } else if self.dropper_rect.contains(*coord) {
+                    ctx.ui.send(TheEvent::StartEyedropper(self.id.clone()));
+                } else if self.preview_rect.contains(*coord) {
+                    // The left half of the preview shows the previous color, clicking it restores it.
+                    if coord.x < self.preview_rect.x + self.preview_rect.width / 2 {
+                        self.set_hsv_from_color(&self.previous.clone());
+                        self.commit(ctx);
+                        redraw = true;
+                    }
+                } else if self.swatch_rect.contains(*coord) {
+                    let index = ((coord.x - self.swatch_rect.x) / (SWATCH_SIZE + 2)) as usize;
+                    if let Some(color) = ctx.ui.recent_colors.get(index).cloned() {
+                        self.set_hsv_from_color(&color);
+                        self.commit(ctx);
+                        redraw = true;
+                    }
+                }
+
+                if self.drag != TheColorPickerDrag::None {
+                    if self.state != TheWidgetState::Selected {
+                        self.state = TheWidgetState::Selected;
+                        ctx.ui.send_widget_state_changed(self.id(), self.state);
+                    }
+                    ctx.ui.set_focus(self.id());
+                    self.drag_to(*coord);
+                    if self.continuous {
+                        self.emit(ctx);
+                    }
+                    redraw = true;
                 }
-                ctx.ui.set_focus(self.id());
-                self.calc_color(*coord, true);
-                //ctx.ui.send(TheEvent::ValueChanged(self.id.clone(), TheValue::ColorObject(TheColor::from_vec3f(self.color))));
                 self.is_dirty = true;
-                redraw = true;
             }
-            TheEvent::MouseDragged(coord) => {
-                self.calc_color(*coord, false);
+            TheEvent::MouseDragged(coord) if self.drag != TheColorPickerDrag::None => {
+                self.drag_to(*coord);
                 if self.continuous {
-                    ctx.ui.send(TheEvent::ValueChanged(
-                        self.id.clone(),
-                        TheValue::ColorObject(TheColor::from_vec3(vek::Vec3::new(
-                            self.color.x,
-                            self.color.y,
-                            self.color.z,
-                        ))),
-                    ));
+                    self.emit(ctx);
                 }
                 self.is_dirty = true;
                 redraw = true;
             }
-            TheEvent::MouseUp(coord) => {
-                self.calc_color(*coord, false);
-                ctx.ui.send(TheEvent::ValueChanged(
-                    self.id.clone(),
-                    TheValue::ColorObject(TheColor::from_vec3(vek::Vec3::new(
-                        self.color.x,
-                        self.color.y,
-                        self.color.z,
-                    ))),
-                ));
+            TheEvent::MouseUp(coord) if self.drag != TheColorPickerDrag::None => {
+                self.drag_to(*coord);
+                self.drag = TheColorPickerDrag::None;
+                self.commit(ctx);
                 self.is_dirty = true;
                 redraw = true;
                 ctx.ui.clear_focus();
             }
+            TheEvent::EyedropperColorPicked(_, color) => {
+                // The composited buffer is opaque, keep the current alpha.
+                let mut color = color.clone();
+                color.a = self.alpha;
+                self.set_hsv_from_color(&color);
+                self.commit(ctx);
+                self.is_dirty = true;
+                redraw = true;
+            }
+            TheEvent::Hover(_coord) if !self.id().equals(&ctx.ui.hover) => {
+                ctx.ui.set_hover(self.id());
+            }
             _ => {}
         }
         redraw
@@ -136,6 +194,7 @@ impl TheWidget for TheColorPicker {
     fn set_dim(&mut self, dim: TheDim, _ctx: &mut TheContext) {
         if self.dim != dim {
             self.dim = dim;
+            self.compute_regions();
             self.is_dirty = true;
         }
     }
@@ -164,134 +223,160 @@ impl TheWidget for TheColorPicker {
     fn draw(
         &mut self,
         buffer: &mut TheRGBABuffer,
-        _style: &mut Box<dyn TheStyle>,
+        style: &mut Box<dyn TheStyle>,
         ctx: &mut TheContext,
     ) {
-        let stride: usize = buffer.stride();
-
-        if !self.dim().is_valid() {
+        if !self.dim().is_valid() || !self.sv_rect.is_valid() {
             return;
         }
 
-        fn get_hue_color(pos: Vec2f) -> Vec3f {
-            let theta = 3.0 + 3.0 * atan2(pos.x, pos.y) / std::f32::consts::PI;
-            clamp(
-                abs(fmod(theta + vec3f(0.0, 4.0, 2.0), vec3f(6.0, 6.0, 6.0)) - 3.0) - 1.0,
-                Vec3f::zero(),
-                Vec3f::one(),
-            )
+        let stride = buffer.stride();
+
+        let background = self
+            .background
+            .unwrap_or(*style.theme().color(DefaultWidgetBackground));
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &self.dim.to_buffer_utuple(),
+            stride,
+            &background,
+        );
+
+        // Saturation / value square for the current hue.
+        let h = self.h;
+        let rect = self.to_buffer_dim(&self.sv_rect);
+        let (w, hh) = (rect.width, rect.height);
+        shade_rect(buffer, &rect, |x, y| {
+            let s = x as f32 / (w - 1).max(1) as f32;
+            let v = 1.0 - y as f32 / (hh - 1).max(1) as f32;
+            TheColor::from_hsv(h, s, v).to_u8_array()
+        });
+
+        // Hue bar, red at the top.
+        let rect = self.to_buffer_dim(&self.hue_rect);
+        let hh = rect.height;
+        shade_rect(buffer, &rect, |_, y| {
+            TheColor::from_hsv(y as f32 / (hh - 1).max(1) as f32 * 360.0, 1.0, 1.0).to_u8_array()
+        });
+
+        let color = self.color();
+
+        // Alpha bar over a checkerboard, opaque at the top.
+        if self.alpha_enabled {
+            let rect = self.to_buffer_dim(&self.alpha_rect);
+            let hh = rect.height;
+            let opaque = color.to_u8_array();
+            shade_rect(buffer, &rect, |x, y| {
+                let a = 1.0 - y as f32 / (hh - 1).max(1) as f32;
+                blend_over_checker(&opaque, a, x, y)
+            });
         }
 
-        //style.draw_widget_border(buffer, self, &mut shrinker, ctx);
-
-        let size = min(self.dim.width, self.dim.height);
-
-        let mut b = TheRGBABuffer::new(TheDim::new(0, 0, size, size));
-
-        let pixels = b.pixels_mut();
-
-        let width = size as usize;
-
-        if let Some(bc) = self.background {
-            let stride = buffer.stride();
-            ctx.draw.rect(
-                buffer.pixels_mut(),
-                &self.dim.to_buffer_utuple(),
-                stride,
-                &bc,
+        // Previous / current color preview.
+        let rect = self.to_buffer_dim(&self.preview_rect);
+        let half = rect.width / 2;
+        let previous = self.previous.to_u8_array();
+        let previous_alpha = self.previous.a;
+        let current = color.to_u8_array();
+        let current_alpha = color.a;
+        shade_rect(buffer, &rect, |x, y| {
+            if x < half {
+                blend_over_checker(&previous, previous_alpha, x, y)
+            } else {
+                blend_over_checker(&current, current_alpha, x, y)
+            }
+        });
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &rect.to_buffer_utuple(),
+            stride,
+            &self.border.unwrap_or(BLACK),
+        );
+
+        // Eyedropper button.
+        let rect = self.to_buffer_dim(&self.dropper_rect);
+        let border_color = if self.id().equals(&ctx.ui.hover) {
+            *style.theme().color(DefaultWidgetBorder)
+        } else {
+            [80, 80, 80, 255]
+        };
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &rect.to_buffer_utuple(),
+            stride,
+            &border_color,
+        );
+        let r = rect.to_buffer_utuple();
+        ctx.draw.circle_with_border(
+            buffer.pixels_mut(),
+            &(r.0 + 3, r.1 + 3, r.2 - 6, r.3 - 6),
+            stride,
+            &[0, 0, 0, 0],
+            (r.2 as f32 - 6.0) / 2.0 - 1.0,
+            &WHITE,
+            1.5,
+        );
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &(r.0 + r.2 / 2, r.1 + 2, 1, r.3 - 4),
+            stride,
+            &WHITE,
+        );
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &(r.0 + 2, r.1 + r.3 / 2, r.2 - 4, 1),
+            stride,
+            &WHITE,
+        );
+
+        // Recently used colors.
+        let rect = self.to_buffer_dim(&self.swatch_rect);
+        let mut x = rect.x;
+        for recent in &ctx.ui.recent_colors {
+            if x + SWATCH_SIZE > rect.x + rect.width {
+                break;
+            }
+            let r = (
+                x as usize,
+                rect.y as usize,
+                SWATCH_SIZE as usize,
+                SWATCH_SIZE as usize,
             );
+            ctx.draw
+                .rect(buffer.pixels_mut(), &r, stride, &recent.to_u8_array());
+            ctx.draw
+                .rect_outline(buffer.pixels_mut(), &r, stride, &[80, 80, 80, 255]);
+            x += SWATCH_SIZE + 2;
         }
 
-        pixels
-            .par_rchunks_exact_mut(width * 4)
-            .enumerate()
-            .for_each(|(j, line)| {
-                for (i, pixel) in line.chunks_exact_mut(4).enumerate() {
-                    let i = j * width + i;
-
-                    let x = (i % width) as i32;
-                    let y = /*height -*/ (i / width) as i32 - 1;
-
-                    let xx = x as f32 / size as f32;
-                    let yy = y as f32 / size as f32;
-
-                    //let c;// = [xx, yy, 0.0, 1.0];
-
-                    let mut uv = vec2f(2.0, -2.0) * (vec2f(xx, yy) - vec2f(0.5, 0.5));
-
-                    let mut l = length(uv);
-
-                    l = 1.0 - abs((l - 0.875) * 8.0);
-                    l = clamp(l * size as f32 * 0.0625, 0.0, 1.0);
-
-                    let t = l * get_hue_color(uv);
-                    let mut col = vec4f(t.x, t.y, t.z, l);
-
-                    if l < 0.75 {
-                        uv /= 0.75;
-
-                        let mut inhsl = vec3f(self.h, self.h, self.l); //rgb2hsl(data->color.xyz);
-                        inhsl.x /= 360.0;
-
-                        let angle = ((inhsl.x * 360.0) - 180.0) * std::f32::consts::PI / 180.0;
-                        let mut mouse = vec2f(sin(angle), cos(angle));
-
-                        let picked_hue_color = get_hue_color(mouse);
-
-                        mouse = normalize(mouse);
-
-                        let sat = 1.5 - (dot(uv, mouse) + 0.5); // [0.0,1.5]
-
-                        if sat < 1.5 {
-                            let h = sat / sqrt(3.0);
-                            let om = cross(vec3f(mouse.x, mouse.y, 0.0), vec3f(0.0, 0.0, 1.0)).xy();
-
-                            let lum = dot(uv, om);
-
-                            if abs(lum) <= h {
-                                l = clamp((h - abs(lum)) * size as f32 * 0.5, 0.0, 1.0)
-                                    * clamp((1.5 - sat) / 1.5 * size as f32 * 0.5, 0.0, 1.0); // Fake antialiasing
-
-                                let p = 0.5 * (lum + h) / h;
-
-                                let r = l * lerp(picked_hue_color, vec3f(p, p, p), sat / 1.5);
-                                col = vec4f(r.x, r.y, r.z, l);
-                            }
-                        }
-
-                        //col.xyz = pickedHueColor;
-                    }
-
-                    let color = [
-                        (col[0] * 255.0) as u8,
-                        (col[1] * 255.0) as u8,
-                        (col[2] * 255.0) as u8,
-                        (col[3] * 255.0) as u8,
-                    ];
-                    pixel.copy_from_slice(&color);
-                }
-            });
-
-        buffer.blend_into(self.dim.buffer_x, self.dim.buffer_y, &b);
-
-        self.dot_size = (size as f32 / 20.0).max(5.0);
-        self.compute_points();
-
-        let mut r = self.dim.to_buffer_utuple();
-        r.0 += self.hue_dot.x as usize;
-        r.1 += self.hue_dot.y as usize;
-        r.2 = self.dot_size as usize * 2;
-        r.3 = self.dot_size as usize * 2;
-        ctx.draw
-            .circle(buffer.pixels_mut(), &r, stride, &BLACK, self.dot_size * 0.8);
-
-        let mut r = self.dim.to_buffer_utuple();
-        r.0 += self.sl_dot.x as usize;
-        r.1 += self.sl_dot.y as usize;
-        r.2 = self.dot_size as usize * 2;
-        r.3 = self.dot_size as usize * 2;
-        ctx.draw
-            .circle(buffer.pixels_mut(), &r, stride, &BLACK, self.dot_size * 0.8);
+        // Markers
+        let rect = self.to_buffer_dim(&self.sv_rect);
+        let mx = rect.x + (self.s * (rect.width - 1) as f32) as i32;
+        let my = rect.y + ((1.0 - self.v) * (rect.height - 1) as f32) as i32;
+        let marker = if self.v > 0.5 && self.s < 0.5 {
+            BLACK
+        } else {
+            WHITE
+        };
+        ctx.draw.circle_with_border(
+            buffer.pixels_mut(),
+            &((mx - 6).max(0) as usize, (my - 6).max(0) as usize, 13, 13),
+            stride,
+            &[0, 0, 0, 0],
+            5.0,
+            &marker,
+            1.5,
+        );
+
+        let rect = self.to_buffer_dim(&self.hue_rect);
+        let y = rect.y + (self.h / 360.0 * (rect.height - 1) as f32) as i32;
+        self.draw_bar_marker(buffer, &rect, y, ctx);
+
+        if self.alpha_enabled {
+            let rect = self.to_buffer_dim(&self.alpha_rect);
+            let y = rect.y + ((1.0 - self.alpha) * (rect.height - 1) as f32) as i32;
+            self.draw_bar_marker(buffer, &rect, y, ctx);
+        }
 
         self.is_dirty = false;
     }
@@ -300,28 +385,28 @@ impl TheWidget for TheColorPicker {
         self
     }
 
+    fn as_color_picker(&mut self) -> Option<&mut dyn TheColorPickerTrait> {
+        Some(self)
+    }
+
+    /// Returns the color as Float3 without alpha, use `color()` for the full color. Value change
+    /// events carry a ColorObject including alpha.
     fn value(&self) -> TheValue {
-        TheValue::Float3(vek::Vec3::new(self.color.x, self.color.y, self.color.z))
+        TheValue::Float3(self.color().to_vec3())
     }
 
     #[allow(clippy::single_match)]
     fn set_value(&mut self, value: TheValue) {
         match value {
             TheValue::ColorObject(color) => {
-                let col = color.to_vec3();
-                self.color = vec3f(col.x, col.y, col.z);
-                let hsl = color.as_hsl();
-                self.h = hsl.x * 360.0;
-                self.s = hsl.y;
-                self.l = hsl.z;
+                self.set_hsv_from_color(&color);
+                self.previous = color;
                 self.is_dirty = true;
             }
             TheValue::Float3(color) => {
-                self.color = vec3f(color.x, color.y, color.z);
-                let hsl = TheColor::from_vec3(color).as_hsl();
-                self.h = hsl.x * 360.0;
-                self.s = hsl.y;
-                self.l = hsl.z;
+                let color = TheColor::from_vec3(color);
+                self.set_hsv_from_color(&color);
+                self.previous = color;
                 self.is_dirty = true;
             }
             _ => {}
@@ -329,17 +414,60 @@ impl TheWidget for TheColorPicker {
     }
 }
 
+/// Fills the given buffer rectangle with the colors returned by the shader for each pixel.
+pub(super) fn shade_rect<F>(buffer: &mut TheRGBABuffer, rect: &TheDim, shader: F)
+where
+    F: Fn(i32, i32) -> [u8; 4] + Sync,
+{
+    let mut b = TheRGBABuffer::new(TheDim::new(0, 0, rect.width, rect.height));
+    let width = rect.width as usize;
+    b.pixels_mut()
+        .par_chunks_exact_mut(width * 4)
+        .enumerate()
+        .for_each(|(y, line)| {
+            for (x, pixel) in line.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(&shader(x as i32, y as i32));
+            }
+        });
+    buffer.copy_into(rect.x, rect.y, &b);
+}
+
+/// Blends the color with the given alpha over a checkerboard pattern.
+pub(super) fn blend_over_checker(color: &[u8; 4], alpha: f32, x: i32, y: i32) -> [u8; 4] {
+    let checker = if ((x / 5) + (y / 5)) % 2 == 0 {
+        200.0
+    } else {
+        120.0
+    };
+    let mix = |c: u8| (c as f32 * alpha + checker * (1.0 - alpha)) as u8;
+    [mix(color[0]), mix(color[1]), mix(color[2]), 255]
+}
+
 pub trait TheColorPickerTrait: TheWidget {
     fn set_background_color(&mut self, color: [u8; 4]);
     fn set_border_color(&mut self, color: [u8; 4]);
 
-    fn set_color(&mut self, color: vek::Vec3<f32>);
+    fn set_color(&mut self, color: Vec3<f32>);
     fn set_continuous(&mut self, continuous: bool);
 
+    /// Returns the current color including alpha.
+    fn color(&self) -> TheColor;
+    /// Updates the current color while keeping the previous color of the preview.
+    fn update_color(&mut self, color: TheColor);
+    /// Show or hide the alpha bar. If hidden the alpha of the color stays unchanged.
+    fn set_alpha_enabled(&mut self, alpha_enabled: bool);
+    /// Redirect value changes to the given layout instead of sending them directly.
+    fn set_associated_layout(&mut self, id: TheId);
+
+    /// Recomputes the regions of the picker parts from the widget size.
     fn compute_points(&mut self);
-    fn calc_color(&mut self, coord: vek::Vec2<i32>, new_op: bool);
-    fn get_hue_at(&mut self, coord: vek::Vec2<i32>);
-    fn get_sl_at(&mut self, coord: vek::Vec2<i32>);
+    /// Updates the color from the given local coordinate. A new operation picks the part under
+    /// the coordinate, otherwise the part of the current operation keeps being dragged.
+    fn calc_color(&mut self, coord: Vec2<i32>, new_op: bool);
+    /// Sets the hue from the given local coordinate inside the hue bar.
+    fn get_hue_at(&mut self, coord: Vec2<i32>);
+    /// Sets the saturation and value from the given local coordinate inside the color square.
+    fn get_sl_at(&mut self, coord: Vec2<i32>);
 }
 
 impl TheColorPickerTrait for TheColorPicker {
@@ -351,170 +479,191 @@ impl TheColorPickerTrait for TheColorPicker {
         self.border = Some(color);
     }
 
-    fn set_color(&mut self, color: vek::Vec3<f32>) {
-        self.color = vec3f(color.x, color.y, color.z);
-        let hsl = TheColor::from_vec3(color).as_hsl();
-        self.h = hsl.x * 360.0;
-        self.s = hsl.y;
-        self.l = hsl.z;
+    fn set_color(&mut self, color: Vec3<f32>) {
+        let mut color = TheColor::from_vec3(color);
+        color.a = self.alpha;
+        self.set_hsv_from_color(&color);
+        self.previous = color;
+        self.is_dirty = true;
     }
 
     fn set_continuous(&mut self, continuous: bool) {
         self.continuous = continuous;
     }
 
-    fn calc_color(&mut self, co: vek::Vec2<i32>, new_op: bool) {
-        let coord = vec2i(co.x, co.y);
-
-        if new_op {
-            let x = coord.x as f32;
-            let y = coord.y as f32;
-
-            let circle_size = min(self.dim.width, self.dim.height) as f32;
-            let center = vec2f(circle_size / 2.0, circle_size / 2.0);
-
-            let dist = distance(center, vec2f(x, y));
-            self.inside = dist < (circle_size * 0.75) / 2.0;
-
-            //print("calcColor", x, y, dist, insideOp)
-        }
-
-        if !self.inside {
-            self.get_hue_at(co);
-            self.compute_points();
-        } else {
-            self.get_sl_at(co);
-            self.compute_points();
-        }
+    fn color(&self) -> TheColor {
+        let mut color = TheColor::from_hsv(self.h, self.s, self.v);
+        color.a = self.alpha;
+        color
     }
 
-    fn get_sl_at(&mut self, co: vek::Vec2<i32>) {
-        let coord = vec2i(co.x, co.y);
-
-        let x = coord.x as f32 - self.dot_size / 1.0;
-        let y = coord.y as f32 - self.dot_size / 1.0;
+    fn update_color(&mut self, color: TheColor) {
+        self.set_hsv_from_color(&color);
+        self.is_dirty = true;
+    }
 
-        // if x < 0 || x > rect.width { return }
-        // if y < 0 || y > rect.width { return }
+    fn set_alpha_enabled(&mut self, alpha_enabled: bool) {
+        self.alpha_enabled = alpha_enabled;
+        self.compute_regions();
+        self.is_dirty = true;
+    }
 
-        fn sign_of(p1: Vec2f, p2: Vec2f, p3: Vec2f) -> f32 {
-            (p2.x - p1.x) * (p3.y - p1.y) - (p2.y - p1.y) * (p3.x - p1.x)
-        }
+    fn set_associated_layout(&mut self, layout_id: TheId) {
+        self.layout_id = Some(layout_id);
+    }
 
-        fn limit(v: f32) -> f32 {
-            if v < 0.0 {
-                return 0.0;
-            }
-            if v > 1.0 {
-                return 1.0;
-            }
-            v
-        }
+    fn compute_points(&mut self) {
+        self.compute_regions();
+    }
 
-        let mut ev = vec2f(x, y);
-
-        let b1 = sign_of(ev, self.ls1_pt, self.ls2_pt) <= 0.0;
-        let b2 = sign_of(ev, self.ls2_pt, self.ls3_pt) <= 0.0;
-        let b3 = sign_of(ev, self.ls3_pt, self.ls1_pt) <= 0.0;
-
-        let mut fail = false;
-        // in this case coordinate axis is clockwise
-        if b1 && b2 && b3 {
-            // inside triangle
-            ev -= self.ls1_pt;
-        } else if b2 && b3 {
-            let line = self.ls2_pt - self.ls1_pt;
-            ev -= self.ls1_pt;
-            ev = line * limit(dot(line, ev) / (length(line) * length(line)))
-        } else if b1 && b2 {
-            let line = self.ls3_pt - self.ls1_pt;
-            ev -= self.ls1_pt;
-            ev = line * limit(dot(line, ev) / (length(line) * length(line)))
-        } else if b1 && b3 {
-            let line = self.ls2_pt - self.ls3_pt;
-            ev -= self.ls3_pt;
-            ev = line * limit(dot(line, ev) / (length(line) * length(line)));
-            ev += self.ls3_pt - self.ls1_pt;
-        } else {
-            fail = true
+    fn calc_color(&mut self, coord: Vec2<i32>, new_op: bool) {
+        if new_op {
+            self.drag = if self.sv_rect.contains(coord) {
+                TheColorPickerDrag::SaturationValue
+            } else if self.hue_rect.contains(coord) {
+                TheColorPickerDrag::Hue
+            } else if self.alpha_enabled && self.alpha_rect.contains(coord) {
+                TheColorPickerDrag::Alpha
+            } else {
+                TheColorPickerDrag::None
+            };
         }
+        self.drag_to(coord);
+        self.is_dirty = true;
+    }
 
-        if !fail {
-            let p3 = self.ls3_pt - self.ls1_pt;
-            let side = length(p3);
-            self.l = dot(ev, p3) / (side * side);
-            if self.l > 0.01 && self.l < 0.99 {
-                let up = ((self.ls3_pt + self.ls1_pt) * -0.5) + self.ls2_pt;
-                let temp = if self.l < 0.5 { self.l } else { 1.0 - self.l };
-                self.s = dot(ev, up) / length(up) / length(up) * 0.5 / temp;
-            }
-            let color = TheColor::from_hsl(self.h, self.s, self.l).to_vec3();
-            self.color = vec3f(color.x, color.y, color.z);
-        }
+    fn get_hue_at(&mut self, coord: Vec2<i32>) {
+        let drag = self.drag;
+        self.drag = TheColorPickerDrag::Hue;
+        self.drag_to(coord);
+        self.drag = drag;
+        self.is_dirty = true;
     }
 
-    fn get_hue_at(&mut self, co: vek::Vec2<i32>) {
-        let coord = vec2i(co.x, co.y);
+    fn get_sl_at(&mut self, coord: Vec2<i32>) {
+        let drag = self.drag;
+        self.drag = TheColorPickerDrag::SaturationValue;
+        self.drag_to(coord);
+        self.drag = drag;
+        self.is_dirty = true;
+    }
+}
 
-        let x = coord.x as f32;
-        let y = coord.y as f32;
+impl TheColorPicker {
+    /// Computes the local regions of the picker parts from the widget size.
+    fn compute_regions(&mut self) {
+        let width = self.dim.width;
+        let height = self.dim.height;
 
-        let hsv =
-            TheColor::from_vec3(vek::Vec3::new(self.color.x, self.color.y, self.color.z)).as_hsl();
+        let bars = if self.alpha_enabled { 2 } else { 1 };
+        let bottom = PREVIEW_HEIGHT + SWATCH_SIZE + GAP * 2;
 
-        let circle_size = min(self.dim.width, self.dim.height) as f32;
+        let sv_width = (width - (BAR_WIDTH + GAP) * bars).max(0);
+        let sv_height = (height - bottom).max(0);
 
-        let center = vec2f(circle_size / 2.0, circle_size / 2.0);
-        let mut mouse = vec2f(x - center.x, y - center.y);
+        self.sv_rect = TheDim::new(0, 0, sv_width, sv_height);
+        self.hue_rect = TheDim::new(sv_width + GAP, 0, BAR_WIDTH, sv_height);
+        self.alpha_rect = if self.alpha_enabled {
+            TheDim::new(sv_width + GAP * 2 + BAR_WIDTH, 0, BAR_WIDTH, sv_height)
+        } else {
+            TheDim::zero()
+        };
 
-        mouse = normalize(mouse);
+        let y = sv_height + GAP;
+        self.preview_rect =
+            TheDim::new(0, y, (width - PREVIEW_HEIGHT - GAP).max(0), PREVIEW_HEIGHT);
+        self.dropper_rect = TheDim::new(width - PREVIEW_HEIGHT, y, PREVIEW_HEIGHT, PREVIEW_HEIGHT);
 
-        mouse *= circle_size / 2.0 - (circle_size * 0.75) / 2.0;
-        let v = center + mouse;
-        let angle = atan2(v.x - center.x, v.y - center.y) * 180.0 / std::f32::consts::PI;
-        let rgb = TheColor::from_hsl(angle + 180.0, hsv.y, hsv.z).to_vec3();
-        self.h = angle + 180.0;
-        self.color.x = rgb.x;
-        self.color.y = rgb.y;
-        self.color.z = rgb.z;
+        self.swatch_rect = TheDim::new(0, y + PREVIEW_HEIGHT + GAP, width, SWATCH_SIZE);
     }
 
-    fn compute_points(&mut self) {
-        let circle_size = min(self.dim.width, self.dim.height) as f32;
-
-        let center = vec2f(circle_size / 2.0, circle_size / 2.0);
-        let mut angle = (self.h - 180.0) * std::f32::consts::PI / 180.0;
-        let mut dir = vec2f(sin(angle), cos(angle));
-
-        dir = normalize(dir);
-
-        let sub = self.dot_size * 1.4;
-
-        let mut ldir = dir;
-        ldir *= circle_size / 2.0 - self.dot_size * 1.4;
+    fn set_hsv_from_color(&mut self, color: &TheColor) {
+        let hsv = color.as_hsv();
+        // Keep the hue for achromatic colors so the square does not jump back to red.
+        if hsv.y > 0.0 && hsv.z > 0.0 {
+            self.h = hsv.x * 360.0;
+        }
+        self.s = hsv.y;
+        self.v = hsv.z;
+        self.alpha = color.a;
+    }
 
-        self.hue_dot = center + ldir - self.dot_size;
+    fn drag_to(&mut self, coord: Vec2<i32>) {
+        fn unit(v: i32, start: i32, size: i32) -> f32 {
+            ((v - start) as f32 / (size - 1).max(1) as f32).clamp(0.0, 1.0)
+        }
 
-        ldir = dir;
-        ldir *= circle_size / 2.0 - sub * 2.0;
+        match self.drag {
+            TheColorPickerDrag::SaturationValue => {
+                self.s = unit(coord.x, self.sv_rect.x, self.sv_rect.width);
+                self.v = 1.0 - unit(coord.y, self.sv_rect.y, self.sv_rect.height);
+            }
+            TheColorPickerDrag::Hue => {
+                self.h = unit(coord.y, self.hue_rect.y, self.hue_rect.height) * 360.0;
+            }
+            TheColorPickerDrag::Alpha => {
+                self.alpha = 1.0 - unit(coord.y, self.alpha_rect.y, self.alpha_rect.height);
+            }
+            TheColorPickerDrag::None => {}
+        }
+    }
 
-        self.ls2_pt = center + ldir - self.dot_size;
+    fn emit(&mut self, ctx: &mut TheContext) {
+        let value = TheValue::ColorObject(self.color());
+        if let Some(layout_id) = &self.layout_id {
+            ctx.ui.send(TheEvent::RedirectWidgetValueToLayout(
+                layout_id.clone(),
+                self.id.clone(),
+                value,
+            ));
+        } else {
+            ctx.ui.send_widget_value_changed(self.id(), value);
+        }
+    }
 
-        angle = (self.h - 180.0 - 120.0) * std::f32::consts::PI / 180.0;
-        dir = vec2f(sin(angle), cos(angle));
-        dir = normalize(dir);
-        dir *= circle_size / 2.0 - sub * 2.0;
-        self.ls1_pt = center + dir - self.dot_size;
+    fn commit(&mut self, ctx: &mut TheContext) {
+        self.emit(ctx);
+        ctx.ui.add_recent_color(self.color());
+    }
 
-        angle = (self.h - 180.0 + 120.0) * std::f32::consts::PI / 180.0;
-        dir = vec2f(sin(angle), cos(angle));
-        dir = normalize(dir);
-        dir *= circle_size / 2.0 - sub * 2.0;
-        self.ls3_pt = center + dir - self.dot_size;
+    fn to_buffer_dim(&self, rect: &TheDim) -> TheDim {
+        TheDim::new(
+            self.dim.buffer_x + rect.x,
+            self.dim.buffer_y + rect.y,
+            rect.width,
+            rect.height,
+        )
+    }
 
-        let base = (self.ls3_pt - self.ls1_pt) * self.l;
-        let up = ((self.ls3_pt + self.ls1_pt) * -0.5) + self.ls2_pt;
-        let temp = (if self.l < 0.5 { self.l } else { 1.0 - self.l }) * 2.0 * self.s;
-        self.sl_dot = base + up * temp + self.ls1_pt;
+    fn draw_bar_marker(
+        &self,
+        buffer: &mut TheRGBABuffer,
+        rect: &TheDim,
+        y: i32,
+        ctx: &mut TheContext,
+    ) {
+        let stride = buffer.stride();
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &(
+                (rect.x - 2).max(0) as usize,
+                (y - 2).max(0) as usize,
+                rect.width as usize + 4,
+                5,
+            ),
+            stride,
+            &BLACK,
+        );
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &(
+                (rect.x - 1).max(0) as usize,
+                (y - 1).max(0) as usize,
+                rect.width as usize + 2,
+                3,
+            ),
+            stride,
+            &WHITE,
+        );
     }
 }
@@ -0,0 +1,423 @@
+use crate::prelude::*;
+
+const HANDLE_SIZE: i32 = 7;
+// How far a point has to be dragged off the widget to be deleted.
+const REMOVE_DISTANCE: i32 = 16;
+
+/// Edits a curve mapping 0..1 to 0..1, given by control points sorted by x. Click to add a
+/// point, drag points to move them or off the widget to delete them. The end points stay at
+/// x = 0 and x = 1.
+pub struct TheCurveEditor {
+    id: TheId,
+    limiter: TheSizeLimiter,
+
+    status: Option<String>,
+
+    dim: TheDim,
+    is_dirty: bool,
+
+    points: Vec<Vec2<f32>>,
+
+    selected: Option<usize>,
+    drag: Option<usize>,
+    removing: bool,
+    changed: bool,
+    continuous: bool,
+}
+
+impl TheWidget for TheCurveEditor {
+    fn new(id: TheId) -> Self
+    where
+        Self: Sized,
+    {
+        let mut limiter = TheSizeLimiter::new();
+        limiter.set_max_size(Vec2::new(200, 120));
+
+        Self {
+            id,
+            limiter,
+
+            status: None,
+
+            dim: TheDim::zero(),
+            is_dirty: false,
+
+            points: vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)],
+
+            selected: None,
+            drag: None,
+            removing: false,
+            changed: false,
+            continuous: false,
+        }
+    }
+
+    fn id(&self) -> &TheId {
+        &self.id
+    }
+
+    fn status_text(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn set_status_text(&mut self, text: &str) {
+        self.status = Some(text.to_string());
+    }
+
+    fn on_event(&mut self, event: &TheEvent, ctx: &mut TheContext) -> bool {
+        let mut redraw = false;
+        match event {
+            TheEvent::MouseDown(coord) => {
+                ctx.ui.set_focus(self.id());
+
+                if let Some(index) = self.point_at(*coord) {
+                    self.selected = Some(index);
+                    self.drag = Some(index);
+                } else if self.area().contains(*coord) {
+                    let point = self.position_at(*coord);
+                    let index = self.points.partition_point(|p| p.x < point.x);
+                    let index = index.clamp(1, self.points.len() - 1);
+                    self.points.insert(index, point);
+                    self.selected = Some(index);
+                    self.drag = Some(index);
+                    self.changed = true;
+                    self.emit(ctx);
+                }
+                self.is_dirty = true;
+                redraw = true;
+            }
+            TheEvent::MouseDragged(coord) if self.drag.is_some() => {
+                if let Some(index) = self.drag {
+                    let mut point = self.position_at(*coord);
+                    let last = self.points.len() - 1;
+                    if index == 0 {
+                        point.x = 0.0;
+                    } else if index == last {
+                        point.x = 1.0;
+                    } else {
+                        point.x = point
+                            .x
+                            .clamp(self.points[index - 1].x, self.points[index + 1].x);
+                    }
+                    self.points[index] = point;
+
+                    let area = self.area();
+                    self.removing = index != 0
+                        && index != last
+                        && (coord.y < area.y - REMOVE_DISTANCE
+                            || coord.y > area.y + area.height + REMOVE_DISTANCE);
+                    self.changed = true;
+                    if self.continuous && !self.removing {
+                        self.emit(ctx);
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+            }
+            TheEvent::MouseUp(_coord) => {
+                if let Some(index) = self.drag {
+                    if self.removing {
+                        self.points.remove(index);
+                        self.selected = None;
+                    }
+                    if self.changed {
+                        self.emit(ctx);
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+                self.drag = None;
+                self.removing = false;
+                self.changed = false;
+            }
+            TheEvent::KeyCodeDown(TheValue::KeyCode(TheKeyCode::Delete)) => {
+                if let Some(index) = self.selected {
+                    if index != 0 && index + 1 < self.points.len() {
+                        self.points.remove(index);
+                        self.selected = None;
+                        self.emit(ctx);
+                        self.is_dirty = true;
+                        redraw = true;
+                    }
+                }
+            }
+            TheEvent::Hover(_coord) if !self.id().equals(&ctx.ui.hover) => {
+                ctx.ui.set_hover(self.id());
+            }
+            _ => {}
+        }
+        redraw
+    }
+
+    fn dim(&self) -> &TheDim {
+        &self.dim
+    }
+
+    fn dim_mut(&mut self) -> &mut TheDim {
+        &mut self.dim
+    }
+
+    fn set_dim(&mut self, dim: TheDim, _ctx: &mut TheContext) {
+        if self.dim != dim {
+            self.dim = dim;
+            self.is_dirty = true;
+        }
+    }
+
+    fn limiter(&self) -> &TheSizeLimiter {
+        &self.limiter
+    }
+
+    fn limiter_mut(&mut self) -> &mut TheSizeLimiter {
+        &mut self.limiter
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        self.is_dirty
+    }
+
+    fn set_needs_redraw(&mut self, redraw: bool) {
+        self.is_dirty = redraw;
+    }
+
+    fn supports_hover(&mut self) -> bool {
+        true
+    }
+
+    fn value(&self) -> TheValue {
+        TheValue::List(self.points.iter().map(|p| TheValue::Float2(*p)).collect())
+    }
+
+    fn set_value(&mut self, value: TheValue) {
+        if let TheValue::List(list) = value {
+            let mut points: Vec<Vec2<f32>> = list
+                .iter()
+                .filter_map(|v| v.to_vec2f())
+                .map(|p| p.clamped(Vec2::zero(), Vec2::one()))
+                .collect();
+            points.sort_by(|a, b| a.x.total_cmp(&b.x));
+            if points.len() >= 2 {
+                points[0].x = 0.0;
+                if let Some(last) = points.last_mut() {
+                    last.x = 1.0;
+                }
+                self.points = points;
+                self.selected = self.selected.filter(|index| *index < self.points.len());
+                self.is_dirty = true;
+            }
+        }
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &mut TheRGBABuffer,
+        style: &mut Box<dyn TheStyle>,
+        ctx: &mut TheContext,
+    ) {
+        let area = self.area();
+        if !self.dim().is_valid() || !area.is_valid() {
+            return;
+        }
+
+        let stride = buffer.stride();
+
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &self.dim.to_buffer_utuple(),
+            stride,
+            style.theme().color(DefaultWidgetBackground),
+        );
+
+        let rect = self.to_buffer_dim(&area);
+
+        // Quarter grid
+        for i in 1..4 {
+            let x = rect.x + rect.width * i / 4;
+            let y = rect.y + rect.height * i / 4;
+            buffer.draw_line(x, rect.y, x, rect.y + rect.height - 1, [70, 70, 70, 255]);
+            buffer.draw_line(rect.x, y, rect.x + rect.width - 1, y, [70, 70, 70, 255]);
+        }
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &rect.to_buffer_utuple(),
+            stride,
+            &BLACK,
+        );
+
+        // The curve, without the point which is about to be removed.
+        let mut points = self.points.clone();
+        if self.removing {
+            if let Some(index) = self.drag {
+                points.remove(index);
+            }
+        }
+        let to_y = |v: f32| rect.y + ((1.0 - v) * (rect.height - 1) as f32).round() as i32;
+        let mut last = to_y(Self::sample(&points, 0.0));
+        for x in 1..rect.width {
+            let y = to_y(Self::sample(&points, x as f32 / (rect.width - 1) as f32));
+            buffer.draw_line(rect.x + x - 1, last, rect.x + x, y, [220, 220, 220, 255]);
+            last = y;
+        }
+
+        for (index, point) in self.points.iter().enumerate() {
+            if self.removing && self.drag == Some(index) {
+                continue;
+            }
+            let handle = self.to_buffer_dim(&self.handle_rect(*point));
+            let color = if self.selected == Some(index) {
+                &WHITE
+            } else {
+                &BLACK
+            };
+            ctx.draw.rect(
+                buffer.pixels_mut(),
+                &handle.to_buffer_utuple(),
+                stride,
+                color,
+            );
+        }
+
+        self.is_dirty = false;
+    }
+
+    fn as_curve_editor(&mut self) -> Option<&mut dyn TheCurveEditorTrait> {
+        Some(self)
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl TheCurveEditor {
+    /// Evaluates the curve given by the control points at x, using cubic Hermite interpolation
+    /// with finite difference tangents. The result is clamped to 0..1.
+    pub fn sample(points: &[Vec2<f32>], x: f32) -> f32 {
+        let Some(first) = points.first() else {
+            return x;
+        };
+        if points.len() == 1 || x <= first.x {
+            return first.y;
+        }
+        let last = points[points.len() - 1];
+        if x >= last.x {
+            return last.y;
+        }
+
+        let i = points
+            .partition_point(|p| p.x <= x)
+            .clamp(1, points.len() - 1)
+            - 1;
+        let (p0, p1) = (points[i], points[i + 1]);
+        let width = p1.x - p0.x;
+        if width <= f32::EPSILON {
+            return p1.y;
+        }
+
+        let tangent = |k: usize| {
+            let a = points[k.saturating_sub(1)];
+            let b = points[(k + 1).min(points.len() - 1)];
+            if b.x - a.x > f32::EPSILON {
+                (b.y - a.y) / (b.x - a.x) * width
+            } else {
+                0.0
+            }
+        };
+        let (m0, m1) = (tangent(i), tangent(i + 1));
+
+        let t = (x - p0.x) / width;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * p0.y
+            + (t3 - 2.0 * t2 + t) * m0
+            + (-2.0 * t3 + 3.0 * t2) * p1.y
+            + (t3 - t2) * m1;
+        y.clamp(0.0, 1.0)
+    }
+
+    /// Sends the points as the new value.
+    fn emit(&mut self, ctx: &mut TheContext) {
+        ctx.ui.send_widget_value_changed(&self.id, self.value());
+    }
+
+    /// The local rectangle of the curve area, inset so that the handles fit.
+    fn area(&self) -> TheDim {
+        let inset = HANDLE_SIZE / 2 + 1;
+        TheDim::new(
+            inset,
+            inset,
+            self.dim.width - inset * 2,
+            self.dim.height - inset * 2,
+        )
+    }
+
+    /// The curve position of a local coordinate.
+    fn position_at(&self, coord: Vec2<i32>) -> Vec2<f32> {
+        let area = self.area();
+        Vec2::new(
+            (coord.x - area.x) as f32 / (area.width - 1).max(1) as f32,
+            1.0 - (coord.y - area.y) as f32 / (area.height - 1).max(1) as f32,
+        )
+        .clamped(Vec2::zero(), Vec2::one())
+    }
+
+    /// The local rectangle of the handle of a point.
+    fn handle_rect(&self, point: Vec2<f32>) -> TheDim {
+        let area = self.area();
+        let x = area.x + (point.x * (area.width - 1) as f32).round() as i32;
+        let y = area.y + ((1.0 - point.y) * (area.height - 1) as f32).round() as i32;
+        TheDim::new(
+            x - HANDLE_SIZE / 2,
+            y - HANDLE_SIZE / 2,
+            HANDLE_SIZE,
+            HANDLE_SIZE,
+        )
+    }
+
+    /// Returns the point whose handle is at the local coordinate, preferring the selected one.
+    fn point_at(&self, coord: Vec2<i32>) -> Option<usize> {
+        if let Some(selected) = self.selected {
+            if let Some(point) = self.points.get(selected) {
+                if self.handle_rect(*point).contains(coord) {
+                    return Some(selected);
+                }
+            }
+        }
+        (0..self.points.len())
+            .rev()
+            .find(|index| self.handle_rect(self.points[*index]).contains(coord))
+    }
+
+    fn to_buffer_dim(&self, rect: &TheDim) -> TheDim {
+        TheDim::new(
+            self.dim.buffer_x + rect.x,
+            self.dim.buffer_y + rect.y,
+            rect.width,
+            rect.height,
+        )
+    }
+}
+
+/// TheCurveEditor specific functions.
+pub trait TheCurveEditorTrait: TheWidget {
+    /// Returns the control points.
+    fn points(&self) -> Vec<Vec2<f32>>;
+    /// Sets the control points. At least two points are required.
+    fn set_points(&mut self, points: Vec<Vec2<f32>>);
+    /// Send value changes while dragging points.
+    fn set_continuous(&mut self, continuous: bool);
+}
+
+impl TheCurveEditorTrait for TheCurveEditor {
+    fn points(&self) -> Vec<Vec2<f32>> {
+        self.points.clone()
+    }
+    fn set_points(&mut self, points: Vec<Vec2<f32>>) {
+        self.set_value(TheValue::List(
+            points.into_iter().map(TheValue::Float2).collect(),
+        ));
+    }
+    fn set_continuous(&mut self, continuous: bool) {
+        self.continuous = continuous;
+    }
+}
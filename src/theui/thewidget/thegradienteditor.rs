@@ -0,0 +1,438 @@
+use super::thecolorpicker::{blend_over_checker, shade_rect};
+use crate::prelude::*;
+use web_time::Instant;
+
+const HANDLE_WIDTH: i32 = 9;
+const HANDLE_HEIGHT: i32 = 10;
+// How far a stop has to be dragged off the widget vertically to be deleted.
+const REMOVE_DISTANCE: i32 = 16;
+
+/// Edits a TheGradient. Click on the bar to add a stop, drag stops to move them or off the widget
+/// to delete them, double-click a stop to edit its color. The color space is chosen via the
+/// context menu.
+pub struct TheGradientEditor {
+    id: TheId,
+    limiter: TheSizeLimiter,
+
+    status: Option<String>,
+
+    dim: TheDim,
+    is_dirty: bool,
+
+    gradient: TheGradient,
+
+    selected: Option<usize>,
+    drag: Option<usize>,
+    removing: bool,
+    changed: bool,
+    continuous: bool,
+
+    last_mouse_down_time: Instant,
+    last_mouse_down_stop: Option<usize>,
+
+    bar_rect: TheDim,
+
+    context_menu: Option<TheContextMenu>,
+}
+
+impl TheWidget for TheGradientEditor {
+    fn new(id: TheId) -> Self
+    where
+        Self: Sized,
+    {
+        let mut limiter = TheSizeLimiter::new();
+        limiter.set_max_size(Vec2::new(200, 34));
+
+        Self {
+            id,
+            limiter,
+
+            status: None,
+
+            dim: TheDim::zero(),
+            is_dirty: false,
+
+            gradient: TheGradient::default(),
+
+            selected: None,
+            drag: None,
+            removing: false,
+            changed: false,
+            continuous: false,
+
+            last_mouse_down_time: Instant::now(),
+            last_mouse_down_stop: None,
+
+            bar_rect: TheDim::zero(),
+
+            context_menu: Some(Self::default_context_menu()),
+        }
+    }
+
+    fn id(&self) -> &TheId {
+        &self.id
+    }
+
+    fn status_text(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn set_status_text(&mut self, text: &str) {
+        self.status = Some(text.to_string());
+    }
+
+    fn set_context_menu(&mut self, menu: Option<TheContextMenu>) {
+        self.context_menu = menu;
+    }
+
+    fn on_event(&mut self, event: &TheEvent, ctx: &mut TheContext) -> bool {
+        let mut redraw = false;
+        // println!("event ({}): {:?}", self.widget_id.name, event);
+        match event {
+            TheEvent::MouseDown(coord) => {
+                ctx.ui.set_focus(self.id());
+
+                if let Some(index) = self.stop_at(*coord) {
+                    let is_double_click = self.last_mouse_down_time.elapsed().as_millis() < 500
+                        && self.last_mouse_down_stop == Some(index);
+
+                    self.selected = Some(index);
+                    if is_double_click {
+                        let x = self.stop_x(self.gradient.stops[index].position);
+                        ctx.ui.send(TheEvent::ShowColorPopup(
+                            self.id.clone(),
+                            Vec2::new(self.dim.x + x, self.dim.y + self.dim.height),
+                            self.gradient.stops[index].color.clone(),
+                        ));
+                    } else {
+                        self.drag = Some(index);
+                    }
+                    self.last_mouse_down_stop = Some(index);
+                } else if self.bar_rect.contains(*coord) {
+                    // Add a stop with the color of the gradient at that position.
+                    let t = self.position_at(coord.x);
+                    let color = self.gradient.sample(t);
+                    let index = self.gradient.add_stop(t, color);
+                    self.selected = Some(index);
+                    self.drag = Some(index);
+                    self.last_mouse_down_stop = None;
+                    self.changed = true;
+                    self.emit(ctx);
+                } else {
+                    self.last_mouse_down_stop = None;
+                }
+                self.last_mouse_down_time = Instant::now();
+                self.is_dirty = true;
+                redraw = true;
+            }
+            TheEvent::MouseDragged(coord) if self.drag.is_some() => {
+                if let Some(index) = self.drag {
+                    let index = self
+                        .gradient
+                        .set_stop_position(index, self.position_at(coord.x));
+                    self.drag = Some(index);
+                    self.selected = Some(index);
+                    self.last_mouse_down_stop = Some(index);
+                    self.removing = self.gradient.stops.len() > 2
+                        && (coord.y < -REMOVE_DISTANCE
+                            || coord.y > self.dim.height + REMOVE_DISTANCE);
+                    self.changed = true;
+                    if self.continuous && !self.removing {
+                        self.emit(ctx);
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+            }
+            TheEvent::MouseUp(_coord) => {
+                if let Some(index) = self.drag {
+                    if self.removing {
+                        self.gradient.remove_stop(index);
+                        self.selected = None;
+                        self.last_mouse_down_stop = None;
+                    }
+                    if self.changed {
+                        self.emit(ctx);
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+                self.drag = None;
+                self.removing = false;
+                self.changed = false;
+            }
+            TheEvent::KeyCodeDown(TheValue::KeyCode(TheKeyCode::Delete)) => {
+                if let Some(index) = self.selected {
+                    if self.gradient.stops.len() > 2 {
+                        self.gradient.remove_stop(index);
+                        self.selected = None;
+                        self.emit(ctx);
+                        self.is_dirty = true;
+                        redraw = true;
+                    }
+                }
+            }
+            TheEvent::Context(coord) => {
+                if let Some(context_menu) = &self.context_menu {
+                    ctx.ui.send(TheEvent::ShowContextMenu(
+                        self.id().clone(),
+                        *coord,
+                        context_menu.clone(),
+                    ));
+                }
+            }
+            TheEvent::ContextMenuSelected(_widget_id, item_id) => {
+                if let Some(space) = TheColorSpace::iterator()
+                    .find(|space| item_id.name == format!("Gradient Space {}", space.to_string()))
+                {
+                    if self.gradient.space != space {
+                        self.gradient.space = space;
+                        self.emit(ctx);
+                        self.is_dirty = true;
+                        redraw = true;
+                    }
+                }
+            }
+            TheEvent::Hover(_coord) if !self.id().equals(&ctx.ui.hover) => {
+                ctx.ui.set_hover(self.id());
+            }
+            _ => {}
+        }
+        redraw
+    }
+
+    fn dim(&self) -> &TheDim {
+        &self.dim
+    }
+
+    fn dim_mut(&mut self) -> &mut TheDim {
+        &mut self.dim
+    }
+
+    fn set_dim(&mut self, dim: TheDim, _ctx: &mut TheContext) {
+        if self.dim != dim {
+            self.dim = dim;
+            let x = HANDLE_WIDTH / 2 + 1;
+            self.bar_rect = TheDim::new(x, 2, dim.width - x * 2, dim.height - HANDLE_HEIGHT - 6);
+            self.is_dirty = true;
+        }
+    }
+
+    fn limiter(&self) -> &TheSizeLimiter {
+        &self.limiter
+    }
+
+    fn limiter_mut(&mut self) -> &mut TheSizeLimiter {
+        &mut self.limiter
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        self.is_dirty
+    }
+
+    fn set_needs_redraw(&mut self, redraw: bool) {
+        self.is_dirty = redraw;
+    }
+
+    fn supports_hover(&mut self) -> bool {
+        true
+    }
+
+    fn value(&self) -> TheValue {
+        TheValue::Gradient(self.gradient.clone())
+    }
+
+    fn set_value(&mut self, value: TheValue) {
+        match value {
+            TheValue::Gradient(gradient) => {
+                self.gradient = gradient;
+                self.selected = self
+                    .selected
+                    .filter(|index| *index < self.gradient.stops.len());
+                self.is_dirty = true;
+            }
+            // Sent by the color popup for the selected stop.
+            TheValue::ColorObject(color) => {
+                if let Some(index) = self.selected {
+                    self.gradient.set_stop_color(index, color);
+                    self.is_dirty = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &mut TheRGBABuffer,
+        style: &mut Box<dyn TheStyle>,
+        ctx: &mut TheContext,
+    ) {
+        if !self.dim().is_valid() || !self.bar_rect.is_valid() {
+            return;
+        }
+
+        let stride = buffer.stride();
+
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &self.dim.to_buffer_utuple(),
+            stride,
+            style.theme().color(DefaultWidgetBackground),
+        );
+
+        // The gradient over a checkerboard, without the stop which is about to be removed.
+        let mut gradient = self.gradient.clone();
+        if self.removing {
+            if let Some(index) = self.drag {
+                gradient.remove_stop(index);
+            }
+        }
+        let rect = self.to_buffer_dim(&self.bar_rect);
+        let colors: Vec<([u8; 4], f32)> = gradient
+            .colors(rect.width as usize)
+            .into_iter()
+            .map(|c| (c.to_u8_array(), c.a))
+            .collect();
+        shade_rect(buffer, &rect, |x, y| {
+            let (color, alpha) = &colors[x as usize];
+            blend_over_checker(color, *alpha, x, y)
+        });
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &rect.to_buffer_utuple(),
+            stride,
+            &BLACK,
+        );
+
+        // The stop handles, the selected one is drawn last.
+        let mut order: Vec<usize> = (0..self.gradient.stops.len())
+            .filter(|index| !(self.removing && self.drag == Some(*index)))
+            .collect();
+        if let Some(selected) = self.selected {
+            order.retain(|index| *index != selected);
+            if selected < self.gradient.stops.len()
+                && !(self.removing && self.drag == Some(selected))
+            {
+                order.push(selected);
+            }
+        }
+
+        for index in order {
+            let rect = self.to_buffer_dim(&self.handle_rect(index));
+            let utuple = rect.to_buffer_utuple();
+            let outline = if self.selected == Some(index) {
+                &WHITE
+            } else {
+                &BLACK
+            };
+            ctx.draw
+                .rect_outline(buffer.pixels_mut(), &utuple, stride, outline);
+            ctx.draw.rect(
+                buffer.pixels_mut(),
+                &(utuple.0 + 1, utuple.1 + 1, utuple.2 - 2, utuple.3 - 2),
+                stride,
+                &self.gradient.stops[index].color.to_u8_array(),
+            );
+        }
+
+        self.is_dirty = false;
+    }
+
+    fn as_gradient_editor(&mut self) -> Option<&mut dyn TheGradientEditorTrait> {
+        Some(self)
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl TheGradientEditor {
+    /// The built-in context menu which selects the interpolation color space.
+    fn default_context_menu() -> TheContextMenu {
+        let mut menu = TheContextMenu::named("Gradient".to_string());
+        for space in TheColorSpace::iterator() {
+            menu.add(TheContextMenuItem::new(
+                space.to_string().to_string(),
+                TheId::named(&format!("Gradient Space {}", space.to_string())),
+            ));
+        }
+        menu
+    }
+
+    /// Sends the gradient as the new value.
+    fn emit(&mut self, ctx: &mut TheContext) {
+        ctx.ui
+            .send_widget_value_changed(&self.id, TheValue::Gradient(self.gradient.clone()));
+    }
+
+    /// The local x coordinate of a stop position.
+    fn stop_x(&self, position: f32) -> i32 {
+        self.bar_rect.x + (position * (self.bar_rect.width - 1) as f32).round() as i32
+    }
+
+    /// The gradient position of a local x coordinate.
+    fn position_at(&self, x: i32) -> f32 {
+        ((x - self.bar_rect.x) as f32 / (self.bar_rect.width - 1).max(1) as f32).clamp(0.0, 1.0)
+    }
+
+    /// The local rectangle of the handle of the stop.
+    fn handle_rect(&self, index: usize) -> TheDim {
+        let x = self.stop_x(self.gradient.stops[index].position);
+        TheDim::new(
+            x - HANDLE_WIDTH / 2,
+            self.bar_rect.y + self.bar_rect.height + 2,
+            HANDLE_WIDTH,
+            HANDLE_HEIGHT,
+        )
+    }
+
+    /// Returns the stop whose handle is at the local coordinate, preferring the selected one.
+    fn stop_at(&self, coord: Vec2<i32>) -> Option<usize> {
+        if let Some(selected) = self.selected {
+            if selected < self.gradient.stops.len() && self.handle_rect(selected).contains(coord) {
+                return Some(selected);
+            }
+        }
+        (0..self.gradient.stops.len())
+            .rev()
+            .find(|index| self.handle_rect(*index).contains(coord))
+    }
+
+    fn to_buffer_dim(&self, rect: &TheDim) -> TheDim {
+        TheDim::new(
+            self.dim.buffer_x + rect.x,
+            self.dim.buffer_y + rect.y,
+            rect.width,
+            rect.height,
+        )
+    }
+}
+
+/// TheGradientEditor specific functions.
+pub trait TheGradientEditorTrait: TheWidget {
+    /// Returns the gradient.
+    fn gradient(&self) -> TheGradient;
+    /// Sets the gradient.
+    fn set_gradient(&mut self, gradient: TheGradient);
+    /// Send value changes while dragging stops.
+    fn set_continuous(&mut self, continuous: bool);
+    /// Returns the index of the selected stop.
+    fn selected_stop(&self) -> Option<usize>;
+}
+
+impl TheGradientEditorTrait for TheGradientEditor {
+    fn gradient(&self) -> TheGradient {
+        self.gradient.clone()
+    }
+    fn set_gradient(&mut self, gradient: TheGradient) {
+        self.set_value(TheValue::Gradient(gradient));
+    }
+    fn set_continuous(&mut self, continuous: bool) {
+        self.continuous = continuous;
+    }
+    fn selected_stop(&self) -> Option<usize> {
+        self.selected
+    }
+}
@@ -7,6 +7,7 @@ pub struct TheIconView {
     is_dirty: bool,
     tile: TheRGBATile,
     index: usize,
+    elapsed: u32,
 
     text: Option<String>,
     text_size: f32,
@@ -34,6 +35,7 @@ impl TheWidget for TheIconView {
             is_dirty: true,
             tile: TheRGBATile::default(),
             index: 0,
+            elapsed: 0,
 
             text: None,
             text_size: 12.0,
@@ -179,6 +181,9 @@ impl TheWidget for TheIconView {
 pub trait TheIconViewTrait {
     fn set_rgba_tile(&mut self, tile: TheRGBATile);
     fn step(&mut self);
+    /// Advances the animation by the elapsed milliseconds, respecting the frame delays of the tile.
+    /// Tiles without delays advance one frame per call.
+    fn advance(&mut self, elapsed: u32);
     fn set_border_color(&mut self, color: Option<RGBA>);
     fn set_text_color(&mut self, color: RGBA);
     /// Set the text to display.
@@ -194,6 +199,7 @@ impl TheIconViewTrait for TheIconView {
         self.tile = tile;
         self.is_dirty = true;
         self.index = 0;
+        self.elapsed = 0;
     }
     fn step(&mut self) {
         if self.tile.buffer.len() >= 2 {
@@ -204,6 +210,27 @@ impl TheIconViewTrait for TheIconView {
             self.is_dirty = true;
         }
     }
+    fn advance(&mut self, elapsed: u32) {
+        if self.tile.buffer.len() < 2 {
+            return;
+        }
+        if self.tile.delays.len() != self.tile.buffer.len() {
+            self.step();
+            return;
+        }
+
+        self.elapsed = self.elapsed.saturating_add(elapsed);
+        // Skip at most one full cycle per call, this also guards against zero delays
+        for _ in 0..self.tile.buffer.len() {
+            let delay = self.tile.delays[self.index].max(1);
+            if self.elapsed < delay {
+                break;
+            }
+            self.elapsed -= delay;
+            self.step();
+        }
+        self.elapsed = self.elapsed.min(self.tile.delays[self.index]);
+    }
     fn set_border_color(&mut self, color: Option<RGBA>) {
         self.border_color = color;
         self.is_dirty = true;
@@ -1,6 +1,7 @@
 use crate::prelude::*;
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
+use web_time::Instant;
 use zeno::{Mask, Stroke};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -27,6 +28,12 @@ pub enum TheNodeAction {
     DragNode,
     ConnectingTerminal(usize, bool, u8),
     CutConnection,
+    BoxSelect,
+    DragFrame(usize),
+    ResizeFrame(usize),
+    NavigateMinimap,
+    /// Dragging a reroute dot: Connection index, dot index.
+    DragReroute(usize, usize),
 }
 
 use TheNodeUIImages::*;
@@ -39,6 +46,8 @@ pub struct TheNodeCanvasView {
     action: TheNodeAction,
 
     render_buffer: TheRGBABuffer,
+    /// The render buffer scaled to the widget size when zoomed.
+    zoom_buffer: TheRGBABuffer,
     wheel_scale: f32,
     accumulated_wheel_delta: Vec2<f32>,
 
@@ -58,6 +67,37 @@ pub struct TheNodeCanvasView {
     action_changed: bool,
 
     overlay: Option<TheRGBABuffer>,
+
+    /// Error messages of the nodes, shown in the node footer.
+    node_errors: FxHashMap<usize, String>,
+
+    /// The node positions at the start of a drag, for the undo of the move.
+    drag_positions: Vec<(Uuid, Vec2<i32>)>,
+    undo_stack: TheUndoStack,
+
+    /// The frame being dragged or resized, as it was when the action started.
+    frame_start: Option<TheNodeFrame>,
+    /// The nodes inside the dragged frame.
+    frame_nodes: Vec<usize>,
+    selected_frame: Option<usize>,
+
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+
+    /// The reroute dots of the dragged dot's connection, as they were when the drag started.
+    reroute_start: Vec<Vec2<i32>>,
+
+    /// Animates the connections of the selected node, redrawing the widget continuously.
+    flow_animation: bool,
+    flow_start: Instant,
+    last_flow_draw: Instant,
+
+    minimap: bool,
+    /// The minimap rect in widget coordinates, if it is visible.
+    minimap_rect: Option<TheDim>,
+    /// The canvas coordinate of the minimap origin and the minimap scale.
+    minimap_transform: (Vec2<i32>, f32),
 }
 
 impl TheWidget for TheNodeCanvasView {
@@ -75,6 +115,7 @@ impl TheWidget for TheNodeCanvasView {
             action: TheNodeAction::None,
 
             render_buffer: TheRGBABuffer::new(TheDim::new(0, 0, 20, 20)),
+            zoom_buffer: TheRGBABuffer::empty(),
             wheel_scale: -0.4,
             accumulated_wheel_delta: Vec2::zero(),
 
@@ -94,6 +135,29 @@ impl TheWidget for TheNodeCanvasView {
             action_changed: false,
 
             overlay: None,
+
+            node_errors: FxHashMap::default(),
+
+            drag_positions: vec![],
+            undo_stack: TheUndoStack::default(),
+
+            frame_start: None,
+            frame_nodes: vec![],
+            selected_frame: None,
+
+            shift: false,
+            ctrl: false,
+            alt: false,
+
+            reroute_start: vec![],
+
+            flow_animation: false,
+            flow_start: Instant::now(),
+            last_flow_draw: Instant::now(),
+
+            minimap: true,
+            minimap_rect: None,
+            minimap_transform: (Vec2::zero(), 1.0),
         }
     }
 
@@ -117,20 +181,32 @@ impl TheWidget for TheNodeCanvasView {
                 ctx.ui.set_focus(self.id());
                 self.action_changed = false;
 
+                if self.minimap_rect.is_some_and(|rect| rect.contains(*coord)) {
+                    self.action = TheNodeAction::NavigateMinimap;
+                    self.navigate_minimap(*coord, ctx);
+                    return true;
+                }
+
                 let selected = self.node_index_at(coord);
+                let coord = &self.unzoom(*coord);
                 if let Some(index) = selected {
-                    if selected != self.canvas.selected_node {
-                        self.canvas.selected_node = Some(index);
+                    let previous = (self.canvas.selected_node, self.canvas.selection.clone());
+                    let modifier = self.shift || self.ctrl;
+                    if modifier && self.canvas.is_selected(index) {
+                        self.canvas.deselect(index);
+                    } else {
+                        // Clicking a node of the selection keeps the selection for dragging.
+                        let add = modifier || self.canvas.is_selected(index);
+                        self.canvas.select(index, add);
+                    }
+                    if self.selected_frame.take().is_some() {
                         self.is_dirty = true;
-                        redraw = true;
-
-                        ctx.ui.send(TheEvent::NodeSelectedIndexChanged(
-                            self.id().clone(),
-                            self.canvas.selected_node,
-                        ));
                     }
+                    redraw = self.send_selection_changed(previous, ctx) || redraw;
 
-                    if let Some(terminal) = self.terminal_at(
+                    if !self.canvas.is_selected(index) {
+                        self.action = TheNodeAction::None;
+                    } else if let Some(terminal) = self.terminal_at(
                         index,
                         *coord + self.canvas.offset - self.canvas.nodes[index].position,
                     ) {
@@ -148,13 +224,78 @@ impl TheWidget for TheNodeCanvasView {
                     } else {
                         self.drag_start = *coord;
                         self.action = TheNodeAction::DragNode;
+                        self.drag_positions = self
+                            .canvas
+                            .nodes
+                            .iter()
+                            .map(|node| (node.id, node.position))
+                            .collect();
                     }
+                } else if let Some((connection, point)) = self.reroute_at(coord) {
+                    let mut points = self
+                        .canvas
+                        .reroute_points(&self.canvas.connections[connection])
+                        .to_vec();
+                    if self.alt {
+                        // Alt-click removes the dot
+                        points.remove(point);
+                        let command = self
+                            .canvas
+                            .set_reroutes_command(&self.canvas.connections[connection], points);
+                        self.execute(command, ctx);
+                    } else {
+                        self.reroute_start = points;
+                        self.action = TheNodeAction::DragReroute(connection, point);
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                } else if let Some((connection, segment)) = self.wire_at(coord).filter(|_| self.alt)
+                {
+                    // Alt-click on a connection inserts a reroute dot
+                    let mut points = self
+                        .canvas
+                        .reroute_points(&self.canvas.connections[connection])
+                        .to_vec();
+                    self.reroute_start = points.clone();
+                    points.insert(segment, *coord + self.canvas.offset);
+                    self.set_reroute_points(connection, points);
+                    self.action = TheNodeAction::DragReroute(connection, segment);
+                    self.is_dirty = true;
+                    redraw = true;
+                } else if let Some((frame_index, resize)) = self.frame_at(coord) {
+                    self.selected_frame = Some(frame_index);
+                    self.frame_start = Some(self.canvas.frames[frame_index].clone());
+                    self.drag_start = *coord;
+                    if resize {
+                        self.action = TheNodeAction::ResizeFrame(frame_index);
+                    } else {
+                        self.action = TheNodeAction::DragFrame(frame_index);
+                        self.frame_nodes = self.canvas.frame_nodes(frame_index);
+                        self.drag_positions = self
+                            .canvas
+                            .nodes
+                            .iter()
+                            .map(|node| (node.id, node.position))
+                            .collect();
+                    }
+                    self.is_dirty = true;
+                    redraw = true;
+                } else if self.shift {
+                    self.action = TheNodeAction::BoxSelect;
+                    self.drag_start = *coord;
+                    self.drag_offset = *coord;
                 } else {
                     self.action = TheNodeAction::CutConnection;
                     self.drag_start = *coord;
                 }
             }
             TheEvent::MouseDragged(coord) => {
+                if self.action == TheNodeAction::NavigateMinimap {
+                    self.navigate_minimap(*coord, ctx);
+                    return true;
+                }
+
+                let coord = &self.unzoom(*coord);
                 if let TheNodeAction::CutConnection = self.action {
                     self.drag_offset = *coord;
                     self.is_dirty = true;
@@ -166,19 +307,18 @@ impl TheWidget for TheNodeCanvasView {
                     self.action_changed = true;
                     redraw = true;
                 } else if self.action == TheNodeAction::DragNode {
-                    if let Some(index) = self.canvas.selected_node {
+                    if self.canvas.selected_node.is_some() {
                         let displacement =
                             Vec2::new(coord.x - self.drag_start.x, coord.y - self.drag_start.y);
 
-                        // Move the selected node
-                        self.canvas.nodes[index].position.x += displacement.x;
-                        self.canvas.nodes[index].position.y += displacement.y;
-
-                        // Now find and move all connected nodes
-                        let connected_nodes = self.find_connected_nodes(index);
-                        for &connected_index in &connected_nodes {
-                            self.canvas.nodes[connected_index].position.x += displacement.x;
-                            self.canvas.nodes[connected_index].position.y += displacement.y;
+                        // Move the selected nodes and all nodes connected to them
+                        let mut moved = FxHashSet::default();
+                        for index in self.canvas.selected_indices() {
+                            moved.insert(index);
+                            moved.extend(self.find_connected_nodes(index));
+                        }
+                        for index in moved {
+                            self.canvas.nodes[index].position += displacement;
                         }
 
                         self.drag_start = *coord;
@@ -186,43 +326,92 @@ impl TheWidget for TheNodeCanvasView {
                         self.action_changed = true;
                         redraw = true;
                     }
+                } else if self.action == TheNodeAction::BoxSelect {
+                    self.drag_offset = *coord;
+                    self.is_dirty = true;
+                    self.action_changed = true;
+                    redraw = true;
+                } else if let TheNodeAction::DragReroute(connection, point) = self.action {
+                    if let Some(c) = self.canvas.connections.get(connection) {
+                        let mut points = self.canvas.reroute_points(c).to_vec();
+                        if let Some(p) = points.get_mut(point) {
+                            *p = *coord + self.canvas.offset;
+                        }
+                        self.set_reroute_points(connection, points);
+                    }
+                    self.is_dirty = true;
+                    self.action_changed = true;
+                    redraw = true;
+                } else if let TheNodeAction::DragFrame(frame_index) = self.action {
+                    let displacement = *coord - self.drag_start;
+                    if let Some(frame) = self.canvas.frames.get_mut(frame_index) {
+                        frame.position += displacement;
+                    }
+                    for index in &self.frame_nodes {
+                        if let Some(node) = self.canvas.nodes.get_mut(*index) {
+                            node.position += displacement;
+                        }
+                    }
+                    self.drag_start = *coord;
+                    self.is_dirty = true;
+                    self.action_changed = true;
+                    redraw = true;
+                } else if let TheNodeAction::ResizeFrame(frame_index) = self.action {
+                    let coord = *coord + self.canvas.offset;
+                    if let Some(frame) = self.canvas.frames.get_mut(frame_index) {
+                        frame.size.x = (coord.x - frame.position.x).max(60);
+                        frame.size.y = (coord.y - frame.position.y).max(40);
+                    }
+                    self.is_dirty = true;
+                    self.action_changed = true;
+                    redraw = true;
                 }
             }
             TheEvent::MouseUp(coord) => {
+                let node_index = self.node_index_at(coord);
+                let coord = &self.unzoom(*coord);
                 if let TheNodeAction::ConnectingTerminal(
                     source_node_index,
                     source_output,
                     source_terminal_index,
                 ) = self.action
                 {
-                    if let Some(dest_node_index) = self.node_index_at(coord) {
+                    if let Some(dest_node_index) = node_index {
                         if let Some((dest_output, dest_terminal_index)) = self.terminal_at(
                             dest_node_index,
                             *coord + self.canvas.offset
                                 - self.canvas.nodes[dest_node_index].position,
                         ) {
-                            if source_node_index != dest_node_index && source_output != dest_output
-                            {
-                                if source_output {
-                                    self.canvas.connections.push((
-                                        source_node_index as u16,
-                                        source_terminal_index,
-                                        dest_node_index as u16,
-                                        dest_terminal_index,
-                                    ));
-                                } else {
-                                    self.canvas.connections.push((
-                                        dest_node_index as u16,
-                                        dest_terminal_index,
-                                        source_node_index as u16,
-                                        source_terminal_index,
-                                    ));
-                                }
+                            let source = (source_node_index, source_output, source_terminal_index);
+                            let dest = (dest_node_index, dest_output, dest_terminal_index);
+                            // Dropping on the terminal the drag started from is not an attempt.
+                            if source != dest {
+                                match self.check_connection(source, dest) {
+                                    (connection, Ok(())) => {
+                                        self.canvas.connect(&connection);
+
+                                        if let Some(connection) =
+                                            self.canvas.connection_id(&connection)
+                                        {
+                                            self.add_undo(
+                                                TheNodeCommand::Connect(connection, vec![]),
+                                                ctx,
+                                            );
+                                        }
 
-                                ctx.ui.send(TheEvent::NodeConnectionAdded(
-                                    self.id().clone(),
-                                    self.canvas.connections.clone(),
-                                ));
+                                        ctx.ui.send(TheEvent::NodeConnectionAdded(
+                                            self.id().clone(),
+                                            self.canvas.index_connections(),
+                                        ));
+                                    }
+                                    (connection, Err(err)) => {
+                                        ctx.ui.send(TheEvent::NodeConnectionRejected(
+                                            self.id().clone(),
+                                            connection,
+                                            err,
+                                        ));
+                                    }
+                                }
                             }
                         }
                     }
@@ -237,62 +426,124 @@ impl TheWidget for TheNodeCanvasView {
                             self.canvas.nodes[index].position,
                         ));
                     }
+
+                    let moves: Vec<(Uuid, Vec2<i32>, Vec2<i32>)> =
+                        std::mem::take(&mut self.drag_positions)
+                            .into_iter()
+                            .filter_map(|(id, from)| {
+                                let to = self.canvas.node(&id)?.position;
+                                (to != from).then_some((id, from, to))
+                            })
+                            .collect();
+                    if !moves.is_empty() {
+                        self.add_undo(TheNodeCommand::MoveNodes(moves), ctx);
+                    }
+                } else if self.action == TheNodeAction::BoxSelect {
+                    let min = self.drag_start.map2(self.drag_offset, |a, b| a.min(b));
+                    let max = self.drag_start.map2(self.drag_offset, |a, b| a.max(b));
+                    let previous = (self.canvas.selected_node, self.canvas.selection.clone());
+                    for (index, rect) in self.node_rects.clone().iter().enumerate() {
+                        if rect.x < max.x
+                            && rect.y < max.y
+                            && rect.x + rect.width > min.x
+                            && rect.y + rect.height > min.y
+                        {
+                            self.canvas.select(index, true);
+                        }
+                    }
+                    self.send_selection_changed(previous, ctx);
+                    self.is_dirty = true;
+                    redraw = true;
+                } else if let TheNodeAction::DragReroute(connection, _) = self.action {
+                    if let Some(connection) = self.canvas.connections.get(connection).copied() {
+                        let points = self.canvas.reroute_points(&connection).to_vec();
+                        if points != self.reroute_start {
+                            let from = std::mem::take(&mut self.reroute_start);
+                            self.add_undo(
+                                TheNodeCommand::SetReroutes {
+                                    connection,
+                                    from,
+                                    to: points,
+                                },
+                                ctx,
+                            );
+                        }
+                    }
+                } else if let TheNodeAction::DragFrame(frame_index)
+                | TheNodeAction::ResizeFrame(frame_index) = self.action
+                {
+                    if let (Some(start), Some(frame)) = (
+                        self.frame_start.take(),
+                        self.canvas.frames.get(frame_index).cloned(),
+                    ) {
+                        let mut commands = vec![];
+                        if start != frame {
+                            commands.push(TheNodeCommand::ChangeFrame(start, frame));
+                        }
+                        let moves: Vec<(Uuid, Vec2<i32>, Vec2<i32>)> =
+                            std::mem::take(&mut self.drag_positions)
+                                .into_iter()
+                                .filter_map(|(id, from)| {
+                                    let to = self.canvas.node(&id)?.position;
+                                    (to != from).then_some((id, from, to))
+                                })
+                                .collect();
+                        if !moves.is_empty() {
+                            commands.push(TheNodeCommand::MoveNodes(moves));
+                        }
+                        if !commands.is_empty() {
+                            self.add_undo(TheNodeCommand::Batch(commands), ctx);
+                        }
+                    }
+                } else if self.action == TheNodeAction::CutConnection && !self.action_changed {
+                    // Clicking the empty canvas reduces the selection to the selected node.
+                    let previous = (self.canvas.selected_node, self.canvas.selection.clone());
+                    self.canvas.selection = self
+                        .canvas
+                        .selected_node
+                        .and_then(|index| self.canvas.nodes.get(index))
+                        .map(|node| vec![node.id])
+                        .unwrap_or_default();
+                    if self.selected_frame.take().is_some() {
+                        self.is_dirty = true;
+                        redraw = true;
+                    }
+                    redraw = self.send_selection_changed(previous, ctx) || redraw;
                 } else if self.action == TheNodeAction::CutConnection && self.action_changed {
-                    let mut new_connections = vec![];
+                    let mut removed = vec![];
                     let cut_start = (self.drag_start.x, self.drag_start.y);
                     let cut_end = (self.drag_offset.x, self.drag_offset.y);
-                    let mut changed = false;
-                    for (
-                        source_node_index,
-                        source_output_index,
-                        dest_node_index,
-                        dest_input_index,
-                    ) in self.canvas.connections.iter()
-                    {
-                        if let Some(mut output) = self.terminal_rect_for(
-                            *source_node_index as usize,
-                            true,
-                            *source_output_index,
-                        ) {
-                            output.x += 10 + 6 - self.canvas.offset.x;
-                            output.y -= self.canvas.offset.y;
-                            output.y += output.height / 2;
-                            output.x += self.canvas.nodes[*source_node_index as usize].position.x;
-                            output.y += self.canvas.nodes[*source_node_index as usize].position.y;
-                            if let Some(mut input) = self.terminal_rect_for(
-                                *dest_node_index as usize,
-                                false,
-                                *dest_input_index,
-                            ) {
-                                input.x -= 6 + self.canvas.offset.x;
-                                input.y -= self.canvas.offset.y;
-                                input.y += input.height / 2;
-                                input.x += self.canvas.nodes[*dest_node_index as usize].position.x;
-                                input.y += self.canvas.nodes[*dest_node_index as usize].position.y;
-
-                                if !do_intersect(
-                                    (output.x, output.y),
-                                    (input.x, input.y),
-                                    cut_start,
-                                    cut_end,
-                                ) {
-                                    new_connections.push((
-                                        *source_node_index,
-                                        *source_output_index,
-                                        *dest_node_index,
-                                        *dest_input_index,
-                                    ));
-                                } else {
-                                    changed = true;
-                                }
-                            }
+                    for connection in self.canvas.connections.iter() {
+                        let cut = self.wire_segments(connection).is_some_and(|segments| {
+                            segments
+                                .iter()
+                                .flatten()
+                                .collect::<Vec<_>>()
+                                .windows(2)
+                                .any(|w| {
+                                    do_intersect(
+                                        (w[0].x as i32, w[0].y as i32),
+                                        (w[1].x as i32, w[1].y as i32),
+                                        cut_start,
+                                        cut_end,
+                                    )
+                                })
+                        });
+                        if cut {
+                            removed.push(TheNodeCommand::Disconnect(
+                                *connection,
+                                self.canvas.reroute_points(connection).to_vec(),
+                            ));
                         }
                     }
-                    if changed {
-                        self.canvas.connections = new_connections;
+                    if !removed.is_empty() {
+                        for command in &removed {
+                            self.canvas.apply(command);
+                        }
+                        self.add_undo(TheNodeCommand::Batch(removed), ctx);
                         ctx.ui.send(TheEvent::NodeConnectionRemoved(
                             self.id().clone(),
-                            self.canvas.connections.clone(),
+                            self.canvas.index_connections(),
                         ));
                     }
                     self.is_dirty = true;
@@ -308,6 +559,11 @@ impl TheWidget for TheNodeCanvasView {
                     redraw = true;
                 }
             }
+            TheEvent::MouseWheel(delta) if self.ctrl => {
+                let zoom = (self.zoom() * (1.0 - delta.y as f32 * 0.005)).clamp(0.2, 2.0);
+                self.set_zoom_centered(zoom, ctx);
+                redraw = true;
+            }
             TheEvent::MouseWheel(delta) => {
                 let scale_factor = self.wheel_scale; // * 1.0 / (self.zoom.powf(0.5));
 
@@ -352,49 +608,52 @@ impl TheWidget for TheNodeCanvasView {
                     redraw = true;
                 }
             }
-            TheEvent::KeyCodeDown(code) => {
-                if code.to_key_code().unwrap() == TheKeyCode::Delete {
-                    if let Some(deleted_node_index) = self.canvas.selected_node {
-                        if self.canvas.nodes[deleted_node_index].can_be_deleted {
-                            self.canvas.nodes.remove(deleted_node_index);
-                            self.node_rects.remove(deleted_node_index);
-
-                            // Filter out connections involving the deleted node and adjust indices for others
-                            self.canvas.connections.retain_mut(
-                                |(src_node_idx, _, dest_node_idx, _)| {
-                                    let src_index = *src_node_idx as usize;
-                                    let dest_index = *dest_node_idx as usize;
-
-                                    if src_index == deleted_node_index
-                                        || dest_index == deleted_node_index
-                                    {
-                                        // Connection involves the deleted node, so remove it
-                                        false
-                                    } else {
-                                        // Adjust indices for remaining connections
-                                        if src_index > deleted_node_index {
-                                            *src_node_idx -= 1;
-                                        }
-                                        if dest_index > deleted_node_index {
-                                            *dest_node_idx -= 1;
-                                        }
-                                        true
-                                    }
-                                },
-                            );
-
-                            ctx.ui.send(TheEvent::NodeDeleted(
-                                self.id().clone(),
-                                deleted_node_index,
-                                self.canvas.connections.clone(),
-                            ));
-
-                            self.is_dirty = true;
-                            redraw = true;
-                        }
+            TheEvent::Undo if self.undo_stack.has_undo() => {
+                let (_id, data) = self.undo_stack.undo();
+                self.apply_undo_data(&data, "Node Undo", ctx);
+                redraw = true;
+            }
+            TheEvent::Redo if self.undo_stack.has_redo() => {
+                let (_id, data) = self.undo_stack.redo();
+                self.apply_undo_data(&data, "Node Redo", ctx);
+                redraw = true;
+            }
+            TheEvent::ModifierChanged(shift, ctrl, alt, logo) => {
+                self.shift = *shift;
+                self.alt = *alt;
+                self.ctrl = *ctrl || *logo;
+            }
+            TheEvent::KeyCodeDown(code) if code.to_key_code() == Some(TheKeyCode::Delete) => {
+                if let Some(frame_index) = self.selected_frame.take() {
+                    if let Some(frame) = self.canvas.frames.get(frame_index).cloned() {
+                        self.execute(
+                            TheNodeCommand::RemoveFrame {
+                                index: frame_index,
+                                frame,
+                            },
+                            ctx,
+                        );
+                        redraw = true;
                     }
+                } else {
+                    redraw = self.delete_selection(ctx);
                 }
             }
+            TheEvent::KeyDown(TheValue::Char(c)) if self.ctrl && c.eq_ignore_ascii_case(&'d') => {
+                redraw = self.duplicate_selection(ctx);
+            }
+            TheEvent::Copy => {
+                self.copy_selection(ctx);
+            }
+            TheEvent::Cut => {
+                self.copy_selection(ctx);
+                redraw = self.delete_selection(ctx);
+            }
+            TheEvent::Paste(TheValue::Text(text), Some(app_type))
+                if app_type == "TheNodeCanvas" =>
+            {
+                redraw = self.paste(&TheNodeCanvas::from_json(text), Vec2::new(20, 20), ctx);
+            }
             _ => {}
         }
         redraw
@@ -431,6 +690,9 @@ impl TheWidget for TheNodeCanvasView {
 
     fn needs_redraw(&mut self) -> bool {
         self.is_dirty
+            || (self.flow_animation
+                && self.last_flow_draw.elapsed().as_millis() >= 33
+                && self.flow_connections().next().is_some())
     }
 
     fn set_needs_redraw(&mut self, redraw: bool) {
@@ -453,6 +715,12 @@ impl TheWidget for TheNodeCanvasView {
 
         //self.render_buffer.fill([128, 128, 128, 255]);
 
+        // The canvas is drawn unzoomed into a buffer of the zoomed size and scaled afterwards.
+        let size = self.virtual_size();
+        if self.render_buffer.dim().width != size.x || self.render_buffer.dim().height != size.y {
+            self.render_buffer.resize(size.x, size.y);
+        }
+
         let width = self.render_buffer.dim().width as usize;
         let height = self.render_buffer.dim().height;
 
@@ -511,6 +779,22 @@ impl TheWidget for TheNodeCanvasView {
         let node_width = self.canvas.node_width;
         let node_rects = Arc::new(Mutex::new(Vec::new()));
 
+        // While connecting, the terminals which accept the connection are highlighted
+        let is_valid_target = |index: usize, output: bool, terminal: u8| {
+            if let TheNodeAction::ConnectingTerminal(source, source_output, source_terminal) =
+                self.action
+            {
+                self.check_connection(
+                    (source, source_output, source_terminal),
+                    (index, output, terminal),
+                )
+                .1
+                .is_ok()
+            } else {
+                false
+            }
+        };
+
         // Draw a node
         let draw_node = |index: usize, node: &TheNode| {
             let max_terminals = node.inputs.len().max(node.outputs.len()) as i32;
@@ -533,7 +817,9 @@ impl TheWidget for TheNodeCanvasView {
 
             let mut nb = TheRGBABuffer::new(TheDim::sized(node_width, node_height));
 
-            let is_selected = Some(index) == self.canvas.selected_node;
+            let is_selected =
+                Some(index) == self.canvas.selected_node || self.canvas.is_selected(index);
+            let error = self.node_errors.get(&index);
 
             // Header
 
@@ -573,7 +859,11 @@ impl TheWidget for TheNodeCanvasView {
                     font,
                     &node.name,
                     10.0,
-                    [188, 188, 188, 255],
+                    if error.is_some() {
+                        [209, 42, 42, 255]
+                    } else {
+                        [188, 188, 188, 255]
+                    },
                     TheHorizontalAlign::Left,
                     TheVerticalAlign::Top,
                 );
@@ -646,6 +936,14 @@ impl TheWidget for TheNodeCanvasView {
                     nb.blend_into(dim.x + 10 + 2, y, &tb)
                 }
 
+                if is_valid_target(index, false, terminal_rects.0.len() as u8) {
+                    nb.draw_disc(
+                        &TheDim::new(dim.x - 2, dim.y - 2, dim.width + 4, dim.height + 4),
+                        &[0, 0, 0, 0],
+                        1.5,
+                        &WHITE,
+                    );
+                }
                 nb.draw_disc(
                     &dim,
                     &self.color_for(&i.category_name).to_u8_array(),
@@ -675,6 +973,14 @@ impl TheWidget for TheNodeCanvasView {
                     nb.blend_into(dim.x - 80 - 2, y, &tb)
                 }
 
+                if is_valid_target(index, true, terminal_rects.1.len() as u8) {
+                    nb.draw_disc(
+                        &TheDim::new(dim.x - 2, dim.y - 2, dim.width + 4, dim.height + 4),
+                        &[0, 0, 0, 0],
+                        1.5,
+                        &WHITE,
+                    );
+                }
                 // nb.draw_disc(&dim, &[245, 245, 245, 255], 1.0, &[105, 105, 105, 255]);
                 nb.draw_disc(
                     &dim,
@@ -741,6 +1047,20 @@ impl TheWidget for TheNodeCanvasView {
                 );
             }
 
+            if let (Some(error), Some(font)) = (error, &ctx.ui.font) {
+                let mut tb = TheRGBABuffer::new(TheDim::sized(node_width - 24, 10));
+                tb.draw_text(
+                    Vec2::new(0, 0),
+                    font,
+                    error,
+                    9.5,
+                    [209, 42, 42, 255],
+                    TheHorizontalAlign::Left,
+                    TheVerticalAlign::Center,
+                );
+                nb.blend_into(12, node_height - 15, &tb);
+            }
+
             let mut node_rects = node_rects.lock().unwrap();
             node_rects.push((index, dim, nb, terminal_rects));
         };
@@ -758,18 +1078,54 @@ impl TheWidget for TheNodeCanvasView {
         let mut sorted_node_rects = Arc::try_unwrap(node_rects).unwrap().into_inner().unwrap();
         sorted_node_rects.sort_by_key(|&(index, _, _, _)| index);
 
-        // First pass to draw all nodes except the selected one
+        // Comment frames are drawn behind the nodes
+        for (index, frame) in self.canvas.frames.iter().enumerate() {
+            let mut fb = TheRGBABuffer::new(TheDim::sized(frame.size.x, frame.size.y));
+            let mut color = frame.color.to_u8_array();
+            color[3] = 60;
+            fb.fill(color);
+            color[3] = 160;
+            for y in 0..20.min(frame.size.y) {
+                fb.draw_horizontal_line(0, frame.size.x - 1, y, color);
+            }
+            if let Some(font) = &ctx.ui.font {
+                fb.draw_text(
+                    Vec2::new(6, 4),
+                    font,
+                    &frame.label,
+                    11.0,
+                    [240, 240, 240, 255],
+                    TheHorizontalAlign::Left,
+                    TheVerticalAlign::Top,
+                );
+            }
+            if Some(index) == self.selected_frame {
+                fb.draw_rect_outline(&TheDim::sized(frame.size.x, frame.size.y), &WHITE);
+            }
+            self.render_buffer.blend_into(
+                frame.position.x - self.canvas.offset.x,
+                frame.position.y - self.canvas.offset.y,
+                &fb,
+            );
+        }
+
+        // First pass to draw all nodes except the selected ones
         self.node_rects.clear();
         self.terminal_rects.clear();
         for (index, dim, nb, tr) in &sorted_node_rects {
-            if Some(*index) != self.canvas.selected_node {
+            if Some(*index) != self.canvas.selected_node && !self.canvas.is_selected(*index) {
                 self.render_buffer.blend_into(dim.x, dim.y, nb);
             }
             self.node_rects.push(*dim);
             self.terminal_rects.push(tr.clone());
         }
 
-        // Second pass to draw the selected node
+        // Second pass to draw the selected nodes, the selected node last
+        for (index, dim, nb, _) in &sorted_node_rects {
+            if Some(*index) != self.canvas.selected_node && self.canvas.is_selected(*index) {
+                self.render_buffer.blend_into(dim.x, dim.y, nb);
+            }
+        }
         for (index, dim, nb, _) in &sorted_node_rects {
             if Some(*index) == self.canvas.selected_node {
                 self.render_buffer.blend_into(dim.x, dim.y, nb);
@@ -778,85 +1134,92 @@ impl TheWidget for TheNodeCanvasView {
 
         // Draw Connections
 
-        let mut line_mask: Vec<u8> =
-            vec![0; (self.render_buffer.dim().width * self.render_buffer.dim().height) as usize];
-        let mut line_path: String = str!("");
-
-        for (source_node_index, source_output_index, dest_node_index, dest_input_index) in
-            self.canvas.connections.iter()
-        {
-            if let Some(mut output) =
-                self.terminal_rect_for(*source_node_index as usize, true, *source_output_index)
-            {
-                output.x += 10 + 6 - self.canvas.offset.x;
-                output.y -= self.canvas.offset.y;
-                output.y += output.height / 2;
-                output.x += self.canvas.nodes[*source_node_index as usize].position.x;
-                output.y += self.canvas.nodes[*source_node_index as usize].position.y;
-                if let Some(mut input) =
-                    self.terminal_rect_for(*dest_node_index as usize, false, *dest_input_index)
-                {
-                    input.x -= 6 + self.canvas.offset.x;
-                    input.y -= self.canvas.offset.y;
-                    input.y += input.height / 2;
-                    input.x += self.canvas.nodes[*dest_node_index as usize].position.x;
-                    input.y += self.canvas.nodes[*dest_node_index as usize].position.y;
-
-                    let dx = output.x - input.x;
-                    let dy = output.y - input.y;
-
-                    let d = ((dx * dx + dy * dy) as f32).sqrt().clamp(0.0, 50.0) as i32;
-
-                    let control_start_x = output.x + d;
-                    let control_start_y = output.y as isize;
-
-                    let control_end_x = input.x - d;
-                    let control_end_y = input.y as isize;
-
-                    line_path += format!(
-                        "M {},{} C {},{} {},{} {},{}",
-                        output.x,
-                        output.y,
-                        control_start_x,
-                        control_start_y,
-                        control_end_x,
-                        control_end_y,
-                        input.x,
-                        input.y
-                    )
-                    .as_str();
-                }
+        let render_path =
+            |buffer: &mut TheRGBABuffer, path: &str, stroke: Stroke, color: &[u8; 4]| {
+                let mut line_mask: Vec<u8> = vec![0; rbw * rbh];
+                Mask::new(path)
+                    .size(rbw as u32, rbh as u32)
+                    .style(stroke)
+                    .render_into(&mut line_mask, None);
+
+                ctx.draw.blend_mask(
+                    buffer.pixels_mut(),
+                    &(0, 0, rbw, rbh),
+                    rbw,
+                    &line_mask[..],
+                    &(rbw, rbh),
+                    color,
+                );
+            };
+
+        // The connection paths grouped by color
+        let mut paths: Vec<([u8; 4], String)> = vec![];
+        let mut dots: Vec<([u8; 4], Vec2<i32>)> = vec![];
+        for connection in &self.canvas.connections {
+            let Some(segments) = self.wire_segments(connection) else {
+                continue;
+            };
+            let color = self.wire_color(connection);
+            let path = Self::svg_path(&segments);
+            if let Some((_, p)) = paths.iter_mut().find(|(c, _)| *c == color) {
+                *p += path.as_str();
+            } else {
+                paths.push((color, path));
+            }
+            for point in self.canvas.reroute_points(connection) {
+                dots.push((color, *point - self.canvas.offset));
             }
         }
 
         // Draw ongoing connection attempt
         if let TheNodeAction::ConnectingTerminal(_, _, _) = self.action {
-            line_path += format!(
+            let path = format!(
                 "M {},{} L {},{}",
                 self.drag_start.x - self.canvas.offset.x,
                 self.drag_start.y - self.canvas.offset.y,
                 self.drag_offset.x,
                 self.drag_offset.y
-            )
-            .as_str();
+            );
+            if let Some((_, p)) = paths.iter_mut().find(|(c, _)| *c == WIRE_COLOR) {
+                *p += path.as_str();
+            } else {
+                paths.push((WIRE_COLOR, path));
+            }
         }
 
-        if !line_path.is_empty() {
-            Mask::new(line_path.as_str())
-                .size(
-                    self.render_buffer.dim().width as u32,
-                    self.render_buffer.dim().height as u32,
-                )
-                .style(Stroke::new(1.5))
-                .render_into(&mut line_mask, None);
-
-            ctx.draw.blend_mask(
-                self.render_buffer.pixels_mut(),
-                &(0, 0, rbw, rbh),
-                rbw,
-                &line_mask[..],
-                &(rbw, rbh),
-                &[90, 90, 90, 255],
+        for (color, path) in &paths {
+            render_path(&mut self.render_buffer, path, Stroke::new(1.5), color);
+        }
+
+        // The connections of the selected node are overlaid by moving dashes
+        let flow: Vec<TheNodeConnectionId> = self.flow_connections().collect();
+        if !flow.is_empty() {
+            let phase = (self.flow_start.elapsed().as_secs_f32() * 20.0) % 10.0;
+            let dashes = [4.0, 6.0];
+            let mut stroke = Stroke::new(2.0);
+            stroke.dash(&dashes, -phase);
+            for connection in &flow {
+                if let Some(segments) = self.wire_segments(connection) {
+                    let color = TheColor::from_u8_array(self.wire_color(connection))
+                        .mix(&TheColor::white(), 0.6)
+                        .to_u8_array();
+                    render_path(
+                        &mut self.render_buffer,
+                        &Self::svg_path(&segments),
+                        stroke,
+                        &color,
+                    );
+                }
+            }
+            self.last_flow_draw = Instant::now();
+        }
+
+        for (color, point) in &dots {
+            self.render_buffer.draw_disc(
+                &TheDim::new(point.x - 4, point.y - 4, 9, 9),
+                color,
+                1.0,
+                &[60, 60, 60, 255],
             );
         }
 
@@ -871,8 +1234,33 @@ impl TheWidget for TheNodeCanvasView {
             );
         }
 
+        // Draw the rubber band of the box selection
+        if TheNodeAction::BoxSelect == self.action {
+            let min = self.drag_start.map2(self.drag_offset, |a, b| a.min(b));
+            let max = self.drag_start.map2(self.drag_offset, |a, b| a.max(b));
+            self.render_buffer.draw_rect_outline(
+                &TheDim::new(min.x, min.y, max.x - min.x + 1, max.y - min.y + 1),
+                &[240, 240, 240, 255],
+            );
+        }
+
+        let mut display = if self.zoom() != 1.0 {
+            self.zoom_buffer.resize(self.dim.width, self.dim.height);
+            self.render_buffer.scaled_into_linear(&mut self.zoom_buffer);
+            std::mem::take(&mut self.zoom_buffer)
+        } else {
+            std::mem::take(&mut self.render_buffer)
+        };
+
+        self.draw_minimap(&mut display);
+
         // Copy the render buffer to the main buffer
-        buffer.copy_into(self.dim.buffer_x, self.dim.buffer_y, &self.render_buffer);
+        buffer.copy_into(self.dim.buffer_x, self.dim.buffer_y, &display);
+        if self.zoom() == 1.0 {
+            self.render_buffer = display;
+        } else {
+            self.zoom_buffer = display;
+        }
 
         // Draw the focus rectangle if necessary
         let stride = buffer.stride();
@@ -892,6 +1280,14 @@ impl TheWidget for TheNodeCanvasView {
         true
     }
 
+    fn supports_clipboard(&mut self) -> bool {
+        true
+    }
+
+    fn supports_undo_redo(&mut self) -> bool {
+        true
+    }
+
     fn as_any(&mut self) -> &mut dyn std::any::Any {
         self
     }
@@ -905,12 +1301,36 @@ pub trait TheNodeCanvasViewTrait: TheWidget {
     fn find_connected_nodes(&self, node_index: usize) -> Vec<usize>;
     fn set_canvas(&mut self, canvas: TheNodeCanvas);
     fn set_overlay(&mut self, overlay: Option<TheRGBABuffer>);
+    /// Set the error messages of the nodes, see TheNodeEvaluator::error_messages().
+    fn set_node_errors(&mut self, errors: FxHashMap<usize, String>);
     fn set_node_preview(&mut self, index: usize, buffer: TheRGBABuffer);
     fn fill_node_ui_images(&mut self, ctx: &mut TheContext);
+    /// Returns the index of the node at the widget coordinate, taking the zoom into account.
     fn node_index_at(&self, coord: &Vec2<i32>) -> Option<usize>;
     fn terminal_at(&self, node_index: usize, coord: Vec2<i32>) -> Option<(bool, u8)>;
     fn terminal_rect_for(&self, node_index: usize, input: bool, index: u8) -> Option<TheDim>;
     fn color_for(&self, name: &str) -> TheColor;
+    /// The canvas, as edited by the user.
+    fn canvas(&self) -> &TheNodeCanvas;
+    /// Applies the command to the canvas and adds it to the undo stack.
+    fn execute(&mut self, command: TheNodeCommand, ctx: &mut TheContext) -> bool;
+    fn undo_stack(&mut self) -> &mut TheUndoStack;
+    /// Adds a comment frame around the selected nodes.
+    fn frame_selection(&mut self, label: &str, color: TheColor, ctx: &mut TheContext) -> bool;
+    fn selected_frame(&self) -> Option<usize>;
+    /// Arranges the selected nodes, or all nodes if less than two are selected, in layers.
+    fn auto_layout(&mut self, ctx: &mut TheContext) -> bool;
+    /// Aligns or distributes the selected nodes.
+    fn align_nodes(&mut self, alignment: TheNodeAlignment, ctx: &mut TheContext) -> bool;
+    /// Zooms and scrolls the view so that all nodes and frames are visible.
+    fn zoom_to_fit(&mut self, ctx: &mut TheContext);
+    fn set_zoom(&mut self, zoom: f32, ctx: &mut TheContext);
+    /// Shows a minimap in the bottom right corner when the graph does not fit into the view.
+    fn set_minimap(&mut self, minimap: bool);
+    fn set_wire_style(&mut self, style: TheNodeWireStyle);
+    /// Animates the connections of the selected node, off by default. While a connected node
+    /// is selected the widget redraws at about 30 fps.
+    fn set_flow_animation(&mut self, flow_animation: bool);
 }
 
 impl TheNodeCanvasViewTrait for TheNodeCanvasView {
@@ -920,7 +1340,7 @@ impl TheNodeCanvasViewTrait for TheNodeCanvasView {
 
         while let Some(current_node) = stack.pop() {
             // Find all connections where the current node is the source (output terminal)
-            for &(src, _, dest, _) in &self.canvas.connections {
+            for (src, _, dest, _) in self.canvas.index_connections() {
                 if src as usize == current_node && !connected_nodes.contains(&(dest as usize)) {
                     connected_nodes.insert(dest as usize);
                     stack.push(dest as usize);
@@ -934,6 +1354,12 @@ impl TheNodeCanvasViewTrait for TheNodeCanvasView {
         self.canvas = canvas;
         self.is_dirty = true;
     }
+    fn set_node_errors(&mut self, errors: FxHashMap<usize, String>) {
+        if self.node_errors != errors {
+            self.node_errors = errors;
+            self.is_dirty = true;
+        }
+    }
     fn set_overlay(&mut self, overlay: Option<TheRGBABuffer>) {
         self.overlay = overlay;
         self.is_dirty = true;
@@ -1012,8 +1438,9 @@ impl TheNodeCanvasViewTrait for TheNodeCanvasView {
         );
     }
     fn node_index_at(&self, coord: &Vec2<i32>) -> Option<usize> {
+        let coord = self.unzoom(*coord);
         for (i, r) in self.node_rects.iter().enumerate().rev() {
-            if r.contains(*coord) {
+            if r.contains(coord) {
                 return Some(i);
             }
         }
@@ -1055,6 +1482,695 @@ impl TheNodeCanvasViewTrait for TheNodeCanvasView {
             TheColor::default()
         }
     }
+
+    fn canvas(&self) -> &TheNodeCanvas {
+        &self.canvas
+    }
+
+    fn execute(&mut self, command: TheNodeCommand, ctx: &mut TheContext) -> bool {
+        let node_count = self.canvas.nodes.len();
+        if self.canvas.apply(&command) {
+            if self.canvas.nodes.len() != node_count {
+                self.node_errors.clear();
+            }
+            self.add_undo(command, ctx);
+            self.is_dirty = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn undo_stack(&mut self) -> &mut TheUndoStack {
+        &mut self.undo_stack
+    }
+
+    fn frame_selection(&mut self, label: &str, color: TheColor, ctx: &mut TheContext) -> bool {
+        let rects: Vec<TheDim> = self
+            .canvas
+            .selected_indices()
+            .into_iter()
+            .filter_map(|index| self.node_rects.get(index).copied())
+            .collect();
+        if rects.is_empty() {
+            return false;
+        }
+
+        let border = 20;
+        let min = rects.iter().fold(Vec2::broadcast(i32::MAX), |min, r| {
+            Vec2::new(min.x.min(r.x), min.y.min(r.y))
+        });
+        let max = rects.iter().fold(Vec2::broadcast(i32::MIN), |max, r| {
+            Vec2::new(max.x.max(r.x + r.width), max.y.max(r.y + r.height))
+        });
+        let position = min + self.canvas.offset - Vec2::new(border, border + 20);
+        let size = max - min + Vec2::new(border * 2, border * 2 + 20);
+
+        let frame = TheNodeFrame::new(label.to_string(), color, position, size);
+        self.selected_frame = Some(self.canvas.frames.len());
+        self.execute(
+            TheNodeCommand::AddFrame {
+                index: self.canvas.frames.len(),
+                frame,
+            },
+            ctx,
+        )
+    }
+
+    fn selected_frame(&self) -> Option<usize> {
+        self.selected_frame
+    }
+
+    fn auto_layout(&mut self, ctx: &mut TheContext) -> bool {
+        let mut indices = self.canvas.selected_indices();
+        if indices.len() < 2 {
+            indices = (0..self.canvas.nodes.len()).collect();
+        }
+        match self.canvas.layout_command(&indices, Vec2::new(60, 30)) {
+            TheNodeCommand::MoveNodes(moves) if moves.is_empty() => false,
+            command => self.execute(command, ctx),
+        }
+    }
+
+    fn align_nodes(&mut self, alignment: TheNodeAlignment, ctx: &mut TheContext) -> bool {
+        let indices = self.canvas.selected_indices();
+        match self.canvas.align_command(&indices, alignment) {
+            TheNodeCommand::MoveNodes(moves) if moves.is_empty() => false,
+            command => self.execute(command, ctx),
+        }
+    }
+
+    fn zoom_to_fit(&mut self, ctx: &mut TheContext) {
+        let Some((min, max)) = self.canvas.bounds() else {
+            return;
+        };
+        let margin = 40;
+        let extent = max - min + Vec2::broadcast(margin * 2);
+        self.canvas.zoom = (self.dim.width as f32 / extent.x as f32)
+            .min(self.dim.height as f32 / extent.y as f32)
+            .clamp(0.2, 1.0);
+        let size = self.virtual_size();
+        self.canvas.offset = min - Vec2::broadcast(margin) - (size - extent) / 2;
+        self.is_dirty = true;
+
+        ctx.ui.send(TheEvent::NodeViewZoomed(
+            self.id().clone(),
+            self.canvas.zoom,
+        ));
+        ctx.ui.send(TheEvent::NodeViewScrolled(
+            self.id().clone(),
+            self.canvas.offset,
+        ));
+    }
+
+    fn set_zoom(&mut self, zoom: f32, ctx: &mut TheContext) {
+        self.set_zoom_centered(zoom.clamp(0.2, 2.0), ctx);
+    }
+
+    fn set_minimap(&mut self, minimap: bool) {
+        self.minimap = minimap;
+        self.is_dirty = true;
+    }
+
+    fn set_wire_style(&mut self, style: TheNodeWireStyle) {
+        self.canvas.wire_style = style;
+        self.is_dirty = true;
+    }
+
+    fn set_flow_animation(&mut self, flow_animation: bool) {
+        self.flow_animation = flow_animation;
+        self.is_dirty = true;
+    }
+}
+
+/// The color of connections without a typed output.
+const WIRE_COLOR: [u8; 4] = [90, 90, 90, 255];
+
+impl TheNodeCanvasView {
+    /// The connections of the selected node, if the flow animation is enabled.
+    fn flow_connections(&self) -> impl Iterator<Item = TheNodeConnectionId> + '_ {
+        let selected = self
+            .canvas
+            .selected_node
+            .filter(|_| self.flow_animation)
+            .and_then(|index| self.canvas.nodes.get(index))
+            .map(|node| node.id);
+        self.canvas
+            .connections
+            .iter()
+            .filter(move |(src, _, dest, _)| Some(*src) == selected || Some(*dest) == selected)
+            .copied()
+    }
+
+    /// The color of the connection, given by the data type of its output terminal.
+    fn wire_color(&self, connection: &TheNodeConnectionId) -> [u8; 4] {
+        self.canvas
+            .node(&connection.0)
+            .and_then(|node| node.outputs.get(connection.1 as usize))
+            .and_then(|terminal| terminal.data_type.color())
+            .map(|color| color.to_u8_array())
+            .unwrap_or(WIRE_COLOR)
+    }
+
+    /// The points of the connection in view coordinates, split at the reroute dots, in the
+    /// current wire style. Curves are flattened. Used for drawing and hit testing.
+    fn wire_segments(&self, connection: &TheNodeConnectionId) -> Option<Vec<Vec<Vec2<f32>>>> {
+        let (src, src_terminal, dest, dest_terminal) =
+            self.canvas.connection_from_id(connection)?;
+        let output = self.terminal_rect_for(src as usize, true, src_terminal)?;
+        let input = self.terminal_rect_for(dest as usize, false, dest_terminal)?;
+        let src = self.canvas.nodes[src as usize].position;
+        let dest = self.canvas.nodes[dest as usize].position;
+
+        let mut points = vec![Vec2::new(
+            src.x + output.x + 10 + 6,
+            src.y + output.y + output.height / 2,
+        )];
+        points.extend_from_slice(self.canvas.reroute_points(connection));
+        points.push(Vec2::new(
+            dest.x + input.x - 6,
+            dest.y + input.y + input.height / 2,
+        ));
+
+        let segments = points
+            .windows(2)
+            .map(|w| {
+                let a = (w[0] - self.canvas.offset).map(|v| v as f32);
+                let b = (w[1] - self.canvas.offset).map(|v| v as f32);
+                match self.canvas.wire_style {
+                    TheNodeWireStyle::Straight => vec![a, b],
+                    TheNodeWireStyle::Bezier => {
+                        let d = a.distance(b).clamp(0.0, 50.0);
+                        let c1 = Vec2::new(a.x + d, a.y);
+                        let c2 = Vec2::new(b.x - d, b.y);
+                        let steps = (a.distance(b) / 8.0).clamp(8.0, 48.0) as usize;
+                        (0..=steps)
+                            .map(|i| {
+                                let t = i as f32 / steps as f32;
+                                let it = 1.0 - t;
+                                a * (it * it * it)
+                                    + c1 * (3.0 * it * it * t)
+                                    + c2 * (3.0 * it * t * t)
+                                    + b * (t * t * t)
+                            })
+                            .collect()
+                    }
+                    TheNodeWireStyle::Orthogonal => {
+                        if b.x >= a.x + 20.0 {
+                            let mx = ((a.x + b.x) / 2.0).round();
+                            vec![a, Vec2::new(mx, a.y), Vec2::new(mx, b.y), b]
+                        } else {
+                            // Going backwards, leave and enter the terminals horizontally
+                            let my = ((a.y + b.y) / 2.0).round();
+                            vec![
+                                a,
+                                Vec2::new(a.x + 10.0, a.y),
+                                Vec2::new(a.x + 10.0, my),
+                                Vec2::new(b.x - 10.0, my),
+                                Vec2::new(b.x - 10.0, b.y),
+                                b,
+                            ]
+                        }
+                    }
+                }
+            })
+            .collect();
+        Some(segments)
+    }
+
+    /// Converts wire segments to an SVG path.
+    fn svg_path(segments: &[Vec<Vec2<f32>>]) -> String {
+        let mut path = String::new();
+        for (i, point) in segments.iter().flatten().enumerate() {
+            path += &format!(
+                "{} {},{} ",
+                if i == 0 { "M" } else { "L" },
+                point.x,
+                point.y
+            );
+        }
+        path
+    }
+
+    /// Returns the connection index and the segment index of the connection near the view
+    /// coordinate. The segment index is where a reroute dot is inserted.
+    fn wire_at(&self, coord: &Vec2<i32>) -> Option<(usize, usize)> {
+        let p = coord.map(|v| v as f32);
+        for (index, connection) in self.canvas.connections.iter().enumerate().rev() {
+            let Some(segments) = self.wire_segments(connection) else {
+                continue;
+            };
+            for (segment, points) in segments.iter().enumerate() {
+                for w in points.windows(2) {
+                    let ab = w[1] - w[0];
+                    let t = if ab.magnitude_squared() > 0.0 {
+                        ((p - w[0]).dot(ab) / ab.magnitude_squared()).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    };
+                    if p.distance(w[0] + ab * t) <= 4.0 {
+                        return Some((index, segment));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the connection index and the dot index of the reroute dot at the view coordinate.
+    fn reroute_at(&self, coord: &Vec2<i32>) -> Option<(usize, usize)> {
+        let coord = *coord + self.canvas.offset;
+        for (index, connection) in self.canvas.connections.iter().enumerate().rev() {
+            for (point, p) in self.canvas.reroute_points(connection).iter().enumerate() {
+                if (p.x - coord.x).abs() <= 5 && (p.y - coord.y).abs() <= 5 {
+                    return Some((index, point));
+                }
+            }
+        }
+        None
+    }
+
+    /// Replaces the reroute dots of the connection without an undo.
+    fn set_reroute_points(&mut self, connection: usize, points: Vec<Vec2<i32>>) {
+        if let Some(connection) = self.canvas.connections.get(connection) {
+            let command = self.canvas.set_reroutes_command(connection, points);
+            self.canvas.apply(&command);
+        }
+    }
+
+    /// Orders the two terminals (node, is output, terminal) as a connection from an output to
+    /// an input and validates it.
+    fn check_connection(
+        &self,
+        a: (usize, bool, u8),
+        b: (usize, bool, u8),
+    ) -> ((u16, u8, u16, u8), Result<(), TheNodeConnectionError>) {
+        let (output, input) = if a.1 { (a, b) } else { (b, a) };
+        let connection = (output.0 as u16, output.2, input.0 as u16, input.2);
+        let result = if a.1 == b.1 {
+            Err(TheNodeConnectionError::SameDirection)
+        } else {
+            self.canvas.validate_connection(&connection)
+        };
+        (connection, result)
+    }
+
+    fn zoom(&self) -> f32 {
+        if self.canvas.zoom > 0.0 {
+            self.canvas.zoom
+        } else {
+            1.0
+        }
+    }
+
+    /// The size of the visible canvas area, in canvas units.
+    fn virtual_size(&self) -> Vec2<i32> {
+        let zoom = self.zoom();
+        Vec2::new(
+            (self.dim.width as f32 / zoom).ceil() as i32,
+            (self.dim.height as f32 / zoom).ceil() as i32,
+        )
+    }
+
+    /// Converts a widget coordinate to the unzoomed coordinate of the render buffer.
+    fn unzoom(&self, coord: Vec2<i32>) -> Vec2<i32> {
+        let zoom = self.zoom();
+        Vec2::new(
+            (coord.x as f32 / zoom) as i32,
+            (coord.y as f32 / zoom) as i32,
+        )
+    }
+
+    /// Sets the zoom, keeping the center of the view in place.
+    fn set_zoom_centered(&mut self, zoom: f32, ctx: &mut TheContext) {
+        let center = self.canvas.offset + self.virtual_size() / 2;
+        self.canvas.zoom = zoom;
+        self.canvas.offset = center - self.virtual_size() / 2;
+        self.is_dirty = true;
+
+        ctx.ui.send(TheEvent::NodeViewZoomed(
+            self.id().clone(),
+            self.canvas.zoom,
+        ));
+        ctx.ui.send(TheEvent::NodeViewScrolled(
+            self.id().clone(),
+            self.canvas.offset,
+        ));
+    }
+
+    /// Centers the view on the canvas position under the minimap coordinate.
+    fn navigate_minimap(&mut self, coord: Vec2<i32>, ctx: &mut TheContext) {
+        let Some(rect) = self.minimap_rect else {
+            return;
+        };
+        let (origin, scale) = self.minimap_transform;
+        let center = origin
+            + Vec2::new(
+                ((coord.x - rect.x) as f32 / scale) as i32,
+                ((coord.y - rect.y) as f32 / scale) as i32,
+            );
+        self.canvas.offset = center - self.virtual_size() / 2;
+        self.is_dirty = true;
+
+        ctx.ui.send(TheEvent::NodeViewScrolled(
+            self.id().clone(),
+            self.canvas.offset,
+        ));
+    }
+
+    /// Draws the minimap with the nodes, frames and the visible area into the bottom right corner.
+    fn draw_minimap(&mut self, target: &mut TheRGBABuffer) {
+        self.minimap_rect = None;
+        if !self.minimap {
+            return;
+        }
+        let Some((min, max)) = self.canvas.bounds() else {
+            return;
+        };
+
+        // Only needed when the graph does not fit into the view.
+        let view_min = self.canvas.offset;
+        let view_max = self.canvas.offset + self.virtual_size();
+        if min.x >= view_min.x && min.y >= view_min.y && max.x <= view_max.x && max.y <= view_max.y
+        {
+            return;
+        }
+
+        let size = Vec2::new(160, 110);
+        let target_size = Vec2::new(target.dim().width, target.dim().height);
+        if target_size.x < size.x + 20 || target_size.y < size.y + 20 {
+            return;
+        }
+
+        let min = min.map2(view_min, |a, b| a.min(b));
+        let max = max.map2(view_max, |a, b| a.max(b));
+        let extent = (max - min).map(|v| v.max(1));
+        let scale = (size.x as f32 / extent.x as f32).min(size.y as f32 / extent.y as f32);
+        let to_map = |p: Vec2<i32>| {
+            Vec2::new(
+                ((p.x - min.x) as f32 * scale) as i32,
+                ((p.y - min.y) as f32 * scale) as i32,
+            )
+        };
+
+        let mut mb = TheRGBABuffer::new(TheDim::sized(size.x, size.y));
+        mb.fill([40, 40, 40, 200]);
+
+        let fill_rect = |mb: &mut TheRGBABuffer, from: Vec2<i32>, to: Vec2<i32>, color: [u8; 4]| {
+            let (from, to) = (to_map(from), to_map(to));
+            for y in from.y..=to.y.max(from.y) {
+                mb.draw_horizontal_line(from.x, to.x.max(from.x), y, color);
+            }
+        };
+
+        for frame in &self.canvas.frames {
+            let mut color = frame.color.to_u8_array();
+            color[3] = 255;
+            fill_rect(&mut mb, frame.position, frame.position + frame.size, color);
+        }
+        for (index, node) in self.canvas.nodes.iter().enumerate() {
+            let color = if self.canvas.is_selected(index) {
+                [240, 240, 240, 255]
+            } else {
+                [150, 150, 150, 255]
+            };
+            let size = Vec2::new(self.canvas.node_width, node.height());
+            fill_rect(&mut mb, node.position, node.position + size, color);
+        }
+
+        let (from, to) = (to_map(view_min), to_map(view_max));
+        mb.draw_rect_outline(
+            &TheDim::new(from.x, from.y, to.x - from.x, to.y - from.y),
+            &WHITE,
+        );
+        mb.draw_rect_outline(&TheDim::sized(size.x, size.y), &[90, 90, 90, 255]);
+
+        let rect = TheDim::new(
+            target_size.x - size.x - 10,
+            target_size.y - size.y - 10,
+            size.x,
+            size.y,
+        );
+        target.blend_into(rect.x, rect.y, &mb);
+        self.minimap_rect = Some(rect);
+        self.minimap_transform = (min, scale);
+    }
+
+    /// Sends the selection events if the selection differs from the previous one.
+    fn send_selection_changed(
+        &mut self,
+        previous: (Option<usize>, Vec<Uuid>),
+        ctx: &mut TheContext,
+    ) -> bool {
+        let mut changed = false;
+        if previous.0 != self.canvas.selected_node {
+            ctx.ui.send(TheEvent::NodeSelectedIndexChanged(
+                self.id().clone(),
+                self.canvas.selected_node,
+            ));
+            changed = true;
+        }
+        if previous.1 != self.canvas.selection {
+            ctx.ui.send(TheEvent::NodeSelectionChanged(
+                self.id().clone(),
+                self.canvas.selected_indices(),
+            ));
+            changed = true;
+        }
+        if changed {
+            self.is_dirty = true;
+        }
+        changed
+    }
+
+    /// Returns the frame at the local coordinate and if the coordinate is on its resize handle.
+    /// Frames can only be grabbed at their header or their bottom right corner.
+    fn frame_at(&self, coord: &Vec2<i32>) -> Option<(usize, bool)> {
+        let coord = *coord + self.canvas.offset;
+        for (index, frame) in self.canvas.frames.iter().enumerate().rev() {
+            if frame.contains(coord) {
+                let corner = frame.position + frame.size;
+                if coord.x >= corner.x - 12 && coord.y >= corner.y - 12 {
+                    return Some((index, true));
+                } else if coord.y < frame.position.y + 20 {
+                    return Some((index, false));
+                }
+            }
+        }
+        None
+    }
+
+    /// The selected nodes, or the selected node if there is no selection.
+    fn selection_or_selected(&self) -> Vec<usize> {
+        let indices = self.canvas.selected_indices();
+        if indices.is_empty() {
+            self.canvas.selected_node.into_iter().collect()
+        } else {
+            indices
+        }
+    }
+
+    /// Deletes the selected nodes, sending a NodeDeleted event for each of them.
+    fn delete_selection(&mut self, ctx: &mut TheContext) -> bool {
+        let command = self
+            .canvas
+            .remove_nodes_command(&self.selection_or_selected());
+        let TheNodeCommand::Batch(commands) = &command else {
+            return false;
+        };
+        if commands.is_empty() {
+            return false;
+        }
+
+        // The commands remove from the back, so the indices of the events stay valid.
+        for command in commands {
+            let TheNodeCommand::RemoveNode { index, .. } = command else {
+                continue;
+            };
+            let deleted_node_index = *index;
+            self.canvas.apply(command);
+            self.node_removed(deleted_node_index);
+
+            ctx.ui.send(TheEvent::NodeDeleted(
+                self.id().clone(),
+                deleted_node_index,
+                self.canvas.index_connections(),
+            ));
+        }
+
+        self.add_undo(command, ctx);
+        self.is_dirty = true;
+        true
+    }
+
+    /// Puts the selected nodes and their connections on the clipboard.
+    fn copy_selection(&mut self, ctx: &mut TheContext) {
+        let indices = self.selection_or_selected();
+        if !indices.is_empty() {
+            let copy = self.canvas.copy_nodes(&indices);
+            ctx.ui.send(TheEvent::SetClipboard(
+                TheValue::Text(copy.to_json()),
+                Some(str!("TheNodeCanvas")),
+            ));
+        }
+    }
+
+    /// Adds the copied nodes and selects them, sending a NodeAdded event for each of them.
+    fn paste(&mut self, copy: &TheNodeCanvas, offset: Vec2<i32>, ctx: &mut TheContext) -> bool {
+        if copy.nodes.is_empty() {
+            return false;
+        }
+        let (command, ids) = self.canvas.paste_command(copy, offset);
+        if !self.execute(command, ctx) {
+            return false;
+        }
+
+        let previous = (self.canvas.selected_node, self.canvas.selection.clone());
+        self.canvas.clear_selection();
+        let connections = self.canvas.index_connections();
+        for id in ids {
+            if let Some(index) = self.canvas.node_index(&id) {
+                self.canvas.select(index, true);
+                ctx.ui.send(TheEvent::NodeAdded(
+                    self.id().clone(),
+                    index,
+                    connections.clone(),
+                ));
+            }
+        }
+        self.send_selection_changed(previous, ctx);
+        true
+    }
+
+    /// Pastes a copy of the selected nodes next to them.
+    fn duplicate_selection(&mut self, ctx: &mut TheContext) -> bool {
+        let indices = self.selection_or_selected();
+        let copy = self.canvas.copy_nodes(&indices);
+        self.paste(&copy, Vec2::new(20, 20), ctx)
+    }
+
+    /// Adds the (already applied) command to the undo stack and sends it as NodeCanvasChanged.
+    fn add_undo(&mut self, command: TheNodeCommand, ctx: &mut TheContext) {
+        let mut undo = TheUndo::new(TheId::named("Node Edit"));
+        undo.set_undo_data(command.inverse().to_json());
+        undo.set_redo_data(command.to_json());
+        self.undo_stack.add(undo.clone());
+        ctx.ui
+            .send(TheEvent::NodeCanvasChanged(self.id().clone(), undo));
+    }
+
+    /// Shifts the node rects and errors after the node at the index was removed.
+    fn node_removed(&mut self, removed_index: usize) {
+        if removed_index < self.node_rects.len() {
+            self.node_rects.remove(removed_index);
+        }
+        self.node_errors = std::mem::take(&mut self.node_errors)
+            .into_iter()
+            .filter(|(index, _)| *index != removed_index)
+            .map(|(index, err)| {
+                if index > removed_index {
+                    (index - 1, err)
+                } else {
+                    (index, err)
+                }
+            })
+            .collect();
+    }
+
+    /// Shifts the node rects and errors after a node was inserted at the index.
+    fn node_inserted(&mut self, inserted_index: usize) {
+        if inserted_index <= self.node_rects.len() {
+            self.node_rects.insert(inserted_index, TheDim::default());
+        }
+        self.node_errors = std::mem::take(&mut self.node_errors)
+            .into_iter()
+            .map(|(index, err)| {
+                if index >= inserted_index {
+                    (index + 1, err)
+                } else {
+                    (index, err)
+                }
+            })
+            .collect();
+    }
+
+    /// Applies the command step by step and sends the events of the equivalent direct edits:
+    /// NodeAdded, NodeDeleted, NodeDragged, NodeConnectionAdded and NodeConnectionRemoved.
+    fn apply_and_announce(&mut self, command: &TheNodeCommand, ctx: &mut TheContext) {
+        match command {
+            TheNodeCommand::Batch(commands) => {
+                for command in commands {
+                    self.apply_and_announce(command, ctx);
+                }
+            }
+            TheNodeCommand::AddNode { node, .. } => {
+                if self.canvas.apply(command) {
+                    if let Some(index) = self.canvas.node_index(&node.id) {
+                        self.node_inserted(index);
+                        ctx.ui.send(TheEvent::NodeAdded(
+                            self.id().clone(),
+                            index,
+                            self.canvas.index_connections(),
+                        ));
+                    }
+                }
+            }
+            TheNodeCommand::RemoveNode { node, .. } => {
+                if let Some(index) = self.canvas.node_index(&node.id) {
+                    self.canvas.apply(command);
+                    self.node_removed(index);
+                    ctx.ui.send(TheEvent::NodeDeleted(
+                        self.id().clone(),
+                        index,
+                        self.canvas.index_connections(),
+                    ));
+                }
+            }
+            TheNodeCommand::MoveNodes(moves) => {
+                self.canvas.apply(command);
+                for (id, _, to) in moves {
+                    if let Some(index) = self.canvas.node_index(id) {
+                        ctx.ui
+                            .send(TheEvent::NodeDragged(self.id().clone(), index, *to));
+                    }
+                }
+            }
+            TheNodeCommand::Connect(..) => {
+                if self.canvas.apply(command) {
+                    ctx.ui.send(TheEvent::NodeConnectionAdded(
+                        self.id().clone(),
+                        self.canvas.index_connections(),
+                    ));
+                }
+            }
+            TheNodeCommand::Disconnect(..) => {
+                if self.canvas.apply(command) {
+                    ctx.ui.send(TheEvent::NodeConnectionRemoved(
+                        self.id().clone(),
+                        self.canvas.index_connections(),
+                    ));
+                }
+            }
+            _ => {
+                self.canvas.apply(command);
+            }
+        }
+    }
+
+    /// Applies the command of the undo or redo data and announces it, see apply_and_announce().
+    fn apply_undo_data(&mut self, data: &str, name: &str, ctx: &mut TheContext) {
+        let Some(command) = TheNodeCommand::from_json(data) else {
+            return;
+        };
+        self.apply_and_announce(&command, ctx);
+        self.is_dirty = true;
+
+        let mut undo = TheUndo::new(TheId::named(name));
+        undo.set_undo_data(command.inverse().to_json());
+        undo.set_redo_data(data.to_string());
+        ctx.ui
+            .send(TheEvent::NodeCanvasChanged(self.id().clone(), undo));
+    }
 }
 
 // https://www.geeksforgeeks.org/check-if-two-given-line-segments-intersect/
@@ -8,6 +8,8 @@ pub struct ThePalettePicker {
 
     palette: ThePalette,
     index: usize,
+    selection: Vec<usize>,
+    usage: Vec<u32>,
     dynamic_layout: bool,
 
     rectangles: Vec<TheDim>,
@@ -15,6 +17,16 @@ pub struct ThePalettePicker {
     rows: i32,
     columns: i32,
 
+    // Drag to swap entries, or to move them when shift is held.
+    drag_from: Option<usize>,
+    drag_to: Option<usize>,
+
+    shift: bool,
+    ctrl: bool,
+
+    context_menu: Option<TheContextMenu>,
+    undo_stack: TheUndoStack,
+
     dim: TheDim,
 }
 
@@ -34,6 +46,8 @@ impl TheWidget for ThePalettePicker {
 
             palette: ThePalette::default(),
             index: 0,
+            selection: vec![0],
+            usage: vec![],
             dynamic_layout: false,
 
             rectangles: vec![],
@@ -41,6 +55,15 @@ impl TheWidget for ThePalettePicker {
             rows: 20,
             columns: 14,
 
+            drag_from: None,
+            drag_to: None,
+
+            shift: false,
+            ctrl: false,
+
+            context_menu: Some(Self::default_context_menu()),
+            undo_stack: TheUndoStack::default(),
+
             dim: TheDim::zero(),
         }
     }
@@ -49,11 +72,37 @@ impl TheWidget for ThePalettePicker {
         &self.id
     }
 
-    #[allow(clippy::single_match)]
+    fn set_context_menu(&mut self, menu: Option<TheContextMenu>) {
+        self.context_menu = menu;
+    }
+
     fn on_event(&mut self, event: &TheEvent, ctx: &mut TheContext) -> bool {
         let mut redraw = false;
         // println!("event ({}): {:?}", self.widget_id.name, event);
         match event {
+            TheEvent::ModifierChanged(shift, ctrl, _alt, logo) => {
+                self.shift = *shift;
+                self.ctrl = *ctrl || *logo;
+            }
+            TheEvent::Context(coord) => {
+                // Right clicking an entry outside the selection selects it first.
+                if let Some(index) = self.index_at(self.dim.to_local(*coord)) {
+                    if !self.selection.contains(&index) {
+                        self.select(index, ctx);
+                        redraw = true;
+                    }
+                }
+                if let Some(context_menu) = &self.context_menu {
+                    ctx.ui.send(TheEvent::ShowContextMenu(
+                        self.id().clone(),
+                        *coord,
+                        context_menu.clone(),
+                    ));
+                }
+            }
+            TheEvent::ContextMenuSelected(_widget_id, item_id) => {
+                redraw = self.context_menu_action(&item_id.name, ctx);
+            }
             TheEvent::MouseDown(coord) => {
                 ctx.ui
                     .send_widget_state_changed(self.id(), TheWidgetState::Clicked);
@@ -62,47 +111,108 @@ impl TheWidget for ThePalettePicker {
                 self.is_dirty = true;
                 redraw = true;
 
-                for (i, rect) in self.rectangles.iter().enumerate() {
-                    if rect.contains(*coord) {
-                        self.index = i;
-                        ctx.ui.send(TheEvent::PaletteIndexChanged(
-                            self.id().clone(),
-                            self.index as u16,
-                        ));
-                        break;
+                if let Some(index) = self.index_at(*coord) {
+                    if self.ctrl {
+                        if let Some(pos) = self.selection.iter().position(|i| *i == index) {
+                            if self.selection.len() > 1 {
+                                self.selection.remove(pos);
+                            }
+                        } else {
+                            self.selection.push(index);
+                        }
+                        self.set_index(index, ctx);
+                    } else if self.shift {
+                        let (start, end) = (self.index.min(index), self.index.max(index));
+                        self.selection = (start..=end).collect();
+                        self.set_index(index, ctx);
+                        // Dragging with shift held moves the entry
+                        self.drag_from = Some(index);
+                    } else {
+                        self.select(index, ctx);
+                        self.drag_from = Some(index);
                     }
                 }
             }
+            TheEvent::MouseDragged(coord) if self.drag_from.is_some() => {
+                let target = self.index_at(*coord);
+                if target != self.drag_to {
+                    self.drag_to = target;
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+            }
+            TheEvent::MouseUp(_coord) => {
+                if let (Some(from), Some(to)) = (self.drag_from, self.drag_to) {
+                    if from != to {
+                        let before = self.palette.clone();
+                        if self.shift {
+                            self.palette.move_color(from, to);
+                            if from < self.usage.len() && to < self.usage.len() {
+                                let count = self.usage.remove(from);
+                                self.usage.insert(to, count);
+                            }
+                            self.finish_edit(before, "Move Color", ctx);
+                        } else {
+                            self.palette.swap(from, to);
+                            if from < self.usage.len() && to < self.usage.len() {
+                                self.usage.swap(from, to);
+                            }
+                            self.finish_edit(before, "Swap Colors", ctx);
+                        }
+                        self.select(to, ctx);
+                    }
+                }
+                if self.drag_from.is_some() {
+                    self.drag_from = None;
+                    self.drag_to = None;
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+            }
             TheEvent::KeyCodeDown(TheValue::KeyCode(code)) => match code {
                 TheKeyCode::Delete => {
-                    self.palette.colors[self.index] = None;
+                    let before = self.palette.clone();
+                    for index in self.selection.clone() {
+                        if let Some(color) = self.palette.colors.get_mut(index) {
+                            *color = None;
+                        }
+                    }
+                    self.finish_edit(before, "Clear Colors", ctx);
                     self.is_dirty = true;
                     redraw = true;
                 }
                 TheKeyCode::Left => {
                     if self.index > 0 {
-                        self.index -= 1;
+                        self.select(self.index - 1, ctx);
                         self.is_dirty = true;
                         redraw = true;
-                        ctx.ui.send(TheEvent::PaletteIndexChanged(
-                            self.id().clone(),
-                            self.index as u16,
-                        ));
                     }
                 }
                 TheKeyCode::Right => {
-                    if self.index < self.palette.colors.len() - 1 {
-                        self.index += 1;
+                    if self.index + 1 < self.palette.colors.len() {
+                        self.select(self.index + 1, ctx);
                         self.is_dirty = true;
                         redraw = true;
-                        ctx.ui.send(TheEvent::PaletteIndexChanged(
-                            self.id().clone(),
-                            self.index as u16,
-                        ));
                     }
                 }
                 _ => {}
             },
+            TheEvent::Copy => {
+                self.copy_hex(ctx);
+            }
+            TheEvent::Paste(TheValue::Text(text), _) => {
+                redraw = self.paste_hex(text, ctx);
+            }
+            TheEvent::Undo if self.undo_stack.has_undo() => {
+                let (_id, data) = self.undo_stack.undo();
+                self.apply_undo_data(&data, "Palette Undo", ctx);
+                redraw = true;
+            }
+            TheEvent::Redo if self.undo_stack.has_redo() => {
+                let (_id, data) = self.undo_stack.redo();
+                self.apply_undo_data(&data, "Palette Redo", ctx);
+                redraw = true;
+            }
             _ => {}
         }
         redraw
@@ -135,6 +245,14 @@ impl TheWidget for ThePalettePicker {
         self.is_dirty
     }
 
+    fn supports_clipboard(&mut self) -> bool {
+        true
+    }
+
+    fn supports_undo_redo(&mut self) -> bool {
+        true
+    }
+
     fn draw(
         &mut self,
         buffer: &mut TheRGBABuffer,
@@ -180,12 +298,21 @@ impl TheWidget for ThePalettePicker {
         for _ in 0..self.rows {
             let mut x_off = 10;
             for _ in 0..self.columns {
-                if self.index == index {
+                let outline = if self.drag_to == Some(index) && self.drag_from != Some(index) {
+                    Some(style.theme().color(DefaultSelection))
+                } else if self.index == index {
+                    Some(&WHITE)
+                } else if self.selection.contains(&index) {
+                    Some(&[160, 160, 160, 255])
+                } else {
+                    None
+                };
+                if let Some(outline) = outline {
                     ctx.draw.rect_outline(
                         buffer.pixels_mut(),
-                        &(utuple.0 + x_off, utuple.0 + y_off, item_width, item_width),
+                        &(utuple.0 + x_off, utuple.1 + y_off, item_width, item_width),
                         stride,
-                        &WHITE,
+                        outline,
                     );
                 }
 
@@ -193,7 +320,7 @@ impl TheWidget for ThePalettePicker {
                     buffer.pixels_mut(),
                     &(
                         utuple.0 + x_off + 1,
-                        utuple.0 + y_off + 1,
+                        utuple.1 + y_off + 1,
                         item_width - 2,
                         item_width - 2,
                     ),
@@ -206,7 +333,7 @@ impl TheWidget for ThePalettePicker {
                         buffer.pixels_mut(),
                         &(
                             utuple.0 + x_off + 2,
-                            utuple.0 + y_off + 2,
+                            utuple.1 + y_off + 2,
                             item_width - 4,
                             item_width - 4,
                         ),
@@ -244,6 +371,207 @@ impl TheWidget for ThePalettePicker {
     }
 }
 
+impl ThePalettePicker {
+    /// The built-in context menu with the editing actions.
+    fn default_context_menu() -> TheContextMenu {
+        let mut sort = TheContextMenu::named("Sort".to_string());
+        for key in ThePaletteSort::iterator() {
+            sort.add(TheContextMenuItem::new(
+                key.to_string().to_string(),
+                TheId::named(&format!("Palette Sort {}", key.to_string())),
+            ));
+        }
+
+        let mut menu = TheContextMenu::named("Palette".to_string());
+        menu.add(TheContextMenuItem::new(
+            "Copy Hex".to_string(),
+            TheId::named("Palette Copy"),
+        ));
+        menu.add(TheContextMenuItem::new(
+            "Paste Hex".to_string(),
+            TheId::named("Palette Paste"),
+        ));
+        menu.add_separator();
+        menu.add(TheContextMenuItem::new(
+            "Insert".to_string(),
+            TheId::named("Palette Insert"),
+        ));
+        menu.add(TheContextMenuItem::new(
+            "Delete".to_string(),
+            TheId::named("Palette Delete"),
+        ));
+        menu.add(TheContextMenuItem::new(
+            "Clear".to_string(),
+            TheId::named("Palette Clear"),
+        ));
+        menu.add_separator();
+        menu.add(TheContextMenuItem::new(
+            "Generate Ramp".to_string(),
+            TheId::named("Palette Ramp"),
+        ));
+        menu.add(TheContextMenuItem::new_submenu(
+            "Sort".to_string(),
+            TheId::named("Palette Sort"),
+            sort,
+        ));
+        menu
+    }
+
+    /// Performs the action of the given context menu item.
+    fn context_menu_action(&mut self, name: &str, ctx: &mut TheContext) -> bool {
+        let before = self.palette.clone();
+        match name {
+            "Palette Copy" => {
+                self.copy_hex(ctx);
+                return false;
+            }
+            "Palette Paste" => {
+                if let Some(TheValue::Text(text)) = ctx.ui.clipboard.clone() {
+                    return self.paste_hex(&text, ctx);
+                }
+                return false;
+            }
+            "Palette Insert" => {
+                self.palette.insert_empty(self.index);
+                if self.index < self.usage.len() {
+                    self.usage.insert(self.index, 0);
+                    self.usage.truncate(self.palette.colors.len());
+                }
+                self.finish_edit(before, "Insert Color", ctx);
+            }
+            "Palette Delete" => {
+                // Remove from the back so the remaining indices stay valid.
+                let mut selection = self.selection.clone();
+                selection.sort_unstable_by(|a, b| b.cmp(a));
+                for index in selection {
+                    self.palette.remove_color(index);
+                    // Keep the use counts aligned with the shifted entries
+                    if index < self.usage.len() {
+                        self.usage.remove(index);
+                        self.usage.push(0);
+                    }
+                }
+                self.finish_edit(before, "Delete Colors", ctx);
+                let index = self.selection.iter().min().copied().unwrap_or(0);
+                self.select(index, ctx);
+            }
+            "Palette Clear" => {
+                for index in self.selection.clone() {
+                    if let Some(color) = self.palette.colors.get_mut(index) {
+                        *color = None;
+                    }
+                }
+                self.finish_edit(before, "Clear Colors", ctx);
+            }
+            "Palette Ramp" => {
+                self.generate_ramp(ctx);
+            }
+            _ => {
+                if let Some(key) = ThePaletteSort::iterator()
+                    .find(|key| name == format!("Palette Sort {}", key.to_string()))
+                {
+                    self.sort(key, ctx);
+                } else {
+                    return false;
+                }
+            }
+        }
+        self.is_dirty = true;
+        true
+    }
+
+    /// Returns the palette index at the given local coordinate.
+    fn index_at(&self, coord: Vec2<i32>) -> Option<usize> {
+        self.rectangles.iter().position(|rect| rect.contains(coord))
+    }
+
+    /// Sets the current index and sends the change.
+    fn set_index(&mut self, index: usize, ctx: &mut TheContext) {
+        self.index = index;
+        self.palette.current_index = index as u16;
+        ctx.ui.send(TheEvent::PaletteIndexChanged(
+            self.id().clone(),
+            self.index as u16,
+        ));
+    }
+
+    /// Makes the index the current and only selected entry.
+    fn select(&mut self, index: usize, ctx: &mut TheContext) {
+        self.selection = vec![index];
+        self.set_index(index, ctx);
+    }
+
+    /// Puts the hex values of the selected colors on the clipboard, one per line.
+    fn copy_hex(&mut self, ctx: &mut TheContext) {
+        let mut selection = self.selection.clone();
+        selection.sort_unstable();
+        let text = selection
+            .iter()
+            .filter_map(|i| self.palette.colors.get(*i).cloned().flatten())
+            .map(|color| color.to_hex())
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !text.is_empty() {
+            ctx.ui
+                .send(TheEvent::SetClipboard(TheValue::Text(text), None));
+        }
+    }
+
+    /// Writes the hex colors of the text into the palette, starting at the first selected entry.
+    fn paste_hex(&mut self, text: &str, ctx: &mut TheContext) -> bool {
+        let colors: Vec<TheColor> = text
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .map(|token| token.trim_start_matches('#'))
+            .filter(|token| {
+                (token.len() == 6 || token.len() == 8)
+                    && token.chars().all(|c| c.is_ascii_hexdigit())
+            })
+            .map(|token| TheColor::from_hex(&format!("#{}", token)))
+            .collect();
+
+        if colors.is_empty() {
+            return false;
+        }
+
+        let before = self.palette.clone();
+        let start = self.selection.iter().min().copied().unwrap_or(self.index);
+        for (slot, color) in self.palette.colors.iter_mut().skip(start).zip(colors) {
+            *slot = Some(color);
+        }
+        self.finish_edit(before, "Paste Colors", ctx);
+        self.is_dirty = true;
+        true
+    }
+
+    /// Adds the edit to the undo stack and sends it as PaletteChanged.
+    fn finish_edit(&mut self, before: ThePalette, name: &str, ctx: &mut TheContext) {
+        if before.colors == self.palette.colors {
+            return;
+        }
+        let mut undo = TheUndo::new(TheId::named(name));
+        undo.set_undo_data(before.to_json());
+        undo.set_redo_data(self.palette.to_json());
+        self.undo_stack.add(undo.clone());
+        ctx.ui
+            .send(TheEvent::PaletteChanged(self.id().clone(), undo));
+    }
+
+    /// Restores the palette from undo or redo data and announces it.
+    fn apply_undo_data(&mut self, data: &str, name: &str, ctx: &mut TheContext) {
+        let mut undo = TheUndo::new(TheId::named(name));
+        undo.set_undo_data(self.palette.to_json());
+        undo.set_redo_data(data.to_string());
+        self.palette = ThePalette::from_json(data);
+        self.selection.retain(|i| *i < self.palette.colors.len());
+        if self.selection.is_empty() {
+            self.selection = vec![self.index];
+        }
+        self.is_dirty = true;
+        ctx.ui
+            .send(TheEvent::PaletteChanged(self.id().clone(), undo));
+    }
+}
+
 pub trait ThePalettePickerTrait {
     fn set_palette(&mut self, palette: ThePalette);
     fn index(&self) -> usize;
@@ -251,12 +579,30 @@ pub trait ThePalettePickerTrait {
     fn set_rows_columns(&mut self, rows: i32, columns: i32);
     fn set_dynamic_layout(&mut self, dynamic_layout: bool);
     fn calc_layout(&self, available: Vec2<i32>, colour_count: usize) -> (i32, i32, i32);
+
+    /// Returns the edited palette.
+    fn palette(&self) -> &ThePalette;
+    /// Returns the selected indices.
+    fn selection(&self) -> Vec<usize>;
+    /// Sets the selected indices, the first one becomes the current index.
+    fn set_selection(&mut self, selection: Vec<usize>);
+    /// Sets the use count of each entry, used for sorting by usage.
+    fn set_usage(&mut self, usage: Vec<u32>);
+    /// Sorts the selected entries, or the whole palette if only one entry is selected.
+    fn sort(&mut self, key: ThePaletteSort, ctx: &mut TheContext);
+    /// Fills the entries between the first and last selected entry with a ramp.
+    fn generate_ramp(&mut self, ctx: &mut TheContext);
+    /// The undo stack of the palette edits.
+    fn undo_stack(&mut self) -> &mut TheUndoStack;
 }
 
 impl ThePalettePickerTrait for ThePalettePicker {
     fn set_palette(&mut self, palette: ThePalette) {
         self.palette = palette;
         self.rectangles.clear();
+        self.selection.retain(|i| *i < self.palette.colors.len());
+        // Edits of the previous palette can not be applied to the new one
+        self.undo_stack.clear();
         self.is_dirty = true;
     }
     fn index(&self) -> usize {
@@ -312,4 +658,71 @@ impl ThePalettePickerTrait for ThePalettePicker {
 
         best
     }
+
+    fn palette(&self) -> &ThePalette {
+        &self.palette
+    }
+
+    fn selection(&self) -> Vec<usize> {
+        self.selection.clone()
+    }
+
+    fn set_selection(&mut self, selection: Vec<usize>) {
+        let len = self.palette.colors.len();
+        self.selection = selection.into_iter().filter(|i| *i < len).collect();
+        if let Some(first) = self.selection.first() {
+            self.index = *first;
+        } else {
+            self.selection = vec![self.index];
+        }
+        self.is_dirty = true;
+    }
+
+    fn set_usage(&mut self, usage: Vec<u32>) {
+        self.usage = usage;
+    }
+
+    fn sort(&mut self, key: ThePaletteSort, ctx: &mut TheContext) {
+        let before = self.palette.clone();
+        let slots: Vec<usize> = if self.selection.len() > 1 {
+            self.selection.clone()
+        } else {
+            (0..self.palette.colors.len()).collect()
+        };
+        let mut slots_sorted = slots.clone();
+        slots_sorted.sort_unstable();
+        let order = self.palette.sort(&slots, key, &self.usage);
+
+        // Keep the use counts attached to their colors.
+        if !self.usage.is_empty() {
+            let usage = self.usage.clone();
+            for (slot, previous) in slots_sorted.iter().zip(order) {
+                if let Some(count) = self.usage.get_mut(*slot) {
+                    *count = usage.get(previous).copied().unwrap_or(0);
+                }
+            }
+        }
+
+        self.finish_edit(before, "Sort Colors", ctx);
+        self.is_dirty = true;
+    }
+
+    fn generate_ramp(&mut self, ctx: &mut TheContext) {
+        let (Some(from), Some(to)) = (
+            self.selection.iter().min().copied(),
+            self.selection.iter().max().copied(),
+        ) else {
+            return;
+        };
+        let before = self.palette.clone();
+        if to > from + 1 && self.palette.ramp(from, to) {
+            self.selection = (from..=to).collect();
+            self.finish_edit(before, "Generate Ramp", ctx);
+            self.is_dirty = true;
+        }
+    }
+
+    fn undo_stack(&mut self) -> &mut TheUndoStack {
+        &mut self.undo_stack
+    }
 }
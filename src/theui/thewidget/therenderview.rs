@@ -6,6 +6,11 @@ pub struct TheRenderView {
     state: TheWidgetState,
 
     render_buffer: TheRGBABuffer,
+    // An optional HDR buffer, tone mapped into the render buffer when drawn.
+    hdr_buffer: Option<TheRGBA32FBuffer>,
+    hdr_dirty: bool,
+    tone_mapping: TheToneMapping,
+    exposure: f32,
     wheel_scale: f32,
     accumulated_wheel_delta: Vec2<f32>,
     context_menu: Option<TheContextMenu>,
@@ -31,6 +36,10 @@ impl TheWidget for TheRenderView {
             state: TheWidgetState::None,
 
             render_buffer: TheRGBABuffer::new(TheDim::new(0, 0, 20, 20)),
+            hdr_buffer: None,
+            hdr_dirty: false,
+            tone_mapping: TheToneMapping::default(),
+            exposure: 1.0,
             wheel_scale: -0.4,
             accumulated_wheel_delta: Vec2::zero(),
             context_menu: None,
@@ -221,6 +230,13 @@ impl TheWidget for TheRenderView {
             return;
         }
 
+        if self.hdr_dirty {
+            if let Some(hdr_buffer) = &self.hdr_buffer {
+                self.render_buffer = hdr_buffer.tone_map(self.tone_mapping, self.exposure);
+            }
+            self.hdr_dirty = false;
+        }
+
         self.render_buffer.scaled_into_using_dim(buffer, &self.dim);
 
         let stride = buffer.stride();
@@ -252,6 +268,12 @@ impl TheWidget for TheRenderView {
 pub trait TheRenderViewTrait: TheWidget {
     fn render_buffer_mut(&mut self) -> &mut TheRGBABuffer;
     fn set_auto_focus(&mut self, auto_focus: bool);
+    /// Sets a float (linear HDR) buffer which is tone mapped into the render buffer for display.
+    fn set_hdr_buffer(&mut self, buffer: Option<TheRGBA32FBuffer>);
+    /// Returns the HDR buffer for rendering into, if set.
+    fn hdr_buffer_mut(&mut self) -> Option<&mut TheRGBA32FBuffer>;
+    /// Sets the tone mapping and exposure for the HDR buffer.
+    fn set_tone_mapping(&mut self, tone_mapping: TheToneMapping, exposure: f32);
 }
 
 impl TheRenderViewTrait for TheRenderView {
@@ -262,4 +284,20 @@ impl TheRenderViewTrait for TheRenderView {
     fn set_auto_focus(&mut self, auto_focus: bool) {
         self.auto_focus = auto_focus;
     }
+    fn set_hdr_buffer(&mut self, buffer: Option<TheRGBA32FBuffer>) {
+        self.hdr_buffer = buffer;
+        self.hdr_dirty = true;
+        self.is_dirty = true;
+    }
+    fn hdr_buffer_mut(&mut self) -> Option<&mut TheRGBA32FBuffer> {
+        self.hdr_dirty = true;
+        self.is_dirty = true;
+        self.hdr_buffer.as_mut()
+    }
+    fn set_tone_mapping(&mut self, tone_mapping: TheToneMapping, exposure: f32) {
+        self.tone_mapping = tone_mapping;
+        self.exposure = exposure;
+        self.hdr_dirty = true;
+        self.is_dirty = true;
+    }
 }
@@ -11,6 +11,64 @@ pub enum TheRGBAViewMode {
     TileEditor,
     /// Pick a single tile
     TilePicker,
+    /// Paint pixels with the built-in pixel tools
+    PixelEditor,
+}
+
+/// The tools of the pixel editor mode of TheRGBAView
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ThePixelTool {
+    #[default]
+    Pencil,
+    Brush,
+    Eraser,
+    Line,
+    Rectangle,
+    Ellipse,
+    Fill,
+    ColorPicker,
+    Select,
+}
+
+impl ThePixelTool {
+    pub fn to_string(self) -> &'static str {
+        match self {
+            Self::Pencil => "Pencil",
+            Self::Brush => "Brush",
+            Self::Eraser => "Eraser",
+            Self::Line => "Line",
+            Self::Rectangle => "Rectangle",
+            Self::Ellipse => "Ellipse",
+            Self::Fill => "Fill",
+            Self::ColorPicker => "Color Picker",
+            Self::Select => "Select",
+        }
+    }
+
+    pub fn iterator() -> impl Iterator<Item = ThePixelTool> {
+        [
+            Self::Pencil,
+            Self::Brush,
+            Self::Eraser,
+            Self::Line,
+            Self::Rectangle,
+            Self::Ellipse,
+            Self::Fill,
+            Self::ColorPicker,
+            Self::Select,
+        ]
+        .iter()
+        .copied()
+    }
+}
+
+/// A region of pixels stored as undo / redo data of the pixel editor.
+#[derive(Serialize, Deserialize)]
+struct ThePixelPatch {
+    x: i32,
+    y: i32,
+    layer: Option<Uuid>,
+    buffer: TheRGBABuffer,
 }
 
 /// A widget for displaying and interacting with RGBA buffers with zoom and grid support
@@ -67,6 +125,25 @@ pub struct TheRGBAView {
 
     show_transparency: bool,
     transparency_color: RGBA,
+
+    pixel_tool: ThePixelTool,
+    brush_size: i32,
+    fill_shapes: bool,
+    fill_tolerance: u8,
+    palette: ThePalette,
+    undo_stack: TheUndoStack,
+
+    // The buffer at the start of the current stroke, the source of the undo data.
+    stroke_backup: Option<TheRGBABuffer>,
+    // The buffer which shapes and moved selections are drawn on during a stroke.
+    stroke_base: Option<TheRGBABuffer>,
+    stroke_start: Vec2<i32>,
+    stroke_last: Vec2<i32>,
+    stroke_bounds: Option<TheDim>,
+    pixel_selection: Option<TheDim>,
+    floating: Option<TheRGBABuffer>,
+
+    document: Option<TheImageDocument>,
 }
 
 impl TheRGBAView {
@@ -117,6 +194,373 @@ impl TheRGBAView {
             255,
         ]
     }
+
+    /// Convert screen coordinates to the buffer pixel under it
+    #[inline]
+    fn screen_to_pixel(&self, coord: Vec2<i32>) -> Vec2<i32> {
+        let (x, y) = self.screen_to_buffer(coord);
+        Vec2::new(x.floor() as i32, y.floor() as i32)
+    }
+
+    /// The color the current pixel tool paints with
+    fn paint_color(&self) -> Option<RGBA> {
+        if self.pixel_tool == ThePixelTool::Eraser {
+            return Some([0, 0, 0, 0]);
+        }
+        self.palette
+            .colors
+            .get(self.palette.current_index as usize)
+            .cloned()
+            .flatten()
+            .map(|color| color.to_u8_array())
+    }
+
+    /// The size of the brush of the current pixel tool
+    fn tool_size(&self) -> i32 {
+        if self.pixel_tool == ThePixelTool::Pencil {
+            1
+        } else {
+            self.brush_size.max(1)
+        }
+    }
+
+    /// Rectangle spanned by the two pixels (inclusive)
+    fn span_rect(a: Vec2<i32>, b: Vec2<i32>) -> TheDim {
+        let min_x = a.x.min(b.x);
+        let min_y = a.y.min(b.y);
+        TheDim::new(
+            min_x,
+            min_y,
+            a.x.max(b.x) - min_x + 1,
+            a.y.max(b.y) - min_y + 1,
+        )
+    }
+
+    /// Rectangle spanned by the two pixels (inclusive), clipped to the buffer
+    fn pixel_rect(&self, a: Vec2<i32>, b: Vec2<i32>) -> TheDim {
+        let min_x = a.x.min(b.x).max(0);
+        let min_y = a.y.min(b.y).max(0);
+        let max_x = a.x.max(b.x).min(self.buffer.dim().width - 1);
+        let max_y = a.y.max(b.y).min(self.buffer.dim().height - 1);
+        TheDim::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+    }
+
+    /// Grows the changed region of the current stroke by the given rectangle
+    fn extend_stroke_bounds(&mut self, a: Vec2<i32>, b: Vec2<i32>, size: i32) {
+        let pad = size / 2 + 1;
+        let mut min = Vec2::new(a.x.min(b.x), a.y.min(b.y)) - pad;
+        let mut max = Vec2::new(a.x.max(b.x), a.y.max(b.y)) + pad;
+        if let Some(bounds) = &self.stroke_bounds {
+            min = Vec2::new(min.x.min(bounds.x), min.y.min(bounds.y));
+            max = Vec2::new(
+                max.x.max(bounds.x + bounds.width - 1),
+                max.y.max(bounds.y + bounds.height - 1),
+            );
+        }
+        self.stroke_bounds = Some(self.pixel_rect(min, max));
+    }
+
+    /// Paints the shape of the current tool from the stroke start to the given pixel onto a copy of the stroke base
+    fn paint_shape(&mut self, to: Vec2<i32>, color: &RGBA) {
+        if let Some(base) = self.stroke_base.clone() {
+            *self.edit_buffer_mut() = base;
+        }
+        let from = self.stroke_start;
+        let size = self.tool_size();
+        let filled = self.fill_shapes;
+        match self.pixel_tool {
+            ThePixelTool::Line => self
+                .edit_buffer_mut()
+                .paint_line(from.x, from.y, to.x, to.y, size, color),
+            ThePixelTool::Rectangle => {
+                let rect = Self::span_rect(from, to);
+                self.edit_buffer_mut()
+                    .paint_rect(&rect, size, color, filled)
+            }
+            ThePixelTool::Ellipse => {
+                let rect = Self::span_rect(from, to);
+                self.edit_buffer_mut()
+                    .paint_ellipse(&rect, size, color, filled)
+            }
+            _ => {}
+        }
+        self.extend_stroke_bounds(from, to, size);
+    }
+
+    /// Sets the palette index to the color under the pixel, if it is part of the palette
+    fn pick_color(&mut self, pixel: Vec2<i32>, ctx: &mut TheContext) {
+        if let Some(color) = self.buffer.get_pixel(pixel.x, pixel.y) {
+            if let Some(index) = self
+                .palette
+                .colors
+                .iter()
+                .position(|c| c.as_ref().map(|c| c.to_u8_array()) == Some(color))
+            {
+                if self.palette.current_index != index as u16 {
+                    self.palette.current_index = index as u16;
+                    ctx.ui
+                        .send(TheEvent::PaletteIndexChanged(self.id.clone(), index as u16));
+                }
+            }
+            ctx.ui.send(TheEvent::PixelEditorColorPicked(
+                self.id.clone(),
+                TheColor::from_u8_array(color),
+            ));
+        }
+    }
+
+    /// Starts a pixel editor operation at the given screen coordinate
+    fn pixel_editor_down(&mut self, coord: Vec2<i32>, ctx: &mut TheContext) {
+        let pixel = self.screen_to_pixel(coord);
+
+        // Reset the stroke on every press so that a drag starting outside of the buffer
+        // does not continue from a previous stroke
+        self.stroke_start = pixel;
+        self.stroke_last = pixel;
+        self.stroke_bounds = None;
+
+        if self.buffer.pixel_index(pixel.x, pixel.y).is_none() {
+            return;
+        }
+        if self.pixel_tool != ThePixelTool::ColorPicker
+            && self
+                .document
+                .as_ref()
+                .is_some_and(|document| !document.is_active_editable())
+        {
+            return;
+        }
+
+        match self.pixel_tool {
+            ThePixelTool::ColorPicker => self.pick_color(pixel, ctx),
+            ThePixelTool::Select => {
+                if let Some(selection) = self.pixel_selection.filter(|s| s.contains(pixel)) {
+                    // Lift the selected pixels so that they can be moved
+                    let mut base = self.edit_buffer().clone();
+                    base.paint_rect(&selection, 1, &[0, 0, 0, 0], true);
+                    self.floating = Some(self.edit_buffer().extract(&selection));
+                    self.stroke_backup = Some(self.edit_buffer().clone());
+                    self.stroke_base = Some(base);
+                    self.stroke_bounds = Some(selection);
+                } else {
+                    // A new selection is only created by dragging, a single click clears it
+                    self.pixel_selection = None;
+                }
+            }
+            ThePixelTool::Fill => {
+                if let Some(color) = self.paint_color() {
+                    let backup = self.edit_buffer().clone();
+                    let tolerance = self.fill_tolerance;
+                    if let Some(dim) = self
+                        .edit_buffer_mut()
+                        .flood_fill(pixel.x, pixel.y, &color, tolerance)
+                    {
+                        self.stroke_backup = Some(backup);
+                        self.stroke_bounds = Some(dim);
+                        self.commit_stroke(ctx);
+                    }
+                }
+            }
+            ThePixelTool::Line | ThePixelTool::Rectangle | ThePixelTool::Ellipse => {
+                if let Some(color) = self.paint_color() {
+                    self.stroke_backup = Some(self.edit_buffer().clone());
+                    self.stroke_base = Some(self.edit_buffer().clone());
+                    self.paint_shape(pixel, &color);
+                }
+            }
+            ThePixelTool::Pencil | ThePixelTool::Brush | ThePixelTool::Eraser => {
+                if let Some(color) = self.paint_color() {
+                    self.stroke_backup = Some(self.edit_buffer().clone());
+                    let size = self.tool_size();
+                    self.edit_buffer_mut()
+                        .paint_brush(pixel.x, pixel.y, size, &color);
+                    self.extend_stroke_bounds(pixel, pixel, size);
+                }
+            }
+        }
+
+        self.sync_document(self.stroke_bounds);
+    }
+
+    /// Continues the current pixel editor operation at the given screen coordinate
+    fn pixel_editor_dragged(&mut self, coord: Vec2<i32>, ctx: &mut TheContext) {
+        let pixel = self.screen_to_pixel(coord);
+        if pixel == self.stroke_last {
+            return;
+        }
+
+        match self.pixel_tool {
+            ThePixelTool::ColorPicker => self.pick_color(pixel, ctx),
+            ThePixelTool::Select => {
+                if let (Some(floating), Some(selection)) =
+                    (self.floating.take(), self.pixel_selection)
+                {
+                    let offset = pixel - self.stroke_start;
+                    if let Some(base) = self.stroke_base.clone() {
+                        *self.edit_buffer_mut() = base;
+                    }
+                    let (x, y) = (selection.x + offset.x, selection.y + offset.y);
+                    self.edit_buffer_mut().copy_into(x, y, &floating);
+                    self.floating = Some(floating);
+                    self.extend_stroke_bounds(
+                        Vec2::new(x, y),
+                        Vec2::new(x + selection.width - 1, y + selection.height - 1),
+                        0,
+                    );
+                } else if self
+                    .buffer
+                    .pixel_index(self.stroke_start.x, self.stroke_start.y)
+                    .is_some()
+                {
+                    self.pixel_selection = Some(self.pixel_rect(self.stroke_start, pixel));
+                }
+            }
+            ThePixelTool::Line | ThePixelTool::Rectangle | ThePixelTool::Ellipse => {
+                if self.stroke_backup.is_some() {
+                    if let Some(color) = self.paint_color() {
+                        self.paint_shape(pixel, &color);
+                    }
+                }
+            }
+            ThePixelTool::Pencil | ThePixelTool::Brush | ThePixelTool::Eraser => {
+                if self.stroke_backup.is_some() {
+                    if let Some(color) = self.paint_color() {
+                        let size = self.tool_size();
+                        let last = self.stroke_last;
+                        self.edit_buffer_mut()
+                            .paint_line(last.x, last.y, pixel.x, pixel.y, size, &color);
+                        self.extend_stroke_bounds(last, pixel, size);
+                    }
+                }
+            }
+            ThePixelTool::Fill => {}
+        }
+
+        self.stroke_last = pixel;
+        self.sync_document(self.stroke_bounds);
+    }
+
+    /// Finishes the current pixel editor operation
+    fn pixel_editor_up(&mut self, ctx: &mut TheContext) {
+        if self.pixel_tool == ThePixelTool::Select {
+            if let (Some(_), Some(selection)) = (self.floating.take(), self.pixel_selection) {
+                let offset = self.stroke_last - self.stroke_start;
+                self.pixel_selection = Some(TheDim::new(
+                    selection.x + offset.x,
+                    selection.y + offset.y,
+                    selection.width,
+                    selection.height,
+                ));
+            }
+            ctx.ui.send(TheEvent::PixelEditorSelectionChanged(
+                self.id.clone(),
+                self.pixel_selection,
+            ));
+        }
+        self.commit_stroke(ctx);
+    }
+
+    /// Adds the changes of the current stroke to the undo stack
+    fn commit_stroke(&mut self, ctx: &mut TheContext) {
+        self.stroke_base = None;
+        if let (Some(backup), Some(bounds)) = (self.stroke_backup.take(), self.stroke_bounds.take())
+        {
+            self.sync_document(Some(bounds));
+            let layer = self
+                .document
+                .as_ref()
+                .and_then(|document| document.active_layer_id());
+            let undo_patch = ThePixelPatch {
+                x: bounds.x,
+                y: bounds.y,
+                layer,
+                buffer: backup.extract(&bounds),
+            };
+            let redo_patch = ThePixelPatch {
+                x: bounds.x,
+                y: bounds.y,
+                layer,
+                buffer: self.edit_buffer().extract(&bounds),
+            };
+            if undo_patch.buffer == redo_patch.buffer {
+                return;
+            }
+            if let (Ok(undo_data), Ok(redo_data)) = (
+                serde_json::to_string(&undo_patch),
+                serde_json::to_string(&redo_patch),
+            ) {
+                let mut undo = TheUndo::new(TheId::named(self.pixel_tool.to_string()));
+                undo.set_undo_data(undo_data);
+                undo.set_redo_data(redo_data);
+                self.undo_stack.add(undo);
+            }
+            ctx.ui
+                .send(TheEvent::PixelEditorChanged(self.id.clone(), bounds));
+        }
+    }
+
+    /// Applies undo or redo data of the pixel editor
+    fn apply_pixel_patch(&mut self, data: &str, ctx: &mut TheContext) {
+        if let Ok(patch) = serde_json::from_str::<ThePixelPatch>(data) {
+            let target = match (&mut self.document, patch.layer) {
+                (Some(document), Some(layer)) => document.layer_buffer_mut(layer),
+                _ => Some(&mut self.buffer),
+            };
+            if let Some(target) = target {
+                target.copy_into(patch.x, patch.y, &patch.buffer);
+            }
+            let region = TheDim::new(
+                patch.x,
+                patch.y,
+                patch.buffer.dim().width,
+                patch.buffer.dim().height,
+            );
+            self.sync_document(Some(region));
+            ctx.ui
+                .send(TheEvent::PixelEditorChanged(self.id.clone(), region));
+            self.is_dirty = true;
+        }
+    }
+
+    /// The buffer edited by the pixel editor, the active layer if a document is set
+    fn edit_buffer(&self) -> &TheRGBABuffer {
+        self.document
+            .as_ref()
+            .and_then(|document| document.active_buffer())
+            .unwrap_or(&self.buffer)
+    }
+
+    /// The mutable buffer edited by the pixel editor, the active layer if a document is set
+    fn edit_buffer_mut(&mut self) -> &mut TheRGBABuffer {
+        if self
+            .document
+            .as_ref()
+            .is_some_and(|document| document.active_buffer().is_some())
+        {
+            self.document.as_mut().unwrap().active_buffer_mut().unwrap()
+        } else {
+            &mut self.buffer
+        }
+    }
+
+    /// Composites the document after the active layer changed in the region and updates the displayed buffer
+    fn sync_document(&mut self, region: Option<TheDim>) {
+        if let Some(document) = &mut self.document {
+            if let Some(region) = region {
+                document.mark_dirty(&region);
+            }
+            let dirty = document.dirty_region();
+            let composite = document.composite();
+            match dirty {
+                Some(dirty) if composite.dim() == self.buffer.dim() => {
+                    self.buffer
+                        .copy_into(dirty.x, dirty.y, &composite.extract(&dirty));
+                }
+                _ => self.buffer = composite.clone(),
+            }
+            self.is_dirty = true;
+        }
+    }
 }
 
 impl TheWidget for TheRGBAView {
@@ -179,6 +623,23 @@ impl TheWidget for TheRGBAView {
 
             show_transparency: false,
             transparency_color: [255, 0, 255, 255], // Magenta - a cool default that stands out
+
+            pixel_tool: ThePixelTool::default(),
+            brush_size: 3,
+            fill_shapes: false,
+            fill_tolerance: 0,
+            palette: ThePalette::default(),
+            undo_stack: TheUndoStack::default(),
+
+            stroke_backup: None,
+            stroke_base: None,
+            stroke_start: Vec2::zero(),
+            stroke_last: Vec2::zero(),
+            stroke_bounds: None,
+            pixel_selection: None,
+            floating: None,
+
+            document: None,
         }
     }
 
@@ -222,7 +683,11 @@ impl TheWidget for TheRGBAView {
 
                 self.mouse_down_pos = *coord;
 
-                if self.mode != TheRGBAViewMode::Display {
+                if self.mode == TheRGBAViewMode::PixelEditor {
+                    self.pixel_editor_down(*coord, ctx);
+                    self.is_dirty = true;
+                    redraw = true;
+                } else if self.mode != TheRGBAViewMode::Display {
                     if let Some(loc) = self.get_grid_location(*coord) {
                         self.last_loc = loc;
                         if let Some(fgrid) = self.get_grid_location_f(*coord) {
@@ -286,7 +751,9 @@ impl TheWidget for TheRGBAView {
                     }
                 }
 
-                if self.mode != TheRGBAViewMode::Display {
+                if self.mode == TheRGBAViewMode::PixelEditor {
+                    self.pixel_editor_dragged(*coord, ctx);
+                } else if self.mode != TheRGBAViewMode::Display {
                     if let Some(loc) = self.get_grid_location(*coord) {
                         if loc != self.last_loc {
                             self.last_loc = loc;
@@ -373,8 +840,26 @@ impl TheWidget for TheRGBAView {
                     }
                 } else if self.mode == TheRGBAViewMode::TileEditor {
                     ctx.ui.send(TheEvent::TileEditorUp(self.id.clone()));
+                } else if self.mode == TheRGBAViewMode::PixelEditor {
+                    self.pixel_editor_up(ctx);
+                    self.is_dirty = true;
+                    redraw = true;
                 }
             }
+            TheEvent::Undo
+                if self.mode == TheRGBAViewMode::PixelEditor && self.undo_stack.has_undo() =>
+            {
+                let (_id, data) = self.undo_stack.undo();
+                self.apply_pixel_patch(&data, ctx);
+                redraw = true;
+            }
+            TheEvent::Redo
+                if self.mode == TheRGBAViewMode::PixelEditor && self.undo_stack.has_redo() =>
+            {
+                let (_id, data) = self.undo_stack.redo();
+                self.apply_pixel_patch(&data, ctx);
+                redraw = true;
+            }
             TheEvent::LostHover(_id) => {
                 if self.hover.is_some() {
                     self.hover = None;
@@ -497,6 +982,18 @@ impl TheWidget for TheRGBAView {
                         self.selected.clone(),
                     ));
                 }
+                if let Some(selection) = self
+                    .pixel_selection
+                    .filter(|_| self.mode == TheRGBAViewMode::PixelEditor)
+                {
+                    self.stroke_backup = Some(self.edit_buffer().clone());
+                    self.stroke_bounds = Some(selection);
+                    self.edit_buffer_mut()
+                        .paint_rect(&selection, 1, &[0, 0, 0, 0], true);
+                    self.commit_stroke(ctx);
+                    self.is_dirty = true;
+                    redraw = true;
+                }
                 if self.hover.is_some() && self.mode == TheRGBAViewMode::TileEditor {
                     let mut selected = self.selected.clone();
                     selected.clear();
@@ -552,6 +1049,10 @@ impl TheWidget for TheRGBAView {
         self.is_dirty = true;
     }
 
+    fn supports_undo_redo(&mut self) -> bool {
+        self.mode == TheRGBAViewMode::PixelEditor
+    }
+
     fn supports_hover(&mut self) -> bool {
         true
     }
@@ -810,6 +1311,39 @@ impl TheWidget for TheRGBAView {
             }
         }
 
+        // Marquee of the pixel editor selection, dashed black and white
+        if self.mode == TheRGBAViewMode::PixelEditor {
+            if let Some(selection) = self.pixel_selection {
+                let x0 = (offset_x + selection.x as f32 * self.zoom).round() as i32;
+                let y0 = (offset_y + selection.y as f32 * self.zoom).round() as i32;
+                let x1 = (offset_x + (selection.x + selection.width) as f32 * self.zoom).round()
+                    as i32
+                    - 1;
+                let y1 = (offset_y + (selection.y + selection.height) as f32 * self.zoom).round()
+                    as i32
+                    - 1;
+
+                let mut plot = |x: i32, y: i32| {
+                    if x >= 0 && x < self.dim.width && y >= 0 && y < self.dim.height {
+                        let i = ((self.dim.buffer_y + y) * target.dim().width
+                            + x
+                            + self.dim.buffer_x) as usize
+                            * 4;
+                        let color = if ((x + y) / 4) % 2 == 0 { BLACK } else { WHITE };
+                        target.pixels_mut()[i..i + 4].copy_from_slice(&color);
+                    }
+                };
+                for x in x0..=x1 {
+                    plot(x, y0);
+                    plot(x, y1);
+                }
+                for y in y0..=y1 {
+                    plot(x0, y);
+                    plot(x1, y);
+                }
+            }
+        }
+
         if Some(self.id.clone()) == ctx.ui.focus {
             let tuple = self.dim().to_buffer_utuple();
             ctx.draw.rect_outline(
@@ -874,6 +1408,34 @@ pub trait TheRGBAViewTrait: TheWidget {
     fn set_show_transparency(&mut self, show: bool);
     fn show_transparency(&self) -> bool;
     fn set_transparency_color(&mut self, color: RGBA);
+
+    /// Set the tool used in the pixel editor mode.
+    fn set_pixel_tool(&mut self, tool: ThePixelTool);
+    fn pixel_tool(&self) -> ThePixelTool;
+    /// Set the brush size (diameter in pixels) of the brush, eraser and shape tools.
+    fn set_brush_size(&mut self, size: i32);
+    fn brush_size(&self) -> i32;
+    /// Set if rectangles and ellipses are filled.
+    fn set_fill_shapes(&mut self, fill_shapes: bool);
+    /// Set the per channel tolerance of the flood fill.
+    fn set_fill_tolerance(&mut self, tolerance: u8);
+    /// Set the palette, the current palette color is used for painting.
+    fn set_palette(&mut self, palette: ThePalette);
+    fn palette(&self) -> &ThePalette;
+    fn palette_mut(&mut self) -> &mut ThePalette;
+    /// The marquee selection of the pixel editor in buffer pixels.
+    fn pixel_selection(&self) -> Option<TheDim>;
+    fn set_pixel_selection(&mut self, selection: Option<TheDim>);
+    /// The undo stack of the pixel editor.
+    fn undo_stack(&mut self) -> &mut TheUndoStack;
+
+    /// Set a layered document, the view displays its composite and the pixel editor edits the active layer.
+    fn set_document(&mut self, document: Option<TheImageDocument>);
+    fn document(&self) -> Option<&TheImageDocument>;
+    /// The mutable document, call document_changed() after modifying it.
+    fn document_mut(&mut self) -> Option<&mut TheImageDocument>;
+    /// Composites the dirty regions of the document and updates the displayed buffer.
+    fn document_changed(&mut self);
 }
 
 impl TheRGBAViewTrait for TheRGBAView {
@@ -1084,4 +1646,73 @@ impl TheRGBAViewTrait for TheRGBAView {
             self.is_dirty = true;
         }
     }
+
+    fn set_pixel_tool(&mut self, tool: ThePixelTool) {
+        self.pixel_tool = tool;
+    }
+
+    fn pixel_tool(&self) -> ThePixelTool {
+        self.pixel_tool
+    }
+
+    fn set_brush_size(&mut self, size: i32) {
+        self.brush_size = size.max(1);
+    }
+
+    fn brush_size(&self) -> i32 {
+        self.brush_size
+    }
+
+    fn set_fill_shapes(&mut self, fill_shapes: bool) {
+        self.fill_shapes = fill_shapes;
+    }
+
+    fn set_fill_tolerance(&mut self, tolerance: u8) {
+        self.fill_tolerance = tolerance;
+    }
+
+    fn set_palette(&mut self, palette: ThePalette) {
+        self.palette = palette;
+    }
+
+    fn palette(&self) -> &ThePalette {
+        &self.palette
+    }
+
+    fn palette_mut(&mut self) -> &mut ThePalette {
+        &mut self.palette
+    }
+
+    fn pixel_selection(&self) -> Option<TheDim> {
+        self.pixel_selection
+    }
+
+    fn set_pixel_selection(&mut self, selection: Option<TheDim>) {
+        self.pixel_selection = selection;
+        self.is_dirty = true;
+    }
+
+    fn undo_stack(&mut self) -> &mut TheUndoStack {
+        &mut self.undo_stack
+    }
+
+    fn set_document(&mut self, document: Option<TheImageDocument>) {
+        self.document = document;
+        if let Some(document) = &mut self.document {
+            document.mark_all_dirty();
+        }
+        self.sync_document(None);
+    }
+
+    fn document(&self) -> Option<&TheImageDocument> {
+        self.document.as_ref()
+    }
+
+    fn document_mut(&mut self) -> Option<&mut TheImageDocument> {
+        self.document.as_mut()
+    }
+
+    fn document_changed(&mut self) {
+        self.sync_document(None);
+    }
 }
@@ -0,0 +1,899 @@
+use crate::prelude::*;
+
+const RULER_HEIGHT: i32 = 20;
+const ROW_HEIGHT: i32 = 20;
+const LABEL_WIDTH: i32 = 120;
+const KEY_SIZE: i32 = 11;
+// The space left and right of the time axis, so that keyframes at its ends are fully visible.
+const TRACK_INSET: i32 = KEY_SIZE / 2 + 2;
+const MINUTES_PER_DAY: i32 = 1440;
+
+/// A keyframe of the timeline: Time, collection and key.
+type TheKeyframeId = (TheTime, String, String);
+
+#[derive(PartialEq, Clone, Debug)]
+enum TheTimelineAction {
+    None,
+    /// Moves the time position.
+    Scrub,
+    /// Drags the selected keyframes from the local coordinate.
+    Drag(Vec2<i32>),
+    /// Selects the keyframes inside the rectangle of the two local coordinates.
+    BoxSelect(Vec2<i32>, Vec2<i32>),
+}
+
+/// Edits a TheTimeline as a dope sheet with one row per collection key. Keyframes can be
+/// selected by clicking or with a selection box, dragged in time, copied, pasted at the time
+/// position and deleted, and the context menu sets their interpolation. The curve view shows
+/// the values of the selected float track, whose keyframes can be dragged in time and value.
+/// Edits are sent as undoable TimelineChanged events.
+pub struct TheTimelineEditor {
+    id: TheId,
+    limiter: TheSizeLimiter,
+
+    status: Option<String>,
+
+    dim: TheDim,
+    is_dirty: bool,
+
+    timeline: TheTimeline,
+    tracks: Vec<(String, String)>,
+    time: TheTime,
+
+    selection: Vec<TheKeyframeId>,
+    track: Option<(String, String)>,
+    curve_view: bool,
+
+    action: TheTimelineAction,
+    drag_to: Vec2<i32>,
+    shift: bool,
+    scroll: i32,
+
+    undo_stack: TheUndoStack,
+}
+
+impl TheWidget for TheTimelineEditor {
+    fn new(id: TheId) -> Self
+    where
+        Self: Sized,
+    {
+        let mut limiter = TheSizeLimiter::new();
+        limiter.set_max_height(200);
+
+        Self {
+            id,
+            limiter,
+
+            status: None,
+
+            dim: TheDim::zero(),
+            is_dirty: false,
+
+            timeline: TheTimeline::default(),
+            tracks: vec![],
+            time: TheTime::default(),
+
+            selection: vec![],
+            track: None,
+            curve_view: false,
+
+            action: TheTimelineAction::None,
+            drag_to: Vec2::zero(),
+            shift: false,
+            scroll: 0,
+
+            undo_stack: TheUndoStack::default(),
+        }
+    }
+
+    fn id(&self) -> &TheId {
+        &self.id
+    }
+
+    fn status_text(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn set_status_text(&mut self, text: &str) {
+        self.status = Some(text.to_string());
+    }
+
+    fn on_event(&mut self, event: &TheEvent, ctx: &mut TheContext) -> bool {
+        let mut redraw = false;
+        match event {
+            TheEvent::MouseDown(coord) => {
+                ctx.ui.set_focus(self.id());
+
+                if coord.x >= LABEL_WIDTH && coord.y < RULER_HEIGHT {
+                    self.scrub(coord.x, ctx);
+                    self.action = TheTimelineAction::Scrub;
+                } else if coord.x < LABEL_WIDTH {
+                    if let Some(track) = self.track_at(coord.y) {
+                        self.track = Some(track);
+                    }
+                } else if let Some(keyframe) = self.keyframe_at(*coord) {
+                    let id = (keyframe.time, keyframe.collection, keyframe.key);
+                    if self.shift {
+                        if let Some(index) = self.selection.iter().position(|s| *s == id) {
+                            self.selection.remove(index);
+                        } else {
+                            self.selection.push(id.clone());
+                        }
+                    } else if !self.selection.contains(&id) {
+                        self.selection = vec![id.clone()];
+                    }
+                    self.track = Some((id.1, id.2));
+                    self.action = TheTimelineAction::Drag(*coord);
+                    self.drag_to = *coord;
+                } else {
+                    if !self.shift {
+                        self.selection.clear();
+                    }
+                    self.action = TheTimelineAction::BoxSelect(*coord, *coord);
+                }
+                self.is_dirty = true;
+                redraw = true;
+            }
+            TheEvent::MouseDragged(coord) => match &mut self.action {
+                TheTimelineAction::Scrub => {
+                    self.scrub(coord.x, ctx);
+                    redraw = true;
+                }
+                TheTimelineAction::Drag(_) => {
+                    self.drag_to = *coord;
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+                TheTimelineAction::BoxSelect(_, end) => {
+                    *end = *coord;
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+                TheTimelineAction::None => {}
+            },
+            TheEvent::MouseUp(_coord) => {
+                match &self.action {
+                    TheTimelineAction::Drag(_) => {
+                        if let Some((edit, selection)) = self.drag_edit() {
+                            self.selection = selection;
+                            self.execute(edit, ctx);
+                        }
+                    }
+                    TheTimelineAction::BoxSelect(start, end) => {
+                        let rect = TheDim::new(
+                            start.x.min(end.x),
+                            start.y.min(end.y),
+                            (start.x - end.x).abs() + 1,
+                            (start.y - end.y).abs() + 1,
+                        );
+                        for keyframe in self.timeline.keyframes() {
+                            if let Some(pos) = self.keyframe_pos(&self.timeline, &keyframe) {
+                                let id = (keyframe.time, keyframe.collection, keyframe.key);
+                                if rect.contains(pos) && !self.selection.contains(&id) {
+                                    self.selection.push(id);
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.action = TheTimelineAction::None;
+                self.is_dirty = true;
+                redraw = true;
+            }
+            TheEvent::Context(coord) => {
+                let local = *coord - Vec2::new(self.dim.x, self.dim.y);
+                if let Some(keyframe) = self.keyframe_at(local) {
+                    let id = (keyframe.time, keyframe.collection, keyframe.key);
+                    if !self.selection.contains(&id) {
+                        self.selection = vec![id];
+                        self.is_dirty = true;
+                        redraw = true;
+                    }
+                }
+                if !self.selection.is_empty() {
+                    let mut menu = TheContextMenu::named("Interpolation".to_string());
+                    for interpolation in TheInterpolation::iterator() {
+                        menu.add(TheContextMenuItem::new(
+                            interpolation.to_string().to_string(),
+                            TheId::named(&format!(
+                                "Timeline Interpolation {}",
+                                interpolation.to_string()
+                            )),
+                        ));
+                    }
+                    ctx.ui
+                        .send(TheEvent::ShowContextMenu(self.id().clone(), *coord, menu));
+                }
+            }
+            TheEvent::ContextMenuSelected(_widget_id, item_id) => {
+                if let Some(interpolation) = TheInterpolation::iterator().find(|interpolation| {
+                    item_id.name == format!("Timeline Interpolation {}", interpolation.to_string())
+                }) {
+                    let removed = self.selected_keyframes();
+                    let mut added = removed.clone();
+                    for keyframe in &mut added {
+                        keyframe.interpolation = interpolation;
+                    }
+                    redraw = self.execute(self.timeline.make_edit(removed, added), ctx);
+                }
+            }
+            TheEvent::Hover(coord) => {
+                if !self.id().equals(&ctx.ui.hover) {
+                    ctx.ui.set_hover(self.id());
+                }
+                let text = if let Some(keyframe) = self.keyframe_at(*coord) {
+                    Some(format!(
+                        "{} {} at {}: {}, {}.",
+                        keyframe.collection,
+                        keyframe.key,
+                        keyframe.time.to_time24(),
+                        keyframe.value.describe(),
+                        keyframe.interpolation.to_string()
+                    ))
+                } else {
+                    self.status.clone()
+                };
+                if let Some(text) = text {
+                    ctx.ui.send(TheEvent::SetStatusText(TheId::empty(), text));
+                }
+            }
+            TheEvent::MouseWheel(delta) => {
+                let max = (self.tracks.len() as i32 * ROW_HEIGHT
+                    - (self.dim.height - RULER_HEIGHT))
+                    .max(0);
+                let scroll = (self.scroll - delta.y / 4).clamp(0, max);
+                if scroll != self.scroll {
+                    self.scroll = scroll;
+                    self.is_dirty = true;
+                    redraw = true;
+                }
+            }
+            TheEvent::Undo if self.undo_stack.has_undo() => {
+                let (_id, data) = self.undo_stack.undo();
+                self.apply_undo_data(&data, "Timeline Undo", ctx);
+                redraw = true;
+            }
+            TheEvent::Redo if self.undo_stack.has_redo() => {
+                let (_id, data) = self.undo_stack.redo();
+                self.apply_undo_data(&data, "Timeline Redo", ctx);
+                redraw = true;
+            }
+            TheEvent::ModifierChanged(shift, _ctrl, _alt, _logo) => {
+                self.shift = *shift;
+            }
+            TheEvent::KeyCodeDown(code) if code.to_key_code() == Some(TheKeyCode::Delete) => {
+                redraw = self.delete_selection(ctx);
+            }
+            TheEvent::Copy => {
+                self.copy_selection(ctx);
+            }
+            TheEvent::Cut => {
+                self.copy_selection(ctx);
+                redraw = self.delete_selection(ctx);
+            }
+            TheEvent::Paste(TheValue::Text(text), Some(app_type))
+                if app_type == "TheTimelineKeyframes" =>
+            {
+                if let Ok(keyframes) = serde_json::from_str::<Vec<TheKeyframe>>(text) {
+                    redraw = self.paste(keyframes, ctx);
+                }
+            }
+            _ => {}
+        }
+        redraw
+    }
+
+    fn dim(&self) -> &TheDim {
+        &self.dim
+    }
+
+    fn dim_mut(&mut self) -> &mut TheDim {
+        &mut self.dim
+    }
+
+    fn set_dim(&mut self, dim: TheDim, _ctx: &mut TheContext) {
+        if self.dim != dim {
+            self.dim = dim;
+            self.is_dirty = true;
+        }
+    }
+
+    fn limiter(&self) -> &TheSizeLimiter {
+        &self.limiter
+    }
+
+    fn limiter_mut(&mut self) -> &mut TheSizeLimiter {
+        &mut self.limiter
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        self.is_dirty
+    }
+
+    fn set_needs_redraw(&mut self, redraw: bool) {
+        self.is_dirty = redraw;
+    }
+
+    fn supports_hover(&mut self) -> bool {
+        true
+    }
+
+    fn supports_undo_redo(&mut self) -> bool {
+        true
+    }
+
+    fn value(&self) -> TheValue {
+        TheValue::Time(self.time)
+    }
+
+    fn set_value(&mut self, value: TheValue) {
+        if let TheValue::Time(time) = value {
+            self.time = time;
+            self.is_dirty = true;
+        }
+    }
+
+    fn draw(
+        &mut self,
+        buffer: &mut TheRGBABuffer,
+        style: &mut Box<dyn TheStyle>,
+        ctx: &mut TheContext,
+    ) {
+        if !self.dim().is_valid() || self.track_width() <= 1 {
+            return;
+        }
+
+        let stride = buffer.stride();
+        let utuple = self.dim.to_buffer_utuple();
+        let (bx, by) = (self.dim.buffer_x, self.dim.buffer_y);
+
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &utuple,
+            stride,
+            style.theme().color(TimeSliderBackground),
+        );
+
+        let background = *style.theme().color(TimeSliderBackground);
+        let line_color = *style.theme().color(TimeSliderLine);
+        let minor_line_color = ctx.draw.mix_color(&background, &line_color, 0.3);
+        let text_color = *style.theme().color(TimeSliderText);
+        let position_color = *style.theme().color(TimeSliderPosition);
+        let marker_color = *style.theme().color(TimeSliderMarker);
+
+        // The dragged keyframes are previewed on a copy of the timeline.
+        let preview;
+        let (timeline, selection) = if let Some((edit, selection)) = self.drag_edit() {
+            let mut timeline = self.timeline.clone();
+            timeline.apply_edit(&edit);
+            preview = (timeline, selection);
+            (&preview.0, &preview.1)
+        } else {
+            (&self.timeline, &self.selection)
+        };
+
+        // Ruler
+        for hour in 0..24 {
+            let x = bx + self.time_x(&TheTime::new_time(hour as u8, 0).unwrap_or_default());
+            buffer.draw_line(
+                x,
+                by + RULER_HEIGHT - 4,
+                x,
+                by + self.dim.height - 1,
+                if hour % 6 == 0 {
+                    line_color
+                } else {
+                    minor_line_color
+                },
+            );
+            if hour % 2 == 0 {
+                ctx.draw.text_rect_blend(
+                    buffer.pixels_mut(),
+                    &(
+                        (x + 3) as usize,
+                        by as usize,
+                        24,
+                        (RULER_HEIGHT - 4) as usize,
+                    ),
+                    stride,
+                    &hour.to_string(),
+                    TheFontSettings {
+                        size: 11.0,
+                        ..Default::default()
+                    },
+                    &text_color,
+                    TheHorizontalAlign::Left,
+                    TheVerticalAlign::Center,
+                );
+            }
+        }
+        buffer.draw_line(
+            bx,
+            by + RULER_HEIGHT - 1,
+            bx + self.dim.width - 1,
+            by + RULER_HEIGHT - 1,
+            line_color,
+        );
+
+        // Track labels
+        for (index, track) in self.tracks.iter().enumerate() {
+            let y = self.row_y(index);
+            if y + ROW_HEIGHT <= RULER_HEIGHT || y >= self.dim.height {
+                continue;
+            }
+            if y >= RULER_HEIGHT && y + ROW_HEIGHT <= self.dim.height {
+                ctx.draw.text_rect_blend(
+                    buffer.pixels_mut(),
+                    &(
+                        (bx + 5) as usize,
+                        (by + y) as usize,
+                        (LABEL_WIDTH - 10) as usize,
+                        ROW_HEIGHT as usize,
+                    ),
+                    stride,
+                    &format!("{}: {}", track.0, track.1),
+                    TheFontSettings {
+                        size: 12.0,
+                        ..Default::default()
+                    },
+                    if self.track.as_ref() == Some(track) {
+                        &position_color
+                    } else {
+                        &text_color
+                    },
+                    TheHorizontalAlign::Left,
+                    TheVerticalAlign::Center,
+                );
+            }
+            if !self.curve_view && y + ROW_HEIGHT < self.dim.height {
+                buffer.draw_line(
+                    bx,
+                    by + y + ROW_HEIGHT,
+                    bx + self.dim.width - 1,
+                    by + y + ROW_HEIGHT,
+                    minor_line_color,
+                );
+            }
+        }
+        buffer.draw_line(
+            bx + LABEL_WIDTH - 1,
+            by,
+            bx + LABEL_WIDTH - 1,
+            by + self.dim.height - 1,
+            line_color,
+        );
+
+        // The curve of the selected track
+        if let Some((collection, key)) = self.curve_track() {
+            let range = self.value_range(timeline);
+            let mut last: Option<i32> = None;
+            for x in LABEL_WIDTH + TRACK_INSET..self.dim.width - TRACK_INSET {
+                let at = self.time_at(x);
+                let y = timeline
+                    .evaluate(&collection, &key, &at)
+                    .and_then(|v| v.to_f32())
+                    .map(|v| self.value_y(v, range));
+                if let (Some(last), Some(y)) = (last, y) {
+                    buffer.draw_line(bx + x - 1, by + last, bx + x, by + y, position_color);
+                }
+                last = y;
+            }
+        }
+
+        // Keyframes
+        for keyframe in timeline.keyframes() {
+            let Some(pos) = self.keyframe_pos(timeline, &keyframe) else {
+                continue;
+            };
+            if pos.x - KEY_SIZE / 2 < LABEL_WIDTH
+                || pos.y - KEY_SIZE / 2 < RULER_HEIGHT
+                || pos.x + KEY_SIZE / 2 >= self.dim.width
+                || pos.y + KEY_SIZE / 2 >= self.dim.height
+            {
+                continue;
+            }
+            let id = (keyframe.time, keyframe.collection, keyframe.key);
+            let color = if selection.contains(&id) {
+                &position_color
+            } else {
+                &marker_color
+            };
+            ctx.draw.rhombus_with_border(
+                buffer.pixels_mut(),
+                &(
+                    (bx + pos.x - KEY_SIZE / 2) as usize,
+                    (by + pos.y - KEY_SIZE / 2) as usize,
+                    KEY_SIZE as usize,
+                    KEY_SIZE as usize,
+                ),
+                stride,
+                color,
+                &BLACK,
+                1.0,
+            );
+        }
+
+        // Time position
+        let x = bx + self.time_x(&self.time);
+        ctx.draw.rect(
+            buffer.pixels_mut(),
+            &(x as usize, by as usize, 2, self.dim.height as usize),
+            stride,
+            &position_color,
+        );
+
+        if let TheTimelineAction::BoxSelect(start, end) = &self.action {
+            let rect = (
+                (bx + start.x.min(end.x)) as usize,
+                (by + start.y.min(end.y)) as usize,
+                ((start.x - end.x).abs() + 1) as usize,
+                ((start.y - end.y).abs() + 1) as usize,
+            );
+            ctx.draw
+                .rect_outline(buffer.pixels_mut(), &rect, stride, &WHITE);
+        }
+
+        ctx.draw.rect_outline(
+            buffer.pixels_mut(),
+            &utuple,
+            stride,
+            style.theme().color(TimeSliderBorder),
+        );
+
+        self.is_dirty = false;
+    }
+
+    fn as_timeline_editor(&mut self) -> Option<&mut dyn TheTimelineEditorTrait> {
+        Some(self)
+    }
+
+    fn as_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+impl TheTimelineEditor {
+    /// The local x coordinate of the time.
+    fn time_x(&self, time: &TheTime) -> i32 {
+        LABEL_WIDTH + TRACK_INSET + (time.total_minutes() * self.track_width()) / MINUTES_PER_DAY
+    }
+
+    /// The time at the local x coordinate, in minutes.
+    fn time_at(&self, x: i32) -> TheTime {
+        let width = self.track_width().max(1);
+        time_from_minutes((x - LABEL_WIDTH - TRACK_INSET) * MINUTES_PER_DAY / width)
+    }
+
+    /// The width of the time axis.
+    fn track_width(&self) -> i32 {
+        self.dim.width - LABEL_WIDTH - TRACK_INSET * 2
+    }
+
+    /// The local y coordinate of the row of the track.
+    fn row_y(&self, index: usize) -> i32 {
+        RULER_HEIGHT + index as i32 * ROW_HEIGHT - self.scroll
+    }
+
+    /// The track of the row at the local y coordinate.
+    fn track_at(&self, y: i32) -> Option<(String, String)> {
+        if y < RULER_HEIGHT {
+            return None;
+        }
+        let index = (y - RULER_HEIGHT + self.scroll) / ROW_HEIGHT;
+        self.tracks.get(index as usize).cloned()
+    }
+
+    /// The track shown in the curve view, if enabled and if it has float keyframes.
+    fn curve_track(&self) -> Option<(String, String)> {
+        if !self.curve_view {
+            return None;
+        }
+        let (collection, key) = self.track.clone()?;
+        self.timeline
+            .track_keyframes(&collection, &key)
+            .iter()
+            .any(|k| k.value.to_f32().is_some())
+            .then_some((collection, key))
+    }
+
+    /// The value range of the curve track, padded by 10%.
+    fn value_range(&self, timeline: &TheTimeline) -> (f32, f32) {
+        let Some((collection, key)) = self.curve_track() else {
+            return (0.0, 1.0);
+        };
+        let values: Vec<f32> = timeline
+            .track_keyframes(&collection, &key)
+            .iter()
+            .filter_map(|k| k.value.to_f32())
+            .collect();
+        let min = values.iter().copied().fold(f32::MAX, f32::min);
+        let max = values.iter().copied().fold(f32::MIN, f32::max);
+        if values.is_empty() {
+            (0.0, 1.0)
+        } else if (max - min).abs() < f32::EPSILON {
+            (min - 1.0, max + 1.0)
+        } else {
+            let padding = (max - min) * 0.1;
+            (min - padding, max + padding)
+        }
+    }
+
+    /// The local y coordinate of a value in the curve view.
+    fn value_y(&self, value: f32, range: (f32, f32)) -> i32 {
+        let height = (self.dim.height - RULER_HEIGHT - KEY_SIZE).max(1) as f32;
+        let t = (value - range.0) / (range.1 - range.0);
+        RULER_HEIGHT + KEY_SIZE / 2 + ((1.0 - t) * height).round() as i32
+    }
+
+    /// The value at the local y coordinate of the curve view.
+    fn value_at(&self, y: i32, range: (f32, f32)) -> f32 {
+        let height = (self.dim.height - RULER_HEIGHT - KEY_SIZE).max(1) as f32;
+        let t = 1.0 - (y - RULER_HEIGHT - KEY_SIZE / 2) as f32 / height;
+        range.0 + t * (range.1 - range.0)
+    }
+
+    /// The local center of the keyframe, None if it is not shown.
+    fn keyframe_pos(&self, timeline: &TheTimeline, keyframe: &TheKeyframe) -> Option<Vec2<i32>> {
+        let x = self.time_x(&keyframe.time);
+        if let Some(track) = self.curve_track() {
+            if track.0 != keyframe.collection || track.1 != keyframe.key {
+                return None;
+            }
+            let value = keyframe.value.to_f32()?;
+            Some(Vec2::new(
+                x,
+                self.value_y(value, self.value_range(timeline)),
+            ))
+        } else {
+            let index = self
+                .tracks
+                .iter()
+                .position(|(c, k)| *c == keyframe.collection && *k == keyframe.key)?;
+            Some(Vec2::new(x, self.row_y(index) + ROW_HEIGHT / 2))
+        }
+    }
+
+    /// The keyframe at the local coordinate, preferring selected keyframes.
+    fn keyframe_at(&self, coord: Vec2<i32>) -> Option<TheKeyframe> {
+        let mut found = None;
+        for keyframe in self.timeline.keyframes() {
+            let Some(pos) = self.keyframe_pos(&self.timeline, &keyframe) else {
+                continue;
+            };
+            if (pos.x - coord.x).abs() <= KEY_SIZE / 2 && (pos.y - coord.y).abs() <= KEY_SIZE / 2 {
+                let id = (
+                    keyframe.time,
+                    keyframe.collection.clone(),
+                    keyframe.key.clone(),
+                );
+                if self.selection.contains(&id) {
+                    return Some(keyframe);
+                }
+                found.get_or_insert(keyframe);
+            }
+        }
+        found
+    }
+
+    /// Sets the time position at the local x coordinate and sends it.
+    fn scrub(&mut self, x: i32, ctx: &mut TheContext) {
+        let time = self.time_at(x.clamp(LABEL_WIDTH, self.dim.width - 1));
+        if time != self.time {
+            self.time = time;
+            ctx.ui
+                .send_widget_value_changed(self.id(), TheValue::Time(self.time));
+        }
+        self.is_dirty = true;
+    }
+
+    /// The selected keyframes.
+    fn selected_keyframes(&self) -> Vec<TheKeyframe> {
+        self.selection
+            .iter()
+            .filter_map(|(time, collection, key)| self.timeline.keyframe(time, collection, key))
+            .collect()
+    }
+
+    /// The edit moving the selected keyframes while dragging, together with their new ids.
+    fn drag_edit(&self) -> Option<(TheTimelineEdit, Vec<TheKeyframeId>)> {
+        let TheTimelineAction::Drag(start) = &self.action else {
+            return None;
+        };
+        let delta = self.drag_to - *start;
+        if delta == Vec2::zero() {
+            return None;
+        }
+
+        let width = self.track_width().max(1);
+        let minutes = delta.x * MINUTES_PER_DAY / width;
+        let curve_track = self.curve_track();
+        let range = self.value_range(&self.timeline);
+
+        let removed = self.selected_keyframes();
+        let mut added = removed.clone();
+        for keyframe in &mut added {
+            let seconds = keyframe.time.seconds;
+            keyframe.time = time_from_minutes(keyframe.time.total_minutes() + minutes);
+            keyframe.time.seconds = seconds;
+
+            let in_curve = curve_track
+                .as_ref()
+                .is_some_and(|(c, k)| *c == keyframe.collection && *k == keyframe.key);
+            if in_curve && delta.y != 0 {
+                if let Some(value) = keyframe.value.to_f32() {
+                    let y = self.value_y(value, range) + delta.y;
+                    let value = self.value_at(y, range);
+                    keyframe.value = match &keyframe.value {
+                        TheValue::FloatRange(_, r) => TheValue::FloatRange(value, r.clone()),
+                        TheValue::Int(_) => TheValue::Int(value.round() as i32),
+                        _ => TheValue::Float(value),
+                    };
+                }
+            }
+        }
+
+        // Keyframes moved onto the same time of a track replace each other.
+        let mut unique: Vec<TheKeyframe> = vec![];
+        for keyframe in added {
+            unique.retain(|k| !k.same_key(&keyframe));
+            unique.push(keyframe);
+        }
+
+        let selection = unique
+            .iter()
+            .map(|k| (k.time, k.collection.clone(), k.key.clone()))
+            .collect();
+        Some((self.timeline.make_edit(removed, unique), selection))
+    }
+
+    /// Deletes the selected keyframes.
+    fn delete_selection(&mut self, ctx: &mut TheContext) -> bool {
+        let removed = self.selected_keyframes();
+        self.selection.clear();
+        if removed.is_empty() {
+            return false;
+        }
+        self.execute(self.timeline.make_edit(removed, vec![]), ctx)
+    }
+
+    /// Copies the selected keyframes to the clipboard.
+    fn copy_selection(&mut self, ctx: &mut TheContext) {
+        let keyframes = self.selected_keyframes();
+        if !keyframes.is_empty() {
+            if let Ok(json) = serde_json::to_string(&keyframes) {
+                ctx.ui.send(TheEvent::SetClipboard(
+                    TheValue::Text(json),
+                    Some(str!("TheTimelineKeyframes")),
+                ));
+            }
+        }
+    }
+
+    /// Pastes the keyframes so that the first one is at the time position and selects them.
+    fn paste(&mut self, keyframes: Vec<TheKeyframe>, ctx: &mut TheContext) -> bool {
+        let Some(first) = keyframes.iter().map(|k| k.time.total_minutes()).min() else {
+            return false;
+        };
+        let offset = self.time.total_minutes() - first;
+
+        let mut added: Vec<TheKeyframe> = vec![];
+        for mut keyframe in keyframes {
+            let seconds = keyframe.time.seconds;
+            keyframe.time = time_from_minutes(keyframe.time.total_minutes() + offset);
+            keyframe.time.seconds = seconds;
+            added.retain(|k| !k.same_key(&keyframe));
+            added.push(keyframe);
+        }
+
+        self.selection = added
+            .iter()
+            .map(|k| (k.time, k.collection.clone(), k.key.clone()))
+            .collect();
+        self.execute(self.timeline.make_edit(vec![], added), ctx)
+    }
+
+    /// Adds the (already applied) edit to the undo stack and sends it as TimelineChanged.
+    fn add_undo(&mut self, edit: &TheTimelineEdit, ctx: &mut TheContext) {
+        let mut undo = TheUndo::new(TheId::named("Timeline Edit"));
+        undo.set_undo_data(edit.inverse().to_json());
+        undo.set_redo_data(edit.to_json());
+        self.undo_stack.add(undo.clone());
+        ctx.ui
+            .send(TheEvent::TimelineChanged(self.id().clone(), undo));
+    }
+
+    /// Applies the edit of the undo or redo data and announces it.
+    fn apply_undo_data(&mut self, data: &str, name: &str, ctx: &mut TheContext) {
+        let Some(edit) = TheTimelineEdit::from_json(data) else {
+            return;
+        };
+        self.timeline.apply_edit(&edit);
+        self.tracks = self.timeline.tracks();
+        self.selection.retain(|(time, collection, key)| {
+            self.timeline.keyframe(time, collection, key).is_some()
+        });
+        self.is_dirty = true;
+
+        let mut undo = TheUndo::new(TheId::named(name));
+        undo.set_undo_data(edit.inverse().to_json());
+        undo.set_redo_data(data.to_string());
+        ctx.ui
+            .send(TheEvent::TimelineChanged(self.id().clone(), undo));
+    }
+}
+
+/// The time of the minutes since midnight, clamped to the day.
+fn time_from_minutes(minutes: i32) -> TheTime {
+    let minutes = minutes.clamp(0, MINUTES_PER_DAY - 1);
+    TheTime {
+        hours: (minutes / 60) as u8,
+        minutes: (minutes % 60) as u8,
+        seconds: 0,
+    }
+}
+
+/// TheTimelineEditor specific functions.
+pub trait TheTimelineEditorTrait: TheWidget {
+    /// The timeline, as edited by the user.
+    fn timeline(&self) -> &TheTimeline;
+    /// Replaces the timeline and clears the selection and the undo stack.
+    fn set_timeline(&mut self, timeline: TheTimeline);
+    /// Applies the edit to the timeline and adds it to the undo stack.
+    fn execute(&mut self, edit: TheTimelineEdit, ctx: &mut TheContext) -> bool;
+    fn undo_stack(&mut self) -> &mut TheUndoStack;
+    /// The time position.
+    fn time(&self) -> TheTime;
+    fn set_time(&mut self, time: TheTime);
+    /// The selected keyframes.
+    fn selection(&self) -> Vec<TheKeyframe>;
+    /// Selects the track, which is shown in the curve view.
+    fn set_track(&mut self, collection: &str, key: &str);
+    /// Shows the values of the selected float track as a curve instead of the dope sheet.
+    fn set_curve_view(&mut self, curve_view: bool);
+}
+
+impl TheTimelineEditorTrait for TheTimelineEditor {
+    fn timeline(&self) -> &TheTimeline {
+        &self.timeline
+    }
+    fn set_timeline(&mut self, timeline: TheTimeline) {
+        self.timeline = timeline;
+        self.tracks = self.timeline.tracks();
+        self.selection.clear();
+        self.undo_stack.clear();
+        self.scroll = 0;
+        self.is_dirty = true;
+    }
+    fn execute(&mut self, edit: TheTimelineEdit, ctx: &mut TheContext) -> bool {
+        if edit.is_empty() {
+            return false;
+        }
+        self.timeline.apply_edit(&edit);
+        self.tracks = self.timeline.tracks();
+        self.add_undo(&edit, ctx);
+        self.is_dirty = true;
+        true
+    }
+    fn undo_stack(&mut self) -> &mut TheUndoStack {
+        &mut self.undo_stack
+    }
+    fn time(&self) -> TheTime {
+        self.time
+    }
+    fn set_time(&mut self, time: TheTime) {
+        self.time = time;
+        self.is_dirty = true;
+    }
+    fn selection(&self) -> Vec<TheKeyframe> {
+        self.selected_keyframes()
+    }
+    fn set_track(&mut self, collection: &str, key: &str) {
+        self.track = Some((collection.to_string(), key.to_string()));
+        self.is_dirty = true;
+    }
+    fn set_curve_view(&mut self, curve_view: bool) {
+        self.curve_view = curve_view;
+        self.is_dirty = true;
+    }
+}
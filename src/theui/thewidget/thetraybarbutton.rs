@@ -312,6 +312,18 @@ impl TheWidget for TheTraybarButton {
             );
             ctx.draw
                 .blend_slice(buffer.pixels_mut(), icon.pixels(), &r, stride);
+        } else if let Some(icon) = ctx.ui.scaled_svg_icon(&self.icon_name, 1.0, style) {
+            let utuple = self.dim.to_buffer_shrunk_utuple(&shrinker);
+            let r = (
+                ((utuple.0 + (utuple.2 - icon.dim().width as usize) / 2) as i32
+                    + self.icon_offset.x) as usize,
+                ((utuple.1 + (utuple.3 - icon.dim().height as usize) / 2) as i32
+                    + self.icon_offset.y) as usize,
+                icon.dim().width as usize,
+                icon.dim().height as usize,
+            );
+            ctx.draw
+                .blend_slice(buffer.pixels_mut(), icon.pixels(), &r, stride);
         }
 
         if !self.text.is_empty() {
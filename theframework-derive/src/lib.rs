@@ -0,0 +1,263 @@
+//! Derive macros for theframework.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Expr, Fields, LitStr};
+
+/// Derives `TheInspect` for structs with named fields and `TheInspectField` for structs and
+/// enums with unit variants, to generate `TheNodeUI` property inspectors.
+///
+/// Fields are mapped to `TheNodeUI` items by their type, see `TheInspectField`. Nested structs
+/// which derive `TheInspect` are shown as a group. The `#[inspect(...)]` field attribute supports:
+///
+/// * `label = "Speed"`: The label of the item, by default the field name in title case.
+/// * `status = "..."`: The status text of the item.
+/// * `min = 0.0`, `max = 10.0`: The value range of numeric items.
+/// * `continuous`: Sends value changes while editing.
+/// * `slider`: Uses a slider instead of a text edit for numeric items.
+/// * `skip`: Excludes the field from the inspector.
+///
+/// Enum variants support `label`.
+#[proc_macro_derive(TheInspect, attributes(inspect))]
+pub fn derive_the_inspect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match &input.data {
+        Data::Struct(data) => derive_struct(&input, &data.fields),
+        Data::Enum(data) => derive_enum(&input, data.variants.iter().collect()),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input.ident,
+            "TheInspect cannot be derived for unions",
+        )),
+    }
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+/// The options of an `#[inspect(...)]` attribute.
+#[derive(Default)]
+struct InspectAttrs {
+    label: Option<String>,
+    status: Option<String>,
+    min: Option<Expr>,
+    max: Option<Expr>,
+    continuous: bool,
+    slider: bool,
+    skip: bool,
+}
+
+impl InspectAttrs {
+    fn parse(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut options = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("inspect")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("label") {
+                    options.label = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("status") {
+                    options.status = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("min") {
+                    options.min = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("max") {
+                    options.max = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("continuous") {
+                    options.continuous = true;
+                } else if meta.path.is_ident("slider") {
+                    options.slider = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("unsupported inspect option"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
+
+    /// The TheInspectAttrs expression of the field.
+    fn to_tokens(&self, name: &str) -> TokenStream2 {
+        let label = self.label.clone().unwrap_or_else(|| title_case(name));
+        let status = self.status.clone().unwrap_or_default();
+        let min = option_f32(&self.min);
+        let max = option_f32(&self.max);
+        let continuous = self.continuous;
+        let slider = self.slider;
+        quote! {
+            ::theframework::prelude::TheInspectAttrs {
+                label: #label.to_string(),
+                status: #status.to_string(),
+                min: #min,
+                max: #max,
+                continuous: #continuous,
+                slider: #slider,
+            }
+        }
+    }
+}
+
+fn option_f32(expr: &Option<Expr>) -> TokenStream2 {
+    match expr {
+        Some(expr) => quote! { Some((#expr) as f32) },
+        None => quote! { None },
+    }
+}
+
+/// "max_speed" -> "Max Speed", "LinearRgb" -> "Linear Rgb".
+fn title_case(name: &str) -> String {
+    let mut title = String::new();
+    let mut prev: Option<char> = None;
+    for c in name.trim_start_matches("r#").chars() {
+        if c == '_' {
+            prev = Some(c);
+            continue;
+        }
+        match prev {
+            None | Some('_') => {
+                if !title.is_empty() {
+                    title.push(' ');
+                }
+                title.extend(c.to_uppercase());
+            }
+            Some(p) if c.is_uppercase() && p.is_lowercase() => {
+                title.push(' ');
+                title.push(c);
+            }
+            _ => title.push(c),
+        }
+        prev = Some(c);
+    }
+    title
+}
+
+fn derive_struct(input: &DeriveInput, fields: &Fields) -> syn::Result<TokenStream2> {
+    let Fields::Named(fields) = fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "TheInspect can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut add = vec![];
+    let mut read = vec![];
+    for field in &fields.named {
+        let attrs = InspectAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let options = attrs.to_tokens(&name);
+        add.push(quote! {
+            ::theframework::prelude::TheInspectField::add_item(
+                &self.#ident,
+                &format!("{}{}", prefix, #name),
+                &#options,
+                ui,
+            );
+        });
+        read.push(quote! {
+            ::theframework::prelude::TheInspectField::read_item(
+                &mut self.#ident,
+                &format!("{}{}", prefix, #name),
+                ui,
+            );
+        });
+    }
+
+    // The fields of generic types are inspected, so every type parameter has to be inspectable.
+    let mut generics = input.generics.clone();
+    let predicates = &mut generics.make_where_clause().predicates;
+    for param in input.generics.type_params() {
+        let param = &param.ident;
+        predicates.push(parse_quote!(#param: ::theframework::prelude::TheInspectField));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::theframework::prelude::TheInspect for #ident #ty_generics #where_clause {
+            fn add_to_node_ui(&self, prefix: &str, ui: &mut ::theframework::prelude::TheNodeUI) {
+                #(#add)*
+            }
+
+            fn read_from_node_ui(&mut self, prefix: &str, ui: &::theframework::prelude::TheNodeUI) {
+                #(#read)*
+            }
+        }
+
+        impl #impl_generics ::theframework::prelude::TheInspectField for #ident #ty_generics #where_clause {
+            fn add_item(
+                &self,
+                id: &str,
+                attrs: &::theframework::prelude::TheInspectAttrs,
+                ui: &mut ::theframework::prelude::TheNodeUI,
+            ) {
+                ui.add_item(::theframework::prelude::TheNodeUIItem::Group(
+                    id.to_string(),
+                    attrs.label.clone(),
+                    false,
+                ));
+                ::theframework::prelude::TheInspect::add_to_node_ui(self, &format!("{}.", id), ui);
+                ui.add_item(::theframework::prelude::TheNodeUIItem::GroupEnd(format!("{}/end", id)));
+            }
+
+            fn read_item(&mut self, id: &str, ui: &::theframework::prelude::TheNodeUI) {
+                ::theframework::prelude::TheInspect::read_from_node_ui(self, &format!("{}.", id), ui);
+            }
+        }
+    })
+}
+
+fn derive_enum(input: &DeriveInput, variants: Vec<&syn::Variant>) -> syn::Result<TokenStream2> {
+    let mut labels = vec![];
+    let mut idents = vec![];
+    for variant in &variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "TheInspect can only be derived for enums with unit variants",
+            ));
+        }
+        let attrs = InspectAttrs::parse(&variant.attrs)?;
+        labels.push(
+            attrs
+                .label
+                .unwrap_or_else(|| title_case(&variant.ident.to_string())),
+        );
+        idents.push(&variant.ident);
+    }
+    let indices = (0..idents.len() as i32).collect::<Vec<_>>();
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::theframework::prelude::TheInspectField for #ident #ty_generics #where_clause {
+            fn add_item(
+                &self,
+                id: &str,
+                attrs: &::theframework::prelude::TheInspectAttrs,
+                ui: &mut ::theframework::prelude::TheNodeUI,
+            ) {
+                let index = match self {
+                    #(Self::#idents => #indices,)*
+                };
+                ui.add_item(::theframework::prelude::TheNodeUIItem::Selector(
+                    id.to_string(),
+                    attrs.label.clone(),
+                    attrs.status.clone(),
+                    vec![#(#labels.to_string()),*],
+                    index,
+                ));
+            }
+
+            fn read_item(&mut self, id: &str, ui: &::theframework::prelude::TheNodeUI) {
+                if let Some(::theframework::prelude::TheValue::Int(index)) = ui.get_value(id) {
+                    match index {
+                        #(#indices => *self = Self::#idents,)*
+                        _ => {}
+                    }
+                }
+            }
+        }
+    })
+}